@@ -18,6 +18,20 @@ fn main() {
     // - https://doc.rust-lang.org/cargo/reference/build-scripts.html#cargorerun-if-changedpath
     println!("cargo:rerun-if-changed=build.rs");
 
+    generate_contract_with_config("BalancerV2AaveLinearPoolFactory", |builder| {
+        builder
+            .contract_mod_override("balancer_v2_aave_linear_pool_factory")
+            .add_network(
+                "1",
+                Network {
+                    address: addr("0xD7FAD3bd59D6477cbe1BE7f646F7f1BA25b230f8"),
+                    // <https://etherscan.io/address/0xD7FAD3bd59D6477cbe1BE7f646F7f1BA25b230f8>
+                    deployment_information: Some(DeploymentInformation::BlockNumber(13718833)),
+                },
+            )
+        // Not deployed on Rinkeby
+        // Not deployed on Görli
+    });
     generate_contract_with_config("BalancerV2Authorizer", |builder| {
         builder.contract_mod_override("balancer_v2_authorizer")
     });
@@ -197,6 +211,26 @@ fn main() {
             // Not deployed on Görli
         },
     );
+    generate_contract_with_config("BalancerV2ComposableStablePoolFactory", |builder| {
+        builder
+            .contract_mod_override("balancer_v2_composable_stable_pool_factory")
+            .add_network(
+                "1",
+                Network {
+                    address: addr("0xf9ac7B9dF2b3454E841110CcE5550bD5AC6f875F"),
+                    // <https://etherscan.io/address/0xf9ac7B9dF2b3454E841110CcE5550bD5AC6f875F>
+                    deployment_information: Some(DeploymentInformation::BlockNumber(15485885)),
+                },
+            )
+        // Not deployed on Rinkeby
+        // Not deployed on Görli
+    });
+    generate_contract_with_config("BalancerV2ComposableStablePool", |builder| {
+        builder.contract_mod_override("balancer_v2_composable_stable_pool")
+    });
+    generate_contract_with_config("BalancerV2LinearPool", |builder| {
+        builder.contract_mod_override("balancer_v2_linear_pool")
+    });
     generate_contract("BalancerV2WeightedPool");
     generate_contract_with_config("BalancerV2StablePool", |builder| {
         builder.add_method_alias(
@@ -211,6 +245,10 @@ fn main() {
     generate_contract_with_config("BaoswapRouter", |builder| {
         builder.add_network_str("100", "0x6093AeBAC87d62b1A5a4cEec91204e35020E38bE")
     });
+    generate_contract("CurvePool");
+    generate_contract_with_config("CurveRegistry", |builder| {
+        builder.add_network_str("1", "0x90E00ACe148ca3b23Ac1bC8C240C2a7Dd9c2d7f5")
+    });
     generate_contract("ERC20");
     generate_contract("ERC20Mintable");
     generate_contract("GPv2AllowListAuthentication");
@@ -312,10 +350,18 @@ fn main() {
     });
     generate_contract_with_config("IUniswapV3Factory", |builder| {
         builder
-            .add_network_str("1", "0x1F98431c8aD98523631AE4a59f267346ea31F984")
+            .add_network(
+                "1",
+                Network {
+                    address: addr("0x1F98431c8aD98523631AE4a59f267346ea31F984"),
+                    // <https://etherscan.io/tx/0x1e20cd6d47d7021ae7e437792823517eeadd835df09dde17ab45afd7a5df4603>
+                    deployment_information: Some(DeploymentInformation::BlockNumber(12369621)),
+                },
+            )
             .add_network_str("4", "0x1F98431c8aD98523631AE4a59f267346ea31F984")
             .add_network_str("5", "0x1F98431c8aD98523631AE4a59f267346ea31F984")
     });
+    generate_contract("IUniswapV3Pool");
     generate_contract_with_config("IZeroEx", |builder| {
         builder
             .add_network_str("1", "0xdef1c0ded9bec7f1a1670819833240f027b25eff")
@@ -118,6 +118,14 @@ fn run() -> Result<()> {
             "balancer-labs/balancer-v2-monorepo/903d34e491a5e9c5d59dabf512c7addf1ccf9bbd/\
             pkg/deployments/tasks/20220609-stable-pool-v2/abi/StablePoolFactory.json",
         )?
+        .manual(
+            "CurvePool",
+            "Curve pools are Vyper contracts without published ABIs, only `exchange` is needed",
+        )
+        .manual(
+            "CurveRegistry",
+            "Only the pool discovery and state getters of the Curve registry are needed",
+        )
         .npm(
             "ERC20",
             "@openzeppelin/contracts@3.3.0/build/contracts/ERC20.json",
@@ -146,10 +154,30 @@ fn run() -> Result<()> {
             "BalancerV2BasePoolFactory",
             "Balancer does not publish ABIs for base contracts",
         )
+        .manual(
+            "BalancerV2ComposableStablePool",
+            "Only the pool state getters of composable stable pools are needed",
+        )
+        .manual(
+            "BalancerV2ComposableStablePoolFactory",
+            "Only the base pool factory interface is needed for indexing",
+        )
+        .manual(
+            "BalancerV2LinearPool",
+            "Only the pool state getters shared by all linear pools are needed",
+        )
+        .manual(
+            "BalancerV2AaveLinearPoolFactory",
+            "Only the base pool factory interface is needed for indexing",
+        )
         .npm(
             "IUniswapV3Factory",
             "@uniswap/v3-core@1.0.0/artifacts/contracts/interfaces/IUniswapV3Factory.sol/IUniswapV3Factory.json",
         )?
+        .manual(
+            "IUniswapV3Pool",
+            "Only the pool events are needed for indexing pool state",
+        )
         .github(
             "IZeroEx",
             "0xProject/protocol/c1177416f50c2465ee030dacc14ff996eebd4e74/\
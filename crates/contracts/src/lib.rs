@@ -3,9 +3,22 @@
 pub mod paths;
 pub mod vault;
 
+include!(concat!(
+    env!("OUT_DIR"),
+    "/BalancerV2AaveLinearPoolFactory.rs"
+));
 include!(concat!(env!("OUT_DIR"), "/BalancerV2Authorizer.rs"));
 include!(concat!(env!("OUT_DIR"), "/BalancerV2BasePool.rs"));
 include!(concat!(env!("OUT_DIR"), "/BalancerV2BasePoolFactory.rs"));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/BalancerV2ComposableStablePool.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/BalancerV2ComposableStablePoolFactory.rs"
+));
+include!(concat!(env!("OUT_DIR"), "/BalancerV2LinearPool.rs"));
 include!(concat!(
     env!("OUT_DIR"),
     "/BalancerV2LiquidityBootstrappingPool.rs"
@@ -36,6 +49,8 @@ include!(concat!(
 ));
 include!(concat!(env!("OUT_DIR"), "/BaoswapFactory.rs"));
 include!(concat!(env!("OUT_DIR"), "/BaoswapRouter.rs"));
+include!(concat!(env!("OUT_DIR"), "/CurvePool.rs"));
+include!(concat!(env!("OUT_DIR"), "/CurveRegistry.rs"));
 include!(concat!(env!("OUT_DIR"), "/ERC20.rs"));
 include!(concat!(env!("OUT_DIR"), "/ERC20Mintable.rs"));
 include!(concat!(env!("OUT_DIR"), "/GPv2AllowListAuthentication.rs"));
@@ -61,6 +76,7 @@ include!(concat!(env!("OUT_DIR"), "/UniswapV2Router02.rs"));
 include!(concat!(env!("OUT_DIR"), "/UniswapV3SwapRouter.rs"));
 include!(concat!(env!("OUT_DIR"), "/WETH9.rs"));
 include!(concat!(env!("OUT_DIR"), "/IUniswapV3Factory.rs"));
+include!(concat!(env!("OUT_DIR"), "/IUniswapV3Pool.rs"));
 include!(concat!(env!("OUT_DIR"), "/IZeroEx.rs"));
 include!(concat!(env!("OUT_DIR"), "/CowProtocolToken.rs"));
 include!(concat!(env!("OUT_DIR"), "/CowProtocolVirtualToken.rs"));
@@ -148,6 +164,8 @@ mod tests {
             assert_has_deployment_address!(HoneyswapRouter for *network);
         }
         assert_has_deployment_address!(BalancerV2StablePoolFactoryV2 for 1);
+        assert_has_deployment_address!(BalancerV2ComposableStablePoolFactory for 1);
+        assert_has_deployment_address!(BalancerV2AaveLinearPoolFactory for 1);
         assert_has_deployment_address!(UniswapV3SwapRouter for 1);
     }
 
@@ -174,5 +192,7 @@ mod tests {
             assert_has_deployment_information!(BalancerV2StablePoolFactory for *network);
         }
         assert_has_deployment_information!(BalancerV2StablePoolFactoryV2 for 1);
+        assert_has_deployment_information!(BalancerV2ComposableStablePoolFactory for 1);
+        assert_has_deployment_information!(BalancerV2AaveLinearPoolFactory for 1);
     }
 }
@@ -0,0 +1,69 @@
+use crate::AppId;
+use sqlx::PgConnection;
+
+/// Stores the full app data document for an app data hash.
+///
+/// Since the hash commits to the document, inserting an already known hash is a no-op.
+pub async fn insert(
+    ex: &mut PgConnection,
+    contract_app_data: &AppId,
+    full_app_data: &[u8],
+) -> Result<(), sqlx::Error> {
+    const QUERY: &str = r#"
+INSERT INTO app_data (contract_app_data, full_app_data)
+VALUES ($1, $2)
+ON CONFLICT DO NOTHING
+    "#;
+    sqlx::query(QUERY)
+        .bind(contract_app_data)
+        .bind(full_app_data)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn fetch(
+    ex: &mut PgConnection,
+    contract_app_data: &AppId,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    const QUERY: &str = r#"
+SELECT full_app_data
+FROM app_data
+WHERE contract_app_data = $1
+    "#;
+    sqlx::query_scalar(QUERY)
+        .bind(contract_app_data)
+        .fetch_optional(ex)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::byte_array::ByteArray;
+    use sqlx::Connection;
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_app_data_roundtrip() {
+        let mut db = PgConnection::connect("postgresql://").await.unwrap();
+        let mut db = db.begin().await.unwrap();
+        crate::clear_DANGER_(&mut db).await.unwrap();
+
+        let contract_app_data = ByteArray([1; 32]);
+        assert_eq!(fetch(&mut db, &contract_app_data).await.unwrap(), None);
+
+        insert(&mut db, &contract_app_data, b"{}").await.unwrap();
+        assert_eq!(
+            fetch(&mut db, &contract_app_data).await.unwrap(),
+            Some(b"{}".to_vec())
+        );
+
+        // Inserting the same hash again keeps the original document.
+        insert(&mut db, &contract_app_data, b"[]").await.unwrap();
+        assert_eq!(
+            fetch(&mut db, &contract_app_data).await.unwrap(),
+            Some(b"{}".to_vec())
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod app_data;
 pub mod byte_array;
 pub mod events;
 pub mod orders;
@@ -36,6 +37,8 @@ pub const ALL_TABLES: &[&str] = &[
     "presignature_events",
     "order_quotes",
     "solver_competitions",
+    "app_data",
+    "order_replacements",
 ];
 
 /// Delete all data in the database. Only used by tests.
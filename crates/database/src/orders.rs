@@ -250,6 +250,38 @@ AND cancellation_timestamp IS NULL
         .map(|_| ())
 }
 
+/// Locks the order row until the end of the transaction so that it can't be modified concurrently.
+///
+/// Returns whether the order exists.
+pub async fn lock_order(ex: &mut PgConnection, uid: &OrderUid) -> Result<bool, sqlx::Error> {
+    const QUERY: &str = r#"
+SELECT uid FROM orders
+WHERE uid = $1
+FOR UPDATE
+    "#;
+    let row: Option<(OrderUid,)> = sqlx::query_as(QUERY).bind(uid).fetch_optional(ex).await?;
+    Ok(row.is_some())
+}
+
+pub async fn insert_order_replacement(
+    ex: &mut PgConnection,
+    old_order_uid: &OrderUid,
+    new_order_uid: &OrderUid,
+    timestamp: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    const QUERY: &str = r#"
+INSERT INTO order_replacements (old_order_uid, new_order_uid, timestamp)
+VALUES ($1, $2, $3)
+    "#;
+    sqlx::query(QUERY)
+        .bind(old_order_uid)
+        .bind(new_order_uid)
+        .bind(timestamp)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
 /// Order with extra information from other tables. Has all the information needed to construct a model::Order.
 #[derive(sqlx::FromRow)]
 pub struct FullOrder {
@@ -278,6 +310,9 @@ pub struct FullOrder {
     pub buy_token_balance: BuyTokenDestination,
     pub presignature_pending: bool,
     pub is_liquidity_order: bool,
+    pub full_app_data: Option<Vec<u8>>,
+    pub replaced_by: Option<OrderUid>,
+    pub replaces: Option<OrderUid>,
 }
 
 // When querying orders we have several specialized use cases working with their own filtering,
@@ -317,7 +352,10 @@ o.is_liquidity_order,
     WHERE o.uid = p.order_uid
     ORDER BY p.block_number DESC, p.log_index DESC
     LIMIT 1
-), true)) AS presignature_pending
+), true)) AS presignature_pending,
+(SELECT ad.full_app_data FROM app_data ad WHERE ad.contract_app_data = o.app_data) AS full_app_data,
+(SELECT r.new_order_uid FROM order_replacements r WHERE r.old_order_uid = o.uid) AS replaced_by,
+(SELECT r.old_order_uid FROM order_replacements r WHERE r.new_order_uid = o.uid) AS replaces
 "#;
 
 const ORDERS_FROM: &str = "orders o";
@@ -525,6 +563,52 @@ mod tests {
         assert_eq!(time, order.cancellation_timestamp.unwrap());
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_order_replacement() {
+        let mut db = PgConnection::connect("postgresql://").await.unwrap();
+        let mut db = db.begin().await.unwrap();
+        crate::clear_DANGER_(&mut db).await.unwrap();
+
+        let old_order = Order {
+            uid: ByteArray([1; 56]),
+            ..Default::default()
+        };
+        let new_order = Order {
+            uid: ByteArray([2; 56]),
+            ..Default::default()
+        };
+        assert!(!lock_order(&mut db, &old_order.uid).await.unwrap());
+        insert_order(&mut db, &old_order).await.unwrap();
+        insert_order(&mut db, &new_order).await.unwrap();
+        assert!(lock_order(&mut db, &old_order.uid).await.unwrap());
+
+        let time = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234567890, 0), Utc);
+        insert_order_replacement(&mut db, &old_order.uid, &new_order.uid, time)
+            .await
+            .unwrap();
+
+        let old_order_ = single_full_order(&mut db, &old_order.uid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(old_order_.replaced_by, Some(new_order.uid));
+        assert_eq!(old_order_.replaces, None);
+        let new_order_ = single_full_order(&mut db, &new_order.uid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(new_order_.replaced_by, None);
+        assert_eq!(new_order_.replaces, Some(old_order.uid));
+
+        // An order can only be replaced once.
+        assert!(
+            insert_order_replacement(&mut db, &old_order.uid, &ByteArray([3; 56]), time)
+                .await
+                .is_err()
+        );
+    }
+
     // In the schema we set the type of executed amounts in individual events to a 78 decimal digit
     // number. Summing over multiple events could overflow this because the smart contract only
     // guarantees that the filled amount (which amount that is depends on order type) does not
@@ -1,6 +1,12 @@
-use crate::{commit_reveal::SettlementSummary, driver::Driver};
+use crate::{
+    commit_reveal::{RevealError, SettlementSummary},
+    driver::Driver,
+};
 use anyhow::Result;
+use model::order::OrderUid;
+use primitive_types::H160;
 use shared::api::{convert_json_response, error, extract_payload, ApiReply, IntoWarpReply};
+use solver::settlement_rater::balance_changes::Balance;
 use std::{convert::Infallible, sync::Arc};
 use tracing::Instrument;
 use warp::{hyper::StatusCode, reply::with_status, Filter, Rejection};
@@ -33,20 +39,69 @@ pub fn post_execute(
 
 #[derive(thiserror::Error, Debug)]
 pub enum ExecuteError {
-    #[error("settlement execution rejected")]
-    ExecutionRejected,
+    #[error("settlement execution rejected: {0}")]
+    ExecutionRejected(RevealError),
+    #[error("settlement executes orders {0:?} at worse prices than on-chain liquidity")]
+    EbboViolation(Vec<OrderUid>),
+    #[error("settlement simulation failed: {0:?}")]
+    SimulationFailed(anyhow::Error),
+    #[error("traders receive less or pay more than their limit prices allow: {0:?}")]
+    LimitPriceViolated(Vec<Balance>),
+    #[error(
+        "settlement contract loses buffers worth {loss} at clearing prices but only \
+         {tolerated_loss} is tolerated"
+    )]
+    TokenConservationViolated { loss: f64, tolerated_loss: f64 },
+    #[error("settlement contract loses buffers of tokens {0:?} that have no price")]
+    UnpricedBufferLoss(Vec<H160>),
+    #[error("balance changes of orders {0:?} can't be verified")]
+    UnverifiableTrades(Vec<OrderUid>),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl From<RevealError> for ExecuteError {
+    fn from(err: RevealError) -> Self {
+        match err {
+            RevealError::Other(err) => Self::Other(err),
+            err => Self::ExecutionRejected(err),
+        }
+    }
+}
+
 impl IntoWarpReply for ExecuteError {
     fn into_warp_reply(self) -> ApiReply {
         match self {
-            Self::ExecutionRejected => with_status(
-                error(
-                    "ExecutionRejected",
-                    "the solver no longer wants to execute the settlement",
-                ),
+            err @ Self::ExecutionRejected(RevealError::UnknownSettlement(_)) => with_status(
+                error("UnknownSettlement", err.to_string()),
+                StatusCode::BAD_REQUEST,
+            ),
+            err @ Self::ExecutionRejected(_) => with_status(
+                error("ExecutionRejected", err.to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            err @ Self::EbboViolation(_) => with_status(
+                error("EbboViolation", err.to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            err @ Self::SimulationFailed(_) => with_status(
+                error("SimulationFailed", err.to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            err @ Self::LimitPriceViolated(_) => with_status(
+                error("LimitPriceViolated", err.to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            err @ Self::TokenConservationViolated { .. } => with_status(
+                error("TokenConservationViolated", err.to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            err @ Self::UnpricedBufferLoss(_) => with_status(
+                error("UnpricedBufferLoss", err.to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            err @ Self::UnverifiableTrades(_) => with_status(
+                error("UnverifiableTrades", err.to_string()),
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
             Self::Other(err) => err.into_warp_reply(),
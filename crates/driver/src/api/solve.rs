@@ -36,6 +36,8 @@ pub fn post_solve(
 pub enum SolveError {
     #[error("not implemented")]
     NotImplemented,
+    #[error("auction contains no user orders that are old enough to get settled")]
+    NoSolvableOrders,
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -47,6 +49,13 @@ impl IntoWarpReply for SolveError {
                 error("Route not yet implemented", "try again later"),
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
+            Self::NoSolvableOrders => with_status(
+                error(
+                    "NoSolvableOrders",
+                    "the auction contains no mature user orders",
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::Other(err) => err.into_warp_reply(),
         }
     }
@@ -1,13 +1,15 @@
+use primitive_types::{H160, H256};
 use reqwest::Url;
 use shared::{
     arguments::{display_list, display_option, duration_from_seconds},
     gas_price_estimation::GasEstimatorType,
+    sources::{balancer_v2::BalancerFactoryKind, BaselineSource},
 };
 use solver::{
     arguments::TransactionStrategyArg, settlement_access_list::AccessListEstimatorType,
-    solver::ExternalSolverArg,
+    settlement_rater::SettlementScoring, solver::ExternalSolverArg,
 };
-use std::{net::SocketAddr, time::Duration};
+use std::{net::SocketAddr, num::NonZeroU64, path::PathBuf, time::Duration};
 use tracing::level_filters::LevelFilter;
 
 #[derive(clap::Parser)]
@@ -25,7 +27,10 @@ pub struct Arguments {
     #[clap(long, env, default_value = "error")]
     pub log_stderr_threshold: LevelFilter,
 
-    /// List of solvers in the form of `name|url|account`.
+    /// List of solvers in the form of `name|url|account` or
+    /// `name|url|account|version`, where the optional version of the batch
+    /// auction instance format sent to the solver is either `v1` (default) or
+    /// `v2`.
     #[clap(long, env, use_value_delimiter = true)]
     pub solvers: Vec<ExternalSolverArg>,
 
@@ -174,6 +179,128 @@ pub struct Arguments {
     /// BlockNative requires api key to work. Optional since BlockNative could be skipped in gas estimators.
     #[clap(long, env)]
     pub blocknative_api_key: Option<String>,
+
+    /// Base tokens used for finding multi-hop paths between multiple AMMs
+    /// Should be the most liquid tokens of the given network.
+    #[clap(long, env, use_value_delimiter = true)]
+    pub base_tokens: Vec<H160>,
+
+    /// The maximum number of AMM paths the baseline price estimator splits an order across to
+    /// reduce price impact. 1 disables splitting.
+    #[clap(long, env, default_value = "1")]
+    pub baseline_max_split_paths: usize,
+
+    /// Which liquidity sources to collect liquidity from for the solvers. The uniswap-like ones
+    /// are also used by the baseline price estimator.
+    #[clap(long, env, arg_enum, ignore_case = true, use_value_delimiter = true)]
+    pub baseline_sources: Option<Vec<BaselineSource>>,
+
+    /// The number of blocks kept in the pool cache.
+    #[clap(long, env, default_value = "10")]
+    pub pool_cache_blocks: NonZeroU64,
+
+    /// The number of pairs that are automatically updated in the pool cache.
+    #[clap(long, env, default_value = "4")]
+    pub pool_cache_maximum_recent_block_age: u64,
+
+    /// How often to retry requests in the pool cache.
+    #[clap(long, env, default_value = "5")]
+    pub pool_cache_maximum_retries: u32,
+
+    /// How long to sleep in seconds between retries in the pool cache.
+    #[clap(long, env, default_value = "1", parse(try_from_str = duration_from_seconds))]
+    pub pool_cache_delay_between_retries_seconds: Duration,
+
+    /// How often in seconds we poll the node to check if the current block has changed.
+    #[clap(
+        long,
+        env,
+        default_value = "5",
+        parse(try_from_str = duration_from_seconds),
+    )]
+    pub block_stream_poll_interval_seconds: Duration,
+
+    #[clap(long, env)]
+    pub zeroex_url: Option<String>,
+
+    #[clap(long, env)]
+    pub zeroex_api_key: Option<String>,
+
+    /// The Balancer V2 factories to consider for indexing liquidity. Allows
+    /// specific pool kinds to be disabled via configuration. Will use all
+    /// supported Balancer V2 factory kinds if not specified.
+    #[clap(long, env, arg_enum, ignore_case = true, use_value_delimiter = true)]
+    pub balancer_factories: Option<Vec<BalancerFactoryKind>>,
+
+    /// Deny list of balancer pool ids.
+    #[clap(long, env, use_value_delimiter = true)]
+    pub balancer_pool_deny_list: Vec<H256>,
+
+    /// Path of the file in which the Uniswap V3 pool indexer persists its checkpoints. Without a
+    /// checkpoint, pool events are replayed from the Uniswap V3 factory deployment on startup.
+    #[clap(long, env)]
+    pub uniswap_v3_checkpoint_path: Option<PathBuf>,
+
+    /// If set, settlements are rejected if any user trade gets a worse price than the baseline
+    /// price estimate for the same amount (EBBO). The value is the tolerated price deviation as a
+    /// fraction, e.g. 0.01 for 1%.
+    #[clap(long, env, parse(try_from_str = shared::arguments::parse_percentage_factor))]
+    pub ebbo_tolerance: Option<f64>,
+
+    /// The value the settlement contract may lose from its buffers when executing a settlement,
+    /// as a fraction of the value sold by the traders. Both are valued at the settlement's
+    /// clearing prices. Settlements are simulated with the trace_callMany API, which the node has
+    /// to support.
+    #[clap(
+        long,
+        env,
+        default_value = "0",
+        parse(try_from_str = shared::arguments::parse_percentage_factor)
+    )]
+    pub buffer_loss_tolerance: f64,
+
+    /// An auction must contain at least one user order older than this duration in seconds for
+    /// it to be solved. Younger orders only get settled together with older ones.
+    #[clap(
+        long,
+        env,
+        default_value = "30",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    pub min_order_age: Duration,
+
+    /// The maximum amount of time in seconds a solver is allowed to take.
+    #[clap(
+        long,
+        env,
+        default_value = "30",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    pub solver_time_limit: Duration,
+
+    /// Fee scaling factor for objective value. This controls the constant
+    /// factor by which order fees are multiplied with.
+    #[clap(long, env, default_value = "1", parse(try_from_str = shared::arguments::parse_unbounded_factor))]
+    pub fee_objective_scaling_factor: f64,
+
+    /// How to rank the settlements of a solver before committing to the best one.
+    /// `ObjectiveValue`: surplus plus fees minus gas cost.
+    /// `RiskAdjusted`: like `ObjectiveValue` but surplus and fees are weighted by the probability
+    /// of the settlement not reverting.
+    #[clap(
+        long,
+        env,
+        default_value = "ObjectiveValue",
+        arg_enum,
+        ignore_case = true
+    )]
+    pub settlement_scoring: SettlementScoring,
+
+    /// Rate settlements by the surplus observed when tracing their simulation instead of the
+    /// surplus they claim, which accounts for fee-on-transfer tokens and similar. Requires a node
+    /// supporting the trace_callMany API.
+    #[clap(long, env)]
+    pub trace_settlement_balance_changes: bool,
 }
 
 impl std::fmt::Display for Arguments {
@@ -245,6 +372,75 @@ impl std::fmt::Display for Arguments {
                 .map(|_| "SECRET")
                 .unwrap_or("None")
         )?;
+        writeln!(f, "base_tokens: {:?}", self.base_tokens)?;
+        writeln!(
+            f,
+            "baseline_max_split_paths: {}",
+            self.baseline_max_split_paths
+        )?;
+        writeln!(f, "baseline_sources: {:?}", self.baseline_sources)?;
+        writeln!(f, "pool_cache_blocks: {}", self.pool_cache_blocks)?;
+        writeln!(
+            f,
+            "pool_cache_maximum_recent_block_age: {}",
+            self.pool_cache_maximum_recent_block_age
+        )?;
+        writeln!(
+            f,
+            "pool_cache_maximum_retries: {}",
+            self.pool_cache_maximum_retries
+        )?;
+        writeln!(
+            f,
+            "pool_cache_delay_between_retries_seconds: {:?}",
+            self.pool_cache_delay_between_retries_seconds
+        )?;
+        writeln!(
+            f,
+            "block_stream_poll_interval_seconds: {:?}",
+            self.block_stream_poll_interval_seconds
+        )?;
+        writeln!(
+            f,
+            "zeroex_url: {}",
+            self.zeroex_url.as_deref().unwrap_or("None")
+        )?;
+        writeln!(
+            f,
+            "zeroex_api_key: {}",
+            self.zeroex_api_key
+                .as_ref()
+                .map(|_| "SECRET")
+                .unwrap_or("None")
+        )?;
+        writeln!(f, "balancer_factories: {:?}", self.balancer_factories)?;
+        writeln!(
+            f,
+            "balancer_pool_deny_list: {:?}",
+            self.balancer_pool_deny_list
+        )?;
+        writeln!(
+            f,
+            "uniswap_v3_checkpoint_path: {:?}",
+            self.uniswap_v3_checkpoint_path
+        )?;
+        write!(f, "ebbo_tolerance: ")?;
+        display_option(&self.ebbo_tolerance, f)?;
+        writeln!(f)?;
+        writeln!(f, "buffer_loss_tolerance: {}", self.buffer_loss_tolerance)?;
+        writeln!(f, "min_order_age: {:?}", self.min_order_age)?;
+        writeln!(f, "solver_time_limit: {:?}", self.solver_time_limit)?;
+        writeln!(
+            f,
+            "fee_objective_scaling_factor: {}",
+            self.fee_objective_scaling_factor
+        )?;
+        writeln!(f, "settlement_scoring: {:?}", self.settlement_scoring)?;
+        writeln!(
+            f,
+            "trace_settlement_balance_changes: {}",
+            self.trace_settlement_balance_changes
+        )?;
         Ok(())
     }
 }
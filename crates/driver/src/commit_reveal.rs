@@ -1,16 +1,36 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context as _, Result};
+use contracts::GPv2Settlement;
+use gas_estimation::GasPriceEstimating;
 use model::order::OrderUid;
+use num::ToPrimitive as _;
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
+use shared::Web3;
 use solver::{
+    driver::solver_settlements::{has_user_order, retain_mature_settlements},
     settlement::Settlement,
+    settlement_rater::{SettlementRating, SettlementScoring},
+    settlement_simulation::simulate_and_estimate_gas,
     solver::{Auction, Solver},
 };
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use web3::types::{AccessList, BlockNumber};
 
 /// A `SolutionSummary` holds all information solvers are willing to disclose during settlement
 /// competition. It does **not** have to include the call data, yet.
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct SettlementSummary {
+    /// Identifies the committed settlement when it gets revealed. IDs of committed settlements
+    /// start at 1, so the default of 0 never identifies one.
+    #[serde(default)]
+    pub settlement_id: u64,
     /// Surplus is denominated in the chain's native token and based off of the auction's external
     /// prices.
     pub surplus: f64,
@@ -28,9 +48,19 @@ pub trait CommitRevealSolving: Send + Sync {
     async fn commit(&self, auction: Auction) -> Result<SettlementSummary>;
 
     /// Finalizes solution for a previously calculated `SolutionSummary` which can be used to compute
-    /// executable call data. If the solver no longer wants to execute the solution it returns
-    /// `Ok(None)`.
-    async fn reveal(&self, summary: SettlementSummary) -> Result<Option<Settlement>>;
+    /// executable call data. If the solver no longer wants to execute the solution, for example
+    /// because it doesn't simulate anymore, it returns a `RevealError` with the reason instead.
+    async fn reveal(&self, summary: SettlementSummary) -> Result<Settlement, RevealError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RevealError {
+    #[error("settlement {0} is unknown or outdated")]
+    UnknownSettlement(u64),
+    #[error("settlement no longer simulates at the current block: {0:?}")]
+    SimulationFailed(anyhow::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
 }
 
 // Wraps a legacy `Solver` implementation and makes it compatible with the commit reveal protocol.
@@ -39,23 +69,362 @@ pub trait CommitRevealSolving: Send + Sync {
 // For now this wrapper is only a compatibility layer to let us use the new driver with existing
 // solvers for faster development.
 pub struct CommitRevealSolver {
-    #[allow(dead_code)]
-    solver: Box<dyn Solver>,
+    solver: Arc<dyn Solver>,
+    settlement_rater: Arc<dyn SettlementRating>,
+    settlement_scoring: SettlementScoring,
+    /// Settlements need to contain at least one user order older than this or one settled
+    /// together with such an order.
+    min_order_age: Duration,
+    gas_price_estimator: Arc<dyn GasPriceEstimating>,
+    settlement_contract: GPv2Settlement,
+    web3: Web3,
+    /// The committed settlements by their summary's ID. Only the settlement of the latest
+    /// auction can still win, so older ones get dropped with every commit.
+    committed: Mutex<HashMap<u64, (Settlement, Option<AccessList>)>>,
+    next_settlement_id: AtomicU64,
 }
 
 impl CommitRevealSolver {
-    pub fn new(solver: Box<dyn Solver>) -> Self {
-        Self { solver }
+    pub fn new(
+        solver: Arc<dyn Solver>,
+        settlement_rater: Arc<dyn SettlementRating>,
+        settlement_scoring: SettlementScoring,
+        min_order_age: Duration,
+        gas_price_estimator: Arc<dyn GasPriceEstimating>,
+        settlement_contract: GPv2Settlement,
+        web3: Web3,
+    ) -> Self {
+        Self {
+            solver,
+            settlement_rater,
+            settlement_scoring,
+            min_order_age,
+            gas_price_estimator,
+            settlement_contract,
+            web3,
+            committed: Default::default(),
+            next_settlement_id: AtomicU64::new(1),
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl CommitRevealSolving for CommitRevealSolver {
-    async fn commit(&self, _auction: Auction) -> Result<SettlementSummary> {
-        todo!()
+    async fn commit(&self, auction: Auction) -> Result<SettlementSummary> {
+        let external_prices = auction.external_prices.clone();
+        let settlements =
+            tokio::time::timeout_at(auction.deadline.into(), self.solver.solve(auction))
+                .await
+                .map_err(|_| anyhow!("solver timed out"))??;
+        let settlements = settlements
+            .into_iter()
+            .filter(has_user_order)
+            .map(|settlement| (self.solver.clone(), settlement))
+            .collect::<Vec<_>>();
+        let settlements = retain_mature_settlements(self.min_order_age, settlements);
+
+        let gas_price = self
+            .gas_price_estimator
+            .estimate()
+            .await
+            .context("failed to estimate gas price")?;
+        let (rated_settlements, errors) = self
+            .settlement_rater
+            .rate_settlements(settlements, &external_prices, gas_price)
+            .await?;
+        for (_, settlement, _, err) in errors {
+            tracing::debug!(?settlement, ?err, "settlement failed simulation");
+        }
+        let (_, best, access_list) = rated_settlements
+            .into_iter()
+            .max_by_key(|(_, settlement, _)| settlement.score(self.settlement_scoring))
+            .context("solver found no valid settlement")?;
+
+        let surplus = best
+            .surplus
+            .to_f64()
+            .context("could not convert surplus to f64")?;
+        let settlement_id = self.next_settlement_id.fetch_add(1, Ordering::SeqCst);
+        let summary = SettlementSummary {
+            settlement_id,
+            surplus,
+            gas_reimbursement: best.gas_estimate,
+            settled_orders: best
+                .settlement
+                .traded_orders()
+                .filter(|order| !order.metadata.is_liquidity_order)
+                .map(|order| order.metadata.uid)
+                .collect(),
+        };
+        let mut committed = self.committed.lock().unwrap();
+        committed.clear();
+        committed.insert(settlement_id, (best.settlement, access_list));
+        Ok(summary)
+    }
+
+    async fn reveal(&self, summary: SettlementSummary) -> Result<Settlement, RevealError> {
+        let (settlement, access_list) = self
+            .committed
+            .lock()
+            .unwrap()
+            .remove(&summary.settlement_id)
+            .ok_or(RevealError::UnknownSettlement(summary.settlement_id))?;
+
+        let gas_price = self
+            .gas_price_estimator
+            .estimate()
+            .await
+            .context("failed to estimate gas price")?;
+        let simulation = simulate_and_estimate_gas(
+            std::iter::once((
+                self.solver.account().clone(),
+                settlement.clone(),
+                access_list,
+            )),
+            &self.settlement_contract,
+            &self.web3,
+            gas_price,
+            BlockNumber::Latest,
+        )
+        .await
+        .context("failed to simulate settlement")?
+        .pop()
+        .context("missing settlement simulation")?;
+        simulation.map_err(|err| RevealError::SimulationFailed(err.into()))?;
+        Ok(settlement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use ethcontract::{Account, H160};
+    use gas_estimation::GasPrice1559;
+    use maplit::hashmap;
+    use model::order::{Order, OrderData, OrderKind, OrderMetadata};
+    use num::{BigRational, Zero as _};
+    use serde_json::json;
+    use shared::{
+        dummy_contract,
+        gas_price_estimation::FakeGasPriceEstimator,
+        transport::{dummy::DummyTransport, mock::MockTransport},
+        Web3Transport,
+    };
+    use solver::{
+        driver::solver_settlements::RatedSettlement, settlement_rater::MockSettlementRating,
+    };
+
+    struct FixedSolver(Vec<Settlement>, Account);
+
+    #[async_trait::async_trait]
+    impl Solver for FixedSolver {
+        async fn solve(&self, _: Auction) -> Result<Vec<Settlement>> {
+            Ok(self.0.clone())
+        }
+
+        fn account(&self) -> &Account {
+            &self.1
+        }
+
+        fn name(&self) -> &'static str {
+            "FixedSolver"
+        }
+    }
+
+    fn settlement(uid: u8) -> Settlement {
+        settlement_created_at(uid, Default::default())
+    }
+
+    fn settlement_created_at(uid: u8, creation_date: DateTime<Utc>) -> Settlement {
+        let token = |value| H160::from_low_u64_be(value);
+        let mut settlement =
+            Settlement::new(hashmap! { token(1) => 1.into(), token(2) => 1.into() });
+        settlement
+            .encoder
+            .add_trade(
+                Order {
+                    data: OrderData {
+                        sell_token: token(1),
+                        buy_token: token(2),
+                        sell_amount: 100.into(),
+                        buy_amount: 100.into(),
+                        kind: OrderKind::Sell,
+                        ..Default::default()
+                    },
+                    metadata: OrderMetadata {
+                        uid: OrderUid([uid; 56]),
+                        creation_date,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                100.into(),
+                0.into(),
+            )
+            .unwrap();
+        settlement
+    }
+
+    fn commit_reveal_solver(
+        settlements: Vec<Settlement>,
+        settlement_rater: MockSettlementRating,
+    ) -> CommitRevealSolver {
+        commit_reveal_solver_with_web3(
+            settlements,
+            settlement_rater,
+            Web3::new(Web3Transport::new(DummyTransport)),
+        )
+    }
+
+    fn commit_reveal_solver_with_web3(
+        settlements: Vec<Settlement>,
+        settlement_rater: MockSettlementRating,
+        web3: Web3,
+    ) -> CommitRevealSolver {
+        CommitRevealSolver::new(
+            Arc::new(FixedSolver(
+                settlements,
+                Account::Local(H160::from_low_u64_be(1), None),
+            )),
+            Arc::new(settlement_rater),
+            SettlementScoring::ObjectiveValue,
+            Duration::from_secs(30),
+            Arc::new(FakeGasPriceEstimator::new(GasPrice1559::default())),
+            dummy_contract!(GPv2Settlement, H160::zero()),
+            web3,
+        )
+    }
+
+    fn rate_settlements_by_id() -> MockSettlementRating {
+        let mut settlement_rater = MockSettlementRating::new();
+        settlement_rater
+            .expect_rate_settlements()
+            .returning(|settlements, _, _| {
+                let rated = settlements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, (solver, settlement))| {
+                        let rated = RatedSettlement {
+                            id,
+                            settlement,
+                            surplus: BigRational::from_integer((id as i64 + 1).into()),
+                            unscaled_subsidized_fee: BigRational::zero(),
+                            scaled_unsubsidized_fee: BigRational::zero(),
+                            gas_estimate: 1000.into(),
+                            gas_price: BigRational::from_integer(2.into()),
+                            revert_probability: 0.,
+                            deviates_from_simulation: false,
+                        };
+                        (solver, rated, None)
+                    })
+                    .collect();
+                Ok((rated, Vec::new()))
+            });
+        settlement_rater
+    }
+
+    fn estimate_gas_transport(result: web3::Result<serde_json::Value>) -> Web3 {
+        let transport = MockTransport::new();
+        transport
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_estimateGas")
+            .return_once(move |_, _| result);
+        Web3::new(Web3Transport::new(transport))
+    }
+
+    #[tokio::test]
+    async fn commits_to_best_settlement() {
+        let solver =
+            commit_reveal_solver(vec![settlement(1), settlement(2)], rate_settlements_by_id());
+
+        let summary = solver.commit(Auction::default()).await.unwrap();
+        assert_ne!(summary.settlement_id, 0);
+        assert_eq!(summary.surplus, 2.);
+        assert_eq!(summary.gas_reimbursement, 1000.into());
+        assert_eq!(summary.settled_orders, vec![OrderUid([2; 56])]);
+        assert!(solver
+            .committed
+            .lock()
+            .unwrap()
+            .contains_key(&summary.settlement_id));
+    }
+
+    #[tokio::test]
+    async fn fails_to_commit_without_settlements() {
+        let mut settlement_rater = MockSettlementRating::new();
+        settlement_rater
+            .expect_rate_settlements()
+            .returning(|_, _, _| Ok((Vec::new(), Vec::new())));
+        let solver = commit_reveal_solver(vec![settlement(1)], settlement_rater);
+
+        assert!(solver.commit(Auction::default()).await.is_err());
     }
 
-    async fn reveal(&self, _summary: SettlementSummary) -> Result<Option<Settlement>> {
-        todo!()
+    #[tokio::test]
+    async fn does_not_commit_to_immature_settlements() {
+        let mut settlement_rater = MockSettlementRating::new();
+        settlement_rater
+            .expect_rate_settlements()
+            .returning(|settlements, _, _| {
+                assert!(settlements.is_empty());
+                Ok((Vec::new(), Vec::new()))
+            });
+        let solver =
+            commit_reveal_solver(vec![settlement_created_at(1, Utc::now())], settlement_rater);
+
+        assert!(solver.commit(Auction::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn does_not_reveal_unknown_settlement() {
+        let solver = commit_reveal_solver(Vec::new(), MockSettlementRating::new());
+
+        let revealed = solver
+            .reveal(SettlementSummary {
+                settlement_id: 42,
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(revealed, Err(RevealError::UnknownSettlement(42))));
+    }
+
+    #[tokio::test]
+    async fn reveals_committed_settlement() {
+        let solver = commit_reveal_solver_with_web3(
+            vec![settlement(1)],
+            rate_settlements_by_id(),
+            estimate_gas_transport(Ok(json!("0x5208"))),
+        );
+
+        let summary = solver.commit(Auction::default()).await.unwrap();
+        let revealed = solver.reveal(summary.clone()).await.unwrap();
+        let revealed_orders: Vec<_> = revealed
+            .traded_orders()
+            .map(|order| order.metadata.uid)
+            .collect();
+        assert_eq!(revealed_orders, summary.settled_orders);
+
+        // A settlement can only be revealed once.
+        assert!(matches!(
+            solver.reveal(summary).await,
+            Err(RevealError::UnknownSettlement(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_reveal_settlement_that_fails_simulation() {
+        let solver = commit_reveal_solver_with_web3(
+            vec![settlement(1)],
+            rate_settlements_by_id(),
+            estimate_gas_transport(Err(web3::Error::Unreachable)),
+        );
+
+        let summary = solver.commit(Auction::default()).await.unwrap();
+        assert!(matches!(
+            solver.reveal(summary).await,
+            Err(RevealError::SimulationFailed(_))
+        ));
     }
 }
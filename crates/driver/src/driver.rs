@@ -1,15 +1,55 @@
 use crate::{
     api::{execute::ExecuteError, solve::SolveError},
     commit_reveal::{CommitRevealSolving, SettlementSummary},
+    settlement_validation::SettlementValidator,
 };
-use anyhow::Result;
+use anyhow::{Context as _, Result};
+use gas_estimation::GasPriceEstimating;
 use model::auction::Auction;
-use solver::{settlement::Settlement, settlement_submission::SolutionSubmitter};
-use std::sync::Arc;
+use primitive_types::H160;
+use shared::recent_block_cache::Block;
+use solver::{
+    auction_preprocessing,
+    in_flight_orders::InFlightOrders,
+    liquidity::order_converter::OrderConverter,
+    liquidity_collector::LiquidityCollector,
+    settlement::{external_prices::ExternalPrices, Settlement},
+    settlement_ebbo::EbboValidator,
+    settlement_submission::SolutionSubmitter,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// The parts of an auction that are needed to validate the settlement committed for it.
+pub struct CommittedAuction {
+    /// The block the liquidity of the auction was fetched at.
+    pub block: u64,
+    pub external_prices: ExternalPrices,
+}
 
 pub struct Driver {
+    pub name: String,
+    /// The address of the account the solver settles from.
+    pub solver_address: H160,
     pub solver: Arc<dyn CommitRevealSolving>,
     pub submitter: Arc<SolutionSubmitter>,
+    pub validator: Arc<SettlementValidator>,
+    /// Rejects settlements giving users worse prices than the on-chain liquidity would.
+    pub ebbo_validator: Option<Arc<EbboValidator>>,
+    pub liquidity_collector: Arc<LiquidityCollector>,
+    pub order_converter: Arc<OrderConverter>,
+    pub min_order_age: Duration,
+    pub solver_time_limit: Duration,
+    pub in_flight_orders: Mutex<InFlightOrders>,
+    pub next_run_id: AtomicU64,
+    /// The auctions of the committed settlements by their summary's ID.
+    pub committed_auctions: Mutex<HashMap<u64, CommittedAuction>>,
 }
 
 impl Driver {
@@ -17,29 +57,124 @@ impl Driver {
     /// for the solver.
     pub async fn on_auction_started(
         &self,
-        _auction: Auction,
+        mut auction: Auction,
     ) -> Result<SettlementSummary, SolveError> {
-        // TODO sanity checks
-        // TODO liquidity collection
-        let auction = solver::solver::Auction::default();
-        self.solver.commit(auction).await.map_err(SolveError::from)
+        let before_count = auction.orders.len();
+        self.in_flight_orders
+            .lock()
+            .unwrap()
+            .update_and_filter(&mut auction);
+        if before_count != auction.orders.len() {
+            tracing::debug!(
+                "reduced {} orders to {} because in flight at last seen block {}",
+                before_count,
+                auction.orders.len(),
+                auction.block
+            );
+        }
+        if !auction_preprocessing::has_at_least_one_mature_user_order(
+            &auction.orders,
+            self.min_order_age,
+        ) {
+            return Err(SolveError::NoSolvableOrders);
+        }
+
+        let orders = auction
+            .orders
+            .into_iter()
+            .filter_map(
+                |order| match self.order_converter.normalize_limit_order(order) {
+                    Ok(order) => Some(order),
+                    Err(err) => {
+                        tracing::error!(?err, "error normalizing limit order");
+                        None
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        let external_prices = ExternalPrices::try_from_auction_prices(
+            self.order_converter.native_token.address(),
+            auction.prices,
+        )
+        .context("malformed auction prices")?;
+        let block = auction.block;
+        let liquidity = self
+            .liquidity_collector
+            .get_liquidity_for_orders(&orders, Block::Number(block))
+            .await?;
+        let gas_price = self
+            .submitter
+            .gas_price_estimator
+            .estimate()
+            .await
+            .context("failed to estimate gas price")?;
+
+        let auction = solver::solver::Auction {
+            id: auction.next_solver_competition,
+            run: self.next_run_id.fetch_add(1, Ordering::SeqCst),
+            orders,
+            liquidity,
+            gas_price: gas_price.effective_gas_price(),
+            deadline: Instant::now() + self.solver_time_limit,
+            external_prices: external_prices.clone(),
+        };
+        let summary = self.solver.commit(auction).await?;
+        // Like the solver, only keep the latest committed settlement around.
+        let mut committed_auctions = self.committed_auctions.lock().unwrap();
+        committed_auctions.clear();
+        committed_auctions.insert(
+            summary.settlement_id,
+            CommittedAuction {
+                block,
+                external_prices,
+            },
+        );
+        Ok(summary)
     }
 
     /// Validates that the `Settlement` satisfies expected fairness and correctness properties.
-    async fn validate_settlement(&self, _settlement: &Settlement) -> Result<()> {
-        // TODO simulation
-        // TODO token conservation
+    async fn validate_settlement(
+        &self,
+        settlement: &Settlement,
+        auction: &CommittedAuction,
+    ) -> Result<(), ExecuteError> {
+        self.validator
+            .validate(self.solver_address, settlement, &auction.external_prices)
+            .await?;
+        if let Some(ebbo_validator) = &self.ebbo_validator {
+            let violations = ebbo_validator
+                .violations(&self.name, settlement, auction.block)
+                .await;
+            if !violations.is_empty() {
+                return Err(ExecuteError::EbboViolation(
+                    violations
+                        .into_iter()
+                        .map(|violation| violation.order_uid)
+                        .collect(),
+                ));
+            }
+        }
         Ok(())
     }
 
     /// When the solver won the competition it finalizes the `Settlement` and decides whether it
     /// still wants to execute and submit that `Settlement`.
     pub async fn on_auction_won(&self, summary: SettlementSummary) -> Result<(), ExecuteError> {
-        let settlement = match self.solver.reveal(summary).await? {
-            None => return Err(ExecuteError::ExecutionRejected),
-            Some(solution) => solution,
-        };
-        self.validate_settlement(&settlement).await?;
+        let settlement_id = summary.settlement_id;
+        let settlement = self.solver.reveal(summary).await?;
+        let auction = self
+            .committed_auctions
+            .lock()
+            .unwrap()
+            .remove(&settlement_id)
+            .context("missing auction of revealed settlement")?;
+        self.validate_settlement(&settlement, &auction).await?;
+        // The orders are in flight as soon as we decide to execute the settlement. Until the
+        // submission reports the block it got mined in, track them from the auction's block on.
+        self.in_flight_orders
+            .lock()
+            .unwrap()
+            .mark_settled_orders(auction.block, &settlement);
         self.submit_settlement(settlement).await?;
         Ok(())
     }
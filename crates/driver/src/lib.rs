@@ -3,3 +3,4 @@ pub mod arguments;
 pub mod commit_reveal;
 pub mod driver;
 pub mod settlement_proposal;
+pub mod settlement_validation;
@@ -3,17 +3,32 @@ use clap::Parser;
 use contracts::WETH9;
 use driver::{
     api::serve_api, arguments::Arguments, commit_reveal::CommitRevealSolver, driver::Driver,
+    settlement_validation::SettlementValidator,
 };
+use gas_estimation::GasPriceEstimating;
 use reqwest::Client;
 use shared::{
+    baseline_solver::BaseTokens,
+    current_block::{current_block_stream, CurrentBlockStream},
     http_solver::{DefaultHttpSolverApi, SolverConfig},
-    token_info::{CachedTokenInfoFetcher, TokenInfoFetcher},
+    maintenance::ServiceMaintenance,
+    recent_block_cache::CacheConfig,
+    sources::{self, uniswap_v2::pool_fetching::PoolFetching},
+    token_info::{CachedTokenInfoFetcher, TokenInfoFetcher, TokenInfoFetching},
     transport::http::HttpTransport,
+    zeroex_api::DefaultZeroExApi,
     Web3Transport,
 };
 use solver::{
     arguments::TransactionStrategyArg,
     interactions::allowances::AllowanceManager,
+    liquidity::order_converter::OrderConverter,
+    liquidity_collector::{build_liquidity_sources, LiquiditySources},
+    settlement_ebbo::EbboValidator,
+    settlement_rater::{
+        balance_changes::TraceCallBalanceChangeTracer, revert_risk::RevertRiskEstimator,
+        SettlementRater,
+    },
     settlement_submission::{
         submitter::{
             custom_nodes_api::CustomNodesApi, eden_api::EdenApi, flashbots_api::FlashbotsApi,
@@ -35,6 +50,10 @@ struct CommonComponents {
     chain_id: u64,
     settlement_contract: contracts::GPv2Settlement,
     native_token_contract: WETH9,
+    gas_price_estimator: Arc<dyn GasPriceEstimating>,
+    base_tokens: Arc<BaseTokens>,
+    token_info_fetcher: Arc<dyn TokenInfoFetching>,
+    current_block_stream: CurrentBlockStream,
 }
 
 async fn init_common_components(args: &Arguments) -> CommonComponents {
@@ -62,6 +81,27 @@ async fn init_common_components(args: &Arguments) -> CommonComponents {
     let native_token_contract = WETH9::deployed(&web3)
         .await
         .expect("couldn't load deployed native token");
+    let gas_price_estimator = Arc::new(
+        shared::gas_price_estimation::create_priority_estimator(
+            client.clone(),
+            &web3,
+            args.gas_estimators.as_slice(),
+            args.blocknative_api_key.clone(),
+        )
+        .await
+        .expect("failed to create gas price estimator"),
+    );
+    let base_tokens = Arc::new(BaseTokens::new(
+        native_token_contract.address(),
+        &args.base_tokens,
+    ));
+    let token_info_fetcher = Arc::new(CachedTokenInfoFetcher::new(Box::new(TokenInfoFetcher {
+        web3: web3.clone(),
+    })));
+    let current_block_stream =
+        current_block_stream(web3.clone(), args.block_stream_poll_interval_seconds)
+            .await
+            .expect("failed to create current block stream");
 
     CommonComponents {
         client,
@@ -70,14 +110,58 @@ async fn init_common_components(args: &Arguments) -> CommonComponents {
         chain_id,
         settlement_contract,
         native_token_contract,
+        gas_price_estimator,
+        base_tokens,
+        token_info_fetcher,
+        current_block_stream,
     }
 }
 
-async fn build_solvers(common: &CommonComponents, args: &Arguments) -> Vec<Box<dyn Solver>> {
-    let token_info_fetcher = Arc::new(CachedTokenInfoFetcher::new(Box::new(TokenInfoFetcher {
-        web3: common.web3.clone(),
-    })));
+async fn init_liquidity_sources(common: &CommonComponents, args: &Arguments) -> LiquiditySources {
+    let baseline_sources = args.baseline_sources.clone().unwrap_or_else(|| {
+        sources::defaults_for_chain(common.chain_id)
+            .expect("failed to get default baseline sources")
+    });
+    tracing::info!(?baseline_sources, "using baseline sources");
+    let cache_config = CacheConfig {
+        number_of_blocks_to_cache: args.pool_cache_blocks,
+        // 0 because we don't make use of the auto update functionality as we always fetch
+        // for specific blocks
+        number_of_entries_to_auto_update: 0,
+        maximum_recent_block_age: args.pool_cache_maximum_recent_block_age,
+        max_retries: args.pool_cache_maximum_retries,
+        delay_between_retries: args.pool_cache_delay_between_retries_seconds,
+    };
+    let zeroex_api = Arc::new(
+        DefaultZeroExApi::new(
+            args.zeroex_url
+                .as_deref()
+                .unwrap_or(DefaultZeroExApi::DEFAULT_URL),
+            args.zeroex_api_key.clone(),
+            common.client.clone(),
+        )
+        .unwrap(),
+    );
+    build_liquidity_sources(
+        &common.web3,
+        &common.client,
+        common.chain_id,
+        &baseline_sources,
+        cache_config,
+        common.current_block_stream.clone(),
+        common.token_info_fetcher.clone(),
+        common.base_tokens.clone(),
+        &common.settlement_contract,
+        zeroex_api,
+        args.balancer_factories.clone(),
+        args.balancer_pool_deny_list.clone(),
+        args.uniswap_v3_checkpoint_path.clone(),
+    )
+    .await
+    .expect("failed to create liquidity sources")
+}
 
+async fn build_solvers(common: &CommonComponents, args: &Arguments) -> Vec<Box<dyn Solver>> {
     let buffer_retriever = Arc::new(BufferRetriever::new(
         common.web3.clone(),
         common.settlement_contract.address(),
@@ -100,12 +184,13 @@ async fn build_solvers(common: &CommonComponents, args: &Arguments) -> Vec<Box<d
                     client: common.client.clone(),
                     config: SolverConfig {
                         use_internal_buffers: Some(args.use_internal_buffers),
+                        instance_version: arg.instance_version,
                         ..Default::default()
                     },
                 },
                 arg.account.clone().into_account(common.chain_id),
                 common.native_token_contract.address(),
-                token_info_fetcher.clone(),
+                common.token_info_fetcher.clone(),
                 buffer_retriever.clone(),
                 allowance_mananger.clone(),
                 http_solver_cache.clone(),
@@ -219,21 +304,11 @@ async fn build_submitter(common: &CommonComponents, args: &Arguments) -> Arc<Sol
         .await
         .expect("failed to create access list estimator"),
     );
-    let gas_price_estimator = Arc::new(
-        shared::gas_price_estimation::create_priority_estimator(
-            client.clone(),
-            web3,
-            args.gas_estimators.as_slice(),
-            args.blocknative_api_key.clone(),
-        )
-        .await
-        .expect("failed to create gas price estimator"),
-    );
 
     Arc::new(SolutionSubmitter {
         web3: web3.clone(),
         contract: common.settlement_contract.clone(),
-        gas_price_estimator,
+        gas_price_estimator: common.gas_price_estimator.clone(),
         target_confirm_time: args.target_confirm_time,
         max_confirm_time: args.max_submission_seconds,
         retry_interval: args.submission_retry_interval_seconds,
@@ -243,6 +318,26 @@ async fn build_submitter(common: &CommonComponents, args: &Arguments) -> Arc<Sol
     })
 }
 
+fn build_ebbo_validator(
+    common: &CommonComponents,
+    args: &Arguments,
+    liquidity_sources: &LiquiditySources,
+) -> Option<Arc<EbboValidator>> {
+    let tolerance = args.ebbo_tolerance?;
+    Some(Arc::new(EbboValidator::baseline(
+        liquidity_sources
+            .pool_caches
+            .values()
+            .map(|pool_cache| pool_cache.clone() as Arc<dyn PoolFetching>)
+            .collect(),
+        common.gas_price_estimator.clone(),
+        common.base_tokens.clone(),
+        common.native_token_contract.address(),
+        args.baseline_max_split_paths,
+        tolerance,
+    )))
+}
+
 #[tokio::main]
 async fn main() {
     let args = driver::arguments::Arguments::parse();
@@ -252,14 +347,64 @@ async fn main() {
     let common = init_common_components(&args).await;
     let solvers = build_solvers(&common, &args).await;
     let submitter = build_submitter(&common, &args).await;
+    let validator = Arc::new(SettlementValidator {
+        settlement_contract: common.settlement_contract.address(),
+        tracer: Arc::new(TraceCallBalanceChangeTracer {
+            web3: common.web3.clone(),
+        }),
+        buffer_loss_tolerance: args.buffer_loss_tolerance,
+    });
+    let liquidity_sources = init_liquidity_sources(&common, &args).await;
+    let ebbo_validator = build_ebbo_validator(&common, &args, &liquidity_sources);
+    let liquidity_collector = Arc::new(liquidity_sources.collector);
+    let maintainer = ServiceMaintenance {
+        maintainers: liquidity_sources.maintainers,
+    };
+    tokio::task::spawn(
+        maintainer.run_maintenance_on_new_block(common.current_block_stream.clone()),
+    );
+    let order_converter = Arc::new(OrderConverter {
+        native_token: common.native_token_contract.clone(),
+        fee_objective_scaling_factor: args.fee_objective_scaling_factor,
+    });
+    let settlement_rater = Arc::new(SettlementRater {
+        access_list_estimator: submitter.access_list_estimator.clone(),
+        settlement_contract: common.settlement_contract.clone(),
+        web3: common.web3.clone(),
+        revert_risk: Arc::new(RevertRiskEstimator::default()),
+        balance_change_tracer: args.trace_settlement_balance_changes.then(|| {
+            Arc::new(TraceCallBalanceChangeTracer {
+                web3: common.web3.clone(),
+            }) as Arc<_>
+        }),
+    });
 
     let drivers = solvers
         .into_iter()
         .map(|solver| {
             let name = solver.name().to_string();
             let driver = Arc::new(Driver {
-                solver: Arc::new(CommitRevealSolver::new(solver)),
+                name: name.clone(),
+                solver_address: solver.account().address(),
+                solver: Arc::new(CommitRevealSolver::new(
+                    Arc::from(solver),
+                    settlement_rater.clone(),
+                    args.settlement_scoring,
+                    args.min_order_age,
+                    common.gas_price_estimator.clone(),
+                    common.settlement_contract.clone(),
+                    common.web3.clone(),
+                )),
                 submitter: submitter.clone(),
+                validator: validator.clone(),
+                ebbo_validator: ebbo_validator.clone(),
+                liquidity_collector: liquidity_collector.clone(),
+                order_converter: order_converter.clone(),
+                min_order_age: args.min_order_age,
+                solver_time_limit: args.solver_time_limit,
+                in_flight_orders: Default::default(),
+                next_run_id: Default::default(),
+                committed_auctions: Default::default(),
             });
             (driver, name)
         })
@@ -274,6 +274,7 @@ impl SettlementProposal {
             surplus,
             gas_reimbursement,
             settled_orders: self.trades.iter().map(|t| t.order.metadata.uid).collect(),
+            ..Default::default()
         })
     }
 }
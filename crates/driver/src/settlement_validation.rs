@@ -0,0 +1,437 @@
+//! Validation of revealed settlements by tracing their simulation and checking the resulting token
+//! balance changes of the traders and of the settlement contract.
+
+use crate::api::execute::ExecuteError;
+use model::order::{BuyTokenDestination, OrderKind, OrderUid, SellTokenSource, BUY_ETH_ADDRESS};
+use num::{BigInt, BigRational, Integer as _, Signed as _, ToPrimitive as _, Zero as _};
+use primitive_types::H160;
+use shared::conversions::U256Ext as _;
+use solver::{
+    settlement::{external_prices::ExternalPrices, Settlement},
+    settlement_rater::balance_changes::{
+        observed_balance_changes, trader_balance_changes, Balance, BalanceChangeTracing,
+    },
+    settlement_simulation::call_data,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use web3::types::{Bytes, CallRequest};
+
+pub struct SettlementValidator {
+    pub settlement_contract: H160,
+    pub tracer: Arc<dyn BalanceChangeTracing>,
+    /// The value the settlement contract may lose from its buffers as a fraction of the value
+    /// sold by the traders, both at the settlement's clearing prices.
+    pub buffer_loss_tolerance: f64,
+}
+
+impl SettlementValidator {
+    /// Simulates the settlement as sent by the solver and checks that
+    /// - every trader receives at least the amount allowed by their limit price and doesn't pay
+    ///   more than it allows
+    /// - the settlement contract doesn't lose more buffer value than tolerated, including buffers
+    ///   of tokens that are only transferred by interactions
+    ///
+    /// Trades whose balance changes can't be observed through ERC20 balances, i.e. buying native
+    /// ETH or using Balancer Vault internal balances, are rejected.
+    pub async fn validate(
+        &self,
+        solver: H160,
+        settlement: &Settlement,
+        external_prices: &ExternalPrices,
+    ) -> Result<(), ExecuteError> {
+        let unverifiable = unverifiable_trades(settlement);
+        if !unverifiable.is_empty() {
+            return Err(ExecuteError::UnverifiableTrades(unverifiable));
+        }
+
+        let limits = trader_balance_limits(settlement);
+        let settlement_tx = CallRequest {
+            from: Some(solver),
+            to: Some(self.settlement_contract),
+            data: Some(Bytes(call_data(settlement.clone().into()))),
+            ..Default::default()
+        };
+        let transferred_tokens = self
+            .tracer
+            .transferred_tokens(settlement_tx.clone())
+            .await
+            .map_err(ExecuteError::SimulationFailed)?;
+        let observed = observed_balance_changes(
+            self.tracer.as_ref(),
+            settlement_tx,
+            self.settlement_contract,
+            limits.keys().copied(),
+            settlement
+                .clearing_prices()
+                .keys()
+                .copied()
+                .chain(transferred_tokens),
+        )
+        .await
+        .map_err(ExecuteError::SimulationFailed)?;
+
+        let violations = limits
+            .into_iter()
+            .filter(|(balance, limit)| observed[balance] < *limit)
+            .map(|(balance, _)| balance)
+            .collect::<Vec<_>>();
+        if !violations.is_empty() {
+            return Err(ExecuteError::LimitPriceViolated(violations));
+        }
+
+        let (buffer_change, tolerated_loss) = buffer_value_change(
+            settlement,
+            external_prices,
+            &observed,
+            self.settlement_contract,
+            self.buffer_loss_tolerance,
+        )
+        .map_err(ExecuteError::UnpricedBufferLoss)?;
+        let loss = -buffer_change;
+        if loss > tolerated_loss {
+            return Err(ExecuteError::TokenConservationViolated {
+                loss: loss.to_f64().unwrap_or(f64::NAN),
+                tolerated_loss: tolerated_loss.to_f64().unwrap_or(f64::NAN),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The user trades whose balance changes aren't ERC20 balance changes of the traders.
+fn unverifiable_trades(settlement: &Settlement) -> Vec<OrderUid> {
+    settlement
+        .encoder
+        .order_trades()
+        .iter()
+        .map(|order_trade| &order_trade.trade.order)
+        .filter(|order| !order.metadata.is_liquidity_order)
+        .filter(|order| {
+            order.data.buy_token == BUY_ETH_ADDRESS
+                || order.data.sell_token_balance == SellTokenSource::Internal
+                || order.data.buy_token_balance != BuyTokenDestination::Erc20
+        })
+        .map(|order| order.metadata.uid)
+        .collect()
+}
+
+/// The smallest balance changes of the traders that still respect the limit prices of all of their
+/// executed orders. Sold amounts are negative.
+fn trader_balance_limits(settlement: &Settlement) -> BTreeMap<Balance, BigInt> {
+    trader_balance_changes(settlement, |trade, execution| {
+        let order = &trade.order;
+        if order.metadata.is_liquidity_order {
+            return None;
+        }
+        let limit_sell = order.data.sell_amount.to_big_int();
+        let limit_buy = order.data.buy_amount.to_big_int();
+        let (max_sold, min_bought) = match order.data.kind {
+            OrderKind::Sell if !limit_sell.is_zero() => (
+                execution.sell_amount.to_big_int(),
+                (execution.sell_amount.to_big_int() * limit_buy) / limit_sell,
+            ),
+            OrderKind::Buy if !limit_buy.is_zero() => (
+                (execution.buy_amount.to_big_int() * limit_sell).div_ceil(&limit_buy),
+                execution.buy_amount.to_big_int(),
+            ),
+            // Orders without an amount to trade can't be executed.
+            _ => return None,
+        };
+        Some((max_sold + execution.fee_amount.to_big_int(), min_bought))
+    })
+}
+
+/// Values the observed buffer changes and the tolerated loss at the settlement's clearing prices.
+/// Solvers may trade buffer tokens against each other, so only the total value matters.
+///
+/// Tokens without a clearing price, like intermediate tokens of multi-hop swaps, are valued at
+/// their external price converted to the scale of the clearing prices. Fails with the tokens that
+/// have neither price but whose buffers decrease.
+fn buffer_value_change(
+    settlement: &Settlement,
+    external_prices: &ExternalPrices,
+    observed: &HashMap<Balance, BigInt>,
+    settlement_contract: H160,
+    tolerance: f64,
+) -> Result<(BigRational, BigRational), Vec<H160>> {
+    // The ratio between clearing prices and external prices, taken from any token with both.
+    let external_to_clearing = settlement
+        .clearing_prices()
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .find_map(|(token, price)| {
+            let external_price = external_prices.price(token)?;
+            (!external_price.is_zero())
+                .then(|| BigRational::from_integer(price.to_big_int()) / external_price)
+        });
+    let value = |token: H160, amount: BigInt| match settlement.clearing_price(token) {
+        Some(price) => Some(BigRational::from_integer(amount * price.to_big_int())),
+        None => {
+            let native_amount =
+                external_prices.try_get_native_amount(token, BigRational::from_integer(amount))?;
+            Some(native_amount * external_to_clearing.as_ref()?)
+        }
+    };
+
+    let mut unpriced = Vec::new();
+    let mut buffer_change = BigRational::zero();
+    for (balance, change) in observed {
+        if balance.owner != settlement_contract {
+            continue;
+        }
+        match value(balance.token, change.clone()) {
+            Some(value) => buffer_change += value,
+            // Gains of unpriced tokens are conservatively ignored.
+            None if change.is_negative() => unpriced.push(balance.token),
+            None => (),
+        }
+    }
+    if !unpriced.is_empty() {
+        unpriced.sort();
+        return Err(unpriced);
+    }
+
+    let sold_value = settlement
+        .executed_trades()
+        .filter(|(trade, _)| !trade.order.metadata.is_liquidity_order)
+        .fold(BigRational::zero(), |total, (_, execution)| {
+            total
+                + value(execution.sell_token, execution.sell_amount.to_big_int())
+                    .unwrap_or_else(BigRational::zero)
+        });
+    let tolerance = BigRational::from_float(tolerance).unwrap_or_else(BigRational::zero);
+
+    Ok((buffer_change, sold_value * tolerance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use model::order::{Order, OrderData, OrderMetadata};
+    use num::One as _;
+    use solver::settlement_rater::balance_changes::MockBalanceChangeTracing;
+
+    fn address(value: u64) -> H160 {
+        H160::from_low_u64_be(value)
+    }
+
+    fn balance(owner: u64, token: u64) -> Balance {
+        Balance {
+            owner: address(owner),
+            token: address(token),
+        }
+    }
+
+    const SETTLEMENT_CONTRACT: u64 = 42;
+
+    // Trader 1 sells 100 of token 1 (plus a fee of 10) for at least 90 of token 2. The clearing
+    // prices give them 100.
+    fn settlement() -> Settlement {
+        let mut settlement =
+            Settlement::new(hashmap! { address(1) => 1.into(), address(2) => 1.into() });
+        settlement
+            .encoder
+            .add_trade(
+                Order {
+                    data: OrderData {
+                        sell_token: address(1),
+                        buy_token: address(2),
+                        sell_amount: 100.into(),
+                        buy_amount: 90.into(),
+                        fee_amount: 10.into(),
+                        kind: OrderKind::Sell,
+                        ..Default::default()
+                    },
+                    metadata: OrderMetadata {
+                        owner: address(1),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                100.into(),
+                10.into(),
+            )
+            .unwrap();
+        settlement
+    }
+
+    // Token 3 has no clearing price and is worth half as much as the other tokens.
+    fn prices() -> ExternalPrices {
+        ExternalPrices::new(
+            address(1),
+            hashmap! {
+                address(2) => BigRational::one(),
+                address(3) => BigRational::new(1.into(), 2.into()),
+            },
+        )
+        .unwrap()
+    }
+
+    fn validator(observed: HashMap<Balance, i64>, tolerance: f64) -> SettlementValidator {
+        let mut tracer = MockBalanceChangeTracing::new();
+        let transferred = observed
+            .keys()
+            .filter(|balance| balance.owner == address(SETTLEMENT_CONTRACT))
+            .map(|balance| balance.token)
+            .collect::<Vec<_>>();
+        tracer
+            .expect_transferred_tokens()
+            .returning(move |_| Ok(transferred.clone()));
+        tracer
+            .expect_trace_balance_changes()
+            .returning(move |_, balances| {
+                Ok(balances
+                    .iter()
+                    .map(|balance| BigInt::from(observed.get(balance).copied().unwrap_or(0)))
+                    .collect())
+            });
+        SettlementValidator {
+            settlement_contract: address(SETTLEMENT_CONTRACT),
+            tracer: Arc::new(tracer),
+            buffer_loss_tolerance: tolerance,
+        }
+    }
+
+    #[test]
+    fn limits_follow_limit_prices() {
+        assert_eq!(
+            trader_balance_limits(&settlement()),
+            BTreeMap::from([
+                (balance(1, 1), BigInt::from(-110)),
+                (balance(1, 2), BigInt::from(90)),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn accepts_settlement_within_limits() {
+        // An AMM swaps 100 of token 1 for 100 of token 2, so the buffers keep the fee.
+        let validator = validator(
+            hashmap! {
+                balance(1, 1) => -110,
+                balance(1, 2) => 100,
+                balance(SETTLEMENT_CONTRACT, 1) => 10,
+            },
+            0.,
+        );
+        assert!(validator
+            .validate(address(0), &settlement(), &prices())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_trader_receiving_less_than_limit_price() {
+        let validator = validator(
+            hashmap! {
+                balance(1, 1) => -110,
+                balance(1, 2) => 80,
+                balance(SETTLEMENT_CONTRACT, 1) => 10,
+            },
+            0.,
+        );
+        assert!(matches!(
+            validator.validate(address(0), &settlement(), &prices()).await,
+            Err(ExecuteError::LimitPriceViolated(balances)) if balances == vec![balance(1, 2)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn buffer_tokens_are_netted_at_clearing_prices() {
+        // The buffers pay out 15 of token 2 but keep 10 of token 1, a loss of 5.
+        let observed = hashmap! {
+            balance(1, 1) => -110,
+            balance(1, 2) => 100,
+            balance(SETTLEMENT_CONTRACT, 1) => 10,
+            balance(SETTLEMENT_CONTRACT, 2) => -15,
+        };
+        assert!(validator(observed.clone(), 0.05)
+            .validate(address(0), &settlement(), &prices())
+            .await
+            .is_ok());
+        assert!(matches!(
+            validator(observed, 0.01)
+                .validate(address(0), &settlement(), &prices())
+                .await,
+            Err(ExecuteError::TokenConservationViolated { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn values_buffers_without_clearing_price_at_external_prices() {
+        // An intermediate hop takes 40 of token 3 (worth 20) out of the buffers.
+        let observed = hashmap! {
+            balance(1, 1) => -110,
+            balance(1, 2) => 100,
+            balance(SETTLEMENT_CONTRACT, 1) => 10,
+            balance(SETTLEMENT_CONTRACT, 3) => -40,
+        };
+        assert!(validator(observed.clone(), 0.2)
+            .validate(address(0), &settlement(), &prices())
+            .await
+            .is_ok());
+        assert!(matches!(
+            validator(observed, 0.05)
+                .validate(address(0), &settlement(), &prices())
+                .await,
+            Err(ExecuteError::TokenConservationViolated { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_buffer_loss_of_unpriced_tokens() {
+        let validator = validator(
+            hashmap! {
+                balance(1, 1) => -110,
+                balance(1, 2) => 100,
+                balance(SETTLEMENT_CONTRACT, 1) => 10,
+                balance(SETTLEMENT_CONTRACT, 4) => -1,
+            },
+            1.,
+        );
+        assert!(matches!(
+            validator.validate(address(0), &settlement(), &prices()).await,
+            Err(ExecuteError::UnpricedBufferLoss(tokens)) if tokens == vec![address(4)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_native_token_buys() {
+        let mut settlement =
+            Settlement::new(hashmap! { address(1) => 1.into(), BUY_ETH_ADDRESS => 1.into() });
+        settlement
+            .encoder
+            .add_trade(
+                Order {
+                    data: OrderData {
+                        sell_token: address(1),
+                        buy_token: BUY_ETH_ADDRESS,
+                        sell_amount: 100.into(),
+                        buy_amount: 90.into(),
+                        kind: OrderKind::Sell,
+                        ..Default::default()
+                    },
+                    metadata: OrderMetadata {
+                        uid: OrderUid([1; 56]),
+                        owner: address(1),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                100.into(),
+                0.into(),
+            )
+            .unwrap();
+        assert!(matches!(
+            validator(HashMap::new(), 0.)
+                .validate(address(0), &settlement, &prices())
+                .await,
+            Err(ExecuteError::UnverifiableTrades(orders)) if orders == vec![OrderUid([1; 56])]
+        ));
+    }
+}
@@ -18,6 +18,7 @@ use solver::{
     liquidity_collector::LiquidityCollector,
     metrics::NoopMetrics,
     settlement_access_list::{create_priority_estimator, AccessListEstimatorType},
+    settlement_rater::SettlementScoring,
     settlement_submission::{
         submitter::{custom_nodes_api::CustomNodesApi, Strategy},
         GlobalTxPool, SolutionSubmitter, StrategyArgs,
@@ -201,6 +202,7 @@ async fn eth_integration(web3: Web3) {
         balancer_v2_liquidity: None,
         zeroex_liquidity: None,
         uniswap_v3_liquidity: None,
+        curve_liquidity: None,
     };
     let network_id = web3.net().version().await.unwrap();
     let submitted_transactions = GlobalTxPool::default();
@@ -257,6 +259,12 @@ async fn eth_integration(web3: Web3) {
         None,
         None.into(),
         None,
+        SettlementScoring::ObjectiveValue,
+        None,
+        false,
+        None,
+        false,
+        None,
     );
     driver.single_run().await.unwrap();
 
@@ -8,6 +8,8 @@ use orderbook::{
     order_quoting::{OrderQuoter, QuoteHandler},
     order_validation::{OrderValidator, SignatureConfiguration},
     orderbook::Orderbook,
+    presignature::PresignatureHandler,
+    signature_validation_cache::{SignatureValidationCache, Web3OwnerStateWatcher},
     solvable_orders::SolvableOrdersCache,
 };
 use reqwest::Client;
@@ -204,6 +206,7 @@ impl OrderbookServices {
         let price_estimator = Arc::new(SanitizedPriceEstimator::new(
             Box::new(BaselinePriceEstimator::new(
                 Arc::new(pool_fetcher),
+                None,
                 gas_estimator.clone(),
                 base_tokens.clone(),
                 contracts.weth.address(),
@@ -212,6 +215,7 @@ impl OrderbookServices {
                     Default::default(),
                     "baseline_estimator".into(),
                 )),
+                1,
             )),
             contracts.weth.address(),
             bad_token_detector.clone(),
@@ -224,7 +228,7 @@ impl OrderbookServices {
         let quoter = Arc::new(OrderQuoter::new(
             price_estimator.clone(),
             native_price_estimator.clone(),
-            gas_estimator,
+            gas_estimator.clone(),
             Arc::new(Subsidy {
                 factor: 0.,
                 ..Default::default()
@@ -246,8 +250,14 @@ impl OrderbookServices {
             bad_token_detector.clone(),
             current_block_stream.clone(),
             native_price_estimator,
-            signature_validator.clone(),
+            gas_estimator,
+            SignatureValidationCache::new(
+                signature_validator.clone(),
+                Arc::new(Web3OwnerStateWatcher::new(web3.clone())),
+                Default::default(),
+            ),
             api_db.clone(),
+            Default::default(),
         );
         let order_validator = Arc::new(OrderValidator::new(
             Box::new(web3.clone()),
@@ -259,8 +269,9 @@ impl OrderbookServices {
             SignatureConfiguration::all(),
             bad_token_detector,
             quoter.clone(),
-            balance_fetcher,
+            balance_fetcher.clone(),
             signature_validator,
+            100,
         ));
         let orderbook = Arc::new(Orderbook::new(
             contracts.domain_separator,
@@ -274,6 +285,13 @@ impl OrderbookServices {
             maintainers: vec![Arc::new(autopilot_db.clone()), event_updater],
         };
         let quotes = Arc::new(QuoteHandler::new(order_validator, quoter));
+        let presignatures = Arc::new(PresignatureHandler::new(
+            api_db.clone(),
+            balance_fetcher,
+            contracts.gp_settlement.clone(),
+            contracts.allowance,
+            Some(contracts.balancer_vault.address()),
+        ));
         orderbook::serve_api(
             api_db.clone(),
             orderbook,
@@ -283,6 +301,7 @@ impl OrderbookServices {
             api_db.clone(),
             None,
             solvable_orders_cache.clone(),
+            presignatures,
         );
 
         Self {
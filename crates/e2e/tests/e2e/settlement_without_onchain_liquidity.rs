@@ -21,6 +21,7 @@ use solver::{
     liquidity_collector::LiquidityCollector,
     metrics::NoopMetrics,
     settlement_access_list::{create_priority_estimator, AccessListEstimatorType},
+    settlement_rater::SettlementScoring,
     settlement_submission::{
         submitter::{custom_nodes_api::CustomNodesApi, Strategy},
         GlobalTxPool, SolutionSubmitter, StrategyArgs,
@@ -187,6 +188,7 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
         balancer_v2_liquidity: None,
         zeroex_liquidity: None,
         uniswap_v3_liquidity: None,
+        curve_liquidity: None,
     };
     let network_id = web3.net().version().await.unwrap();
     let market_makable_token_list = TokenList::new(maplit::hashmap! {
@@ -251,6 +253,12 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
         None,
         None.into(),
         None,
+        SettlementScoring::ObjectiveValue,
+        None,
+        false,
+        None,
+        false,
+        None,
     );
     driver.single_run().await.unwrap();
 
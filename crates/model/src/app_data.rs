@@ -0,0 +1,118 @@
+//! Full app data documents and the order policies they carry.
+//!
+//! Orders only sign the 32 byte `appData` field, which is the keccak256 hash of a JSON document.
+//! The document itself can optionally be provided when creating an order, in which case the
+//! orderbook verifies that it matches the signed hash and interprets the policies it contains.
+
+use crate::app_id::AppId;
+use primitive_types::{H160, U256};
+use serde::{Deserialize, Serialize};
+use web3::signing;
+
+/// The denominator for fees expressed in basis points.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// A fee in the order's buy token that is paid by the trader to an integrator's address as part of
+/// the settlement.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartnerFee {
+    pub recipient: H160,
+    /// The fee relative to the executed buy amount in basis points. For sell orders this is the
+    /// amount bought at the uniform clearing prices before deducting the fee.
+    pub bps: u64,
+}
+
+impl PartnerFee {
+    /// Computes the fee amount for the specified executed buy amount.
+    ///
+    /// Returns `None` on overflow.
+    pub fn fee_amount(&self, executed_buy_amount: U256) -> Option<U256> {
+        executed_buy_amount
+            .checked_mul(self.bps.into())
+            .map(|amount| amount / BPS_DENOMINATOR)
+    }
+}
+
+/// The parts of an app data document we care about. Unknown fields are ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppData {
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    partner_fee: Option<PartnerFee>,
+}
+
+/// Computes the `appData` hash that an order signs for a full app data document.
+pub fn hash_full_app_data(full_app_data: &str) -> AppId {
+    AppId(signing::keccak256(full_app_data.as_bytes()))
+}
+
+/// Parses the partner fee policy out of a full app data document.
+pub fn parse_partner_fee(full_app_data: &str) -> Result<Option<PartnerFee>, serde_json::Error> {
+    let app_data = serde_json::from_str::<AppData>(full_app_data)?;
+    Ok(app_data.metadata.partner_fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn parses_partner_fee() {
+        let full_app_data = r#"{
+            "appCode": "CoW Swap",
+            "metadata": {
+                "partnerFee": {
+                    "recipient": "0x0101010101010101010101010101010101010101",
+                    "bps": 25
+                },
+                "referrer": {
+                    "address": "0x0202020202020202020202020202020202020202"
+                }
+            }
+        }"#;
+        assert_eq!(
+            parse_partner_fee(full_app_data).unwrap(),
+            Some(PartnerFee {
+                recipient: H160([1; 20]),
+                bps: 25,
+            })
+        );
+    }
+
+    #[test]
+    fn documents_without_partner_fee() {
+        assert_eq!(parse_partner_fee("{}").unwrap(), None);
+        assert_eq!(parse_partner_fee(r#"{"metadata":{}}"#).unwrap(), None);
+        assert!(parse_partner_fee("not json").is_err());
+        assert!(parse_partner_fee(r#"{"metadata":{"partnerFee":{"bps":1}}}"#).is_err());
+    }
+
+    #[test]
+    fn hashes_full_app_data() {
+        assert_eq!(
+            hash_full_app_data("{}"),
+            AppId(hex!(
+                "b48d38f93eaa084033fc5970bf96e559c33c4cdc07d889ab00b4d63f9590739d"
+            ))
+        );
+    }
+
+    #[test]
+    fn computes_fee_amount() {
+        let fee = PartnerFee {
+            recipient: H160([1; 20]),
+            bps: 25,
+        };
+        assert_eq!(fee.fee_amount(1_000_000.into()), Some(2_500.into()));
+        assert_eq!(fee.fee_amount(399.into()), Some(0.into()));
+        assert_eq!(fee.fee_amount(U256::MAX), None);
+    }
+}
@@ -1,6 +1,7 @@
 //! Contains the order type as described by the specification with serialization as described by the openapi documentation.
 
 use crate::{
+    app_data::{self, PartnerFee},
     app_id::AppId,
     quote::QuoteId,
     signature::{EcdsaSignature, EcdsaSigningScheme, Signature, VerificationError},
@@ -51,6 +52,16 @@ pub enum OrderStatus {
     Expired,
 }
 
+/// The reason why an open order is currently excluded from auctions.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum UnfillableReason {
+    /// The executable amount is worth less than the configured minimum native token value.
+    BelowMinNativeValue,
+    /// The executable amount is worth less than the gas needed to settle the order.
+    BelowGasCost,
+}
+
 impl Order {
     pub fn from_order_creation(
         order: &OrderCreation,
@@ -68,6 +79,7 @@ impl Order {
                 settlement_contract,
                 full_fee_amount,
                 is_liquidity_order,
+                full_app_data: order.full_app_data.clone(),
                 ..Default::default()
             },
             signature: order.signature.clone(),
@@ -79,6 +91,14 @@ impl Order {
         self.into()
     }
 
+    /// Returns the partner fee policy specified in the order's full app data, if any.
+    pub fn partner_fee(&self) -> Result<Option<PartnerFee>> {
+        match &self.metadata.full_app_data {
+            Some(full_app_data) => Ok(app_data::parse_partner_fee(full_app_data)?),
+            None => Ok(None),
+        }
+    }
+
     pub fn contains_token_from(&self, token_list: &HashSet<H160>) -> bool {
         token_list.contains(&self.data.buy_token) || token_list.contains(&self.data.sell_token)
     }
@@ -354,6 +374,10 @@ pub struct OrderCreation {
     #[serde(flatten)]
     pub signature: Signature,
     pub quote_id: Option<QuoteId>,
+    /// The JSON document whose keccak256 hash is the order's `appData`. Only needed for orders
+    /// that specify policies like a partner fee.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_app_data: Option<String>,
 }
 
 impl OrderCreation {
@@ -380,6 +404,7 @@ impl Default for OrderCreation {
             from: None,
             signature: Signature::Eip712(EcdsaSignature::non_zero()),
             quote_id: None,
+            full_app_data: None,
         }
     }
 }
@@ -391,6 +416,7 @@ impl From<Order> for OrderCreation {
             from: Some(order.metadata.owner),
             signature: order.signature,
             quote_id: None,
+            full_app_data: order.metadata.full_app_data,
         }
     }
 }
@@ -440,6 +466,56 @@ impl OrderCancellation {
     }
 }
 
+/// An order replacement as provided to the orderbook by the frontend.
+///
+/// The signature commits to both the order being replaced and the new order, which allows
+/// cancelling an order and creating a new one in a single atomic operation.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct OrderReplacement {
+    pub old_order_uid: OrderUid,
+    pub new_order: OrderCreation,
+    pub signature: EcdsaSignature,
+    pub signing_scheme: EcdsaSigningScheme,
+}
+
+impl Default for OrderReplacement {
+    fn default() -> Self {
+        let mut result = Self {
+            old_order_uid: OrderUid::default(),
+            new_order: OrderCreation::default(),
+            signature: Default::default(),
+            signing_scheme: EcdsaSigningScheme::Eip712,
+        };
+        result.signature = EcdsaSignature::sign(
+            result.signing_scheme,
+            &DomainSeparator::default(),
+            &result.hash_struct(),
+            SecretKeyRef::new(&ONE_KEY),
+        );
+        result
+    }
+}
+
+// EIP-712
+impl OrderReplacement {
+    // keccak256("OrderReplacement(bytes oldOrderUid,bytes32 newOrderHash)")
+    const TYPE_HASH: [u8; 32] =
+        hex!("40997ca0915b916aa7aa9b0f7161c2327368ea30c6d22eaf0e7a990ed02526a6");
+
+    pub fn hash_struct(&self) -> [u8; 32] {
+        let mut hash_data = [0u8; 96];
+        hash_data[0..32].copy_from_slice(&Self::TYPE_HASH);
+        hash_data[32..64].copy_from_slice(&signing::keccak256(&self.old_order_uid.0));
+        hash_data[64..96].copy_from_slice(&self.new_order.data.hash_struct());
+        signing::keccak256(&hash_data)
+    }
+
+    pub fn validate(&self, domain_separator: &DomainSeparator) -> Result<H160> {
+        self.signature
+            .recover(self.signing_scheme, domain_separator, &self.hash_struct())
+    }
+}
+
 /// An order as provided to the orderbook by the frontend.
 #[serde_as]
 #[derive(Eq, PartialEq, Clone, Derivative, Deserialize, Serialize, Hash)]
@@ -467,6 +543,18 @@ pub struct OrderMetadata {
     #[serde(default, with = "u256_decimal")]
     pub full_fee_amount: U256,
     pub is_liquidity_order: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_app_data: Option<String>,
+    /// The order that replaced this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<OrderUid>,
+    /// The order that was replaced by this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaces: Option<OrderUid>,
+    /// Set if the order is open but excluded from auctions because its executable amount is too
+    /// small to be worth settling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unfillable_reason: Option<UnfillableReason>,
 }
 
 impl Default for OrderMetadata {
@@ -485,6 +573,10 @@ impl Default for OrderMetadata {
             settlement_contract: H160::default(),
             full_fee_amount: U256::default(),
             is_liquidity_order: false,
+            full_app_data: None,
+            replaced_by: None,
+            replaces: None,
+            unfillable_reason: None,
         }
     }
 }
@@ -731,6 +823,10 @@ mod tests {
                 settlement_contract: H160::from_low_u64_be(2),
                 full_fee_amount: U256::MAX,
                 is_liquidity_order: false,
+                full_app_data: None,
+                replaced_by: None,
+                replaces: None,
+                unfillable_reason: None,
             },
             data: OrderData {
                 sell_token: H160::from_low_u64_be(10),
@@ -807,6 +903,7 @@ mod tests {
                 from,
                 signature,
                 quote_id: Some(42),
+                full_app_data: None,
             };
             let order_json = json!({
                 "sellToken": "0x1111111111111111111111111111111111111111",
@@ -945,6 +1042,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn order_replacement_signature() {
+        let domain_separator = DomainSeparator([0x42; 32]);
+        let key = SecretKeyRef::new(&ONE_KEY);
+        let expected_owner = key.address();
+
+        for signing_scheme in [EcdsaSigningScheme::Eip712, EcdsaSigningScheme::EthSign] {
+            let mut replacement = OrderReplacement {
+                old_order_uid: OrderUid([0x2a; 56]),
+                new_order: OrderCreation {
+                    data: OrderData {
+                        sell_amount: 1.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                signing_scheme,
+                ..Default::default()
+            };
+            replacement.signature = EcdsaSignature::sign(
+                signing_scheme,
+                &domain_separator,
+                &replacement.hash_struct(),
+                key,
+            );
+            assert_eq!(
+                replacement.validate(&domain_separator).unwrap(),
+                expected_owner
+            );
+
+            // The signature does not apply to a different new order.
+            let mut other_order = replacement.clone();
+            other_order.new_order.data.sell_amount = 2.into();
+            assert_ne!(
+                other_order.validate(&domain_separator).unwrap(),
+                expected_owner
+            );
+        }
+    }
+
     #[test]
     fn domain_separator_does_not_panic_in_debug() {
         println!("{:?}", DomainSeparator::default());
@@ -0,0 +1,145 @@
+//! Types for inspecting pending pre-sign orders and preparing the Safe transactions that make them
+//! solvable.
+
+use crate::{bytes_hex, order::OrderStatus, u256_decimal};
+use primitive_types::{H160, U256};
+use serde::{Deserialize, Serialize};
+
+/// A reason why a pre-sign order is not (yet) included in the solvable orders.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PendingReason {
+    /// No `PreSignature` event has been indexed for the order yet.
+    MissingPresignature,
+    /// The owner does not hold enough sell tokens to fill the order.
+    InsufficientBalance,
+    /// The owner has not approved enough sell tokens for the settlement contract to use.
+    InsufficientAllowance,
+    /// Balance and allowance are sufficient but transferring the sell token fails anyway.
+    TransferFailed,
+}
+
+/// The pre-signature status of an order.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignatureStatus {
+    pub status: OrderStatus,
+    /// All the reasons keeping the order from being solvable. Empty if the order can be settled.
+    pub pending_reasons: Vec<PendingReason>,
+}
+
+/// The operation a Safe executes a transaction with.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(into = "u8", try_from = "u8")]
+pub enum SafeOperation {
+    Call,
+    DelegateCall,
+}
+
+impl From<SafeOperation> for u8 {
+    fn from(operation: SafeOperation) -> Self {
+        match operation {
+            SafeOperation::Call => 0,
+            SafeOperation::DelegateCall => 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for SafeOperation {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Call),
+            1 => Ok(Self::DelegateCall),
+            _ => Err("invalid Safe operation"),
+        }
+    }
+}
+
+/// A Safe transaction ready to be signed by the Safe owners.
+///
+/// The fields follow the `SafeTx` EIP-712 struct and the format accepted by the Safe transaction
+/// service.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeTransaction {
+    pub safe: H160,
+    pub to: H160,
+    #[serde(with = "u256_decimal")]
+    pub value: U256,
+    #[serde(with = "bytes_hex")]
+    pub data: Vec<u8>,
+    pub operation: SafeOperation,
+    #[serde(with = "u256_decimal")]
+    pub safe_tx_gas: U256,
+    #[serde(with = "u256_decimal")]
+    pub base_gas: U256,
+    #[serde(with = "u256_decimal")]
+    pub gas_price: U256,
+    pub gas_token: H160,
+    pub refund_receiver: H160,
+    #[serde(with = "u256_decimal")]
+    pub nonce: U256,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serialize_presignature_status() {
+        let status = PresignatureStatus {
+            status: OrderStatus::PresignaturePending,
+            pending_reasons: vec![
+                PendingReason::MissingPresignature,
+                PendingReason::InsufficientAllowance,
+            ],
+        };
+        let json = json!({
+            "status": "presignaturePending",
+            "pendingReasons": ["missingPresignature", "insufficientAllowance"],
+        });
+        assert_eq!(serde_json::to_value(&status).unwrap(), json);
+        assert_eq!(
+            serde_json::from_value::<PresignatureStatus>(json).unwrap(),
+            status
+        );
+    }
+
+    #[test]
+    fn serialize_safe_transaction() {
+        let transaction = SafeTransaction {
+            safe: H160([0x5a; 20]),
+            to: H160([0x01; 20]),
+            value: 0.into(),
+            data: vec![0x8d, 0x80, 0xff, 0x0a],
+            operation: SafeOperation::DelegateCall,
+            safe_tx_gas: 0.into(),
+            base_gas: 0.into(),
+            gas_price: 0.into(),
+            gas_token: H160::zero(),
+            refund_receiver: H160::zero(),
+            nonce: 42.into(),
+        };
+        let json = json!({
+            "safe": "0x5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+            "to": "0x0101010101010101010101010101010101010101",
+            "value": "0",
+            "data": "0x8d80ff0a",
+            "operation": 1,
+            "safeTxGas": "0",
+            "baseGas": "0",
+            "gasPrice": "0",
+            "gasToken": "0x0000000000000000000000000000000000000000",
+            "refundReceiver": "0x0000000000000000000000000000000000000000",
+            "nonce": "42",
+        });
+        assert_eq!(serde_json::to_value(&transaction).unwrap(), json);
+        assert_eq!(
+            serde_json::from_value::<SafeTransaction>(json).unwrap(),
+            transaction
+        );
+    }
+}
@@ -35,12 +35,19 @@ pub struct CompetitionAuction {
 #[serde(rename_all = "camelCase")]
 pub struct SolverSettlement {
     pub solver: String,
+    /// The solvers whose settlements were merged into this one. Empty unless this settlement was
+    /// built by the driver from the settlements of multiple solvers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged_solvers: Vec<String>,
     pub objective: Objective,
     #[serde_as(as = "BTreeMap<_, DecimalU256>")]
     pub clearing_prices: BTreeMap<H160, U256>,
     pub orders: Vec<Order>,
     #[serde(with = "crate::bytes_hex")]
     pub call_data: Vec<u8>,
+    /// User orders that got executed at a worse price than the EBBO price estimate.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ebbo_violations: Vec<OrderUid>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
@@ -51,6 +58,9 @@ pub struct Objective {
     pub fees: f64,
     pub cost: f64,
     pub gas: u64,
+    /// The estimated probability of the settlement reverting on-chain.
+    #[serde(default)]
+    pub revert_probability: f64,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
@@ -101,6 +111,7 @@ mod tests {
                         "fees": 5.0f64,
                         "cost": 6.0f64,
                         "gas": 7u64,
+                        "revertProbability": 0.25f64,
                     },
                     "clearingPrices": {
                         "0x2222222222222222222222222222222222222222": "8",
@@ -138,12 +149,14 @@ mod tests {
             },
             solutions: vec![SolverSettlement {
                 solver: "2".to_string(),
+                merged_solvers: Default::default(),
                 objective: Objective {
                     total: 3.,
                     surplus: 4.,
                     fees: 5.,
                     cost: 6.,
                     gas: 7,
+                    revert_probability: 0.25,
                 },
                 clearing_prices: btreemap! {
                     H160([0x22; 20]) => 8.into(),
@@ -153,6 +166,7 @@ mod tests {
                     executed_amount: 12.into(),
                 }],
                 call_data: vec![0x13],
+                ebbo_violations: Default::default(),
             }],
         };
 
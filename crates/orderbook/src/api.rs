@@ -6,6 +6,7 @@ mod get_fee_info;
 mod get_markets;
 mod get_order_by_uid;
 mod get_orders_by_tx;
+mod get_presignature;
 mod get_solvable_orders;
 mod get_solvable_orders_v2;
 mod get_solver_competition;
@@ -17,7 +18,10 @@ mod replace_order;
 
 use self::post_solver_competition::SolvableOrdersCache;
 use crate::solver_competition::SolverCompetitionStoring;
-use crate::{database::trades::TradeRetrieving, order_quoting::QuoteHandler, orderbook::Orderbook};
+use crate::{
+    database::trades::TradeRetrieving, order_quoting::QuoteHandler, orderbook::Orderbook,
+    presignature::PresignatureHandler,
+};
 use shared::api::{error, finalize_router, internal_error, ApiReply};
 use std::sync::Arc;
 use warp::{Filter, Rejection, Reply};
@@ -29,6 +33,7 @@ pub fn handle_all_routes(
     solver_competition: Arc<dyn SolverCompetitionStoring>,
     solver_competition_auth: Option<String>,
     solvable_orders: Arc<dyn SolvableOrdersCache>,
+    presignatures: Arc<PresignatureHandler>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     // Routes for api v1.
 
@@ -72,6 +77,13 @@ pub fn handle_all_routes(
     let get_orders_by_tx = get_orders_by_tx::get_orders_by_tx(orderbook.clone())
         .map(|result| (result, "v1/get_orders_by_tx"))
         .boxed();
+    let get_presignature_status = get_presignature::get_presignature_status(presignatures.clone())
+        .map(|result| (result, "v1/get_presignature_status"))
+        .boxed();
+    let get_presignature_safe_transaction =
+        get_presignature::get_presignature_safe_transaction(presignatures)
+            .map(|result| (result, "v1/get_presignature_safe_transaction"))
+            .boxed();
     let post_quote = post_quote::post_quote(quotes)
         .map(|result| (result, "v1/post_quote"))
         .boxed();
@@ -111,6 +123,10 @@ pub fn handle_all_routes(
                 .unify()
                 .or(get_orders_by_tx)
                 .unify()
+                .or(get_presignature_status)
+                .unify()
+                .or(get_presignature_safe_transaction)
+                .unify()
                 .or(post_quote)
                 .unify()
                 .or(get_auction)
@@ -159,6 +159,24 @@ impl IntoWarpReply for ValidationError {
                 error("ZeroAmount", "Buy or sell amount is zero."),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::AppDataHashMismatch => with_status(
+                error(
+                    "AppDataHashMismatch",
+                    "Full app data does not hash to the order's app data",
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::InvalidAppData(err) => with_status(
+                error("InvalidAppData", format!("Invalid full app data: {err}")),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::InvalidPartnerFee => with_status(
+                error(
+                    "InvalidPartnerFee",
+                    "Partner fee exceeds the maximum, has no recipient or is paid in native ETH",
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::Other(err) => with_status(
                 internal_error(err.context("order_validation")),
                 StatusCode::INTERNAL_SERVER_ERROR,
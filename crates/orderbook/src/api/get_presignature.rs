@@ -0,0 +1,95 @@
+use crate::presignature::{PresignatureError, PresignatureHandler};
+use anyhow::Result;
+use model::order::OrderUid;
+use shared::api::{convert_json_response, IntoWarpReply};
+use std::{convert::Infallible, sync::Arc};
+use warp::{hyper::StatusCode, reply::with_status, Filter, Rejection};
+
+fn get_presignature_status_request() -> impl Filter<Extract = (OrderUid,), Error = Rejection> + Clone
+{
+    warp::path!("orders" / OrderUid / "presignature").and(warp::get())
+}
+
+fn get_presignature_safe_transaction_request(
+) -> impl Filter<Extract = (OrderUid,), Error = Rejection> + Clone {
+    warp::path!("orders" / OrderUid / "presignature" / "safe_transaction").and(warp::get())
+}
+
+impl IntoWarpReply for PresignatureError {
+    fn into_warp_reply(self) -> super::ApiReply {
+        match self {
+            Self::OrderNotFound => with_status(
+                super::error("NotFound", "Order was not found"),
+                StatusCode::NOT_FOUND,
+            ),
+            Self::NotPresignOrder => with_status(
+                super::error("NotPresignOrder", "Order is not a pre-sign order"),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::OrderClosed => with_status(
+                super::error("OrderClosed", "Order is fulfilled, cancelled or expired"),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::NotASafe => with_status(
+                super::error("NotASafe", "Order owner is not a Safe"),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::Other(err) => with_status(
+                super::internal_error(err.context("presignature")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        }
+    }
+}
+
+pub fn get_presignature_status(
+    presignatures: Arc<PresignatureHandler>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    get_presignature_status_request().and_then(move |uid: OrderUid| {
+        let presignatures = presignatures.clone();
+        async move {
+            let result = presignatures.status(&uid).await;
+            Result::<_, Infallible>::Ok(convert_json_response(result))
+        }
+    })
+}
+
+pub fn get_presignature_safe_transaction(
+    presignatures: Arc<PresignatureHandler>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    get_presignature_safe_transaction_request().and_then(move |uid: OrderUid| {
+        let presignatures = presignatures.clone();
+        async move {
+            let result = presignatures.safe_transaction(&uid).await;
+            Result::<_, Infallible>::Ok(convert_json_response(result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::test::request;
+
+    #[tokio::test]
+    async fn get_presignature_status_request_ok() {
+        let uid = OrderUid::default();
+        let request = request()
+            .path(&format!("/orders/{:}/presignature", uid))
+            .method("GET");
+        let filter = get_presignature_status_request();
+        let result = request.filter(&filter).await.unwrap();
+        assert_eq!(result, uid);
+    }
+
+    #[tokio::test]
+    async fn get_presignature_safe_transaction_request_ok() {
+        let uid = OrderUid::default();
+        let request = request()
+            .path(&format!("/orders/{:}/presignature/safe_transaction", uid))
+            .method("GET");
+        let filter = get_presignature_safe_transaction_request();
+        let result = request.filter(&filter).await.unwrap();
+        assert_eq!(result, uid);
+    }
+}
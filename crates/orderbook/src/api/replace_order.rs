@@ -1,15 +1,33 @@
 use crate::orderbook::{Orderbook, ReplaceOrderError};
 use anyhow::Result;
-use model::order::{OrderCreation, OrderUid};
+use model::{
+    order::{OrderCreation, OrderReplacement, OrderUid},
+    signature::{EcdsaSignature, EcdsaSigningScheme},
+};
 use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use shared::api::{extract_payload, IntoWarpReply};
 use std::{convert::Infallible, sync::Arc};
 use warp::{reply, Filter, Rejection};
 
-fn request() -> impl Filter<Extract = (OrderUid, OrderCreation), Error = Rejection> + Clone {
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplacementPayload {
+    new_order: OrderCreation,
+    signature: EcdsaSignature,
+    signing_scheme: EcdsaSigningScheme,
+}
+
+fn request() -> impl Filter<Extract = (OrderReplacement,), Error = Rejection> + Clone {
     warp::path!("orders" / OrderUid)
         .and(warp::patch())
         .and(extract_payload())
+        .map(|uid, payload: ReplacementPayload| OrderReplacement {
+            old_order_uid: uid,
+            new_order: payload.new_order,
+            signature: payload.signature,
+            signing_scheme: payload.signing_scheme,
+        })
 }
 
 fn response(result: Result<OrderUid, ReplaceOrderError>) -> super::ApiReply {
@@ -22,10 +40,10 @@ fn response(result: Result<OrderUid, ReplaceOrderError>) -> super::ApiReply {
 pub fn filter(
     orderbook: Arc<Orderbook>,
 ) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
-    request().and_then(move |old_order, new_order| {
+    request().and_then(move |replacement| {
         let orderbook = orderbook.clone();
         async move {
-            let result = orderbook.replace_order(old_order, new_order).await;
+            let result = orderbook.replace_order(replacement).await;
             Result::<_, Infallible>::Ok(response(result))
         }
     })
@@ -50,18 +68,25 @@ mod tests {
 
     #[tokio::test]
     async fn replace_order_request_filter() {
-        let old_order = OrderUid::default();
-        let new_order = OrderCreation::default();
+        let replacement = OrderReplacement {
+            old_order_uid: OrderUid([1; 56]),
+            ..Default::default()
+        };
+        let payload = ReplacementPayload {
+            new_order: replacement.new_order.clone(),
+            signature: replacement.signature,
+            signing_scheme: replacement.signing_scheme,
+        };
 
         let result = warp::test::request()
-            .path(&format!("/orders/{old_order}"))
+            .path(&format!("/orders/{}", replacement.old_order_uid))
             .method("PATCH")
             .header("content-type", "application/json")
-            .json(&new_order)
+            .json(&payload)
             .filter(&request())
             .await
             .unwrap();
 
-        assert_eq!(result, (old_order, new_order));
+        assert_eq!(result, replacement);
     }
 }
@@ -76,6 +76,16 @@ pub struct Arguments {
     #[clap(long, env, parse(try_from_str), default_value = "false")]
     pub enable_eip1271_orders: bool,
 
+    /// The number of blocks after which a cached valid EIP-1271 signature validation result is
+    /// re-checked, even if no state change of the owner contract was observed.
+    #[clap(long, env, default_value = "50")]
+    pub eip1271_valid_signature_recheck_blocks: u64,
+
+    /// The number of blocks after which a cached invalid EIP-1271 signature validation result is
+    /// re-checked, even if no state change of the owner contract was observed.
+    #[clap(long, env, default_value = "10")]
+    pub eip1271_invalid_signature_recheck_blocks: u64,
+
     /// Enable pre-sign orders. Pre-sign orders are accepted into the database without a valid
     /// signature, so this flag allows this feature to be turned off if malicious users are
     /// abusing the database by inserting a bunch of order rows that won't ever be valid.
@@ -128,6 +138,23 @@ pub struct Arguments {
     )]
     pub partner_additional_fee_factors: HashMap<AppId, f64>,
 
+    /// The maximum partner fee in basis points that orders can specify in their full app data.
+    /// Partner fees are withheld from the buy tokens the trader receives, in addition to paying the
+    /// protocol fee, and are independent of the `partner_additional_fee_factors` fee subsidies.
+    #[clap(long, env, default_value = "100")]
+    pub max_partner_fee_bps: u64,
+
+    /// Open orders whose executable sell amount is worth less than this amount of native token
+    /// atoms are considered unfillable and excluded from auctions.
+    #[clap(long, env, default_value = "0", parse(try_from_str = U256::from_dec_str))]
+    pub unfillable_min_native_value: U256,
+
+    /// The gas needed to settle a single order. Open orders whose executable sell amount is worth
+    /// less than this amount of gas at the current gas price are considered unfillable and
+    /// excluded from auctions. A value of 0 disables this check.
+    #[clap(long, env, default_value = "0")]
+    pub unfillable_gas_per_order: u64,
+
     /// Used to configure how much of the regular fee a user should pay based on their
     /// COW + VCOW balance in base units on the current network.
     ///
@@ -252,6 +279,16 @@ impl std::fmt::Display for Arguments {
         writeln!(f, "allowed_tokens: {:?}", self.allowed_tokens)?;
         writeln!(f, "pool_cache_lru_size: {}", self.pool_cache_lru_size)?;
         writeln!(f, "enable_eip1271_orders: {}", self.enable_eip1271_orders)?;
+        writeln!(
+            f,
+            "eip1271_valid_signature_recheck_blocks: {}",
+            self.eip1271_valid_signature_recheck_blocks
+        )?;
+        writeln!(
+            f,
+            "eip1271_invalid_signature_recheck_blocks: {}",
+            self.eip1271_invalid_signature_recheck_blocks
+        )?;
         writeln!(f, "enable_presign_orders: {}", self.enable_presign_orders)?;
         writeln!(
             f,
@@ -266,6 +303,17 @@ impl std::fmt::Display for Arguments {
             "partner_additional_fee_factors: {:?}",
             self.partner_additional_fee_factors
         )?;
+        writeln!(f, "max_partner_fee_bps: {}", self.max_partner_fee_bps)?;
+        writeln!(
+            f,
+            "unfillable_min_native_value: {}",
+            self.unfillable_min_native_value
+        )?;
+        writeln!(
+            f,
+            "unfillable_gas_per_order: {}",
+            self.unfillable_gas_per_order
+        )?;
         writeln!(f, "cow_fee_factors: {:?}", self.cow_fee_factors)?;
         write!(f, "quasimodo_solver_url: ")?;
         display_option(&self.quasimodo_solver_url, f)?;
@@ -31,12 +31,14 @@ pub trait OrderStoring: Send + Sync {
     async fn insert_order(&self, order: &Order, quote: Option<Quote>)
         -> Result<(), InsertionError>;
     async fn cancel_order(&self, order_uid: &OrderUid, now: DateTime<Utc>) -> Result<()>;
+    /// Atomically cancels an open order, inserts its replacement and records the replacement in
+    /// the order history.
     async fn replace_order(
         &self,
         old_order: &OrderUid,
         new_order: &Order,
         new_quote: Option<Quote>,
-    ) -> Result<(), InsertionError>;
+    ) -> Result<(), ReplacementError>;
     async fn orders_for_tx(&self, tx_hash: &H256) -> Result<Vec<Order>>;
     async fn single_order(&self, uid: &OrderUid) -> Result<Option<Order>>;
     /// Orders that are solvable: minimum valid to, not fully executed, not invalidated.
@@ -129,8 +131,28 @@ impl From<sqlx::Error> for InsertionError {
     }
 }
 
+#[derive(Debug)]
+pub enum ReplacementError {
+    OldOrderNotFound,
+    /// The old order can no longer be replaced, for example because it got cancelled concurrently.
+    OldOrderNotOpen(OrderStatus),
+    Insertion(InsertionError),
+}
+
+impl From<InsertionError> for ReplacementError {
+    fn from(err: InsertionError) -> Self {
+        Self::Insertion(err)
+    }
+}
+
+impl From<sqlx::Error> for ReplacementError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Insertion(InsertionError::DbError(err))
+    }
+}
+
 async fn insert_order(order: &Order, ex: &mut PgConnection) -> Result<(), InsertionError> {
-    let order = database::orders::Order {
+    let db_order = database::orders::Order {
         uid: ByteArray(order.metadata.uid.0),
         owner: ByteArray(order.metadata.owner.0),
         creation_timestamp: order.metadata.creation_date,
@@ -153,7 +175,7 @@ async fn insert_order(order: &Order, ex: &mut PgConnection) -> Result<(), Insert
         is_liquidity_order: order.metadata.is_liquidity_order,
         cancellation_timestamp: None,
     };
-    database::orders::insert_order(ex, &order)
+    database::orders::insert_order(ex, &db_order)
         .await
         .map_err(|err| {
             if database::orders::is_duplicate_record_error(&err) {
@@ -161,7 +183,11 @@ async fn insert_order(order: &Order, ex: &mut PgConnection) -> Result<(), Insert
             } else {
                 InsertionError::DbError(err)
             }
-        })
+        })?;
+    if let Some(full_app_data) = &order.metadata.full_app_data {
+        database::app_data::insert(ex, &db_order.app_data, full_app_data.as_bytes()).await?;
+    }
+    Ok(())
 }
 
 async fn insert_quote(
@@ -229,7 +255,7 @@ impl OrderStoring for Postgres {
         old_order: &model::order::OrderUid,
         new_order: &model::order::Order,
         new_quote: Option<Quote>,
-    ) -> anyhow::Result<(), super::orders::InsertionError> {
+    ) -> Result<(), ReplacementError> {
         let _timer = super::Metrics::get()
             .database_queries
             .with_label_values(&["replace_order"])
@@ -241,9 +267,24 @@ impl OrderStoring for Postgres {
         connection
             .transaction(move |ex| {
                 async move {
+                    // Lock the old order so that its status can't change before the replacement
+                    // is committed.
+                    let old_order = ByteArray(old_order.0);
+                    if !database::orders::lock_order(ex, &old_order).await? {
+                        return Err(ReplacementError::OldOrderNotFound);
+                    }
+                    let status = database::orders::single_full_order(ex, &old_order)
+                        .await?
+                        .as_ref()
+                        .map(calculate_status)
+                        .ok_or(ReplacementError::OldOrderNotFound)?;
+                    if status != OrderStatus::Open {
+                        return Err(ReplacementError::OldOrderNotOpen(status));
+                    }
+
                     database::orders::cancel_order(
                         ex,
-                        &ByteArray(old_order.0),
+                        &old_order,
                         new_order.metadata.creation_date,
                     )
                     .await?;
@@ -251,6 +292,13 @@ impl OrderStoring for Postgres {
                     if let Some(quote) = new_quote {
                         insert_quote(&new_order.metadata.uid, &quote, ex).await?;
                     }
+                    database::orders::insert_order_replacement(
+                        ex,
+                        &old_order,
+                        &ByteArray(new_order.metadata.uid.0),
+                        new_order.metadata.creation_date,
+                    )
+                    .await?;
                     Ok(())
                 }
                 .boxed()
@@ -385,6 +433,14 @@ fn full_order_into_model_order(order: FullOrder) -> Result<Order> {
         full_fee_amount: big_decimal_to_u256(&order.full_fee_amount)
             .ok_or_else(|| anyhow!("full_fee_amount is not U256"))?,
         is_liquidity_order: order.is_liquidity_order,
+        full_app_data: order
+            .full_app_data
+            .map(String::from_utf8)
+            .transpose()
+            .context("full app data is not valid utf-8")?,
+        replaced_by: order.replaced_by.map(|uid| OrderUid(uid.0)),
+        replaces: order.replaces.map(|uid| OrderUid(uid.0)),
+        unfillable_reason: None,
     };
     let data = OrderData {
         sell_token: H160(order.sell_token.0),
@@ -475,6 +531,9 @@ mod tests {
             buy_token_balance: DbBuyTokenDestination::Internal,
             presignature_pending: false,
             is_liquidity_order: true,
+            full_app_data: None,
+            replaced_by: None,
+            replaces: None,
         };
 
         // Open - sell (filled - 0%)
@@ -705,6 +764,46 @@ mod tests {
             old_order_cancellation.unwrap().timestamp_millis(),
             new_order.metadata.creation_date.timestamp_millis(),
         );
+
+        // The order history links both orders.
+        let old_order_ = db
+            .single_order(&old_order.metadata.uid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            old_order_.metadata.replaced_by,
+            Some(new_order.metadata.uid)
+        );
+        let new_order_ = db
+            .single_order(&new_order.metadata.uid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(new_order_.metadata.replaces, Some(old_order.metadata.uid));
+
+        // The old order is no longer open and can't be replaced again.
+        let another_order = Order {
+            metadata: OrderMetadata {
+                owner,
+                uid: OrderUid([3; 56]),
+                ..Default::default()
+            },
+            ..new_order
+        };
+        let err = db
+            .replace_order(&old_order.metadata.uid, &another_order, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ReplacementError::OldOrderNotOpen(OrderStatus::Cancelled)
+        ));
+        assert!(db
+            .single_order(&another_order.metadata.uid)
+            .await
+            .unwrap()
+            .is_none());
     }
 
     #[tokio::test]
@@ -716,6 +815,10 @@ mod tests {
         database::clear_DANGER(&db.pool).await.unwrap();
 
         let old_order = Order {
+            data: OrderData {
+                valid_to: u32::MAX,
+                ..Default::default()
+            },
             metadata: OrderMetadata {
                 owner,
                 uid: OrderUid([1; 56]),
@@ -741,7 +844,10 @@ mod tests {
             .replace_order(&old_order.metadata.uid, &new_order, None)
             .await
             .unwrap_err();
-        assert!(matches!(err, InsertionError::DuplicatedRecord));
+        assert!(matches!(
+            err,
+            ReplacementError::Insertion(InsertionError::DuplicatedRecord)
+        ));
 
         // Old order cancellation status should remain unchanged.
         let (old_order_cancellation,): (Option<DateTime<Utc>>,) =
@@ -870,4 +976,37 @@ mod tests {
         insert_presignature(true).await;
         assert_eq!(order_status().await, OrderStatus::Open);
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_full_app_data_roundtrip() {
+        let db = Postgres::new("postgresql://").unwrap();
+        database::clear_DANGER(&db.pool).await.unwrap();
+
+        let full_app_data = r#"{"metadata":{}}"#.to_string();
+        let app_data = model::app_data::hash_full_app_data(&full_app_data);
+        let order = |uid: u8, full_app_data: Option<String>| Order {
+            data: OrderData {
+                app_data,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([uid; 56]),
+                full_app_data,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let with_document = order(1, Some(full_app_data.clone()));
+        db.insert_order(&with_document, None).await.unwrap();
+        // Orders with the same app data hash share the stored document.
+        let without_document = order(2, None);
+        db.insert_order(&without_document, None).await.unwrap();
+
+        for uid in [with_document.metadata.uid, without_document.metadata.uid] {
+            let order = db.single_order(&uid).await.unwrap().unwrap();
+            assert_eq!(order.metadata.full_app_data, Some(full_app_data.clone()));
+        }
+    }
 }
@@ -83,10 +83,12 @@ mod tests {
             },
             solutions: vec![SolverSettlement {
                 solver: "asdf".to_string(),
+                merged_solvers: vec!["asdf".to_string(), "qwer".to_string()],
                 objective: Default::default(),
                 clearing_prices: [Default::default()].into_iter().collect(),
                 orders: vec![Default::default()],
                 call_data: vec![1, 2],
+                ebbo_violations: vec![Default::default()],
             }],
         };
         let id = db.save(expected.clone()).await.unwrap();
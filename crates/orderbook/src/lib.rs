@@ -5,11 +5,13 @@ pub mod fee_subsidy;
 pub mod order_quoting;
 pub mod order_validation;
 pub mod orderbook;
+pub mod presignature;
+pub mod signature_validation_cache;
 pub mod solvable_orders;
 pub mod solver_competition;
 
 use crate::database::trades::TradeRetrieving;
-use crate::{order_quoting::QuoteHandler, orderbook::Orderbook};
+use crate::{order_quoting::QuoteHandler, orderbook::Orderbook, presignature::PresignatureHandler};
 use anyhow::{anyhow, Context as _, Result};
 use api::post_solver_competition::SolvableOrdersCache;
 use contracts::GPv2Settlement;
@@ -30,6 +32,7 @@ pub fn serve_api(
     solver_competition: Arc<dyn SolverCompetitionStoring>,
     solver_competition_auth: Option<String>,
     solvable_orders: Arc<dyn SolvableOrdersCache>,
+    presignatures: Arc<PresignatureHandler>,
 ) -> JoinHandle<()> {
     let filter = api::handle_all_routes(
         database,
@@ -38,6 +41,7 @@ pub fn serve_api(
         solver_competition,
         solver_competition_auth,
         solvable_orders,
+        presignatures,
     )
     .boxed();
     tracing::info!(%address, "serving order book");
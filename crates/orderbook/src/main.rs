@@ -13,8 +13,12 @@ use orderbook::{
     order_quoting::{Forget, OrderQuoter, QuoteHandler, QuoteStoring},
     order_validation::{OrderValidator, SignatureConfiguration},
     orderbook::Orderbook,
+    presignature::PresignatureHandler,
     serve_api,
-    solvable_orders::SolvableOrdersCache,
+    signature_validation_cache::{
+        SignatureValidationCache, ValidationCacheConfig, Web3OwnerStateWatcher,
+    },
+    solvable_orders::{SolvableOrdersCache, UnfillableOrderPolicy},
     verify_deployed_contract_constants,
 };
 use primitive_types::U256;
@@ -62,7 +66,10 @@ use shared::{
         self,
         balancer_v2::{pool_fetching::BalancerContracts, BalancerPoolFetcher},
         uniswap_v2::pool_cache::PoolCache,
-        uniswap_v3::pool_fetching::AutoUpdatingUniswapV3PoolFetcher,
+        uniswap_v3::{
+            event_fetching::UniswapV3PoolIndexer,
+            pool_fetching::PoolFetching as UniswapV3PoolFetching,
+        },
         BaselineSource, PoolAggregator,
     },
     token_info::{CachedTokenInfoFetcher, TokenInfoFetcher},
@@ -289,10 +296,11 @@ async fn main() {
     };
     let uniswap_v3_pool_fetcher = if baseline_sources.contains(&BaselineSource::UniswapV3) {
         let uniswap_v3_pool_fetcher = Arc::new(
-            AutoUpdatingUniswapV3PoolFetcher::new(
+            UniswapV3PoolIndexer::new(
                 chain_id,
-                args.shared.liquidity_fetcher_max_age_update,
                 client.clone(),
+                web3.clone(),
+                args.shared.uniswap_v3_checkpoint_path.clone(),
             )
             .await
             .expect("failed to create UniswapV3 pool fetcher in orderbook"),
@@ -347,7 +355,9 @@ async fn main() {
                     }),
                     pool_fetcher.clone(),
                     balancer_pool_fetcher.clone(),
-                    uniswap_v3_pool_fetcher.clone(),
+                    uniswap_v3_pool_fetcher
+                        .clone()
+                        .map(|fetcher| fetcher as Arc<dyn UniswapV3PoolFetching>),
                     token_info_fetcher.clone(),
                     gas_price_estimator.clone(),
                     native_token.address(),
@@ -359,11 +369,15 @@ async fn main() {
             let instance: Box<dyn PriceEstimating> = match estimator {
                 PriceEstimatorType::Baseline => Box::new(BaselinePriceEstimator::new(
                     pool_fetcher.clone(),
+                    uniswap_v3_pool_fetcher
+                        .clone()
+                        .map(|fetcher| fetcher as Arc<dyn UniswapV3PoolFetching>),
                     gas_price_estimator.clone(),
                     base_tokens.clone(),
                     native_token.address(),
                     native_token_price_estimation_amount,
                     rate_limiter(estimator.name()),
+                    args.shared.baseline_max_split_paths,
                 )),
                 PriceEstimatorType::Paraswap => Box::new(ParaswapPriceEstimator::new(
                     Arc::new(DefaultParaswapApi {
@@ -516,8 +530,20 @@ async fn main() {
         bad_token_detector.clone(),
         current_block_stream.clone(),
         native_price_estimator.clone(),
-        signature_validator.clone(),
+        gas_price_estimator.clone(),
+        SignatureValidationCache::new(
+            signature_validator.clone(),
+            Arc::new(Web3OwnerStateWatcher::new(web3.clone())),
+            ValidationCacheConfig {
+                valid_recheck_blocks: args.eip1271_valid_signature_recheck_blocks,
+                invalid_recheck_blocks: args.eip1271_invalid_signature_recheck_blocks,
+            },
+        ),
         database.clone(),
+        UnfillableOrderPolicy {
+            min_native_value: args.unfillable_min_native_value,
+            gas_per_order: args.unfillable_gas_per_order,
+        },
     );
     let block = current_block_stream.borrow().number.unwrap().as_u64();
     solvable_orders_cache
@@ -537,8 +563,9 @@ async fn main() {
         },
         bad_token_detector.clone(),
         optimal_quoter.clone(),
-        balance_fetcher,
+        balance_fetcher.clone(),
         signature_validator,
+        args.max_partner_fee_bps,
     ));
     let orderbook = Arc::new(Orderbook::new(
         domain_separator,
@@ -554,9 +581,19 @@ async fn main() {
     if let Some(balancer) = balancer_pool_fetcher {
         service_maintainer.maintainers.push(balancer);
     }
+    if let Some(uniswap_v3) = uniswap_v3_pool_fetcher {
+        service_maintainer.maintainers.push(uniswap_v3);
+    }
     check_database_connection(orderbook.as_ref()).await;
     let quotes =
         Arc::new(QuoteHandler::new(order_validator, optimal_quoter).with_fast_quoter(fast_quoter));
+    let presignatures = Arc::new(PresignatureHandler::new(
+        database.clone(),
+        balance_fetcher,
+        settlement_contract.clone(),
+        vault_relayer,
+        vault.as_ref().map(|vault| vault.address()),
+    ));
     let (shutdown_sender, shutdown_receiver) = tokio::sync::oneshot::channel();
     let serve_api = serve_api(
         database.clone(),
@@ -569,6 +606,7 @@ async fn main() {
         database.clone(),
         args.shared.solver_competition_auth,
         solvable_orders_cache.clone(),
+        presignatures,
     );
     let maintenance_task =
         task::spawn(service_maintainer.run_maintenance_on_new_block(current_block_stream));
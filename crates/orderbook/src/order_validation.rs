@@ -6,6 +6,7 @@ use anyhow::anyhow;
 use contracts::WETH9;
 use ethcontract::{H160, U256};
 use model::{
+    app_data,
     order::{
         BuyTokenDestination, Order, OrderCreation, OrderData, OrderKind, SellTokenSource,
         BUY_ETH_ADDRESS,
@@ -47,7 +48,9 @@ pub trait OrderValidating: Send + Sync {
     ///     - buy & sell amounts are non-zero,
     ///     - order's signature recovers correctly
     ///     - fee is sufficient,
-    ///     - user has sufficient (transferable) funds to execute the order.
+    ///     - user has sufficient (transferable) funds to execute the order,
+    ///     - the full app data, if provided, matches the signed app data hash and specifies a
+    ///       valid partner fee.
     ///
     /// Furthermore, full order validation also calls partial_validate to ensure that
     /// other aspects of the order are not malformed.
@@ -97,6 +100,13 @@ pub enum ValidationError {
     MissingFrom,
     WrongOwner(H160),
     ZeroAmount,
+    /// The full app data does not hash to the app data signed by the order.
+    AppDataHashMismatch,
+    /// The full app data is not a valid app data document.
+    InvalidAppData(String),
+    /// The partner fee specified in the app data exceeds the maximum, has no recipient or is for
+    /// an order buying native ETH.
+    InvalidPartnerFee,
     Other(anyhow::Error),
 }
 
@@ -170,6 +180,7 @@ pub struct OrderValidator {
     quoter: Arc<dyn OrderQuoting>,
     balance_fetcher: Arc<dyn BalanceFetching>,
     signature_validator: Arc<dyn SignatureValidating>,
+    max_partner_fee_bps: u64,
 }
 
 #[derive(Debug, PartialEq, Default)]
@@ -231,6 +242,7 @@ impl OrderValidator {
         quoter: Arc<dyn OrderQuoting>,
         balance_fetcher: Arc<dyn BalanceFetching>,
         signature_validator: Arc<dyn SignatureValidating>,
+        max_partner_fee_bps: u64,
     ) -> Self {
         Self {
             code_fetcher,
@@ -244,6 +256,7 @@ impl OrderValidator {
             quoter,
             balance_fetcher,
             signature_validator,
+            max_partner_fee_bps,
         }
     }
 }
@@ -347,6 +360,8 @@ impl OrderValidating for OrderValidator {
             return Err(ValidationError::ZeroAmount);
         }
 
+        validate_full_app_data(&order, self.max_partner_fee_bps)?;
+
         let liquidity_owner = self.liquidity_order_owners.contains(&owner);
         self.partial_validate(PreOrderData::from_order_creation(
             owner,
@@ -502,6 +517,33 @@ fn minimum_balance(order: &OrderData) -> Option<U256> {
     order.sell_amount.checked_add(order.fee_amount)
 }
 
+/// Verifies that the full app data of an order matches its signed app data hash and that the
+/// partner fee it specifies is acceptable.
+fn validate_full_app_data(
+    order: &OrderCreation,
+    max_partner_fee_bps: u64,
+) -> Result<(), ValidationError> {
+    let full_app_data = match &order.full_app_data {
+        Some(full_app_data) => full_app_data,
+        None => return Ok(()),
+    };
+    if app_data::hash_full_app_data(full_app_data) != order.data.app_data {
+        return Err(ValidationError::AppDataHashMismatch);
+    }
+    let partner_fee = app_data::parse_partner_fee(full_app_data)
+        .map_err(|err| ValidationError::InvalidAppData(err.to_string()))?;
+    if let Some(partner_fee) = partner_fee {
+        // Partner fees are paid with an ERC20 transfer, which isn't possible for native ETH.
+        if partner_fee.bps > max_partner_fee_bps
+            || partner_fee.recipient.is_zero()
+            || order.data.buy_token == BUY_ETH_ADDRESS
+        {
+            return Err(ValidationError::InvalidPartnerFee);
+        }
+    }
+    Ok(())
+}
+
 /// Retrieves the quote for an order that is being created and verify that its
 /// fee is sufficient.
 ///
@@ -592,6 +634,8 @@ mod tests {
         web3_traits::MockCodeFetching,
     };
 
+    const MAX_PARTNER_FEE_BPS: u64 = 100;
+
     #[test]
     fn minimum_balance_() {
         let order = OrderData {
@@ -673,6 +717,7 @@ mod tests {
             Arc::new(MockOrderQuoting::new()),
             Arc::new(MockBalanceFetching::new()),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         assert!(matches!(
             validator
@@ -792,6 +837,7 @@ mod tests {
             Arc::new(MockOrderQuoting::new()),
             Arc::new(MockBalanceFetching::new()),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
 
         assert!(matches!(
@@ -834,6 +880,7 @@ mod tests {
             Arc::new(MockOrderQuoting::new()),
             Arc::new(MockBalanceFetching::new()),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = || PreOrderData {
             valid_to: model::time::now_in_epoch_seconds()
@@ -892,6 +939,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
 
         let creation = OrderCreation {
@@ -966,6 +1014,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = OrderCreation {
             data: OrderData {
@@ -1011,6 +1060,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = OrderCreation {
             data: OrderData {
@@ -1056,6 +1106,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = OrderCreation {
             data: OrderData {
@@ -1103,6 +1154,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = OrderCreation {
             data: OrderData {
@@ -1153,6 +1205,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = OrderCreation {
             data: OrderData {
@@ -1199,6 +1252,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(MockSignatureValidating::new()),
+            MAX_PARTNER_FEE_BPS,
         );
         let order = OrderCreation {
             data: OrderData {
@@ -1249,6 +1303,7 @@ mod tests {
             Arc::new(order_quoter),
             Arc::new(balance_fetcher),
             Arc::new(signature_validator),
+            MAX_PARTNER_FEE_BPS,
         );
 
         let creation = OrderCreation {
@@ -1302,6 +1357,7 @@ mod tests {
                     Arc::new(order_quoter),
                     Arc::new(balance_fetcher),
                     Arc::new(MockSignatureValidating::new()),
+                    MAX_PARTNER_FEE_BPS,
                 );
 
                 let order = OrderBuilder::default()
@@ -1649,4 +1705,60 @@ mod tests {
             &quote
         ));
     }
+
+    #[test]
+    fn validates_full_app_data() {
+        let order = |full_app_data: &str| OrderCreation {
+            data: OrderData {
+                app_data: app_data::hash_full_app_data(full_app_data),
+                ..Default::default()
+            },
+            full_app_data: Some(full_app_data.to_string()),
+            ..Default::default()
+        };
+        let partner_fee = |recipient: &str, bps: u64| {
+            format!(r#"{{"metadata":{{"partnerFee":{{"recipient":"{recipient}","bps":{bps}}}}}}}"#)
+        };
+        let recipient = "0x0101010101010101010101010101010101010101";
+
+        assert!(validate_full_app_data(&OrderCreation::default(), MAX_PARTNER_FEE_BPS).is_ok());
+        assert!(validate_full_app_data(&order("{}"), MAX_PARTNER_FEE_BPS).is_ok());
+        assert!(validate_full_app_data(
+            &order(&partner_fee(recipient, MAX_PARTNER_FEE_BPS)),
+            MAX_PARTNER_FEE_BPS
+        )
+        .is_ok());
+
+        assert!(matches!(
+            validate_full_app_data(
+                &OrderCreation {
+                    full_app_data: Some("{}".to_string()),
+                    ..Default::default()
+                },
+                MAX_PARTNER_FEE_BPS
+            ),
+            Err(ValidationError::AppDataHashMismatch)
+        ));
+        assert!(matches!(
+            validate_full_app_data(&order("not json"), MAX_PARTNER_FEE_BPS),
+            Err(ValidationError::InvalidAppData(_))
+        ));
+        assert!(matches!(
+            validate_full_app_data(
+                &order(&partner_fee(recipient, MAX_PARTNER_FEE_BPS + 1)),
+                MAX_PARTNER_FEE_BPS
+            ),
+            Err(ValidationError::InvalidPartnerFee)
+        ));
+        assert!(matches!(
+            validate_full_app_data(
+                &order(&partner_fee(
+                    "0x0000000000000000000000000000000000000000",
+                    1
+                )),
+                MAX_PARTNER_FEE_BPS
+            ),
+            Err(ValidationError::InvalidPartnerFee)
+        ));
+    }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    database::orders::{InsertionError, OrderStoring},
+    database::orders::{InsertionError, OrderStoring, ReplacementError},
     order_validation::{OrderValidating, ValidationError},
     solvable_orders::{SolvableOrders, SolvableOrdersCache},
 };
@@ -8,7 +8,7 @@ use chrono::Utc;
 use ethcontract::H256;
 use model::{
     auction::Auction,
-    order::{Order, OrderCancellation, OrderCreation, OrderStatus, OrderUid},
+    order::{Order, OrderCancellation, OrderCreation, OrderReplacement, OrderStatus, OrderUid},
     DomainSeparator,
 };
 use primitive_types::H160;
@@ -110,9 +110,27 @@ impl From<ValidationError> for ReplaceOrderError {
     }
 }
 
-impl From<InsertionError> for ReplaceOrderError {
-    fn from(err: InsertionError) -> Self {
-        Self::Add(err.into())
+impl From<ReplacementError> for ReplaceOrderError {
+    fn from(err: ReplacementError) -> Self {
+        match err {
+            ReplacementError::OldOrderNotFound => OrderCancellationError::OrderNotFound.into(),
+            ReplacementError::OldOrderNotOpen(status) => {
+                cancellation_error_for_status(status).into()
+            }
+            ReplacementError::Insertion(err) => Self::Add(err.into()),
+        }
+    }
+}
+
+/// The error for cancelling an order that is no longer open.
+fn cancellation_error_for_status(status: OrderStatus) -> OrderCancellationError {
+    match status {
+        OrderStatus::Fulfilled => OrderCancellationError::OrderFullyExecuted,
+        OrderStatus::Cancelled => OrderCancellationError::AlreadyCancelled,
+        OrderStatus::Expired => OrderCancellationError::OrderExpired,
+        OrderStatus::PresignaturePending | OrderStatus::Open => {
+            OrderCancellationError::OnChainOrder
+        }
     }
 }
 
@@ -173,17 +191,9 @@ impl Orderbook {
             .ok_or(OrderCancellationError::OrderNotFound)?;
 
         match order.metadata.status {
-            OrderStatus::PresignaturePending => return Err(OrderCancellationError::OnChainOrder),
-            OrderStatus::Open if !order.signature.scheme().is_ecdsa_scheme() => {
-                return Err(OrderCancellationError::OnChainOrder);
-            }
-            OrderStatus::Fulfilled => return Err(OrderCancellationError::OrderFullyExecuted),
-            OrderStatus::Cancelled => return Err(OrderCancellationError::AlreadyCancelled),
-            OrderStatus::Expired => return Err(OrderCancellationError::OrderExpired),
-            _ => {}
+            OrderStatus::Open if order.signature.scheme().is_ecdsa_scheme() => Ok(order),
+            status => Err(cancellation_error_for_status(status)),
         }
-
-        Ok(order)
     }
 
     pub async fn cancel_order(
@@ -216,41 +226,44 @@ impl Orderbook {
 
     pub async fn replace_order(
         &self,
-        old_order: OrderUid,
-        new_order: OrderCreation,
+        replacement: OrderReplacement,
     ) -> Result<OrderUid, ReplaceOrderError> {
         // Replacement order signatures need to be validated meaning we cannot
         // accept `PreSign` orders, otherwise anyone can cancel a user order by
         // submitting a `PreSign` order on someone's behalf.
-        new_order
+        replacement
+            .new_order
             .signature
             .scheme()
             .try_to_ecdsa_scheme()
             .ok_or(ReplaceOrderError::InvalidReplacement)?;
 
-        let old_order = self.find_order_for_cancellation(&old_order).await?;
+        let old_order = self
+            .find_order_for_cancellation(&replacement.old_order_uid)
+            .await?;
+
+        // Verify that the old order owner signed the replacement.
+        let signer = replacement
+            .validate(&self.domain_separator)
+            .map_err(|_| OrderCancellationError::InvalidSignature)?;
+        if signer != old_order.metadata.owner {
+            return Err(OrderCancellationError::WrongOwner.into());
+        }
+
         let (new_order, new_quote) = self
             .order_validator
             .validate_and_construct_order(
-                new_order,
+                replacement.new_order,
                 &self.domain_separator,
                 self.settlement_contract,
             )
             .await?;
-
-        // Verify that the new order is a valid replacement order by checking
-        // that the `app_data` encodes an order cancellation and that both the
-        // old and new orders have the same signer.
-        let cancellation = OrderCancellation {
-            order_uid: old_order.metadata.uid,
-            ..Default::default()
-        };
-        if new_order.data.app_data != cancellation.hash_struct()
-            || new_order.metadata.owner != old_order.metadata.owner
-        {
+        if new_order.metadata.owner != old_order.metadata.owner {
             return Err(ReplaceOrderError::InvalidReplacement);
         }
 
+        // The database checks again that the old order is still open in the
+        // same transaction that swaps the orders.
         self.database
             .replace_order(&old_order.metadata.uid, &new_order, new_quote)
             .await?;
@@ -322,6 +335,7 @@ fn set_available_balances(orders: &mut [Order], cache: &SolvableOrdersCache) {
     for order in orders.iter_mut() {
         order.metadata.available_balance =
             cache.cached_balance(&shared::account_balances::Query::from_order(order));
+        order.metadata.unfillable_reason = cache.unfillable_reason(&order.metadata.uid);
     }
 }
 
@@ -329,18 +343,27 @@ fn set_available_balances(orders: &mut [Order], cache: &SolvableOrdersCache) {
 mod tests {
     use super::*;
     use crate::{
-        database::orders::MockOrderStoring, order_validation::MockOrderValidating,
+        database::orders::MockOrderStoring,
+        order_validation::MockOrderValidating,
+        signature_validation_cache::{
+            MockOwnerStateWatching, SignatureValidationCache, ValidationCacheConfig,
+        },
         solver_competition::MockSolverCompetitionStoring,
     };
-    use ethcontract::H160;
+    use ethcontract::{
+        web3::signing::{Key, SecretKeyRef},
+        H160,
+    };
     use mockall::predicate::eq;
     use model::{
         app_id::AppId,
         order::{OrderData, OrderMetadata},
-        signature::Signature,
+        signature::{EcdsaSignature, EcdsaSigningScheme, Signature},
     };
+    use secp256k1::{SecretKey, ONE_KEY};
     use shared::{
         account_balances::MockBalanceFetching, bad_token::MockBadTokenDetecting, current_block,
+        gas_price_estimation::FakeGasPriceEstimator,
         price_estimation::native::MockNativePriceEstimating,
         signature_validator::MockSignatureValidating,
     };
@@ -358,29 +381,51 @@ mod tests {
                 Arc::new(MockBadTokenDetecting::new()),
                 current_block::mock_single_block(Default::default()),
                 Arc::new(MockNativePriceEstimating::new()),
-                Arc::new(MockSignatureValidating::new()),
+                Arc::new(FakeGasPriceEstimator::default()),
+                SignatureValidationCache::new(
+                    Arc::new(MockSignatureValidating::new()),
+                    Arc::new(MockOwnerStateWatching::new()),
+                    ValidationCacheConfig::default(),
+                ),
                 Arc::new(MockSolverCompetitionStoring::new()),
+                Default::default(),
             ),
             solvable_orders_max_update_age: Default::default(),
             order_validator: Arc::new(MockOrderValidating::new()),
         }
     }
 
+    fn signed_replacement(
+        old_order_uid: OrderUid,
+        new_order: OrderCreation,
+        key: &SecretKey,
+    ) -> OrderReplacement {
+        let mut replacement = OrderReplacement {
+            old_order_uid,
+            new_order,
+            ..Default::default()
+        };
+        replacement.signature = EcdsaSignature::sign(
+            EcdsaSigningScheme::Eip712,
+            &Default::default(),
+            &replacement.hash_struct(),
+            SecretKeyRef::new(key),
+        );
+        replacement
+    }
+
     #[tokio::test]
-    async fn replace_order_verifies_signer_and_app_data() {
+    async fn replace_order_verifies_signers() {
+        let owner = SecretKeyRef::new(&ONE_KEY).address();
         let old_order = Order {
             metadata: OrderMetadata {
                 uid: OrderUid([1; 56]),
-                owner: H160([1; 20]),
+                owner,
                 ..Default::default()
             },
             ..Default::default()
         };
         let new_order_uid = OrderUid([2; 56]);
-        let cancellation = OrderCancellation {
-            order_uid: old_order.metadata.uid,
-            ..Default::default()
-        };
 
         let mut database = MockOrderStoring::new();
         database
@@ -415,37 +460,40 @@ mod tests {
             order_validator: Arc::new(order_validator),
             ..mock_orderbook()
         };
+        let new_order = |from: H160, signature: Signature| OrderCreation {
+            from: Some(from),
+            signature,
+            data: OrderData {
+                // Replacement orders can use arbitrary app data.
+                app_data: AppId([0x42; 32]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
 
-        // App data does not encode cancellation.
+        // Replacement not signed by the old order owner.
+        let other_key = SecretKey::from_slice(&[2; 32]).unwrap();
         assert!(matches!(
             orderbook
-                .replace_order(
+                .replace_order(signed_replacement(
                     old_order.metadata.uid,
-                    OrderCreation {
-                        from: Some(old_order.metadata.owner),
-                        signature: Signature::Eip712(Default::default()),
-                        ..Default::default()
-                    },
-                )
+                    new_order(owner, Signature::Eip712(Default::default())),
+                    &other_key,
+                ))
                 .await,
-            Err(ReplaceOrderError::InvalidReplacement)
+            Err(ReplaceOrderError::Cancellation(
+                OrderCancellationError::WrongOwner
+            ))
         ));
 
-        // Different owner
+        // Different owner of the new order.
         assert!(matches!(
             orderbook
-                .replace_order(
+                .replace_order(signed_replacement(
                     old_order.metadata.uid,
-                    OrderCreation {
-                        from: Some(H160([2; 20])),
-                        signature: Signature::Eip712(Default::default()),
-                        data: OrderData {
-                            app_data: AppId(cancellation.hash_struct()),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    },
-                )
+                    new_order(H160([2; 20]), Signature::Eip712(Default::default())),
+                    &ONE_KEY,
+                ))
                 .await,
             Err(ReplaceOrderError::InvalidReplacement)
         ));
@@ -453,40 +501,100 @@ mod tests {
         // Non-signed order.
         assert!(matches!(
             orderbook
-                .replace_order(
+                .replace_order(signed_replacement(
                     old_order.metadata.uid,
-                    OrderCreation {
-                        from: Some(old_order.metadata.owner),
-                        signature: Signature::PreSign,
-                        data: OrderData {
-                            app_data: AppId(cancellation.hash_struct()),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    },
-                )
+                    new_order(owner, Signature::PreSign),
+                    &ONE_KEY,
+                ))
                 .await,
             Err(ReplaceOrderError::InvalidReplacement)
         ));
 
+        // Signature for a different new order.
+        let mut replacement = signed_replacement(
+            old_order.metadata.uid,
+            new_order(owner, Signature::Eip712(Default::default())),
+            &ONE_KEY,
+        );
+        replacement.new_order.data.sell_amount = 1.into();
+        assert!(matches!(
+            orderbook.replace_order(replacement).await,
+            Err(ReplaceOrderError::Cancellation(
+                OrderCancellationError::WrongOwner
+            ))
+        ));
+
         // Stars align...
         assert_eq!(
             orderbook
-                .replace_order(
+                .replace_order(signed_replacement(
                     old_order.metadata.uid,
-                    OrderCreation {
-                        from: Some(old_order.metadata.owner),
-                        signature: Signature::Eip712(Default::default()),
-                        data: OrderData {
-                            app_data: AppId(cancellation.hash_struct()),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    },
-                )
+                    new_order(owner, Signature::Eip712(Default::default())),
+                    &ONE_KEY,
+                ))
                 .await
                 .unwrap(),
             new_order_uid,
         );
     }
+
+    #[tokio::test]
+    async fn replace_order_fails_if_old_order_closes_concurrently() {
+        let owner = SecretKeyRef::new(&ONE_KEY).address();
+        let old_order = Order {
+            metadata: OrderMetadata {
+                uid: OrderUid([1; 56]),
+                owner,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut database = MockOrderStoring::new();
+        database.expect_single_order().returning({
+            let old_order = old_order.clone();
+            move |_| Ok(Some(old_order.clone()))
+        });
+        database
+            .expect_replace_order()
+            .returning(|_, _, _| Err(ReplacementError::OldOrderNotOpen(OrderStatus::Cancelled)));
+
+        let mut order_validator = MockOrderValidating::new();
+        order_validator
+            .expect_validate_and_construct_order()
+            .returning(move |creation, _, _| {
+                Ok((
+                    Order {
+                        metadata: OrderMetadata {
+                            owner: creation.from.unwrap(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    Default::default(),
+                ))
+            });
+
+        let orderbook = Orderbook {
+            database: Arc::new(database),
+            order_validator: Arc::new(order_validator),
+            ..mock_orderbook()
+        };
+        assert!(matches!(
+            orderbook
+                .replace_order(signed_replacement(
+                    old_order.metadata.uid,
+                    OrderCreation {
+                        from: Some(owner),
+                        signature: Signature::Eip712(Default::default()),
+                        ..Default::default()
+                    },
+                    &ONE_KEY,
+                ))
+                .await,
+            Err(ReplaceOrderError::Cancellation(
+                OrderCancellationError::AlreadyCancelled
+            ))
+        ));
+    }
 }
@@ -0,0 +1,365 @@
+//! Inspection of pre-sign orders and preparation of the Safe transactions that make them solvable.
+//!
+//! Pre-sign orders are mostly placed by smart contract wallets like Safes, which often need to
+//! execute a token approval along with the `setPreSignature` call. Since multisig transactions can
+//! take days to collect signatures, we expose why an order is still pending and provide a ready to
+//! sign Safe transaction bundling both calls with `MultiSendCallOnly`.
+
+use crate::{database::orders::OrderStoring, solvable_orders::max_transfer_out_amount};
+use anyhow::{anyhow, Context as _};
+use contracts::{GPv2Settlement, GnosisSafe, ERC20};
+use ethcontract::{
+    common::abi::{self, Token},
+    errors::MethodError,
+    Bytes,
+};
+use hex_literal::hex;
+use model::{
+    order::{Order, OrderStatus, OrderUid, SellTokenSource},
+    presignature::{PendingReason, PresignatureStatus, SafeOperation, SafeTransaction},
+    signature::Signature,
+};
+use primitive_types::{H160, U256};
+use shared::{
+    account_balances::{BalanceFetching, TransferSimulationError},
+    ethcontract_error::EthcontractErrorType,
+};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The canonical `MultiSendCallOnly` v1.3.0 deployment. It has the same address on all networks.
+const MULTI_SEND_CALL_ONLY: H160 = H160(hex!("40A2aCCbd92BCA938b02010E17A5b8929b49130D"));
+
+/// The `multiSend(bytes)` function selector.
+const MULTI_SEND_SELECTOR: [u8; 4] = hex!("8d80ff0a");
+
+#[derive(Debug, Error)]
+pub enum PresignatureError {
+    #[error("order not found")]
+    OrderNotFound,
+    #[error("order is not a pre-sign order")]
+    NotPresignOrder,
+    #[error("order can no longer be settled")]
+    OrderClosed,
+    #[error("order owner is not a Safe")]
+    NotASafe,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+pub struct PresignatureHandler {
+    database: Arc<dyn OrderStoring>,
+    balance_fetcher: Arc<dyn BalanceFetching>,
+    settlement_contract: GPv2Settlement,
+    vault_relayer: H160,
+    vault: Option<H160>,
+}
+
+impl PresignatureHandler {
+    pub fn new(
+        database: Arc<dyn OrderStoring>,
+        balance_fetcher: Arc<dyn BalanceFetching>,
+        settlement_contract: GPv2Settlement,
+        vault_relayer: H160,
+        vault: Option<H160>,
+    ) -> Self {
+        Self {
+            database,
+            balance_fetcher,
+            settlement_contract,
+            vault_relayer,
+            vault,
+        }
+    }
+
+    /// Returns the status of a pre-sign order along with all reasons keeping it from being
+    /// solvable.
+    pub async fn status(&self, uid: &OrderUid) -> Result<PresignatureStatus, PresignatureError> {
+        let order = self.presign_order(uid).await?;
+        let status = order.metadata.status;
+
+        let mut pending_reasons = Vec::new();
+        if status == OrderStatus::PresignaturePending {
+            pending_reasons.push(PendingReason::MissingPresignature);
+        }
+        if matches!(status, OrderStatus::PresignaturePending | OrderStatus::Open) {
+            let amount = max_transfer_out_amount(&order)?;
+            let transfer = self
+                .balance_fetcher
+                .can_transfer(
+                    order.data.sell_token,
+                    order.metadata.owner,
+                    amount,
+                    order.data.sell_token_balance,
+                )
+                .await;
+            match transfer {
+                Ok(()) => (),
+                Err(TransferSimulationError::InsufficientBalance) => {
+                    pending_reasons.push(PendingReason::InsufficientBalance)
+                }
+                Err(TransferSimulationError::InsufficientAllowance) => {
+                    pending_reasons.push(PendingReason::InsufficientAllowance)
+                }
+                Err(TransferSimulationError::TransferFailed) => {
+                    pending_reasons.push(PendingReason::TransferFailed)
+                }
+                Err(TransferSimulationError::Other(err)) => return Err(err.into()),
+            }
+        }
+
+        Ok(PresignatureStatus {
+            status,
+            pending_reasons,
+        })
+    }
+
+    /// Returns a Safe transaction that approves the order's sell token and sets the
+    /// pre-signature in a single multisig execution.
+    pub async fn safe_transaction(
+        &self,
+        uid: &OrderUid,
+    ) -> Result<SafeTransaction, PresignatureError> {
+        let order = self.presign_order(uid).await?;
+        if !matches!(
+            order.metadata.status,
+            OrderStatus::PresignaturePending | OrderStatus::Open
+        ) {
+            return Err(PresignatureError::OrderClosed);
+        }
+
+        let web3 = self.settlement_contract.raw_instance().web3();
+        let mut calls = Vec::new();
+        if let Some(spender) = self.approval_spender(order.data.sell_token_balance)? {
+            let amount = max_transfer_out_amount(&order)?;
+            let approve = ERC20::at(&web3, order.data.sell_token).approve(spender, amount);
+            let data = approve.tx.data.context("missing approve calldata")?;
+            calls.push((order.data.sell_token, data.0));
+        }
+        let set_pre_signature = self
+            .settlement_contract
+            .set_pre_signature(Bytes(uid.0.to_vec()), true);
+        let data = set_pre_signature
+            .tx
+            .data
+            .context("missing setPreSignature calldata")?;
+        calls.push((self.settlement_contract.address(), data.0));
+
+        let safe = order.metadata.owner;
+        let nonce = GnosisSafe::at(&web3, safe)
+            .nonce()
+            .call()
+            .await
+            .map_err(nonce_error)?;
+
+        Ok(SafeTransaction {
+            safe,
+            to: MULTI_SEND_CALL_ONLY,
+            value: U256::zero(),
+            data: encode_multi_send(&calls),
+            operation: SafeOperation::DelegateCall,
+            safe_tx_gas: U256::zero(),
+            base_gas: U256::zero(),
+            gas_price: U256::zero(),
+            gas_token: H160::zero(),
+            refund_receiver: H160::zero(),
+            nonce,
+        })
+    }
+
+    async fn presign_order(&self, uid: &OrderUid) -> Result<Order, PresignatureError> {
+        let order = self
+            .database
+            .single_order(uid)
+            .await
+            .context("single_order")?
+            .ok_or(PresignatureError::OrderNotFound)?;
+        if !matches!(order.signature, Signature::PreSign) {
+            return Err(PresignatureError::NotPresignOrder);
+        }
+        Ok(order)
+    }
+
+    /// Returns the account that needs an ERC20 approval for the order's sell token balance.
+    fn approval_spender(&self, source: SellTokenSource) -> anyhow::Result<Option<H160>> {
+        match source {
+            SellTokenSource::Erc20 => Ok(Some(self.vault_relayer)),
+            SellTokenSource::External => self
+                .vault
+                .map(Some)
+                .ok_or_else(|| anyhow!("external balances require a deployed vault")),
+            // Internal balances are already held by the Vault and don't need approvals.
+            SellTokenSource::Internal => Ok(None),
+        }
+    }
+}
+
+/// Accounts that aren't Safes either revert or return no data when queried for their nonce. Any
+/// other error is a problem with the node.
+fn nonce_error(err: MethodError) -> PresignatureError {
+    match EthcontractErrorType::classify(&err) {
+        EthcontractErrorType::Contract => PresignatureError::NotASafe,
+        EthcontractErrorType::Node => PresignatureError::Other(anyhow!(err).context("Safe nonce")),
+    }
+}
+
+/// Encodes `MultiSendCallOnly.multiSend` calldata executing the specified `(to, data)` calls.
+fn encode_multi_send(calls: &[(H160, Vec<u8>)]) -> Vec<u8> {
+    let mut transactions = Vec::new();
+    for (to, data) in calls {
+        transactions.push(u8::from(SafeOperation::Call));
+        transactions.extend_from_slice(to.as_bytes());
+        transactions.extend_from_slice(&abi::encode(&[
+            Token::Uint(U256::zero()),
+            Token::Uint(data.len().into()),
+        ]));
+        transactions.extend_from_slice(data);
+    }
+
+    let mut calldata = MULTI_SEND_SELECTOR.to_vec();
+    calldata.extend(abi::encode(&[Token::Bytes(transactions)]));
+    calldata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::orders::MockOrderStoring;
+    use futures::FutureExt;
+    use model::order::{OrderData, OrderMetadata};
+    use shared::{account_balances::MockBalanceFetching, dummy_contract};
+
+    fn handler(
+        database: MockOrderStoring,
+        balance_fetcher: MockBalanceFetching,
+    ) -> PresignatureHandler {
+        PresignatureHandler::new(
+            Arc::new(database),
+            Arc::new(balance_fetcher),
+            dummy_contract!(GPv2Settlement, H160([0x90; 20])),
+            H160([0xa0; 20]),
+            None,
+        )
+    }
+
+    fn presign_order(status: OrderStatus) -> Order {
+        Order {
+            metadata: OrderMetadata {
+                status,
+                ..Default::default()
+            },
+            data: OrderData {
+                sell_amount: 100.into(),
+                fee_amount: 1.into(),
+                ..Default::default()
+            },
+            signature: Signature::PreSign,
+        }
+    }
+
+    #[test]
+    fn pending_reasons() {
+        let mut database = MockOrderStoring::new();
+        database
+            .expect_single_order()
+            .returning(|_| Ok(Some(presign_order(OrderStatus::PresignaturePending))));
+        let mut balance_fetcher = MockBalanceFetching::new();
+        balance_fetcher
+            .expect_can_transfer()
+            .withf(|_, _, amount, _| *amount == U256::from(101))
+            .returning(|_, _, _, _| Err(TransferSimulationError::InsufficientAllowance));
+
+        let status = handler(database, balance_fetcher)
+            .status(&Default::default())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            status,
+            PresignatureStatus {
+                status: OrderStatus::PresignaturePending,
+                pending_reasons: vec![
+                    PendingReason::MissingPresignature,
+                    PendingReason::InsufficientAllowance,
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn no_pending_reasons_for_solvable_order() {
+        let mut database = MockOrderStoring::new();
+        database
+            .expect_single_order()
+            .returning(|_| Ok(Some(presign_order(OrderStatus::Open))));
+        let mut balance_fetcher = MockBalanceFetching::new();
+        balance_fetcher
+            .expect_can_transfer()
+            .returning(|_, _, _, _| Ok(()));
+
+        let status = handler(database, balance_fetcher)
+            .status(&Default::default())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert!(status.pending_reasons.is_empty());
+    }
+
+    #[test]
+    fn rejects_non_presign_orders() {
+        let mut database = MockOrderStoring::new();
+        database
+            .expect_single_order()
+            .returning(|_| Ok(Some(Order::default())));
+
+        let result = handler(database, MockBalanceFetching::new())
+            .status(&Default::default())
+            .now_or_never()
+            .unwrap();
+        assert!(matches!(result, Err(PresignatureError::NotPresignOrder)));
+    }
+
+    #[test]
+    fn only_contract_errors_mean_not_a_safe() {
+        assert!(matches!(
+            nonce_error(shared::ethcontract_error::testing_contract_error()),
+            PresignatureError::NotASafe
+        ));
+        assert!(matches!(
+            nonce_error(shared::ethcontract_error::testing_node_error()),
+            PresignatureError::Other(_)
+        ));
+    }
+
+    fn uint(value: u64) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        U256::from(value).to_big_endian(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn encodes_multi_send() {
+        let calldata = encode_multi_send(&[
+            (H160([0x11; 20]), vec![0xaa, 0xbb]),
+            (H160([0x22; 20]), vec![0xcc]),
+        ]);
+
+        let mut transactions = Vec::new();
+        transactions.push(0);
+        transactions.extend_from_slice(&[0x11; 20]);
+        transactions.extend_from_slice(&[0; 32]);
+        transactions.extend_from_slice(&uint(2));
+        transactions.extend_from_slice(&[0xaa, 0xbb]);
+        transactions.push(0);
+        transactions.extend_from_slice(&[0x22; 20]);
+        transactions.extend_from_slice(&[0; 32]);
+        transactions.extend_from_slice(&uint(1));
+        transactions.push(0xcc);
+
+        assert_eq!(calldata[..4], MULTI_SEND_SELECTOR);
+        assert_eq!(
+            abi::decode(&[abi::ParamType::Bytes], &calldata[4..]).unwrap(),
+            vec![Token::Bytes(transactions)]
+        );
+    }
+}
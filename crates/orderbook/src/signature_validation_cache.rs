@@ -0,0 +1,619 @@
+//! Caching of EIP-1271 signature validation results for the solvable orders cache.
+//!
+//! Re-validating every EIP-1271 order on every auction update is expensive, as it requires an
+//! `isValidSignature` call per order. Signature validity can only change when the state of the
+//! owner contract changes, so we remember the validation result together with the last block at
+//! which we observed the owner contract emitting an event. A cached result is reused as long as
+//! the owner contract has not changed and the configured re-check period has not passed.
+
+use anyhow::{ensure, Context as _, Result};
+use ethcontract::H256;
+use model::{order::Order, signature::Signature};
+use primitive_types::H160;
+use prometheus::IntCounterVec;
+use shared::{
+    signature_validator::{SignatureCheck, SignatureValidating, SignatureValidationError},
+    Web3,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+use web3::types::{BlockNumber, FilterBuilder};
+
+/// The maximum number of blocks we are willing to scan for owner events in one request. If more
+/// blocks than this have passed since the last update, all owners are considered changed.
+const MAX_BLOCK_RANGE: u64 = 100;
+
+#[derive(prometheus_metric_storage::MetricStorage)]
+struct Metrics {
+    /// EIP-1271 signature validation cache hits and misses
+    #[metric(labels("result"))]
+    eip1271_validation_cache: IntCounterVec,
+
+    /// EIP-1271 orders filtered from the solvable orders by reason
+    #[metric(labels("reason"))]
+    eip1271_filtered_orders: IntCounterVec,
+}
+
+/// Detects state changes of EIP-1271 order owner contracts.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait OwnerStateWatching: Send + Sync {
+    /// Returns the subset of `owners` whose state may have changed in the inclusive block range
+    /// `from_block..=to_block`.
+    async fn changed_owners(
+        &self,
+        owners: &[H160],
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<HashSet<H160>>;
+}
+
+/// Watches owner contracts by looking at the events that they emit. Smart contract wallets such as
+/// Safes emit events on every state change that could affect signature validation (owner changes,
+/// threshold changes, signed message updates, module executions).
+pub struct Web3OwnerStateWatcher {
+    web3: Web3,
+}
+
+impl Web3OwnerStateWatcher {
+    pub fn new(web3: Web3) -> Self {
+        Self { web3 }
+    }
+}
+
+#[async_trait::async_trait]
+impl OwnerStateWatching for Web3OwnerStateWatcher {
+    async fn changed_owners(
+        &self,
+        owners: &[H160],
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<HashSet<H160>> {
+        ensure!(from_block <= to_block, "invalid block range");
+        if owners.is_empty() {
+            return Ok(Default::default());
+        }
+
+        let filter = FilterBuilder::default()
+            .address(owners.to_vec())
+            .from_block(BlockNumber::Number(from_block.into()))
+            .to_block(BlockNumber::Number(to_block.into()))
+            .build();
+        let logs = self
+            .web3
+            .eth()
+            .logs(filter)
+            .await
+            .context("failed to fetch owner contract logs")?;
+
+        Ok(logs.into_iter().map(|log| log.address).collect())
+    }
+}
+
+/// Configuration for how long cached signature validation results are trusted.
+#[derive(Clone, Copy, Debug)]
+pub struct ValidationCacheConfig {
+    /// The number of blocks after which a valid signature is re-checked even if no owner state
+    /// change was observed.
+    pub valid_recheck_blocks: u64,
+    /// The number of blocks after which an invalid signature is re-checked even if no owner state
+    /// change was observed.
+    pub invalid_recheck_blocks: u64,
+}
+
+impl Default for ValidationCacheConfig {
+    fn default() -> Self {
+        Self {
+            valid_recheck_blocks: 50,
+            invalid_recheck_blocks: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct CacheKey {
+    owner: H160,
+    hash: [u8; 32],
+    /// The last block at which we observed a state change for the owner.
+    owner_state_block: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CacheEntry {
+    valid: bool,
+    checked_at_block: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<CacheKey, CacheEntry>,
+    owner_state_blocks: HashMap<H160, u64>,
+    last_scanned_block: Option<u64>,
+}
+
+/// Validates EIP-1271 order signatures reusing previous results where possible.
+pub struct SignatureValidationCache {
+    validator: Arc<dyn SignatureValidating>,
+    owner_watcher: Arc<dyn OwnerStateWatching>,
+    config: ValidationCacheConfig,
+    // std mutex is fine because we don't hold lock across await.
+    state: Mutex<State>,
+    metrics: &'static Metrics,
+}
+
+impl SignatureValidationCache {
+    pub fn new(
+        validator: Arc<dyn SignatureValidating>,
+        owner_watcher: Arc<dyn OwnerStateWatching>,
+        config: ValidationCacheConfig,
+    ) -> Self {
+        Self {
+            validator,
+            owner_watcher,
+            config,
+            state: Default::default(),
+            metrics: Metrics::instance(global_metrics::get_metric_storage_registry()).unwrap(),
+        }
+    }
+
+    /// Filters EIP-1271 orders whose signatures are no longer validating at `block`.
+    pub async fn filter_invalid_signature_orders(
+        &self,
+        orders: Vec<Order>,
+        block: u64,
+    ) -> Vec<Order> {
+        let checks = orders
+            .iter()
+            .filter_map(|order| match &order.signature {
+                Signature::Eip1271(signature) => {
+                    let (H256(hash), signer, _) = order.metadata.uid.parts();
+                    Some(SignatureCheck {
+                        signer,
+                        hash,
+                        signature: signature.clone(),
+                    })
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if checks.is_empty() {
+            return orders;
+        }
+
+        self.update_owner_states(&checks, block).await;
+        let keys = checks
+            .iter()
+            .map(|check| self.cache_key(check))
+            .collect::<Vec<_>>();
+
+        let (mut results, missing) = self.get_cached_results(&keys, block);
+        let validations = if missing.is_empty() {
+            Vec::new()
+        } else {
+            let missing_checks = missing.iter().map(|i| checks[*i].clone()).collect();
+            self.validator.validate_signatures(missing_checks).await
+        };
+        {
+            let mut state = self.state.lock().unwrap();
+            for (i, validation) in missing.into_iter().zip(validations) {
+                let valid = match &validation {
+                    Ok(()) => Some(true),
+                    Err(SignatureValidationError::Invalid) => Some(false),
+                    // Node errors are not cached as they may be temporary.
+                    Err(SignatureValidationError::Other(_)) => None,
+                };
+                if let Some(valid) = valid {
+                    state.entries.insert(
+                        keys[i],
+                        CacheEntry {
+                            valid,
+                            checked_at_block: block,
+                        },
+                    );
+                }
+                results[i] = Some(validation);
+            }
+
+            // Only keep entries for orders that are still around.
+            let keys = keys.iter().collect::<HashSet<_>>();
+            state.entries.retain(|key, _| keys.contains(key));
+            let owners = checks
+                .iter()
+                .map(|check| check.signer)
+                .collect::<HashSet<_>>();
+            state
+                .owner_state_blocks
+                .retain(|owner, _| owners.contains(owner));
+        }
+
+        let mut results = results.into_iter().map(Option::unwrap);
+        orders
+            .into_iter()
+            .filter(|order| {
+                if let Signature::Eip1271(_) = &order.signature {
+                    if let Err(err) = results.next().unwrap() {
+                        let reason = match err {
+                            SignatureValidationError::Invalid => "invalid",
+                            SignatureValidationError::Other(_) => "error",
+                        };
+                        self.metrics
+                            .eip1271_filtered_orders
+                            .with_label_values(&[reason])
+                            .inc();
+                        tracing::warn!(
+                            order_uid =% order.metadata.uid, ?err,
+                            "filtering EIP-1271 order as signature became invalid"
+                        );
+                        return false;
+                    }
+                }
+
+                true
+            })
+            .collect()
+    }
+
+    /// Updates the last known owner state change blocks for all owners in the checks.
+    async fn update_owner_states(&self, checks: &[SignatureCheck], block: u64) {
+        let (owners, last_scanned_block) = {
+            let state = self.state.lock().unwrap();
+            let owners = checks
+                .iter()
+                .map(|check| check.signer)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            (owners, state.last_scanned_block)
+        };
+
+        let changed_owners = match last_scanned_block {
+            Some(last) if last >= block => Ok(Default::default()),
+            Some(last) if block - last <= MAX_BLOCK_RANGE => {
+                self.owner_watcher
+                    .changed_owners(&owners, last + 1, block)
+                    .await
+            }
+            // Either this is the first update or we fell too far behind, in both cases we can't
+            // tell which owners changed so treat them all as changed.
+            _ => Ok(owners.iter().copied().collect()),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let changed_owners = match changed_owners {
+            Ok(changed_owners) => {
+                state.last_scanned_block = Some(block.max(last_scanned_block.unwrap_or_default()));
+                changed_owners
+            }
+            Err(err) => {
+                tracing::warn!(?err, "failed to detect EIP-1271 owner state changes");
+                state.last_scanned_block = None;
+                owners.iter().copied().collect()
+            }
+        };
+        for owner in owners {
+            let owner_state_block = state.owner_state_blocks.entry(owner).or_insert(block);
+            if changed_owners.contains(&owner) {
+                *owner_state_block = block;
+            }
+        }
+    }
+
+    fn cache_key(&self, check: &SignatureCheck) -> CacheKey {
+        let state = self.state.lock().unwrap();
+        CacheKey {
+            owner: check.signer,
+            hash: check.hash,
+            owner_state_block: state
+                .owner_state_blocks
+                .get(&check.signer)
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns cached results and uncached indices.
+    #[allow(clippy::type_complexity)]
+    fn get_cached_results(
+        &self,
+        keys: &[CacheKey],
+        block: u64,
+    ) -> (
+        Vec<Option<Result<(), SignatureValidationError>>>,
+        Vec<usize>,
+    ) {
+        let state = self.state.lock().unwrap();
+        let mut results = Vec::with_capacity(keys.len());
+        let mut missing = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            let cached = state.entries.get(key).filter(|entry| {
+                let recheck_blocks = if entry.valid {
+                    self.config.valid_recheck_blocks
+                } else {
+                    self.config.invalid_recheck_blocks
+                };
+                block.saturating_sub(entry.checked_at_block) < recheck_blocks
+            });
+            match cached {
+                Some(entry) => {
+                    self.metrics
+                        .eip1271_validation_cache
+                        .with_label_values(&["hits"])
+                        .inc();
+                    results.push(Some(if entry.valid {
+                        Ok(())
+                    } else {
+                        Err(SignatureValidationError::Invalid)
+                    }));
+                }
+                None => {
+                    self.metrics
+                        .eip1271_validation_cache
+                        .with_label_values(&["misses"])
+                        .inc();
+                    results.push(None);
+                    missing.push(i);
+                }
+            }
+        }
+        (results, missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashset;
+    use mockall::predicate::eq;
+    use model::order::{OrderMetadata, OrderUid};
+    use shared::signature_validator::MockSignatureValidating;
+
+    fn eip1271_order(hash: u8, owner: u8) -> Order {
+        Order {
+            metadata: OrderMetadata {
+                uid: OrderUid::from_parts(H256([hash; 32]), H160([owner; 20]), 0),
+                ..Default::default()
+            },
+            signature: Signature::Eip1271(vec![hash]),
+            ..Default::default()
+        }
+    }
+
+    fn check(hash: u8, owner: u8) -> SignatureCheck {
+        SignatureCheck {
+            signer: H160([owner; 20]),
+            hash: [hash; 32],
+            signature: vec![hash],
+        }
+    }
+
+    #[tokio::test]
+    async fn filters_invalidated_eip1271_signatures() {
+        let orders = vec![
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_parts(H256([1; 32]), H160([11; 20]), 1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_parts(H256([2; 32]), H160([22; 20]), 2),
+                    ..Default::default()
+                },
+                signature: Signature::Eip1271(vec![2, 2]),
+                ..Default::default()
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_parts(H256([3; 32]), H160([33; 20]), 3),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_parts(H256([4; 32]), H160([44; 20]), 4),
+                    ..Default::default()
+                },
+                signature: Signature::Eip1271(vec![4, 4, 4, 4]),
+                ..Default::default()
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_parts(H256([5; 32]), H160([55; 20]), 5),
+                    ..Default::default()
+                },
+                signature: Signature::Eip1271(vec![5, 5, 5, 5, 5]),
+                ..Default::default()
+            },
+        ];
+
+        let mut signature_validator = MockSignatureValidating::new();
+        signature_validator
+            .expect_validate_signatures()
+            .with(eq(vec![
+                SignatureCheck {
+                    signer: H160([22; 20]),
+                    hash: [2; 32],
+                    signature: vec![2, 2],
+                },
+                SignatureCheck {
+                    signer: H160([44; 20]),
+                    hash: [4; 32],
+                    signature: vec![4, 4, 4, 4],
+                },
+                SignatureCheck {
+                    signer: H160([55; 20]),
+                    hash: [5; 32],
+                    signature: vec![5, 5, 5, 5, 5],
+                },
+            ]))
+            .returning(|_| vec![Ok(()), Err(SignatureValidationError::Invalid), Ok(())]);
+
+        let mut watcher = MockOwnerStateWatching::new();
+        watcher
+            .expect_changed_owners()
+            .returning(|_, _, _| Ok(Default::default()));
+        let cache = SignatureValidationCache::new(
+            Arc::new(signature_validator),
+            Arc::new(watcher),
+            ValidationCacheConfig::default(),
+        );
+
+        let filtered = cache.filter_invalid_signature_orders(orders, 0).await;
+        let remaining_uids = filtered
+            .iter()
+            .map(|order| order.metadata.uid)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            remaining_uids,
+            vec![
+                OrderUid::from_parts(H256([1; 32]), H160([11; 20]), 1),
+                OrderUid::from_parts(H256([2; 32]), H160([22; 20]), 2),
+                OrderUid::from_parts(H256([3; 32]), H160([33; 20]), 3),
+                OrderUid::from_parts(H256([5; 32]), H160([55; 20]), 5),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reuses_cached_validations() {
+        let orders = vec![eip1271_order(1, 1), eip1271_order(2, 2)];
+
+        let mut validator = MockSignatureValidating::new();
+        validator
+            .expect_validate_signatures()
+            .with(eq(vec![check(1, 1), check(2, 2)]))
+            .times(1)
+            .returning(|_| vec![Ok(()), Err(SignatureValidationError::Invalid)]);
+        let mut watcher = MockOwnerStateWatching::new();
+        watcher
+            .expect_changed_owners()
+            .returning(|_, _, _| Ok(Default::default()));
+
+        let cache = SignatureValidationCache::new(
+            Arc::new(validator),
+            Arc::new(watcher),
+            ValidationCacheConfig::default(),
+        );
+
+        for block in [1, 2, 3] {
+            let filtered = cache
+                .filter_invalid_signature_orders(orders.clone(), block)
+                .await;
+            assert_eq!(filtered, orders[..1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn revalidates_on_owner_state_change() {
+        let orders = vec![eip1271_order(1, 1), eip1271_order(2, 2)];
+
+        let mut validator = MockSignatureValidating::new();
+        validator
+            .expect_validate_signatures()
+            .with(eq(vec![check(1, 1), check(2, 2)]))
+            .times(1)
+            .returning(|_| vec![Ok(()), Ok(())]);
+        validator
+            .expect_validate_signatures()
+            .with(eq(vec![check(2, 2)]))
+            .times(1)
+            .returning(|_| vec![Err(SignatureValidationError::Invalid)]);
+        let mut watcher = MockOwnerStateWatching::new();
+        watcher
+            .expect_changed_owners()
+            .with(mockall::predicate::always(), eq(2), eq(2))
+            .returning(|_, _, _| Ok(hashset! { H160([2; 20]) }));
+
+        let cache = SignatureValidationCache::new(
+            Arc::new(validator),
+            Arc::new(watcher),
+            ValidationCacheConfig::default(),
+        );
+
+        let filtered = cache
+            .filter_invalid_signature_orders(orders.clone(), 1)
+            .await;
+        assert_eq!(filtered, orders);
+        let filtered = cache
+            .filter_invalid_signature_orders(orders.clone(), 2)
+            .await;
+        assert_eq!(filtered, orders[..1]);
+    }
+
+    #[tokio::test]
+    async fn revalidates_after_recheck_period() {
+        let orders = vec![eip1271_order(1, 1)];
+
+        let mut validator = MockSignatureValidating::new();
+        validator
+            .expect_validate_signatures()
+            .times(2)
+            .returning(|_| vec![Ok(())]);
+        let mut watcher = MockOwnerStateWatching::new();
+        watcher
+            .expect_changed_owners()
+            .returning(|_, _, _| Ok(Default::default()));
+
+        let cache = SignatureValidationCache::new(
+            Arc::new(validator),
+            Arc::new(watcher),
+            ValidationCacheConfig {
+                valid_recheck_blocks: 2,
+                invalid_recheck_blocks: 2,
+            },
+        );
+
+        // Validated at block 1, cached for block 2 and re-validated at block 3.
+        for block in [1, 2, 3] {
+            let filtered = cache
+                .filter_invalid_signature_orders(orders.clone(), block)
+                .await;
+            assert_eq!(filtered, orders);
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_node_errors() {
+        let orders = vec![eip1271_order(1, 1)];
+
+        let mut validator = MockSignatureValidating::new();
+        validator
+            .expect_validate_signatures()
+            .times(1)
+            .returning(|_| {
+                vec![Err(SignatureValidationError::Other(
+                    shared::ethcontract_error::testing_node_error(),
+                ))]
+            });
+        validator
+            .expect_validate_signatures()
+            .times(1)
+            .returning(|_| vec![Ok(())]);
+        let mut watcher = MockOwnerStateWatching::new();
+        watcher
+            .expect_changed_owners()
+            .returning(|_, _, _| Ok(Default::default()));
+
+        let cache = SignatureValidationCache::new(
+            Arc::new(validator),
+            Arc::new(watcher),
+            ValidationCacheConfig::default(),
+        );
+
+        let filtered = cache
+            .filter_invalid_signature_orders(orders.clone(), 1)
+            .await;
+        assert!(filtered.is_empty());
+        let filtered = cache
+            .filter_invalid_signature_orders(orders.clone(), 2)
+            .await;
+        assert_eq!(filtered, orders);
+    }
+}
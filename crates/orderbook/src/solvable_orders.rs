@@ -1,17 +1,23 @@
-use crate::{database::orders::OrderStoring, solver_competition::SolverCompetitionStoring};
+use crate::{
+    database::orders::OrderStoring, signature_validation_cache::SignatureValidationCache,
+    solver_competition::SolverCompetitionStoring,
+};
 use anyhow::{Context as _, Result};
-use ethcontract::H256;
 use futures::StreamExt;
-use model::{auction::Auction, order::Order, signature::Signature, time::now_in_epoch_seconds};
+use gas_estimation::GasPriceEstimating;
+use model::{
+    auction::Auction,
+    order::{Order, OrderUid, UnfillableReason},
+    time::now_in_epoch_seconds,
+};
 use primitive_types::{H160, U256};
-use prometheus::{IntCounter, IntGauge};
+use prometheus::{IntCounter, IntGauge, IntGaugeVec};
 use shared::{
     account_balances::{BalanceFetching, Query},
     bad_token::BadTokenDetecting,
     current_block::CurrentBlockStream,
     maintenance::Maintaining,
     price_estimation::native::NativePriceEstimating,
-    signature_validator::{SignatureCheck, SignatureValidating},
 };
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
@@ -42,6 +48,25 @@ pub struct Metrics {
 
     /// auction price estimate timeouts
     auction_price_estimate_timeouts: IntCounter,
+
+    /// auction orders excluded as unfillable
+    #[metric(labels("reason"))]
+    auction_unfillable_orders: IntGaugeVec,
+}
+
+/// Configures when open orders are considered "unfillable" and excluded from auctions because
+/// their executable amount is too small to be worth settling.
+///
+/// Orders are re-evaluated on every cache update so they get included again once their owner's
+/// balance or the gas price changes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnfillableOrderPolicy {
+    /// Orders whose executable sell amount is worth less than this amount of native token atoms
+    /// are unfillable.
+    pub min_native_value: U256,
+    /// The gas needed to settle a single order. Orders whose executable sell amount is worth less
+    /// than this amount of gas at the current gas price are unfillable. Zero disables the check.
+    pub gas_per_order: u64,
 }
 
 /// Keeps track and updates the set of currently solvable orders.
@@ -59,8 +84,10 @@ pub struct SolvableOrdersCache {
     notify: Notify,
     cache: Mutex<Inner>,
     native_price_estimator: Arc<dyn NativePriceEstimating>,
-    signature_validator: Arc<dyn SignatureValidating>,
+    gas_price_estimator: Arc<dyn GasPriceEstimating>,
+    signature_validation_cache: SignatureValidationCache,
     solver_competition: Arc<dyn SolverCompetitionStoring>,
+    unfillable_order_policy: UnfillableOrderPolicy,
     metrics: &'static Metrics,
 }
 
@@ -70,6 +97,7 @@ struct Inner {
     orders: SolvableOrders,
     balances: Balances,
     auction: Auction,
+    unfillable_orders: HashMap<OrderUid, UnfillableReason>,
 }
 
 #[derive(Clone, Debug)]
@@ -90,8 +118,10 @@ impl SolvableOrdersCache {
         bad_token_detector: Arc<dyn BadTokenDetecting>,
         current_block: CurrentBlockStream,
         native_price_estimator: Arc<dyn NativePriceEstimating>,
-        signature_validator: Arc<dyn SignatureValidating>,
+        gas_price_estimator: Arc<dyn GasPriceEstimating>,
+        signature_validation_cache: SignatureValidationCache,
         solver_competition: Arc<dyn SolverCompetitionStoring>,
+        unfillable_order_policy: UnfillableOrderPolicy,
     ) -> Arc<Self> {
         let self_ = Arc::new(Self {
             min_order_validity_period,
@@ -109,10 +139,13 @@ impl SolvableOrdersCache {
                 },
                 balances: Default::default(),
                 auction: Auction::default(),
+                unfillable_orders: Default::default(),
             }),
             native_price_estimator,
-            signature_validator,
+            gas_price_estimator,
+            signature_validation_cache,
             solver_competition,
+            unfillable_order_policy,
             metrics: Metrics::instance(global_metrics::get_metric_storage_registry()).unwrap(),
         });
         tokio::task::spawn(update_task(Arc::downgrade(&self_), current_block));
@@ -124,6 +157,12 @@ impl SolvableOrdersCache {
         inner.balances.get(key).copied()
     }
 
+    /// The reason why an open order was excluded from the last auction as unfillable, if it was.
+    pub fn unfillable_reason(&self, uid: &OrderUid) -> Option<UnfillableReason> {
+        let inner = self.cache.lock().unwrap();
+        inner.unfillable_orders.get(uid).copied()
+    }
+
     /// Orders and timestamp at which last update happened.
     pub fn cached_solvable_orders(&self) -> SolvableOrders {
         self.cache.lock().unwrap().orders.clone()
@@ -156,8 +195,10 @@ impl SolvableOrdersCache {
         let db_solvable_orders = self.database.solvable_orders(min_valid_to).await?;
         let orders = filter_banned_user_orders(db_solvable_orders.orders, &self.banned_users);
         let orders = filter_unsupported_tokens(orders, self.bad_token_detector.as_ref()).await?;
-        let orders =
-            filter_invalid_signature_orders(orders, self.signature_validator.as_ref()).await;
+        let orders = self
+            .signature_validation_cache
+            .filter_invalid_signature_orders(orders, block)
+            .await;
 
         // If we update due to an explicit notification we can reuse existing balances as they
         // cannot have changed.
@@ -195,13 +236,21 @@ impl SolvableOrdersCache {
         }
 
         // create auction
-        let (orders, prices) = get_orders_with_native_prices(
+        let (orders, mut prices) = get_orders_with_native_prices(
             orders.clone(),
             &*self.native_price_estimator,
             Instant::now() + MAX_AUCTION_CREATION_TIME,
             self.metrics,
         )
         .await;
+        let min_native_value = self.min_executable_native_value().await;
+        let (orders, unfillable_orders) =
+            filter_unfillable_orders(orders, &prices, min_native_value, self.metrics);
+        prices.retain(|token, _| {
+            orders
+                .iter()
+                .any(|order| order.data.sell_token == *token || order.data.buy_token == *token)
+        });
         let next_solver_competition = self.solver_competition.next_solver_competition().await?;
         let auction = Auction {
             block,
@@ -220,10 +269,39 @@ impl SolvableOrdersCache {
             },
             balances: new_balances,
             auction,
+            unfillable_orders,
         };
 
         Ok(())
     }
+
+    /// The native token value an order's executable amount needs to have in order to be worth
+    /// settling according to the unfillable order policy, and the reason for orders below it.
+    async fn min_executable_native_value(&self) -> (U256, UnfillableReason) {
+        let policy = &self.unfillable_order_policy;
+        let min_native_value = (
+            policy.min_native_value,
+            UnfillableReason::BelowMinNativeValue,
+        );
+        if policy.gas_per_order == 0 {
+            return min_native_value;
+        }
+        let gas_cost = match self.gas_price_estimator.estimate().await {
+            Ok(gas_price) => {
+                U256::from_f64_lossy(gas_price.effective_gas_price() * policy.gas_per_order as f64)
+            }
+            Err(err) => {
+                // Not knowing the gas price should not cause orders to be excluded.
+                tracing::warn!(?err, "failed to estimate gas price for unfillable orders");
+                U256::zero()
+            }
+        };
+        if gas_cost > policy.min_native_value {
+            (gas_cost, UnfillableReason::BelowGasCost)
+        } else {
+            min_native_value
+        }
+    }
 }
 
 /// Filters all orders whose owners are in the set of "banned" users.
@@ -232,52 +310,6 @@ fn filter_banned_user_orders(mut orders: Vec<Order>, banned_users: &HashSet<H160
     orders
 }
 
-/// Filters EIP-1271 orders whose signatures are no longer validating.
-async fn filter_invalid_signature_orders(
-    orders: Vec<Order>,
-    signature_validator: &dyn SignatureValidating,
-) -> Vec<Order> {
-    let checks = orders
-        .iter()
-        .filter_map(|order| match &order.signature {
-            Signature::Eip1271(signature) => {
-                let (H256(hash), signer, _) = order.metadata.uid.parts();
-                Some(SignatureCheck {
-                    signer,
-                    hash,
-                    signature: signature.clone(),
-                })
-            }
-            _ => None,
-        })
-        .collect::<Vec<_>>();
-
-    if checks.is_empty() {
-        return orders;
-    }
-
-    let mut validations = signature_validator
-        .validate_signatures(checks)
-        .await
-        .into_iter();
-    orders
-        .into_iter()
-        .filter(|order| {
-            if let Signature::Eip1271(_) = &order.signature {
-                if let Err(err) = validations.next().unwrap() {
-                    tracing::warn!(
-                        order_uid =% order.metadata.uid, ?err,
-                        "filtering EIP-1271 order as signature became invalid"
-                    );
-                    return false;
-                }
-            }
-
-            true
-        })
-        .collect()
-}
-
 /// Returns existing balances and Vec of queries that need to be peformed.
 fn new_balances(old_balances: &Balances, orders: &[Order]) -> (HashMap<Query, U256>, Vec<Query>) {
     let mut new_balances = HashMap::new();
@@ -353,7 +385,7 @@ fn solvable_orders(mut orders: Vec<Order>, balances: &Balances) -> Vec<Order> {
 /// a half-filled order would be `(sell_amount + fee_amount) / 2`).
 ///
 /// Returns `Err` on overflow.
-fn max_transfer_out_amount(order: &Order) -> Result<U256> {
+pub(crate) fn max_transfer_out_amount(order: &Order) -> Result<U256> {
     let amounts = order.remaining_amounts()?;
     amounts
         .sell_amount
@@ -361,6 +393,81 @@ fn max_transfer_out_amount(order: &Order) -> Result<U256> {
         .context("overflow computing maximum transfer out amount")
 }
 
+/// Removes orders whose executable amount is worth less than the specified native token value
+/// and returns them separately together with the reason why they are unfillable.
+///
+/// Expects the sell token prices of all orders to be known.
+fn filter_unfillable_orders(
+    mut orders: Vec<Order>,
+    prices: &BTreeMap<H160, U256>,
+    (min_native_value, reason): (U256, UnfillableReason),
+    metrics: &Metrics,
+) -> (Vec<Order>, HashMap<OrderUid, UnfillableReason>) {
+    let mut unfillable_orders = HashMap::new();
+    if !min_native_value.is_zero() {
+        orders.retain(|order| {
+            let native_value = match executable_native_value(order, prices) {
+                Some(value) => value,
+                None => return true,
+            };
+            if native_value >= min_native_value {
+                return true;
+            }
+            tracing::debug!(
+                order_uid = ?order.metadata.uid,
+                ?reason,
+                %native_value,
+                "filtered unfillable order",
+            );
+            unfillable_orders.insert(order.metadata.uid, reason);
+            false
+        });
+    }
+
+    for reason in [
+        UnfillableReason::BelowMinNativeValue,
+        UnfillableReason::BelowGasCost,
+    ] {
+        let count = unfillable_orders.values().filter(|r| **r == reason).count();
+        metrics
+            .auction_unfillable_orders
+            .with_label_values(&[reason_label(reason)])
+            .set(count as i64);
+    }
+    metrics
+        .auction_solvable_orders
+        .sub(unfillable_orders.len() as i64);
+
+    (orders, unfillable_orders)
+}
+
+/// Computes the value in native token atoms of the sell amount an order can currently execute.
+/// This is limited by both the remaining amount of the order and the owner's available balance.
+///
+/// Returns `None` if the value cannot be computed, in which case the order should not be
+/// considered unfillable.
+fn executable_native_value(order: &Order, prices: &BTreeMap<H160, U256>) -> Option<U256> {
+    let price = prices.get(&order.data.sell_token)?;
+    let mut sell_amount = order.remaining_amounts().ok()?.sell_amount;
+    if let Some(balance) = order.metadata.available_balance {
+        sell_amount = sell_amount.min(balance);
+    }
+    // Prices are normalized such that 1e18 corresponds to a price of 1 native token atom per
+    // token atom.
+    let value = sell_amount
+        .checked_mul(*price)
+        .map(|value| value / U256::exp10(18))
+        .unwrap_or(U256::MAX);
+    Some(value)
+}
+
+fn reason_label(reason: UnfillableReason) -> &'static str {
+    match reason {
+        UnfillableReason::BelowMinNativeValue => "below_min_native_value",
+        UnfillableReason::BelowGasCost => "below_gas_cost",
+    }
+}
+
 /// Keep updating the cache every N seconds or when an update notification happens.
 /// Exits when this becomes the only reference to the cache.
 async fn update_task(cache: Weak<SolvableOrdersCache>, current_block: CurrentBlockStream) {
@@ -531,22 +638,22 @@ async fn filter_unsupported_tokens(
 mod tests {
     use super::*;
     use crate::{
-        database::orders::MockOrderStoring, database::orders::SolvableOrders as DbOrders,
+        database::orders::MockOrderStoring,
+        database::orders::SolvableOrders as DbOrders,
+        signature_validation_cache::{MockOwnerStateWatching, ValidationCacheConfig},
         solver_competition::MockSolverCompetitionStoring,
     };
     use chrono::{DateTime, NaiveDateTime, Utc};
     use futures::{FutureExt, StreamExt};
     use maplit::{btreemap, hashmap, hashset};
-    use mockall::predicate::eq;
-    use model::order::{
-        OrderBuilder, OrderData, OrderKind, OrderMetadata, OrderUid, SellTokenSource,
-    };
+    use model::order::{OrderBuilder, OrderData, OrderKind, OrderMetadata, SellTokenSource};
     use primitive_types::H160;
     use shared::{
         account_balances::MockBalanceFetching,
         bad_token::list_based::ListBasedDetector,
+        gas_price_estimation::FakeGasPriceEstimator,
         price_estimation::{native::MockNativePriceEstimating, PriceEstimationError},
-        signature_validator::{MockSignatureValidating, SignatureValidationError},
+        signature_validator::MockSignatureValidating,
     };
 
     #[tokio::test]
@@ -696,8 +803,14 @@ mod tests {
             Arc::new(bad_token_detector),
             receiver,
             Arc::new(native),
-            Arc::new(MockSignatureValidating::new()),
+            Arc::new(FakeGasPriceEstimator::default()),
+            SignatureValidationCache::new(
+                Arc::new(MockSignatureValidating::new()),
+                Arc::new(MockOwnerStateWatching::new()),
+                ValidationCacheConfig::default(),
+            ),
             Arc::new(solver_competition),
+            UnfillableOrderPolicy::default(),
         );
 
         cache.update(0).await.unwrap();
@@ -841,6 +954,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn filters_unfillable_orders() {
+        let token = H160([1; 20]);
+        let order = |uid: u8, sell_amount: u128, available_balance: u128| Order {
+            data: OrderData {
+                sell_token: token,
+                sell_amount: sell_amount.into(),
+                buy_amount: 1.into(),
+                kind: OrderKind::Sell,
+                partially_fillable: true,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([uid; 56]),
+                available_balance: Some(available_balance.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let orders = vec![
+            order(0, 1_000, 1_000),
+            // Only a fraction of the order can be executed with the available balance.
+            order(1, 1_000, 10),
+            order(2, 10, 1_000),
+            // Orders without a sell token price are left alone.
+            Order {
+                data: OrderData {
+                    sell_token: H160([2; 20]),
+                    ..order(3, 10, 10).data
+                },
+                ..order(3, 10, 10)
+            },
+        ];
+        // 1 token atom is worth 0.5 native token atoms.
+        let prices = btreemap! { token => U256::from(500_000_000_000_000_000_u128) };
+        let metrics = Metrics::instance(global_metrics::get_metric_storage_registry()).unwrap();
+
+        let (orders_, unfillable) = filter_unfillable_orders(
+            orders.clone(),
+            &prices,
+            (U256::zero(), UnfillableReason::BelowMinNativeValue),
+            metrics,
+        );
+        assert_eq!(orders_, orders);
+        assert!(unfillable.is_empty());
+
+        let (orders_, unfillable) = filter_unfillable_orders(
+            orders.clone(),
+            &prices,
+            (100.into(), UnfillableReason::BelowGasCost),
+            metrics,
+        );
+        assert_eq!(orders_, [orders[0].clone(), orders[3].clone()]);
+        assert_eq!(
+            unfillable,
+            hashmap! {
+                OrderUid([1; 56]) => UnfillableReason::BelowGasCost,
+                OrderUid([2; 56]) => UnfillableReason::BelowGasCost,
+            }
+        );
+    }
+
     #[test]
     fn computes_max_transfer_out_amount_for_order() {
         // For fill-or-kill orders, we don't overflow even for very large buy
@@ -1049,88 +1224,6 @@ mod tests {
         assert_eq!(expected_result, filtered_orders);
     }
 
-    #[tokio::test]
-    async fn filters_invalidated_eip1271_signatures() {
-        let orders = vec![
-            Order {
-                metadata: OrderMetadata {
-                    uid: OrderUid::from_parts(H256([1; 32]), H160([11; 20]), 1),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            Order {
-                metadata: OrderMetadata {
-                    uid: OrderUid::from_parts(H256([2; 32]), H160([22; 20]), 2),
-                    ..Default::default()
-                },
-                signature: Signature::Eip1271(vec![2, 2]),
-                ..Default::default()
-            },
-            Order {
-                metadata: OrderMetadata {
-                    uid: OrderUid::from_parts(H256([3; 32]), H160([33; 20]), 3),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            Order {
-                metadata: OrderMetadata {
-                    uid: OrderUid::from_parts(H256([4; 32]), H160([44; 20]), 4),
-                    ..Default::default()
-                },
-                signature: Signature::Eip1271(vec![4, 4, 4, 4]),
-                ..Default::default()
-            },
-            Order {
-                metadata: OrderMetadata {
-                    uid: OrderUid::from_parts(H256([5; 32]), H160([55; 20]), 5),
-                    ..Default::default()
-                },
-                signature: Signature::Eip1271(vec![5, 5, 5, 5, 5]),
-                ..Default::default()
-            },
-        ];
-
-        let mut signature_validator = MockSignatureValidating::new();
-        signature_validator
-            .expect_validate_signatures()
-            .with(eq(vec![
-                SignatureCheck {
-                    signer: H160([22; 20]),
-                    hash: [2; 32],
-                    signature: vec![2, 2],
-                },
-                SignatureCheck {
-                    signer: H160([44; 20]),
-                    hash: [4; 32],
-                    signature: vec![4, 4, 4, 4],
-                },
-                SignatureCheck {
-                    signer: H160([55; 20]),
-                    hash: [5; 32],
-                    signature: vec![5, 5, 5, 5, 5],
-                },
-            ]))
-            .returning(|_| vec![Ok(()), Err(SignatureValidationError::Invalid), Ok(())]);
-
-        let filtered = filter_invalid_signature_orders(orders, &signature_validator).await;
-        let remaining_uids = filtered
-            .iter()
-            .map(|order| order.metadata.uid)
-            .collect::<Vec<_>>();
-
-        assert_eq!(
-            remaining_uids,
-            vec![
-                OrderUid::from_parts(H256([1; 32]), H160([11; 20]), 1),
-                OrderUid::from_parts(H256([2; 32]), H160([22; 20]), 2),
-                OrderUid::from_parts(H256([3; 32]), H160([33; 20]), 3),
-                OrderUid::from_parts(H256([5; 32]), H160([55; 20]), 5),
-            ]
-        );
-    }
-
     #[test]
     fn filter_unsupported_tokens_() {
         let token0 = H160::from_low_u64_le(0);
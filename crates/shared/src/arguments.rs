@@ -9,6 +9,7 @@ use ethcontract::{H160, H256, U256};
 use std::{
     fmt::{Display, Formatter},
     num::{NonZeroU64, ParseFloatError},
+    path::PathBuf,
     str::FromStr,
     time::Duration,
 };
@@ -65,6 +66,11 @@ pub struct Arguments {
     #[clap(long, env, use_value_delimiter = true)]
     pub base_tokens: Vec<H160>,
 
+    /// The maximum number of AMM paths the baseline solver and price estimator split an order
+    /// across to reduce price impact. 1 disables splitting.
+    #[clap(long, env, default_value = "1")]
+    pub baseline_max_split_paths: usize,
+
     /// Which Liquidity sources to be used by Price Estimator.
     #[clap(long, env, arg_enum, ignore_case = true, use_value_delimiter = true)]
     pub baseline_sources: Option<Vec<BaselineSource>>,
@@ -163,6 +169,11 @@ pub struct Arguments {
         parse(try_from_str = duration_from_seconds),
     )]
     pub liquidity_fetcher_max_age_update: Duration,
+
+    /// Path of the file in which the Uniswap V3 pool indexer persists its checkpoints. Without a
+    /// checkpoint, pool events are replayed from the Uniswap V3 factory deployment on startup.
+    #[clap(long, env)]
+    pub uniswap_v3_checkpoint_path: Option<PathBuf>,
 }
 
 pub fn display_option(option: &Option<impl Display>, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -202,6 +213,11 @@ impl Display for Arguments {
                 .unwrap_or("None")
         )?;
         writeln!(f, "base_tokens: {:?}", self.base_tokens)?;
+        writeln!(
+            f,
+            "baseline_max_split_paths: {}",
+            self.baseline_max_split_paths
+        )?;
         writeln!(f, "baseline_sources: {:?}", self.baseline_sources)?;
         writeln!(f, "pool_cache_blocks: {}", self.pool_cache_blocks)?;
         writeln!(
@@ -288,6 +304,11 @@ impl Display for Arguments {
                 .map(|_| "SECRET")
                 .unwrap_or("None")
         )?;
+        writeln!(
+            f,
+            "uniswap_v3_checkpoint_path: {:?}",
+            self.uniswap_v3_checkpoint_path
+        )?;
         Ok(())
     }
 }
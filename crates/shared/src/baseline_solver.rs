@@ -23,6 +23,13 @@ pub trait BaselineSolvable {
 
     // Returns the approximate amount of gas that using this piece of liquidity would incur
     fn gas_cost(&self) -> usize;
+
+    // Identifies the pool providing this liquidity. Liquidity of a pool trading several token
+    // pairs that gets listed under each of them has to return the same identifier every time.
+    // Defaults to the identity of the liquidity itself.
+    fn pool_id(&self) -> usize {
+        (self as *const Self).cast::<()>() as usize
+    }
 }
 
 pub struct Estimate<'a, V, L> {
@@ -117,6 +124,207 @@ pub fn estimate_sell_amount<'a, L: BaselineSolvable>(
         })
 }
 
+/// The number of equal parts an amount is divided into when splitting it across paths.
+const SPLIT_PARTS: u64 = 20;
+
+/// An estimate for an amount that is split across multiple independent paths.
+pub struct SplitEstimate<'a, L> {
+    // The total result amount over all routes
+    pub value: U256,
+    // The routes the amount is split into
+    pub routes: Vec<Route<'a, L>>,
+}
+
+/// The part of a split estimate that trades along a single path.
+pub struct Route<'a, L> {
+    // The part of the input amount (the sell amount when estimating buy amounts, the buy amount
+    // when estimating sell amounts) that is routed along this path
+    pub amount: U256,
+    // The token path of this route
+    pub tokens: Vec<H160>,
+    // The estimate for trading `amount` along the path
+    pub estimate: Estimate<'a, U256, L>,
+}
+
+impl<'a, L: BaselineSolvable> SplitEstimate<'a, L> {
+    pub fn gas_cost(&self) -> usize {
+        // Like for single path estimates, the trade itself is only paid once.
+        let cost_of_hops: usize = self
+            .routes
+            .iter()
+            .flat_map(|route| route.estimate.path.iter())
+            .map(|item| item.gas_cost())
+            .sum();
+        50_000 + cost_of_hops
+    }
+}
+
+// Given path candidates and a sell amount estimates the buy amount when splitting the sell amount
+// across up to `max_paths` of the candidates that don't share any pools.
+// Returns None if no path candidate is valid.
+pub fn estimate_split_buy_amount<'a, L: BaselineSolvable>(
+    sell_amount: U256,
+    paths: &[Vec<H160>],
+    liquidity: &'a HashMap<TokenPair, Vec<L>>,
+    max_paths: usize,
+) -> Option<SplitEstimate<'a, L>> {
+    estimate_split(
+        sell_amount,
+        paths,
+        max_paths,
+        |path| path_pools(path, liquidity),
+        |amount, path| estimate_buy_amount(amount, path, liquidity),
+        // More bought is better.
+        |a, b| a > b,
+    )
+}
+
+// Given path candidates and a buy amount estimates the sell amount when splitting the buy amount
+// across up to `max_paths` of the candidates that don't share any pools.
+// Returns None if no path candidate is valid.
+pub fn estimate_split_sell_amount<'a, L: BaselineSolvable>(
+    buy_amount: U256,
+    paths: &[Vec<H160>],
+    liquidity: &'a HashMap<TokenPair, Vec<L>>,
+    max_paths: usize,
+) -> Option<SplitEstimate<'a, L>> {
+    estimate_split(
+        buy_amount,
+        paths,
+        max_paths,
+        |path| path_pools(path, liquidity),
+        |amount, path| estimate_sell_amount(amount, path, liquidity),
+        // Less sold is better.
+        |a, b| a < b,
+    )
+}
+
+/// Splits `amount` across the best paths by repeatedly assigning the next part of the amount to
+/// the path with the best marginal result. This equalizes the marginal prices of the paths as
+/// well as the part size allows.
+///
+/// Only paths that can't trade on any of the same pools are combined so that the estimates for
+/// each path are independent of each other. The split is only used if it beats the best single
+/// path.
+fn estimate_split<'a, L: BaselineSolvable>(
+    amount: U256,
+    paths: &[Vec<H160>],
+    max_paths: usize,
+    pools: impl Fn(&[H160]) -> HashSet<usize>,
+    estimate: impl Fn(U256, &[H160]) -> Option<Estimate<'a, U256, L>>,
+    is_better: impl Fn(U256, U256) -> bool,
+) -> Option<SplitEstimate<'a, L>> {
+    let mut ranked = paths
+        .iter()
+        .filter_map(|path| Some((path, estimate(amount, &path[..])?)))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| {
+        if is_better(a.value, b.value) {
+            std::cmp::Ordering::Less
+        } else if is_better(b.value, a.value) {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    let mut ranked = ranked.into_iter();
+    let (best_path, best_estimate) = ranked.next()?;
+    let single = SplitEstimate {
+        value: best_estimate.value,
+        routes: vec![Route {
+            amount,
+            tokens: best_path.clone(),
+            estimate: best_estimate,
+        }],
+    };
+
+    let mut used_pools = pools(best_path);
+    let mut selected = vec![best_path];
+    for (path, _) in ranked {
+        if selected.len() >= max_paths {
+            break;
+        }
+        let path_pools = pools(path);
+        if !used_pools.is_disjoint(&path_pools) {
+            continue;
+        }
+        used_pools.extend(path_pools);
+        selected.push(path);
+    }
+
+    let part = amount / SPLIT_PARTS;
+    if selected.len() < 2 || part.is_zero() {
+        return Some(single);
+    }
+
+    // The amount and result currently assigned to each selected path.
+    let mut assigned = vec![(U256::zero(), U256::zero()); selected.len()];
+    for i in 0..SPLIT_PARTS {
+        // Rounding remainders are assigned with the last part.
+        let part = if i + 1 == SPLIT_PARTS {
+            amount - part * (SPLIT_PARTS - 1)
+        } else {
+            part
+        };
+        let next = selected
+            .iter()
+            .zip(&assigned)
+            .enumerate()
+            .filter_map(|(index, (path, (assigned_amount, assigned_value)))| {
+                let value = estimate(*assigned_amount + part, &path[..])?.value;
+                Some((index, value, value.saturating_sub(*assigned_value)))
+            })
+            .reduce(|best, candidate| {
+                if is_better(candidate.2, best.2) {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        let (index, value, _) = match next {
+            Some(next) => next,
+            None => return Some(single),
+        };
+        assigned[index] = (assigned[index].0 + part, value);
+    }
+
+    let routes = selected
+        .into_iter()
+        .zip(assigned)
+        .filter(|(_, (amount, _))| !amount.is_zero())
+        .map(|(path, (amount, _))| {
+            Some(Route {
+                amount,
+                tokens: path.clone(),
+                estimate: estimate(amount, &path[..])?,
+            })
+        })
+        .collect::<Option<Vec<_>>>();
+    let value = routes.as_ref().and_then(|routes| {
+        routes.iter().try_fold(U256::zero(), |sum, route| {
+            sum.checked_add(route.estimate.value)
+        })
+    });
+    match (routes, value) {
+        (Some(routes), Some(value)) if is_better(value, single.value) => {
+            Some(SplitEstimate { value, routes })
+        }
+        _ => Some(single),
+    }
+}
+
+/// The identifiers of all pools that can be traded on along a path.
+fn path_pools<L: BaselineSolvable>(
+    path: &[H160],
+    liquidity: &HashMap<TokenPair, Vec<L>>,
+) -> HashSet<usize> {
+    path.windows(2)
+        .filter_map(|tokens| liquidity.get(&TokenPair::new(tokens[0], tokens[1])?))
+        .flatten()
+        .map(|liquidity| liquidity.pool_id())
+        .collect()
+}
+
 pub struct BaseTokens {
     /// The base tokens used to determine potential paths in the baseline solver.
     ///
@@ -440,6 +648,154 @@ mod tests {
         assert_eq!(sell_estimate.path, [&valid_pool]);
     }
 
+    #[test]
+    fn split_estimate_spreads_amount_over_independent_paths() {
+        let sell_token = H160::from_low_u64_be(1);
+        let intermediate = H160::from_low_u64_be(2);
+        let buy_token = H160::from_low_u64_be(3);
+
+        let direct = vec![sell_token, buy_token];
+        let indirect = vec![sell_token, intermediate, buy_token];
+        let paths = vec![direct.clone(), indirect.clone()];
+        let pools = [
+            Pool::uniswap(
+                TokenPair::new(sell_token, buy_token).unwrap(),
+                (100_000, 100_000),
+            ),
+            Pool::uniswap(
+                TokenPair::new(sell_token, intermediate).unwrap(),
+                (1_000_000, 1_000_000),
+            ),
+            Pool::uniswap(
+                TokenPair::new(intermediate, buy_token).unwrap(),
+                (1_000_000, 1_000_000),
+            ),
+        ];
+        let pools = hashmap! {
+            pools[0].tokens => vec![pools[0]],
+            pools[1].tokens => vec![pools[1]],
+            pools[2].tokens => vec![pools[2]],
+        };
+
+        let amount = U256::from(50_000);
+        let single_buy = estimate_buy_amount(amount, &indirect, &pools).unwrap();
+        let split_buy = estimate_split_buy_amount(amount, &paths, &pools, 3).unwrap();
+        assert_eq!(split_buy.routes.len(), 2);
+        assert!(split_buy.value > single_buy.value);
+        assert_eq!(
+            split_buy
+                .routes
+                .iter()
+                .fold(U256::zero(), |sum, route| sum + route.amount),
+            amount
+        );
+        assert_eq!(
+            split_buy.gas_cost(),
+            50_000 + 3 * pools[&TokenPair::new(sell_token, buy_token).unwrap()][0].gas_cost()
+        );
+
+        let single_sell = estimate_sell_amount(amount, &indirect, &pools).unwrap();
+        let split_sell = estimate_split_sell_amount(amount, &paths, &pools, 3).unwrap();
+        assert_eq!(split_sell.routes.len(), 2);
+        assert!(split_sell.value < single_sell.value);
+
+        // Limiting the split to a single path yields the best single path estimate.
+        let unsplit = estimate_split_buy_amount(amount, &paths, &pools, 1).unwrap();
+        assert_eq!(unsplit.routes.len(), 1);
+        assert_eq!(unsplit.routes[0].tokens, indirect);
+        assert_eq!(unsplit.value, single_buy.value);
+    }
+
+    #[test]
+    fn split_estimate_does_not_combine_paths_sharing_pools() {
+        let sell_token = H160::from_low_u64_be(1);
+        let first = H160::from_low_u64_be(2);
+        let second = H160::from_low_u64_be(3);
+        let buy_token = H160::from_low_u64_be(4);
+
+        // Both paths trade on the sell token to first token pool.
+        let paths = vec![
+            vec![sell_token, first, buy_token],
+            vec![sell_token, first, second, buy_token],
+        ];
+        let pairs = [
+            TokenPair::new(sell_token, first).unwrap(),
+            TokenPair::new(first, buy_token).unwrap(),
+            TokenPair::new(first, second).unwrap(),
+            TokenPair::new(second, buy_token).unwrap(),
+        ];
+        let pools = pairs
+            .iter()
+            .map(|pair| (*pair, vec![Pool::uniswap(*pair, (100_000, 100_000))]))
+            .collect::<HashMap<_, _>>();
+
+        let split = estimate_split_buy_amount(50_000.into(), &paths, &pools, 3).unwrap();
+        assert_eq!(split.routes.len(), 1);
+        assert_eq!(split.routes[0].tokens, paths[0]);
+    }
+
+    #[test]
+    fn split_estimate_does_not_combine_paths_sharing_multi_token_pools() {
+        /// A pool trading several token pairs that is listed under each of them.
+        struct MultiTokenPool {
+            id: usize,
+            pool: Pool,
+        }
+
+        impl BaselineSolvable for MultiTokenPool {
+            fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+                BaselineSolvable::get_amount_out(&self.pool, out_token, input)
+            }
+
+            fn get_amount_in(&self, in_token: H160, out: (U256, H160)) -> Option<U256> {
+                BaselineSolvable::get_amount_in(&self.pool, in_token, out)
+            }
+
+            fn gas_cost(&self) -> usize {
+                BaselineSolvable::gas_cost(&self.pool)
+            }
+
+            fn pool_id(&self) -> usize {
+                self.id
+            }
+        }
+
+        let sell_token = H160::from_low_u64_be(1);
+        let first = H160::from_low_u64_be(2);
+        let second = H160::from_low_u64_be(3);
+        let buy_token = H160::from_low_u64_be(4);
+
+        // The paths have no token pair in common.
+        let paths = vec![
+            vec![sell_token, first, buy_token],
+            vec![sell_token, second, buy_token],
+        ];
+        let pools = |ids: [usize; 4]| {
+            [
+                TokenPair::new(sell_token, first).unwrap(),
+                TokenPair::new(first, buy_token).unwrap(),
+                TokenPair::new(sell_token, second).unwrap(),
+                TokenPair::new(second, buy_token).unwrap(),
+            ]
+            .into_iter()
+            .zip(ids)
+            .map(|(pair, id)| {
+                let pool = Pool::uniswap(pair, (100_000, 100_000));
+                (pair, vec![MultiTokenPool { id, pool }])
+            })
+            .collect::<HashMap<_, _>>()
+        };
+
+        let independent = pools([0, 1, 2, 3]);
+        let split = estimate_split_buy_amount(50_000.into(), &paths, &independent, 3).unwrap();
+        assert_eq!(split.routes.len(), 2);
+
+        // The first pool of the first path also trades the second pair of the second path.
+        let shared = pools([0, 1, 2, 0]);
+        let split = estimate_split_buy_amount(50_000.into(), &paths, &shared, 3).unwrap();
+        assert_eq!(split.routes.len(), 1);
+    }
+
     #[test]
     fn base_token_pairs_() {
         let base_tokens: Vec<H160> = [0, 1, 2]
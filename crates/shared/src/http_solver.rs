@@ -3,6 +3,7 @@ use ::model::solver_competition::SolverCompetitionId;
 use anyhow::{anyhow, ensure, Context, Result};
 use reqwest::header::{self, HeaderValue};
 use reqwest::{Client, Url};
+use serde::Serialize;
 use std::time::Duration;
 
 pub mod gas_model;
@@ -66,6 +67,9 @@ pub struct SolverConfig {
 
     /// Controls the objective function to optimize for.
     pub objective: Option<Objective>,
+
+    /// The version of the batch auction instance format the solver expects.
+    pub instance_version: model::InstanceVersion,
 }
 
 impl Default for SolverConfig {
@@ -76,6 +80,7 @@ impl Default for SolverConfig {
             has_ucp_policy_parameter: false,
             use_internal_buffers: None,
             objective: None,
+            instance_version: Default::default(),
         }
     }
 }
@@ -92,6 +97,31 @@ impl HttpSolverApi for DefaultHttpSolverApi {
         &self,
         model: &model::BatchAuctionModel,
         timeout: Duration,
+    ) -> Result<model::SettledBatchAuctionModel> {
+        let auction_id = model.metadata.as_ref().and_then(|data| data.auction_id);
+        self.send(model, model::InstanceVersion::V1, auction_id, timeout)
+            .await
+    }
+}
+
+impl DefaultHttpSolverApi {
+    /// Submit a batch auction in the version 2 instance format to the solver
+    /// and wait for a solution.
+    pub async fn solve_v2(
+        &self,
+        model: &model::BatchAuctionModelV2,
+        timeout: Duration,
+    ) -> Result<model::SettledBatchAuctionModel> {
+        self.send(model, model::InstanceVersion::V2, model.auction_id, timeout)
+            .await
+    }
+
+    async fn send(
+        &self,
+        model: &(impl Serialize + Sync),
+        version: model::InstanceVersion,
+        maybe_auction_id: Option<SolverCompetitionId>,
+        timeout: Duration,
     ) -> Result<model::SettledBatchAuctionModel> {
         // The timeout we give to the solver is one second less than
         // the deadline to make up for overhead from the network.
@@ -102,7 +132,6 @@ impl HttpSolverApi for DefaultHttpSolverApi {
 
         let mut url = self.base.join("solve")?;
 
-        let maybe_auction_id = model.metadata.as_ref().and_then(|data| data.auction_id);
         let instance_name = self.generate_instance_name(maybe_auction_id.unwrap_or(0));
         tracing::debug!("http solver instance name is {}", instance_name);
 
@@ -155,6 +184,11 @@ impl HttpSolverApi for DefaultHttpSolverApi {
             header.set_sensitive(true);
             request = request.header("X-API-KEY", header);
         }
+        // Solvers expecting the original format don't know about the header,
+        // so it is only sent for newer versions.
+        if version == model::InstanceVersion::V2 {
+            request = request.header("X-Instance-Version", "2");
+        }
         let request = request.body(body.clone());
         let mut response = request.send().await.context("failed to send request")?;
         let status = response.status();
@@ -179,9 +213,7 @@ impl HttpSolverApi for DefaultHttpSolverApi {
         serde_json::from_str(text)
             .with_context(|| format!("failed to decode response json, {}", context()))
     }
-}
 
-impl DefaultHttpSolverApi {
     fn generate_instance_name(&self, auction_id: SolverCompetitionId) -> String {
         let now = chrono::Utc::now();
         format!(
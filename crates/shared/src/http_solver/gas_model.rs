@@ -30,4 +30,8 @@ impl GasModel {
     pub fn balancer_cost(&self) -> TokenAmount {
         self.cost_for_gas(GAS_PER_BALANCER_SWAP.into())
     }
+
+    pub fn curve_cost(&self) -> TokenAmount {
+        self.cost_for_gas(GAS_PER_CURVE_SWAP.into())
+    }
 }
@@ -1,6 +1,10 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
 use derivative::Derivative;
 use ethcontract::H160;
 use model::{
+    app_id::AppId,
+    order::OrderUid,
     ratio_as_decimal,
     solver_competition::SolverCompetitionId,
     u256_decimal::{self, DecimalU256},
@@ -9,7 +13,10 @@ use num::BigRational;
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
 
 use crate::sources::uniswap_v3::pool_fetching::PoolInfo;
 
@@ -21,6 +28,85 @@ pub struct BatchAuctionModel {
     pub metadata: Option<MetadataModel>,
 }
 
+/// The version of the batch auction instance format that is sent to a solver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InstanceVersion {
+    /// The original format, see [`BatchAuctionModel`].
+    V1,
+    /// The format including order identities and native prices, see
+    /// [`BatchAuctionModelV2`].
+    V2,
+}
+
+impl Default for InstanceVersion {
+    fn default() -> Self {
+        Self::V1
+    }
+}
+
+impl FromStr for InstanceVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v1" | "1" => Ok(Self::V1),
+            "v2" | "2" => Ok(Self::V2),
+            _ => Err(anyhow!("unknown instance version {}", s)),
+        }
+    }
+}
+
+/// Version 2 of the batch auction instance.
+///
+/// In addition to the data of [`BatchAuctionModel`] it contains the identity
+/// of the auction and its orders as well as the native token prices of the
+/// auction, which allows solvers to apply their own risk and reputation logic.
+#[serde_as]
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BatchAuctionModelV2 {
+    pub auction_id: Option<SolverCompetitionId>,
+    pub tokens: BTreeMap<H160, TokenInfoModel>,
+    pub orders: BTreeMap<usize, OrderModelV2>,
+    pub amms: BTreeMap<usize, AmmModel>,
+    /// The prices of one unit of each token denominated in the native token
+    /// with 18 decimals, as used by the driver for computing objective values.
+    #[serde_as(as = "BTreeMap<_, DecimalU256>")]
+    pub native_prices: BTreeMap<H160, U256>,
+    pub metadata: Option<MetadataModel>,
+}
+
+impl From<BatchAuctionModelV2> for BatchAuctionModel {
+    fn from(model: BatchAuctionModelV2) -> Self {
+        Self {
+            tokens: model.tokens,
+            orders: model
+                .orders
+                .into_iter()
+                .map(|(index, order)| (index, order.order))
+                .collect(),
+            amms: model.amms,
+            metadata: model.metadata,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderModelV2 {
+    #[serde(flatten)]
+    pub order: OrderModel,
+    /// The identity of the order. This is only set for orders placed in the
+    /// protocol and not for other liquidity like 0x orders.
+    pub identity: Option<OrderIdentityModel>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderIdentityModel {
+    pub uid: OrderUid,
+    pub owner: H160,
+    pub app_data: AppId,
+    pub creation_date: DateTime<Utc>,
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct OrderModel {
     pub sell_token: H160,
@@ -193,9 +279,29 @@ pub struct SettledBatchAuctionModel {
     #[serde(default)]
     pub interaction_data: Vec<InteractionData>,
     pub metadata: Option<SettledBatchAuctionMetadataModel>,
+    /// Alternative solutions to the same batch auction, ranked in order of
+    /// preference after this solution. This allows solvers to propose, for
+    /// example, a "safe" and an "aggressive" variant and let simulation decide
+    /// between them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<SettledBatchAuctionModel>,
 }
 
 impl SettledBatchAuctionModel {
+    /// Returns this solution followed by its alternatives in ranked order.
+    ///
+    /// Alternatives of alternatives are not part of the protocol and get
+    /// discarded.
+    pub fn into_ranked_solutions(mut self) -> Vec<Self> {
+        let alternatives = std::mem::take(&mut self.alternatives);
+        std::iter::once(self)
+            .chain(alternatives.into_iter().map(|mut alternative| {
+                alternative.alternatives.clear();
+                alternative
+            }))
+            .collect()
+    }
+
     pub fn has_execution_plan(&self) -> bool {
         // Its a bit weird that we expect all entries to contain an execution plan. Could make
         // execution plan required and assert that the vector of execution updates is non-empty
@@ -659,6 +765,80 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn model_v2_serialization() {
+        let token = H160([0x11; 20]);
+        let order = OrderModel {
+            sell_token: token,
+            buy_token: token,
+            sell_amount: 1.into(),
+            buy_amount: 2.into(),
+            allow_partial_fill: false,
+            is_sell_order: true,
+            fee: Default::default(),
+            cost: Default::default(),
+            is_liquidity_order: false,
+            mandatory: false,
+            has_atomic_execution: false,
+        };
+        let model = BatchAuctionModelV2 {
+            auction_id: Some(42),
+            orders: btreemap! {
+                0 => OrderModelV2 {
+                    order: order.clone(),
+                    identity: Some(OrderIdentityModel {
+                        uid: OrderUid([0x22; 56]),
+                        owner: H160([0x33; 20]),
+                        app_data: AppId([0x44; 32]),
+                        creation_date: DateTime::from_utc(
+                            chrono::NaiveDateTime::from_timestamp(0, 0),
+                            Utc,
+                        ),
+                    }),
+                },
+                1 => OrderModelV2 {
+                    order,
+                    identity: None,
+                },
+            },
+            native_prices: btreemap! { token => U256::exp10(18) },
+            ..Default::default()
+        };
+
+        let serialized = serde_json::to_value(&model).unwrap();
+        assert_eq!(serialized["auction_id"], json!(42));
+        assert_eq!(
+            serialized["native_prices"],
+            json!({ "0x1111111111111111111111111111111111111111": "1000000000000000000" })
+        );
+        assert_eq!(serialized["orders"]["0"]["sell_amount"], json!("1"));
+        assert_eq!(
+            serialized["orders"]["0"]["identity"]["owner"],
+            json!("0x3333333333333333333333333333333333333333")
+        );
+        assert_eq!(
+            serialized["orders"]["0"]["identity"]["creation_date"],
+            json!("1970-01-01T00:00:00Z")
+        );
+        assert_eq!(serialized["orders"]["1"]["identity"], json!(null));
+
+        let v1 = serde_json::to_value(&BatchAuctionModel::from(model)).unwrap();
+        assert_eq!(v1["orders"]["0"]["sell_amount"], json!("1"));
+        assert!(v1["orders"]["0"].get("identity").is_none());
+        assert!(v1.get("native_prices").is_none());
+        assert!(v1.get("auction_id").is_none());
+    }
+
+    #[test]
+    fn parse_instance_version() {
+        assert_eq!(
+            "v1".parse::<InstanceVersion>().unwrap(),
+            InstanceVersion::V1
+        );
+        assert_eq!("2".parse::<InstanceVersion>().unwrap(), InstanceVersion::V2);
+        assert!("v3".parse::<InstanceVersion>().is_err());
+    }
+
     #[test]
     fn deserialize_approval_model() {
         let approval = r#"
@@ -706,6 +886,44 @@ mod tests {
         assert!(serde_json::from_str::<SettledBatchAuctionModel>(empty_solution).is_ok());
     }
 
+    #[test]
+    fn decode_ranked_solutions() {
+        let solution = |price: &str| {
+            json!({
+                "orders": {},
+                "prices": {
+                    "0xa7d1c04faf998f9161fc9f800a99a809b84cfc9d": price,
+                },
+            })
+        };
+        let mut response = solution("1");
+        response["alternatives"] = json!([solution("2"), solution("3")]);
+        response["alternatives"][0]["alternatives"] = json!([solution("4")]);
+
+        let token = H160(hex_literal::hex!(
+            "a7d1c04faf998f9161fc9f800a99a809b84cfc9d"
+        ));
+        let solutions = serde_json::from_value::<SettledBatchAuctionModel>(response)
+            .unwrap()
+            .into_ranked_solutions();
+        assert_eq!(
+            solutions
+                .iter()
+                .map(|solution| solution.prices[&token])
+                .collect::<Vec<_>>(),
+            vec![1.into(), 2.into(), 3.into()],
+        );
+        assert!(solutions
+            .iter()
+            .all(|solution| solution.alternatives.is_empty()));
+
+        // Responses without alternatives consist of a single solution.
+        let solutions = serde_json::from_value::<SettledBatchAuctionModel>(solution("1"))
+            .unwrap()
+            .into_ranked_solutions();
+        assert_eq!(solutions.len(), 1);
+    }
+
     #[test]
     fn decode_trivial_solution_without_ref_token() {
         let x = r#"
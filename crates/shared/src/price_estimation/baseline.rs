@@ -1,5 +1,8 @@
 use crate::{
-    baseline_solver::{self, estimate_buy_amount, estimate_sell_amount, BaseTokens},
+    baseline_solver::{
+        self, estimate_buy_amount, estimate_sell_amount, estimate_split_buy_amount,
+        estimate_split_sell_amount, BaseTokens, BaselineSolvable, SplitEstimate,
+    },
     conversions::U256Ext,
     price_estimation::{
         gas, rate_limited, Estimate, PriceEstimateResult, PriceEstimating, PriceEstimationError,
@@ -7,7 +10,10 @@ use crate::{
     },
     rate_limiter::RateLimiter,
     recent_block_cache::Block,
-    sources::uniswap_v2::pool_fetching::{Pool, PoolFetching},
+    sources::{
+        uniswap_v2::pool_fetching::{Pool, PoolFetching},
+        uniswap_v3::pool_fetching::{PoolFetching as UniswapV3PoolFetching, PoolInfo},
+    },
 };
 use anyhow::Result;
 use ethcontract::{H160, U256};
@@ -19,40 +25,90 @@ use std::{collections::HashMap, sync::Arc};
 
 pub struct BaselinePriceEstimator {
     pool_fetcher: Arc<dyn PoolFetching>,
+    uniswap_v3_pool_fetcher: Option<Arc<dyn UniswapV3PoolFetching>>,
     gas_estimator: Arc<dyn GasPriceEstimating>,
     base_tokens: Arc<BaseTokens>,
     native_token: H160,
     native_token_price_estimation_amount: U256,
     rate_limiter: Arc<RateLimiter>,
+    max_split_paths: usize,
 }
 
 impl BaselinePriceEstimator {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pool_fetcher: Arc<dyn PoolFetching>,
+        uniswap_v3_pool_fetcher: Option<Arc<dyn UniswapV3PoolFetching>>,
         gas_estimator: Arc<dyn GasPriceEstimating>,
         base_tokens: Arc<BaseTokens>,
         native_token: H160,
         native_token_price_estimation_amount: U256,
         rate_limiter: Arc<RateLimiter>,
+        max_split_paths: usize,
     ) -> Self {
         Self {
             pool_fetcher,
+            uniswap_v3_pool_fetcher,
             gas_estimator,
             base_tokens,
             native_token,
             native_token_price_estimation_amount,
             rate_limiter,
+            max_split_paths,
+        }
+    }
+}
+
+/// The liquidity the baseline price estimator routes through.
+#[derive(Clone, Debug)]
+enum BaselinePool {
+    UniswapV2(Pool),
+    UniswapV3(PoolInfo),
+}
+
+impl BaselineSolvable for BaselinePool {
+    fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+        match self {
+            BaselinePool::UniswapV2(pool) => pool.get_amount_out(out_token, input),
+            BaselinePool::UniswapV3(pool) => pool.get_amount_out(out_token, input),
+        }
+    }
+
+    fn get_amount_in(&self, in_token: H160, output: (U256, H160)) -> Option<U256> {
+        match self {
+            BaselinePool::UniswapV2(pool) => pool.get_amount_in(in_token, output),
+            BaselinePool::UniswapV3(pool) => pool.get_amount_in(in_token, output),
+        }
+    }
+
+    fn gas_cost(&self) -> usize {
+        match self {
+            BaselinePool::UniswapV2(pool) => pool.gas_cost(),
+            BaselinePool::UniswapV3(pool) => pool.gas_cost(),
         }
     }
 }
 
-type Pools = HashMap<TokenPair, Vec<Pool>>;
+type Pools = HashMap<TokenPair, Vec<BaselinePool>>;
 
 impl PriceEstimating for BaselinePriceEstimator {
     fn estimates<'a>(
         &'a self,
         queries: &'a [Query],
     ) -> futures::stream::BoxStream<'_, (usize, PriceEstimateResult)> {
+        self.estimates_at_block(queries, Block::Recent)
+    }
+}
+
+impl BaselinePriceEstimator {
+    /// Like `PriceEstimating::estimates` but routes through the pools as they were at the
+    /// specified block instead of the most recent ones. Uniswap V3 pools can't be fetched for a
+    /// specific block and are always the most recent ones.
+    pub fn estimates_at_block<'a>(
+        &'a self,
+        queries: &'a [Query],
+        block: Block,
+    ) -> futures::stream::BoxStream<'a, (usize, PriceEstimateResult)> {
         debug_assert!(queries.iter().all(|query| {
             query.buy_token != model::order::BUY_ETH_ADDRESS
                 && query.sell_token != model::order::BUY_ETH_ADDRESS
@@ -68,7 +124,7 @@ impl PriceEstimating for BaselinePriceEstimator {
             Ok(gas_price.effective_gas_price())
         };
         let pools = async {
-            self.pools_for_queries(queries)
+            self.pools_for_queries(queries, block)
                 .await
                 .map_err(PriceEstimationError::Other)
         };
@@ -79,8 +135,8 @@ impl PriceEstimating for BaselinePriceEstimator {
 
         let estimate_single = |init: &Init, query: &Query| -> PriceEstimateResult {
             let (gas_price, pools) = init.as_ref().map_err(Clone::clone)?;
-            let (path, out_amount) = self.estimate_price_helper(query, true, pools, *gas_price)?;
-            let gas = estimate_gas(path.len());
+            let (paths, out_amount) = self.estimate_price_helper(query, true, pools, *gas_price)?;
+            let gas = estimate_split_gas(paths.iter().map(Vec::len));
             Ok(Estimate { out_amount, gas })
         };
         let estimate_all = move |init: Init| {
@@ -92,27 +148,47 @@ impl PriceEstimating for BaselinePriceEstimator {
         };
         futures::stream::once(init).flat_map(estimate_all).boxed()
     }
-}
 
-impl BaselinePriceEstimator {
-    async fn pools_for_queries(&self, queries: &[Query]) -> Result<Pools> {
+    async fn pools_for_queries(&self, queries: &[Query], block: Block) -> Result<Pools> {
         let pairs = self.base_tokens.relevant_pairs(
             &mut queries
                 .iter()
                 .flat_map(|query| TokenPair::new(query.buy_token, query.sell_token)),
         );
-        let pools = self.pool_fetcher.fetch(pairs, Block::Recent).await?;
-        Ok(pools_vec_to_map(pools))
+        let uniswap_v3_pools = async {
+            match &self.uniswap_v3_pool_fetcher {
+                Some(fetcher) => fetcher.fetch(&pairs).await,
+                None => Ok(Vec::new()),
+            }
+        };
+        let (pools, uniswap_v3_pools) = futures::try_join!(
+            self.pool_fetcher.fetch(pairs.clone(), block),
+            uniswap_v3_pools
+        )?;
+        let mut pools = pools_vec_to_map(pools);
+        for pool in uniswap_v3_pools {
+            let pair = match pool.tokens.as_slice() {
+                [token0, token1] => TokenPair::new(token0.id, token1.id),
+                _ => None,
+            };
+            if let Some(pair) = pair {
+                pools
+                    .entry(pair)
+                    .or_default()
+                    .push(BaselinePool::UniswapV3(pool));
+            }
+        }
+        Ok(pools)
     }
 
-    /// Returns the path and the out amount.
+    /// Returns the paths the amount is split across and the out amount.
     fn estimate_price_helper(
         &self,
         query: &Query,
         consider_gas_costs: bool,
         pools: &Pools,
         gas_price: f64,
-    ) -> Result<(Vec<H160>, U256), PriceEstimationError> {
+    ) -> Result<(Vec<Vec<H160>>, U256), PriceEstimationError> {
         if query.sell_token == query.buy_token {
             return Ok((Vec::new(), query.in_amount));
         }
@@ -145,7 +221,7 @@ impl BaselinePriceEstimator {
                 } else {
                     None
                 };
-                let (path, sell_amount) = self.best_execution_buy_order(
+                let (paths, sell_amount) = self.best_execution_buy_order(
                     query.sell_token,
                     query.buy_token,
                     query.in_amount,
@@ -153,7 +229,7 @@ impl BaselinePriceEstimator {
                     sell_token_price_in_native_token,
                     pools,
                 )?;
-                Ok((path, sell_amount))
+                Ok((paths, sell_amount))
             }
             OrderKind::Sell => {
                 // Do not consider gas costs below to avoid infinite recursion.
@@ -180,7 +256,7 @@ impl BaselinePriceEstimator {
                 } else {
                     None
                 };
-                let (path, buy_amount) = self.best_execution_sell_order(
+                let (paths, buy_amount) = self.best_execution_sell_order(
                     query.sell_token,
                     query.buy_token,
                     query.in_amount,
@@ -188,12 +264,12 @@ impl BaselinePriceEstimator {
                     buy_token_price_in_native_token,
                     pools,
                 )?;
-                Ok((path, buy_amount))
+                Ok((paths, buy_amount))
             }
         }
     }
 
-    /// Returns path and out (buy) amount.
+    /// Returns paths and out (buy) amount.
     /// If buy_token_price_in_native_token is set then it will be used to take gas cost into
     /// account.
     fn best_execution_sell_order(
@@ -204,37 +280,31 @@ impl BaselinePriceEstimator {
         gas_price: f64,
         buy_token_price_in_native_token: Option<BigRational>,
         pools: &Pools,
-    ) -> Result<(Vec<H160>, U256), PriceEstimationError> {
-        let path_comparison = |buy_estimate: baseline_solver::Estimate<U256, Pool>| {
+    ) -> Result<(Vec<Vec<H160>>, U256), PriceEstimationError> {
+        let path_comparison = |buy_amount: U256, gas_cost: usize| {
             if let Some(buy_token_price_in_native_token) = &buy_token_price_in_native_token {
                 let buy_amount_in_native_token =
-                    buy_estimate.value.to_big_rational() * buy_token_price_in_native_token;
+                    buy_amount.to_big_rational() * buy_token_price_in_native_token;
                 let tx_cost_in_native_token = U256::from_f64_lossy(gas_price).to_big_rational()
-                    * BigRational::from_integer(buy_estimate.gas_cost().into());
+                    * BigRational::from_integer(gas_cost.into());
                 buy_amount_in_native_token - tx_cost_in_native_token
             } else {
-                buy_estimate.value.to_big_rational()
+                buy_amount.to_big_rational()
             }
         };
 
-        let (path, buy_amount) = self.best_execution(
+        self.best_execution(
             sell_token,
             buy_token,
             sell_amount,
-            |amount, path, pools| {
-                estimate_buy_amount(amount, path, pools)
-                    .map(&path_comparison)
-                    .unwrap_or_else(|| -U256::max_value().to_big_rational())
-            },
-            |amount, path, pools| {
-                estimate_buy_amount(amount, path, pools).map(|estimate| estimate.value)
-            },
+            estimate_buy_amount,
+            estimate_split_buy_amount,
+            path_comparison,
             pools,
-        )?;
-        Ok((path, buy_amount))
+        )
     }
 
-    /// Returns path and out (sell) amount.
+    /// Returns paths and out (sell) amount.
     /// If sell_token_price_in_native_token is set then it will be used to take gas cost into
     /// account.
     fn best_execution_buy_order(
@@ -245,76 +315,114 @@ impl BaselinePriceEstimator {
         gas_price: f64,
         sell_token_price_in_native_token: Option<BigRational>,
         pools: &Pools,
-    ) -> Result<(Vec<H160>, U256), PriceEstimationError> {
-        let path_comparison = |sell_estimate: baseline_solver::Estimate<U256, Pool>| {
+    ) -> Result<(Vec<Vec<H160>>, U256), PriceEstimationError> {
+        let path_comparison = |sell_amount: U256, gas_cost: usize| {
             if let Some(sell_token_price_in_native_token) = &sell_token_price_in_native_token {
                 let sell_amount_in_native_token =
-                    sell_estimate.value.to_big_rational() * sell_token_price_in_native_token;
+                    sell_amount.to_big_rational() * sell_token_price_in_native_token;
                 let tx_cost_in_native_token = U256::from_f64_lossy(gas_price).to_big_rational()
-                    * BigRational::from_integer(sell_estimate.gas_cost().into());
+                    * BigRational::from_integer(gas_cost.into());
                 -sell_amount_in_native_token - tx_cost_in_native_token
             } else {
-                -sell_estimate.value.to_big_rational()
+                -sell_amount.to_big_rational()
             }
         };
 
-        let (path, sell_amount) = self.best_execution(
+        self.best_execution(
             sell_token,
             buy_token,
             buy_amount,
-            |amount, path, pools| {
-                estimate_sell_amount(amount, path, pools)
-                    .map(path_comparison)
-                    .unwrap_or_else(|| -U256::max_value().to_big_rational())
-            },
-            |amount, path, pools| {
-                estimate_sell_amount(amount, path, pools).map(|estimate| estimate.value)
-            },
+            estimate_sell_amount,
+            estimate_split_sell_amount,
+            path_comparison,
             pools,
-        )?;
-        Ok((path, sell_amount))
+        )
     }
 
-    fn best_execution<AmountFn, CompareFn, O, Amount>(
+    /// Picks the best single path or split across multiple paths according to `comparison`,
+    /// which gets passed the resulting amount and gas cost of an execution.
+    #[allow(clippy::too_many_arguments)]
+    fn best_execution<SingleFn, SplitFn, CompareFn, O>(
         &self,
         sell_token: H160,
         buy_token: H160,
         amount: U256,
+        estimate_single: SingleFn,
+        estimate_split: SplitFn,
         comparison: CompareFn,
-        resulting_amount: AmountFn,
         pools: &Pools,
-    ) -> Result<(Vec<H160>, Amount), PriceEstimationError>
+    ) -> Result<(Vec<Vec<H160>>, U256), PriceEstimationError>
     where
-        AmountFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> Option<Amount>,
-        CompareFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> O,
+        SingleFn: for<'a> Fn(
+            U256,
+            &[H160],
+            &'a Pools,
+        ) -> Option<baseline_solver::Estimate<'a, U256, BaselinePool>>,
+        SplitFn: for<'a> Fn(
+            U256,
+            &[Vec<H160>],
+            &'a Pools,
+            usize,
+        ) -> Option<SplitEstimate<'a, BaselinePool>>,
+        CompareFn: Fn(U256, usize) -> O,
         O: Ord,
     {
         debug_assert!(sell_token != buy_token);
         debug_assert!(!amount.is_zero());
 
-        let path_candidates = self.base_tokens.path_candidates(sell_token, buy_token);
-        let best_path = path_candidates
-            .iter()
-            .max_by_key(|path| comparison(amount, path, pools))
+        let path_candidates = self
+            .base_tokens
+            .path_candidates(sell_token, buy_token)
+            .into_iter()
+            .collect::<Vec<_>>();
+        let single_paths = path_candidates.iter().filter_map(|path| {
+            let estimate = estimate_single(amount, path, pools)?;
+            Some((vec![path.clone()], estimate.value, estimate.gas_cost()))
+        });
+        // The split is selected without considering gas costs, so it only competes with the
+        // single paths above instead of replacing them.
+        let split = if self.max_split_paths > 1 {
+            estimate_split(amount, &path_candidates, pools, self.max_split_paths)
+        } else {
+            None
+        };
+        let split = split.map(|split| {
+            let gas_cost = split.gas_cost();
+            let paths = split.routes.into_iter().map(|route| route.tokens).collect();
+            (paths, split.value, gas_cost)
+        });
+        let (paths, resulting_amount, _) = single_paths
+            .chain(split)
+            .max_by_key(|(_, amount, gas_cost)| comparison(*amount, *gas_cost))
             .ok_or(PriceEstimationError::NoLiquidity)?;
-        let resulting_amount =
-            resulting_amount(amount, best_path, pools).ok_or(PriceEstimationError::NoLiquidity)?;
-        Ok((best_path.clone(), resulting_amount))
+        Ok((paths, resulting_amount))
     }
 }
 
 fn pools_vec_to_map(pools: Vec<Pool>) -> Pools {
     pools.into_iter().fold(Pools::new(), |mut pools, pool| {
-        pools.entry(pool.tokens).or_default().push(pool);
+        pools
+            .entry(pool.tokens)
+            .or_default()
+            .push(BaselinePool::UniswapV2(pool));
         pools
     })
 }
 
+#[cfg(test)]
 fn estimate_gas(path_len: usize) -> u64 {
-    let hops = match path_len.checked_sub(1) {
-        Some(len) => len,
-        None => return 0,
-    };
+    estimate_split_gas(std::iter::once(path_len))
+}
+
+/// Estimates the gas of a trade that is split across paths of the specified lengths.
+fn estimate_split_gas(path_lens: impl IntoIterator<Item = usize>) -> u64 {
+    let hops: usize = path_lens
+        .into_iter()
+        .map(|path_len| path_len.saturating_sub(1))
+        .sum();
+    if hops == 0 {
+        return 0;
+    }
     // Can be reduced to one erc20 transfer when #675 is fixed.
     let per_hop = gas::ERC20_TRANSFER * 2 + 40_000;
     gas::SETTLEMENT_SINGLE_TRADE + per_hop * (hops as u64)
@@ -328,9 +436,16 @@ mod tests {
         gas_price_estimation::FakeGasPriceEstimator,
         price_estimation::single_estimate,
         rate_limiter::RateLimiter,
-        sources::uniswap_v2::pool_fetching::{Pool, PoolFetching},
+        sources::{
+            uniswap_v2::pool_fetching::{Pool, PoolFetching},
+            uniswap_v3::{
+                graph_api::Token,
+                pool_fetching::{PoolState, PoolStats},
+            },
+        },
     };
     use gas_estimation::gas_price::GasPrice1559;
+    use num::rational::Ratio;
     use std::{collections::HashSet, sync::Mutex};
 
     #[derive(Default)]
@@ -365,11 +480,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
         let estimator = BaselinePriceEstimator::new(
             pool_fetcher,
+            None,
             gas_estimator,
             base_tokens,
             token_a,
             1.into(),
             default_rate_limiter(),
+            1,
         );
 
         assert!(single_estimate(
@@ -398,11 +515,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
         let estimator = BaselinePriceEstimator::new(
             pool_fetcher,
+            None,
             gas_estimator,
             base_tokens,
             token_a,
             1.into(),
             default_rate_limiter(),
+            1,
         );
 
         assert!(single_estimate(
@@ -438,11 +557,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(base_token, &[]));
         let estimator = BaselinePriceEstimator::new(
             pool_fetcher,
+            None,
             gas_estimator,
             base_tokens,
             token_b,
             1.into(),
             default_rate_limiter(),
+            1,
         );
 
         assert!(single_estimate(
@@ -505,11 +626,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
         let estimator = BaselinePriceEstimator::new(
             pool_fetcher,
+            None,
             gas_estimator,
             base_tokens,
             token_a,
             10.into(),
             default_rate_limiter(),
+            1,
         );
 
         let query = Query {
@@ -559,11 +682,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(intermediate, &[]));
         let estimator = BaselinePriceEstimator::new(
             pool_fetcher,
+            None,
             gas_estimator,
             base_tokens,
             intermediate,
             10.into(),
             default_rate_limiter(),
+            1,
         );
 
         for kind in &[OrderKind::Sell, OrderKind::Buy] {
@@ -597,6 +722,123 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn price_estimate_splits_across_paths() {
+        let token_a = H160::from_low_u64_be(1);
+        let intermediate = H160::from_low_u64_be(2);
+        let token_b = H160::from_low_u64_be(3);
+
+        let pools = vec![
+            Pool::uniswap(
+                TokenPair::new(token_a, token_b).unwrap(),
+                (100_000, 100_000),
+            ),
+            Pool::uniswap(
+                TokenPair::new(token_a, intermediate).unwrap(),
+                (1_000_000, 1_000_000),
+            ),
+            Pool::uniswap(
+                TokenPair::new(intermediate, token_b).unwrap(),
+                (1_000_000, 1_000_000),
+            ),
+        ];
+        let estimator = |max_split_paths| {
+            BaselinePriceEstimator::new(
+                Arc::new(FakePoolFetcher(pools.clone())),
+                None,
+                Arc::new(FakeGasPriceEstimator::default()),
+                Arc::new(BaseTokens::new(intermediate, &[])),
+                intermediate,
+                10.into(),
+                default_rate_limiter(),
+                max_split_paths,
+            )
+        };
+        let single_path = estimator(1);
+        let split = estimator(3);
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            let query = Query {
+                sell_token: token_a,
+                buy_token: token_b,
+                in_amount: 50_000.into(),
+                kind,
+            };
+            let single_path = single_estimate(&single_path, &query).await.unwrap();
+            let split = single_estimate(&split, &query).await.unwrap();
+
+            assert_eq!(single_path.gas, estimate_gas(3));
+            assert_eq!(split.gas, estimate_split_gas([2, 3]));
+            match kind {
+                OrderKind::Sell => assert!(split.out_amount > single_path.out_amount),
+                OrderKind::Buy => assert!(split.out_amount < single_path.out_amount),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn price_estimate_uses_uniswap_v3_pools() {
+        struct FakeUniswapV3PoolFetcher(Vec<PoolInfo>);
+        #[async_trait::async_trait]
+        impl UniswapV3PoolFetching for FakeUniswapV3PoolFetcher {
+            async fn fetch(&self, _: &HashSet<TokenPair>) -> Result<Vec<PoolInfo>> {
+                Ok(self.0.clone())
+            }
+        }
+
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let token = |id| Token {
+            id,
+            symbol: Default::default(),
+            decimals: 18,
+        };
+        let liquidity = 10i128.pow(18);
+        let pool = PoolInfo {
+            address: H160::from_low_u64_be(3),
+            tokens: vec![token(token_a), token(token_b)],
+            state: PoolState {
+                // A price of 1 with liquidity in the tick range [-600, 600].
+                sqrt_price: U256::one() << 96,
+                liquidity: U256::from(liquidity as u128),
+                tick: 0.into(),
+                liquidity_net: vec![
+                    ((-600).into(), liquidity.into()),
+                    (600.into(), (-liquidity).into()),
+                ],
+                fee: Ratio::new(3, 1000),
+            },
+            gas_stats: PoolStats {
+                mean_gas: 300_000.into(),
+            },
+        };
+
+        let estimator = BaselinePriceEstimator::new(
+            Arc::new(FakePoolFetcher::default()),
+            Some(Arc::new(FakeUniswapV3PoolFetcher(vec![pool]))),
+            Arc::new(FakeGasPriceEstimator::default()),
+            Arc::new(BaseTokens::new(token_a, &[])),
+            token_a,
+            10u128.pow(15).into(),
+            default_rate_limiter(),
+            1,
+        );
+
+        let estimate = single_estimate(
+            &estimator,
+            &Query {
+                sell_token: token_a,
+                buy_token: token_b,
+                in_amount: 10u128.pow(15).into(),
+                kind: OrderKind::Sell,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(estimate.out_amount, 996_006_981_039_903u128.into());
+        assert_eq!(estimate.gas, estimate_gas(2));
+    }
+
     #[tokio::test]
     async fn price_estimate_takes_gas_costs_into_account() {
         let native = H160::from_low_u64_be(0);
@@ -632,11 +874,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(native, &[intermediate]));
         let estimator = BaselinePriceEstimator::new(
             pool_fetcher,
+            None,
             gas_estimator.clone(),
             base_tokens,
             native,
             1_000_000_000.into(),
             default_rate_limiter(),
+            1,
         );
 
         // Uses 1 hop because high gas price doesn't make the intermediate hop worth it.
@@ -711,11 +955,13 @@ mod tests {
         let base_tokens = Arc::new(BaseTokens::new(token_b, &[]));
         let estimator = BaselinePriceEstimator::new(
             Arc::new(FakePoolFetcher::default()),
+            None,
             Arc::new(FakeGasPriceEstimator::default()),
             base_tokens,
             token_a,
             10u128.pow(18).into(),
             default_rate_limiter(),
+            1,
         );
 
         let gas_price = 1000000000000000.0;
@@ -54,6 +54,9 @@ pub static GAS_PER_ZEROEX_ORDER: u64 = 66_358;
 // estimated with https://dune.com/queries/639857
 pub static GAS_PER_BALANCER_SWAP: u64 = 88_892;
 
+/// Approximate gas used per CurveExchangeInteraction on a plain pool.
+pub static GAS_PER_CURVE_SWAP: u64 = 130_000;
+
 /// Median gas used per UnwrapWethInteraction.
 // estimated with https://dune.com/queries/640753
 pub static GAS_PER_WETH_UNWRAP: u64 = 9_223;
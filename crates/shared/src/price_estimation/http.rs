@@ -22,9 +22,7 @@ use crate::{
             pools::common::compute_scaling_rate, BalancerPoolFetcher, BalancerPoolFetching,
         },
         uniswap_v2::{pool_cache::PoolCache, pool_fetching::PoolFetching},
-        uniswap_v3::pool_fetching::{
-            AutoUpdatingUniswapV3PoolFetcher, PoolFetching as UniswapV3PoolFetching,
-        },
+        uniswap_v3::pool_fetching::PoolFetching as UniswapV3PoolFetching,
     },
     token_info::TokenInfoFetching,
 };
@@ -48,7 +46,7 @@ pub struct HttpPriceEstimator {
     >,
     pools: Arc<PoolCache>,
     balancer_pools: Option<Arc<BalancerPoolFetcher>>,
-    uniswap_v3_pools: Option<Arc<AutoUpdatingUniswapV3PoolFetcher>>,
+    uniswap_v3_pools: Option<Arc<dyn UniswapV3PoolFetching>>,
     token_info: Arc<dyn TokenInfoFetching>,
     gas_info: Arc<dyn GasPriceEstimating>,
     native_token: H160,
@@ -63,7 +61,7 @@ impl HttpPriceEstimator {
         api: Arc<dyn HttpSolverApi>,
         pools: Arc<PoolCache>,
         balancer_pools: Option<Arc<BalancerPoolFetcher>>,
-        uniswap_v3_pools: Option<Arc<AutoUpdatingUniswapV3PoolFetcher>>,
+        uniswap_v3_pools: Option<Arc<dyn UniswapV3PoolFetching>>,
         token_info: Arc<dyn TokenInfoFetching>,
         gas_info: Arc<dyn GasPriceEstimating>,
         native_token: H160,
@@ -335,8 +333,38 @@ impl HttpPriceEstimator {
                     mandatory: false,
                 })
             });
+        // Composable stable pools use the same math as regular stable pools,
+        // with scaling rates that account for token rates. Linear pools don't
+        // have an HTTP solver model, so they are not included.
+        let composable_stable =
+            pools
+                .composable_stable_pools
+                .into_iter()
+                .map(|pool| -> Result<AmmModel> {
+                    Ok(AmmModel {
+                        parameters: AmmParameters::Stable(StablePoolParameters {
+                            reserves: pool
+                                .reserves
+                                .iter()
+                                .map(|(token, state)| (*token, state.common.balance))
+                                .collect(),
+                            scaling_rates: pool
+                                .reserves
+                                .iter()
+                                .map(|(token, state)| Ok((*token, state.scaling_rate()?)))
+                                .collect::<Result<_>>()
+                                .with_context(|| {
+                                    "convert composable stable pool to solver model".to_string()
+                                })?,
+                            amplification_parameter: pool.amplification_parameter.as_big_rational(),
+                        }),
+                        fee: pool.common.swap_fee.into(),
+                        cost: gas_model.balancer_cost(),
+                        mandatory: false,
+                    })
+                });
         let mut models = Vec::from_iter(weighted);
-        for stable in stable {
+        for stable in stable.chain(composable_stable) {
             models.push(stable?);
         }
         Ok(models)
@@ -383,6 +411,7 @@ mod tests {
     use crate::sources::balancer_v2::pool_fetching::BalancerContracts;
     use crate::sources::balancer_v2::BalancerFactoryKind;
     use crate::sources::uniswap_v2;
+    use crate::sources::uniswap_v3::pool_fetching::AutoUpdatingUniswapV3PoolFetcher;
     use crate::token_info::TokenInfoFetcher;
     use crate::transport::http::HttpTransport;
     use crate::Web3;
@@ -2,6 +2,7 @@
 
 pub mod balancer_v2;
 pub mod baoswap;
+pub mod curve;
 pub mod honeyswap;
 pub mod sushiswap;
 pub mod swapr;
@@ -32,6 +33,7 @@ pub enum BaselineSource {
     Swapr,
     ZeroEx,
     UniswapV3,
+    Curve,
 }
 
 pub fn defaults_for_chain(chain_id: u64) -> Result<Vec<BaselineSource>> {
@@ -80,6 +82,7 @@ pub async fn uniswap_like_liquidity_sources(
             BaselineSource::BalancerV2 => continue,
             BaselineSource::ZeroEx => continue,
             BaselineSource::UniswapV3 => continue,
+            BaselineSource::Curve => continue,
         };
 
         liquidity_sources.insert(*source, liquidity_source);
@@ -164,6 +164,8 @@ pub enum PoolType {
     Stable,
     Weighted,
     LiquidityBootstrapping,
+    ComposableStable,
+    AaveLinear,
 }
 
 /// Token data for pools.
@@ -192,6 +194,8 @@ mod pools_query {
                         "Stable",
                         "Weighted",
                         "LiquidityBootstrapping",
+                        "ComposableStable",
+                        "AaveLinear",
                     ]
                 }
             ) {
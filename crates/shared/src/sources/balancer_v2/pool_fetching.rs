@@ -17,7 +17,7 @@ use super::{
     pool_init::PoolInitializing,
     pools::{
         common::{self, PoolInfoFetcher},
-        stable, weighted, FactoryIndexing, Pool, PoolIndexing, PoolKind,
+        composable_stable, linear, stable, weighted, FactoryIndexing, Pool, PoolIndexing, PoolKind,
     },
     swap::fixed_point::Bfp,
 };
@@ -31,12 +31,13 @@ use crate::{
 use anyhow::Result;
 use clap::ArgEnum;
 use contracts::{
+    BalancerV2AaveLinearPoolFactory, BalancerV2ComposableStablePoolFactory,
     BalancerV2LiquidityBootstrappingPoolFactory,
     BalancerV2NoProtocolFeeLiquidityBootstrappingPoolFactory, BalancerV2StablePoolFactory,
     BalancerV2StablePoolFactoryV2, BalancerV2Vault, BalancerV2WeightedPool2TokensFactory,
     BalancerV2WeightedPoolFactory,
 };
-use ethcontract::{dyns::DynInstance, Instance, H160, H256};
+use ethcontract::{dyns::DynInstance, Instance, H160, H256, U256};
 use model::TokenPair;
 use reqwest::Client;
 use std::{
@@ -44,14 +45,14 @@ use std::{
     sync::Arc,
 };
 
-pub use common::TokenState;
+pub use common::{ScaledTokenState, TokenState};
 pub use stable::AmplificationParameter;
 pub use weighted::TokenState as WeightedTokenState;
 pub trait BalancerPoolEvaluating {
     fn properties(&self) -> CommonPoolState;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CommonPoolState {
     pub id: H256,
     pub address: H160,
@@ -101,10 +102,71 @@ impl StablePool {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComposableStablePool {
+    pub common: CommonPoolState,
+    /// The pool reserves, excluding the pool's own BPT.
+    pub reserves: HashMap<H160, ScaledTokenState>,
+    pub amplification_parameter: AmplificationParameter,
+}
+
+impl ComposableStablePool {
+    pub fn new_unpaused(pool_id: H256, state: composable_stable::PoolState) -> Self {
+        ComposableStablePool {
+            common: CommonPoolState {
+                id: pool_id,
+                address: pool_address_from_id(pool_id),
+                swap_fee: state.swap_fee,
+                paused: false,
+            },
+            reserves: state.tokens.into_iter().collect(),
+            amplification_parameter: state.amplification_parameter,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearPool {
+    pub common: CommonPoolState,
+    /// The pool reserves, including the pool's own BPT.
+    pub reserves: HashMap<H160, ScaledTokenState>,
+    pub main_token: H160,
+    pub wrapped_token: H160,
+    pub lower_target: U256,
+    pub upper_target: U256,
+    pub virtual_supply: U256,
+}
+
+impl LinearPool {
+    pub fn new_unpaused(pool_id: H256, state: linear::PoolState) -> Self {
+        LinearPool {
+            common: CommonPoolState {
+                id: pool_id,
+                address: pool_address_from_id(pool_id),
+                swap_fee: state.swap_fee,
+                paused: false,
+            },
+            reserves: state.tokens.into_iter().collect(),
+            main_token: state.main_token,
+            wrapped_token: state.wrapped_token,
+            lower_target: state.lower_target,
+            upper_target: state.upper_target,
+            virtual_supply: state.virtual_supply,
+        }
+    }
+
+    /// Returns the address of the pool's BPT token.
+    pub fn bpt(&self) -> H160 {
+        self.common.address
+    }
+}
+
 #[derive(Default)]
 pub struct FetchedBalancerPools {
     pub stable_pools: Vec<StablePool>,
     pub weighted_pools: Vec<WeightedPool>,
+    pub composable_stable_pools: Vec<ComposableStablePool>,
+    pub linear_pools: Vec<LinearPool>,
 }
 
 impl FetchedBalancerPools {
@@ -120,6 +182,16 @@ impl FetchedBalancerPools {
                 .iter()
                 .flat_map(|pool| pool.reserves.keys().copied()),
         );
+        tokens.extend(
+            self.composable_stable_pools
+                .iter()
+                .flat_map(|pool| pool.reserves.keys().copied()),
+        );
+        tokens.extend(
+            self.linear_pools
+                .iter()
+                .flat_map(|pool| pool.reserves.keys().copied()),
+        );
         tokens
     }
 }
@@ -153,6 +225,8 @@ pub enum BalancerFactoryKind {
     StableV2,
     LiquidityBootstrapping,
     NoProtocolFeeLiquidityBootstrapping,
+    ComposableStable,
+    AaveLinear,
 }
 
 impl BalancerFactoryKind {
@@ -204,6 +278,10 @@ impl BalancerContracts {
                 BalancerFactoryKind::NoProtocolFeeLiquidityBootstrapping => {
                     instance!(BalancerV2NoProtocolFeeLiquidityBootstrappingPoolFactory)
                 }
+                BalancerFactoryKind::ComposableStable => {
+                    instance!(BalancerV2ComposableStablePoolFactory)
+                }
+                BalancerFactoryKind::AaveLinear => instance!(BalancerV2AaveLinearPoolFactory),
             };
 
             factories.insert(kind, instance);
@@ -275,6 +353,12 @@ impl BalancerPoolFetching for BalancerPoolFetcher {
                     PoolKind::Stable(state) => fetched_pools
                         .stable_pools
                         .push(StablePool::new_unpaused(pool.id, state)),
+                    PoolKind::ComposableStable(state) => fetched_pools
+                        .composable_stable_pools
+                        .push(ComposableStablePool::new_unpaused(pool.id, state)),
+                    PoolKind::Linear(state) => fetched_pools
+                        .linear_pools
+                        .push(LinearPool::new_unpaused(pool.id, state)),
                 }
                 fetched_pools
             },
@@ -337,6 +421,12 @@ async fn create_aggregate_pool_fetcher(
                     instance
                 )
             }
+            BalancerFactoryKind::ComposableStable => {
+                registry!(BalancerV2ComposableStablePoolFactory, instance)
+            }
+            BalancerFactoryKind::AaveLinear => {
+                registry!(BalancerV2AaveLinearPoolFactory, instance)
+            }
         };
         fetchers.push(registry);
     }
@@ -548,6 +638,23 @@ mod tests {
                         assert_eq!(token_state.scaling_exponent, 18 - token.decimals);
                     }
                 }
+                PoolKind::ComposableStable(state) => {
+                    // The subgraph includes the pool's BPT in its tokens,
+                    // which we don't keep in the pool state.
+                    for token in &subgraph_pool.tokens {
+                        if token.address == subgraph_pool.address {
+                            continue;
+                        }
+                        let token_state = &state.tokens[&token.address];
+                        assert_eq!(token_state.common.scaling_exponent, 18 - token.decimals);
+                    }
+                }
+                PoolKind::Linear(state) => {
+                    for token in &subgraph_pool.tokens {
+                        let token_state = &state.tokens[&token.address];
+                        assert_eq!(token_state.common.scaling_exponent, 18 - token.decimals);
+                    }
+                }
             };
         }
         tracing::warn!(?unknown_pools);
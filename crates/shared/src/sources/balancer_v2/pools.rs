@@ -8,6 +8,8 @@
 //! types by just implementing the required `BalancerFactory` trait.
 
 pub mod common;
+pub mod composable_stable;
+pub mod linear;
 pub mod liquidity_bootstrapping;
 pub mod no_protocol_fee_liquidity_bootstrapping;
 pub mod stable;
@@ -34,6 +36,8 @@ pub struct Pool {
 pub enum PoolKind {
     Weighted(weighted::PoolState),
     Stable(stable::PoolState),
+    ComposableStable(composable_stable::PoolState),
+    Linear(linear::PoolState),
 }
 
 macro_rules! impl_from_state {
@@ -48,6 +52,8 @@ macro_rules! impl_from_state {
 
 impl_from_state!(weighted::PoolState, Weighted);
 impl_from_state!(stable::PoolState, Stable);
+impl_from_state!(composable_stable::PoolState, ComposableStable);
+impl_from_state!(linear::PoolState, Linear);
 
 #[derive(Clone, Debug, PartialEq)]
 /// Balancer pool status.
@@ -273,6 +273,56 @@ pub struct TokenState {
     pub scaling_exponent: u8,
 }
 
+/// Token state for pools whose scaling factors include a token rate on top of
+/// the decimal scaling, such as wrapped tokens in linear pools or tokens with
+/// rate providers in composable stable pools.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScaledTokenState {
+    pub common: TokenState,
+    pub scaling_factor: Bfp,
+}
+
+impl ScaledTokenState {
+    /// Returns the scaling rate that corresponds to the token's scaling
+    /// factor. This is the generalization of `compute_scaling_rate` for tokens
+    /// with a rate.
+    pub fn scaling_rate(&self) -> Result<U256> {
+        U256::exp10(36)
+            .checked_div(self.scaling_factor.as_uint256())
+            .ok_or_else(|| anyhow!("zero scaling factor"))
+    }
+}
+
+/// Combines common token states with the scaling factors returned by a pool's
+/// `getScalingFactors` method. The scaling factors are expected to be in the
+/// same order as the pool's tokens.
+pub fn scaled_token_states(
+    pool: &PoolInfo,
+    mut tokens: BTreeMap<H160, TokenState>,
+    scaling_factors: Vec<U256>,
+) -> Result<BTreeMap<H160, ScaledTokenState>> {
+    ensure!(
+        pool.tokens.len() == scaling_factors.len(),
+        "pool scaling factor count mismatch"
+    );
+    pool.tokens
+        .iter()
+        .zip(scaling_factors)
+        .map(|(&address, scaling_factor)| {
+            let common = tokens
+                .remove(&address)
+                .ok_or_else(|| anyhow!("missing token state for {:?}", address))?;
+            Ok((
+                address,
+                ScaledTokenState {
+                    common,
+                    scaling_factor: Bfp::from_wei(scaling_factor),
+                },
+            ))
+        })
+        .collect()
+}
+
 /// Compute the scaling rate from a Balancer pool's scaling exponent.
 ///
 /// This method returns an error on any arithmetic underflow when computing the
@@ -926,4 +976,70 @@ mod tests {
         // Tokens with invalid number of decimals, i.e. greater than 18
         assert!(compute_scaling_rate(42).is_err());
     }
+
+    #[test]
+    fn compute_scaling_rates_with_token_rates() {
+        let token = |scaling_factor| ScaledTokenState {
+            common: TokenState {
+                balance: 0.into(),
+                scaling_exponent: 12,
+            },
+            scaling_factor,
+        };
+
+        // Tokens with 6 decimals and no rate
+        assert_eq!(
+            token(bfp!("1000000000000.0")).scaling_rate().unwrap(),
+            U256::from(1_000_000),
+        );
+        // Tokens with 6 decimals and a rate of 1.25
+        assert_eq!(
+            token(bfp!("1250000000000.0")).scaling_rate().unwrap(),
+            U256::from(800_000),
+        );
+
+        assert!(token(Bfp::zero()).scaling_rate().is_err());
+    }
+
+    #[test]
+    fn combines_token_states_with_scaling_factors() {
+        let pool = PoolInfo {
+            tokens: vec![H160([1; 20]), H160([2; 20])],
+            scaling_exponents: vec![0, 12],
+            ..Default::default()
+        };
+        let tokens = btreemap! {
+            H160([1; 20]) => TokenState {
+                balance: 1.into(),
+                scaling_exponent: 0,
+            },
+            H160([2; 20]) => TokenState {
+                balance: 2.into(),
+                scaling_exponent: 12,
+            },
+        };
+
+        assert_eq!(
+            scaled_token_states(
+                &pool,
+                tokens.clone(),
+                vec![
+                    bfp!("1.1").as_uint256(),
+                    bfp!("1000000000000.0").as_uint256()
+                ],
+            )
+            .unwrap(),
+            btreemap! {
+                H160([1; 20]) => ScaledTokenState {
+                    common: tokens[&H160([1; 20])].clone(),
+                    scaling_factor: bfp!("1.1"),
+                },
+                H160([2; 20]) => ScaledTokenState {
+                    common: tokens[&H160([2; 20])].clone(),
+                    scaling_factor: bfp!("1000000000000.0"),
+                },
+            }
+        );
+        assert!(scaled_token_states(&pool, tokens, vec![bfp!("1.0").as_uint256()]).is_err());
+    }
 }
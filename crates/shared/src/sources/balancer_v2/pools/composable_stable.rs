@@ -0,0 +1,227 @@
+//! Module implementing composable stable pool specific indexing logic.
+//!
+//! Composable stable pools register their own BPT as one of the pool tokens in
+//! the Vault. This allows joins and exits through swaps, but the BPT is not
+//! part of the stable swap invariant, so it is removed from the pool state.
+
+use super::{common, stable::AmplificationParameter, FactoryIndexing, PoolIndexing};
+use crate::{
+    sources::balancer_v2::{
+        graph_api::{PoolData, PoolType},
+        swap::fixed_point::Bfp,
+    },
+    Web3CallBatch,
+};
+use anyhow::Result;
+use contracts::{BalancerV2ComposableStablePool, BalancerV2ComposableStablePoolFactory};
+use ethcontract::{BlockId, H160};
+use futures::{future::BoxFuture, FutureExt as _};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PoolInfo {
+    pub common: common::PoolInfo,
+}
+
+impl PoolIndexing for PoolInfo {
+    fn from_graph_data(pool: &PoolData, block_created: u64) -> Result<Self> {
+        Ok(PoolInfo {
+            common: common::PoolInfo::for_type(PoolType::ComposableStable, pool, block_created)?,
+        })
+    }
+
+    fn common(&self) -> &common::PoolInfo {
+        &self.common
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolState {
+    /// The pool tokens, excluding the pool's own BPT.
+    pub tokens: BTreeMap<H160, common::ScaledTokenState>,
+    pub swap_fee: Bfp,
+    pub amplification_parameter: AmplificationParameter,
+}
+
+#[async_trait::async_trait]
+impl FactoryIndexing for BalancerV2ComposableStablePoolFactory {
+    type PoolInfo = PoolInfo;
+    type PoolState = PoolState;
+
+    async fn specialize_pool_info(&self, pool: common::PoolInfo) -> Result<Self::PoolInfo> {
+        Ok(PoolInfo { common: pool })
+    }
+
+    fn fetch_pool_state(
+        &self,
+        pool_info: &Self::PoolInfo,
+        common_pool_state: BoxFuture<'static, common::PoolState>,
+        batch: &mut Web3CallBatch,
+        block: BlockId,
+    ) -> BoxFuture<'static, Result<Option<Self::PoolState>>> {
+        let pool_contract = BalancerV2ComposableStablePool::at(
+            &self.raw_instance().web3(),
+            pool_info.common.address,
+        );
+
+        let amplification_parameter = pool_contract
+            .get_amplification_parameter()
+            .block(block)
+            .batch_call(batch);
+        let scaling_factors = pool_contract
+            .get_scaling_factors()
+            .block(block)
+            .batch_call(batch);
+
+        let pool_info = pool_info.clone();
+        async move {
+            let common = common_pool_state.await;
+            let amplification_parameter = {
+                let (factor, _, precision) = amplification_parameter.await?;
+                AmplificationParameter::new(factor, precision)?
+            };
+            let mut tokens = common::scaled_token_states(
+                &pool_info.common,
+                common.tokens,
+                scaling_factors.await?,
+            )?;
+            // The BPT of a composable stable pool is the pool contract itself.
+            tokens.remove(&pool_info.common.address);
+
+            Ok(Some(PoolState {
+                tokens,
+                swap_fee: common.swap_fee,
+                amplification_parameter,
+            }))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::balancer_v2::graph_api::Token;
+    use ethcontract::{H160, H256};
+    use ethcontract_mock::Mock;
+    use futures::future;
+    use maplit::btreemap;
+
+    #[tokio::test]
+    async fn fetch_pool_state() {
+        let mock = Mock::new(42);
+        let web3 = mock.web3();
+
+        let pool = mock.deploy(BalancerV2ComposableStablePool::raw_contract().abi.clone());
+        let tokens = btreemap! {
+            H160([1; 20]) => common::TokenState {
+                balance: bfp!("1000.0").as_uint256(),
+                scaling_exponent: 0,
+            },
+            H160([2; 20]) => common::TokenState {
+                balance: 15_000_000.into(),
+                scaling_exponent: 12,
+            },
+            pool.address() => common::TokenState {
+                balance: bfp!("2596148429267413.814265248164610048").as_uint256(),
+                scaling_exponent: 0,
+            },
+        };
+        let scaling_factors = btreemap! {
+            H160([1; 20]) => bfp!("1.05"),
+            H160([2; 20]) => bfp!("1000000000000.0"),
+            pool.address() => Bfp::one(),
+        };
+        let swap_fee = bfp!("0.0001");
+        let amplification_parameter = AmplificationParameter::new(200.into(), 1000.into()).unwrap();
+
+        pool.expect_call(
+            BalancerV2ComposableStablePool::signatures().get_amplification_parameter(),
+        )
+        .returns((200.into(), false, 1000.into()));
+        pool.expect_call(BalancerV2ComposableStablePool::signatures().get_scaling_factors())
+            .returns(
+                scaling_factors
+                    .values()
+                    .map(|factor| factor.as_uint256())
+                    .collect(),
+            );
+
+        let factory = dummy_contract!(BalancerV2ComposableStablePoolFactory, H160::default());
+        let pool_info = PoolInfo {
+            common: common::PoolInfo {
+                id: H256([0x90; 32]),
+                address: pool.address(),
+                tokens: tokens.keys().copied().collect(),
+                scaling_exponents: tokens
+                    .values()
+                    .map(|token| token.scaling_exponent)
+                    .collect(),
+                block_created: 1337,
+            },
+        };
+        let common_pool_state = common::PoolState {
+            paused: false,
+            swap_fee,
+            tokens: tokens.clone(),
+        };
+
+        let pool_state = {
+            let mut batch = Web3CallBatch::new(web3.transport().clone());
+            let block = web3.eth().block_number().await.unwrap();
+
+            let pool_state = factory.fetch_pool_state(
+                &pool_info,
+                future::ready(common_pool_state).boxed(),
+                &mut batch,
+                block.into(),
+            );
+
+            batch.execute_all(100).await;
+            pool_state.await.unwrap()
+        };
+
+        assert_eq!(
+            pool_state,
+            Some(PoolState {
+                tokens: btreemap! {
+                    H160([1; 20]) => common::ScaledTokenState {
+                        common: tokens[&H160([1; 20])].clone(),
+                        scaling_factor: bfp!("1.05"),
+                    },
+                    H160([2; 20]) => common::ScaledTokenState {
+                        common: tokens[&H160([2; 20])].clone(),
+                        scaling_factor: bfp!("1000000000000.0"),
+                    },
+                },
+                swap_fee,
+                amplification_parameter,
+            })
+        );
+    }
+
+    #[test]
+    fn errors_when_converting_wrong_pool_type() {
+        let pool = PoolData {
+            pool_type: PoolType::Stable,
+            id: H256([2; 32]),
+            address: H160([1; 20]),
+            factory: H160([0xfa; 20]),
+            swap_enabled: true,
+            tokens: vec![
+                Token {
+                    address: H160([0x11; 20]),
+                    decimals: 1,
+                    weight: None,
+                },
+                Token {
+                    address: H160([0x22; 20]),
+                    decimals: 2,
+                    weight: None,
+                },
+            ],
+        };
+
+        assert!(PoolInfo::from_graph_data(&pool, 42).is_err());
+    }
+}
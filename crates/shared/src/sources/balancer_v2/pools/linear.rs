@@ -0,0 +1,269 @@
+//! Module implementing linear pool specific indexing logic.
+//!
+//! Linear pools hold a main token, a wrapped version of it (for example an Aave
+//! aToken) and their own pre-minted BPT. Unlike composable stable pools, the
+//! BPT is a regular swap token for linear pools, so it is kept in the state.
+
+use super::{common, FactoryIndexing, PoolIndexing};
+use crate::{
+    sources::balancer_v2::{
+        graph_api::{PoolData, PoolType},
+        swap::fixed_point::Bfp,
+    },
+    Web3CallBatch,
+};
+use anyhow::{anyhow, Result};
+use contracts::{BalancerV2AaveLinearPoolFactory, BalancerV2LinearPool};
+use ethcontract::{BlockId, H160, U256};
+use futures::{future::BoxFuture, FutureExt as _};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PoolInfo {
+    pub common: common::PoolInfo,
+}
+
+impl PoolIndexing for PoolInfo {
+    fn from_graph_data(pool: &PoolData, block_created: u64) -> Result<Self> {
+        Ok(PoolInfo {
+            common: common::PoolInfo::for_type(PoolType::AaveLinear, pool, block_created)?,
+        })
+    }
+
+    fn common(&self) -> &common::PoolInfo {
+        &self.common
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolState {
+    /// The pool tokens, including the pool's own BPT.
+    pub tokens: BTreeMap<H160, common::ScaledTokenState>,
+    pub swap_fee: Bfp,
+    pub main_token: H160,
+    pub wrapped_token: H160,
+    /// The lower and upper targets of the main token balance, in main token
+    /// units.
+    pub lower_target: U256,
+    pub upper_target: U256,
+    /// The circulating BPT supply. This differs from the BPT's total supply
+    /// since the Vault holds the pre-minted BPT that is not in circulation.
+    pub virtual_supply: U256,
+}
+
+impl PoolState {
+    /// Returns the address of the pool's BPT token.
+    pub fn bpt(&self) -> Option<H160> {
+        self.tokens
+            .keys()
+            .copied()
+            .find(|&token| token != self.main_token && token != self.wrapped_token)
+    }
+}
+
+#[async_trait::async_trait]
+impl FactoryIndexing for BalancerV2AaveLinearPoolFactory {
+    type PoolInfo = PoolInfo;
+    type PoolState = PoolState;
+
+    async fn specialize_pool_info(&self, pool: common::PoolInfo) -> Result<Self::PoolInfo> {
+        Ok(PoolInfo { common: pool })
+    }
+
+    fn fetch_pool_state(
+        &self,
+        pool_info: &Self::PoolInfo,
+        common_pool_state: BoxFuture<'static, common::PoolState>,
+        batch: &mut Web3CallBatch,
+        block: BlockId,
+    ) -> BoxFuture<'static, Result<Option<Self::PoolState>>> {
+        let pool_contract =
+            BalancerV2LinearPool::at(&self.raw_instance().web3(), pool_info.common.address);
+
+        let main_index = pool_contract
+            .get_main_index()
+            .block(block)
+            .batch_call(batch);
+        let wrapped_index = pool_contract
+            .get_wrapped_index()
+            .block(block)
+            .batch_call(batch);
+        let targets = pool_contract.get_targets().block(block).batch_call(batch);
+        let virtual_supply = pool_contract
+            .get_virtual_supply()
+            .block(block)
+            .batch_call(batch);
+        let scaling_factors = pool_contract
+            .get_scaling_factors()
+            .block(block)
+            .batch_call(batch);
+
+        let pool_info = pool_info.clone();
+        async move {
+            let common = common_pool_state.await;
+            let token_at = |index: U256| {
+                pool_info
+                    .common
+                    .tokens
+                    .get(index.as_usize())
+                    .copied()
+                    .ok_or_else(|| anyhow!("linear pool token index {} out of bounds", index))
+            };
+            let main_token = token_at(main_index.await?)?;
+            let wrapped_token = token_at(wrapped_index.await?)?;
+            let (lower_target, upper_target) = targets.await?;
+
+            Ok(Some(PoolState {
+                tokens: common::scaled_token_states(
+                    &pool_info.common,
+                    common.tokens,
+                    scaling_factors.await?,
+                )?,
+                swap_fee: common.swap_fee,
+                main_token,
+                wrapped_token,
+                lower_target,
+                upper_target,
+                virtual_supply: virtual_supply.await?,
+            }))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::balancer_v2::graph_api::Token;
+    use ethcontract::H256;
+    use ethcontract_mock::Mock;
+    use futures::future;
+    use maplit::btreemap;
+
+    #[tokio::test]
+    async fn fetch_pool_state() {
+        let mock = Mock::new(42);
+        let web3 = mock.web3();
+
+        let pool = mock.deploy(BalancerV2LinearPool::raw_contract().abi.clone());
+        let tokens = btreemap! {
+            H160([1; 20]) => common::TokenState {
+                balance: 1_000_000_000.into(),
+                scaling_exponent: 12,
+            },
+            H160([2; 20]) => common::TokenState {
+                balance: 4_000_000_000.into(),
+                scaling_exponent: 12,
+            },
+            pool.address() => common::TokenState {
+                balance: bfp!("5192296858534827.628530496329220096").as_uint256(),
+                scaling_exponent: 0,
+            },
+        };
+        let scaling_factors = btreemap! {
+            H160([1; 20]) => bfp!("1000000000000.0"),
+            H160([2; 20]) => bfp!("1100000000000.0"),
+            pool.address() => Bfp::one(),
+        };
+        let token_index =
+            |token: H160| -> U256 { tokens.keys().position(|&t| t == token).unwrap().into() };
+
+        pool.expect_call(BalancerV2LinearPool::signatures().get_main_index())
+            .returns(token_index(H160([1; 20])));
+        pool.expect_call(BalancerV2LinearPool::signatures().get_wrapped_index())
+            .returns(token_index(H160([2; 20])));
+        pool.expect_call(BalancerV2LinearPool::signatures().get_targets())
+            .returns((2_000_000_000.into(), 3_000_000_000.into()));
+        pool.expect_call(BalancerV2LinearPool::signatures().get_virtual_supply())
+            .returns(bfp!("5400.0").as_uint256());
+        pool.expect_call(BalancerV2LinearPool::signatures().get_scaling_factors())
+            .returns(
+                scaling_factors
+                    .values()
+                    .map(|factor| factor.as_uint256())
+                    .collect(),
+            );
+
+        let factory = dummy_contract!(BalancerV2AaveLinearPoolFactory, H160::default());
+        let pool_info = PoolInfo {
+            common: common::PoolInfo {
+                id: H256([0x90; 32]),
+                address: pool.address(),
+                tokens: tokens.keys().copied().collect(),
+                scaling_exponents: tokens
+                    .values()
+                    .map(|token| token.scaling_exponent)
+                    .collect(),
+                block_created: 1337,
+            },
+        };
+        let common_pool_state = common::PoolState {
+            paused: false,
+            swap_fee: bfp!("0.0002"),
+            tokens: tokens.clone(),
+        };
+
+        let pool_state = {
+            let mut batch = Web3CallBatch::new(web3.transport().clone());
+            let block = web3.eth().block_number().await.unwrap();
+
+            let pool_state = factory.fetch_pool_state(
+                &pool_info,
+                future::ready(common_pool_state).boxed(),
+                &mut batch,
+                block.into(),
+            );
+
+            batch.execute_all(100).await;
+            pool_state.await.unwrap().unwrap()
+        };
+
+        assert_eq!(
+            pool_state,
+            PoolState {
+                tokens: tokens
+                    .iter()
+                    .map(|(&address, token)| (
+                        address,
+                        common::ScaledTokenState {
+                            common: token.clone(),
+                            scaling_factor: scaling_factors[&address],
+                        }
+                    ))
+                    .collect(),
+                swap_fee: bfp!("0.0002"),
+                main_token: H160([1; 20]),
+                wrapped_token: H160([2; 20]),
+                lower_target: 2_000_000_000.into(),
+                upper_target: 3_000_000_000.into(),
+                virtual_supply: bfp!("5400.0").as_uint256(),
+            }
+        );
+        assert_eq!(pool_state.bpt(), Some(pool.address()));
+    }
+
+    #[test]
+    fn errors_when_converting_wrong_pool_type() {
+        let pool = PoolData {
+            pool_type: PoolType::Stable,
+            id: H256([2; 32]),
+            address: H160([1; 20]),
+            factory: H160([0xfa; 20]),
+            swap_enabled: true,
+            tokens: vec![
+                Token {
+                    address: H160([0x11; 20]),
+                    decimals: 1,
+                    weight: None,
+                },
+                Token {
+                    address: H160([0x22; 20]),
+                    decimals: 2,
+                    weight: None,
+                },
+            ],
+        };
+
+        assert!(PoolInfo::from_graph_data(&pool, 42).is_err());
+    }
+}
@@ -52,6 +52,14 @@ impl AmplificationParameter {
         Ok(Self { factor, precision })
     }
 
+    pub fn factor(&self) -> U256 {
+        self.factor
+    }
+
+    pub fn precision(&self) -> U256 {
+        self.precision
+    }
+
     /// This is the format used to pass into smart contracts.
     pub fn as_u256(&self) -> U256 {
         self.factor * self.precision
@@ -1,7 +1,10 @@
 use crate::{
     baseline_solver::BaselineSolvable,
     sources::balancer_v2::{
-        pool_fetching::{StablePool, TokenState, WeightedPool, WeightedTokenState},
+        pool_fetching::{
+            ComposableStablePool, LinearPool, ScaledTokenState, StablePool, TokenState,
+            WeightedPool, WeightedTokenState,
+        },
         swap::math::BalU256,
     },
 };
@@ -12,6 +15,7 @@ use std::collections::HashMap;
 
 mod error;
 pub mod fixed_point;
+mod linear_math;
 mod math;
 mod stable_math;
 mod weighted_math;
@@ -19,6 +23,9 @@ mod weighted_math;
 const WEIGHTED_SWAP_GAS_COST: usize = 100_000;
 // See https://dune.xyz/queries/219641 for cost of pure stable swaps
 const STABLE_SWAP_GAS_COST: usize = 183_520;
+// Linear pools don't need to iterate to compute the swap amounts, making them
+// cheaper than stable pools.
+const LINEAR_SWAP_GAS_COST: usize = 120_000;
 
 fn add_swap_fee_amount(amount: U256, swap_fee: Bfp) -> Result<U256, Error> {
     // https://github.com/balancer-labs/balancer-v2-monorepo/blob/6c9e24e22d0c46cca6dd15861d3d33da61a60b98/pkg/core/contracts/pools/BasePool.sol#L454-L457
@@ -34,27 +41,47 @@ fn subtract_swap_fee_amount(amount: U256, swap_fee: Bfp) -> Result<U256, Error>
     Ok(amount_without_fees.as_uint256())
 }
 
-impl TokenState {
+/// Conversions between token amounts and their internal representation as
+/// Balancer fixed point numbers.
+pub trait TokenScaling {
+    /// The token balance of the pool.
+    fn balance(&self) -> U256;
+
+    /// Scales the input token amount to the value that is used by the Balancer
+    /// contract to execute math operations.
+    fn upscale(&self, amount: U256) -> Option<Bfp>;
+
+    /// Returns the token amount corresponding to the internal Balancer
+    /// representation for the same amount, rounded up.
+    fn downscale_up(&self, amount: Bfp) -> Result<U256, Error>;
+
+    /// Similar to `downscale_up` above, but rounded down.
+    fn downscale_down(&self, amount: Bfp) -> Option<U256>;
+
     /// Converts the stored balance into its internal representation as a
     /// Balancer fixed point number.
     fn upscaled_balance(&self) -> Option<Bfp> {
-        self.upscale(self.balance)
+        self.upscale(self.balance())
     }
+}
 
+impl TokenState {
     fn scaling_exponent_as_factor(&self) -> Option<U256> {
         U256::from(10).checked_pow(self.scaling_exponent.into())
     }
+}
+
+impl TokenScaling for TokenState {
+    fn balance(&self) -> U256 {
+        self.balance
+    }
 
-    /// Scales the input token amount to the value that is used by the Balancer
-    /// contract to execute math operations.
     fn upscale(&self, amount: U256) -> Option<Bfp> {
         amount
             .checked_mul(self.scaling_exponent_as_factor()?)
             .map(Bfp::from_wei)
     }
 
-    /// Returns the token amount corresponding to the internal Balancer
-    /// representation for the same amount.
     /// Based on contract code here:
     /// https://github.com/balancer-labs/balancer-v2-monorepo/blob/c18ff2686c61a8cbad72cdcfc65e9b11476fdbc3/pkg/pool-utils/contracts/BasePool.sol#L560-L562
     fn downscale_up(&self, amount: Bfp) -> Result<U256, Error> {
@@ -64,7 +91,7 @@ impl TokenState {
         amount.as_uint256().bdiv_up(scaling_factor)
     }
 
-    /// Similar to downscale up above, but rounded down, this is just checked div.
+    /// This is just checked div.
     /// https://github.com/balancer-labs/balancer-v2-monorepo/blob/c18ff2686c61a8cbad72cdcfc65e9b11476fdbc3/pkg/pool-utils/contracts/BasePool.sol#L542-L544
     fn downscale_down(&self, amount: Bfp) -> Option<U256> {
         amount
@@ -73,6 +100,27 @@ impl TokenState {
     }
 }
 
+impl TokenScaling for ScaledTokenState {
+    fn balance(&self) -> U256 {
+        self.common.balance
+    }
+
+    /// Scaling factors that include token rates are not powers of 10, so
+    /// amounts are scaled with fixed point multiplication instead:
+    /// https://github.com/balancer-labs/balancer-v2-monorepo/blob/linear-deployment/pkg/pool-utils/contracts/BasePool.sol#L596-L598
+    fn upscale(&self, amount: U256) -> Option<Bfp> {
+        Bfp::from_wei(amount).mul_down(self.scaling_factor).ok()
+    }
+
+    fn downscale_up(&self, amount: Bfp) -> Result<U256, Error> {
+        Ok(amount.div_up(self.scaling_factor)?.as_uint256())
+    }
+
+    fn downscale_down(&self, amount: Bfp) -> Option<U256> {
+        Some(amount.div_down(self.scaling_factor).ok()?.as_uint256())
+    }
+}
+
 /// Weighted pool data as a reference used for computing input and output amounts.
 pub struct WeightedPoolRef<'a> {
     pub reserves: &'a HashMap<H160, WeightedTokenState>,
@@ -125,8 +173,8 @@ impl BaselineSolvable for WeightedPoolRef<'_> {
 }
 
 /// Stable pool data as a reference used for computing input and output amounts.
-pub struct StablePoolRef<'a> {
-    pub reserves: &'a HashMap<H160, TokenState>,
+pub struct StablePoolRef<'a, T = TokenState> {
+    pub reserves: &'a HashMap<H160, T>,
     pub swap_fee: Bfp,
     pub amplification_parameter: U256,
 }
@@ -138,7 +186,7 @@ struct BalancesWithIndices {
     balances: Vec<Bfp>,
 }
 
-impl StablePoolRef<'_> {
+impl<T: TokenScaling> StablePoolRef<'_, T> {
     // TODO - https://github.com/gnosis/gp-v2-services/pull/1225#discussion_r739033527
     // Based on this discussion, it remains to verify that the non-deterministic ordering
     // of the Balance array returned by this method cannot give rise to any undesired
@@ -172,7 +220,7 @@ impl StablePoolRef<'_> {
     }
 }
 
-impl BaselineSolvable for StablePoolRef<'_> {
+impl<T: TokenScaling> BaselineSolvable for StablePoolRef<'_, T> {
     /// Stable pools use the BaseGeneralPool.sol for these methods, called from within `onSwap`
     /// https://github.com/balancer-labs/balancer-v2-monorepo/blob/589542001aeca5bdc120404874fe0137f6a4c749/pkg/pool-utils/contracts/BaseGeneralPool.sol#L31-L44
 
@@ -235,6 +283,16 @@ impl StablePool {
     }
 }
 
+impl ComposableStablePool {
+    fn as_pool_ref(&self) -> StablePoolRef<ScaledTokenState> {
+        StablePoolRef {
+            reserves: &self.reserves,
+            swap_fee: self.common.swap_fee,
+            amplification_parameter: self.amplification_parameter.as_u256(),
+        }
+    }
+}
+
 impl WeightedPool {
     fn as_pool_ref(&self) -> WeightedPoolRef {
         WeightedPoolRef {
@@ -272,10 +330,154 @@ impl BaselineSolvable for StablePool {
     }
 }
 
+impl BaselineSolvable for ComposableStablePool {
+    fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+        self.as_pool_ref().get_amount_out(out_token, input)
+    }
+
+    fn get_amount_in(&self, in_token: H160, output: (U256, H160)) -> Option<U256> {
+        self.as_pool_ref().get_amount_in(in_token, output)
+    }
+
+    fn gas_cost(&self) -> usize {
+        self.as_pool_ref().gas_cost()
+    }
+}
+
+/// The role of a token in a linear pool.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LinearToken {
+    Main,
+    Wrapped,
+    Bpt,
+}
+
+/// Upscaled linear pool balances used for computing swap amounts.
+struct LinearBalances {
+    main: Bfp,
+    wrapped: Bfp,
+    bpt_supply: Bfp,
+    params: linear_math::Params,
+}
+
+impl LinearPool {
+    fn token_kind(&self, token: H160) -> Option<LinearToken> {
+        if token == self.main_token {
+            Some(LinearToken::Main)
+        } else if token == self.wrapped_token {
+            Some(LinearToken::Wrapped)
+        } else if token == self.bpt() {
+            Some(LinearToken::Bpt)
+        } else {
+            None
+        }
+    }
+
+    fn upscaled_balances(&self) -> Option<LinearBalances> {
+        let main = self.reserves.get(&self.main_token)?;
+        let wrapped = self.reserves.get(&self.wrapped_token)?;
+        Some(LinearBalances {
+            main: main.upscaled_balance()?,
+            wrapped: wrapped.upscaled_balance()?,
+            // The BPT has 18 decimals and no rate, so its virtual supply is
+            // already in its upscaled representation.
+            bpt_supply: Bfp::from_wei(self.virtual_supply),
+            params: linear_math::Params {
+                fee: self.common.swap_fee,
+                lower_target: main.upscale(self.lower_target)?,
+                upper_target: main.upscale(self.upper_target)?,
+            },
+        })
+    }
+}
+
+impl BaselineSolvable for LinearPool {
+    /// Linear pools don't charge the regular swap fee, instead the fee is part
+    /// of the linear math for moving the main token balance out of the target
+    /// range. See `onSwap` in:
+    /// https://github.com/balancer-labs/balancer-v2-monorepo/blob/linear-deployment/pkg/pool-linear/contracts/LinearPool.sol
+    fn get_amount_out(&self, out_token: H160, (in_amount, in_token): (U256, H160)) -> Option<U256> {
+        let in_reserves = self.reserves.get(&in_token)?;
+        let out_reserves = self.reserves.get(&out_token)?;
+        let LinearBalances {
+            main,
+            wrapped,
+            bpt_supply,
+            params,
+        } = self.upscaled_balances()?;
+        let amount = in_reserves.upscale(in_amount)?;
+
+        let out_amount = match (self.token_kind(in_token)?, self.token_kind(out_token)?) {
+            (LinearToken::Main, LinearToken::Wrapped) => {
+                linear_math::calc_wrapped_out_per_main_in(amount, main, &params)
+            }
+            (LinearToken::Main, LinearToken::Bpt) => {
+                linear_math::calc_bpt_out_per_main_in(amount, main, wrapped, bpt_supply, &params)
+            }
+            (LinearToken::Wrapped, LinearToken::Main) => {
+                linear_math::calc_main_out_per_wrapped_in(amount, main, &params)
+            }
+            (LinearToken::Wrapped, LinearToken::Bpt) => {
+                linear_math::calc_bpt_out_per_wrapped_in(amount, main, wrapped, bpt_supply, &params)
+            }
+            (LinearToken::Bpt, LinearToken::Main) => {
+                linear_math::calc_main_out_per_bpt_in(amount, main, wrapped, bpt_supply, &params)
+            }
+            (LinearToken::Bpt, LinearToken::Wrapped) => {
+                linear_math::calc_wrapped_out_per_bpt_in(amount, main, wrapped, bpt_supply, &params)
+            }
+            _ => return None,
+        }
+        .ok()?;
+        out_reserves.downscale_down(out_amount)
+    }
+
+    fn get_amount_in(&self, in_token: H160, (out_amount, out_token): (U256, H160)) -> Option<U256> {
+        let in_reserves = self.reserves.get(&in_token)?;
+        let out_reserves = self.reserves.get(&out_token)?;
+        let LinearBalances {
+            main,
+            wrapped,
+            bpt_supply,
+            params,
+        } = self.upscaled_balances()?;
+        let amount = out_reserves.upscale(out_amount)?;
+
+        let in_amount = match (self.token_kind(in_token)?, self.token_kind(out_token)?) {
+            (LinearToken::Main, LinearToken::Wrapped) => {
+                linear_math::calc_main_in_per_wrapped_out(amount, main, &params)
+            }
+            (LinearToken::Main, LinearToken::Bpt) => {
+                linear_math::calc_main_in_per_bpt_out(amount, main, wrapped, bpt_supply, &params)
+            }
+            (LinearToken::Wrapped, LinearToken::Main) => {
+                linear_math::calc_wrapped_in_per_main_out(amount, main, &params)
+            }
+            (LinearToken::Wrapped, LinearToken::Bpt) => {
+                linear_math::calc_wrapped_in_per_bpt_out(amount, main, wrapped, bpt_supply, &params)
+            }
+            (LinearToken::Bpt, LinearToken::Main) => {
+                linear_math::calc_bpt_in_per_main_out(amount, main, wrapped, bpt_supply, &params)
+            }
+            (LinearToken::Bpt, LinearToken::Wrapped) => {
+                linear_math::calc_bpt_in_per_wrapped_out(amount, main, wrapped, bpt_supply, &params)
+            }
+            _ => return None,
+        }
+        .ok()?;
+        in_reserves.downscale_up(in_amount).ok()
+    }
+
+    fn gas_cost(&self) -> usize {
+        LINEAR_SWAP_GAS_COST
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::sources::balancer_v2::pool_fetching::{AmplificationParameter, CommonPoolState};
+    use maplit::hashmap;
     use std::collections::HashMap;
 
     fn create_weighted_pool_with(
@@ -504,4 +706,130 @@ mod tests {
         let res_out = pool.get_amount_in(usdc, (amount_out, dai));
         assert_eq!(res_out.unwrap(), amount_in.into());
     }
+
+    #[test]
+    fn composable_stable_get_amount_out() {
+        let dai = H160::from_low_u64_be(1);
+        let usdc = H160::from_low_u64_be(2);
+        let tusd = H160::from_low_u64_be(3);
+        let tokens = vec![dai, usdc, tusd];
+        let scaling_exps = vec![0, 12, 12];
+        let amplification_parameter = AmplificationParameter::new(570.into(), 1000.into()).unwrap();
+        let balances = vec![
+            40_927_687_702_846_622_465_144_342_i128.into(),
+            59_448_574_675_062_i128.into(),
+            55_199_308_926_456_i128.into(),
+        ];
+        let swap_fee_percentage = 300_000_000_000_000u128.into();
+        let stable_pool = create_stable_pool_with(
+            tokens,
+            balances,
+            amplification_parameter.clone(),
+            scaling_exps,
+            swap_fee_percentage,
+        );
+        let composable_stable_pool = ComposableStablePool {
+            common: stable_pool.common.clone(),
+            reserves: stable_pool
+                .reserves
+                .iter()
+                .map(|(&token, state)| {
+                    (
+                        token,
+                        ScaledTokenState {
+                            common: state.clone(),
+                            scaling_factor: Bfp::from_wei(U256::exp10(
+                                18 + state.scaling_exponent as usize,
+                            )),
+                        },
+                    )
+                })
+                .collect(),
+            amplification_parameter,
+        };
+
+        // Same swap as in `stable_get_amount_out`.
+        let amount_in = 1_886_982_823_746_269_817_650_i128.into();
+        let amount_out = 1_887_770_905_i128;
+        assert_eq!(
+            composable_stable_pool
+                .get_amount_out(usdc, (amount_in, dai))
+                .unwrap(),
+            amount_out.into()
+        );
+    }
+
+    fn create_linear_pool() -> LinearPool {
+        let usdc = H160::from_low_u64_be(1);
+        let ausdc = H160::from_low_u64_be(2);
+        let bpt = H160::from_low_u64_be(3);
+        let token = |balance: U256, scaling_factor: Bfp| ScaledTokenState {
+            common: TokenState {
+                balance,
+                scaling_exponent: 0,
+            },
+            scaling_factor,
+        };
+
+        LinearPool {
+            common: CommonPoolState {
+                id: Default::default(),
+                address: bpt,
+                swap_fee: "0.01".parse().unwrap(),
+                paused: false,
+            },
+            reserves: hashmap! {
+                usdc => token(1_500_000_000_u128.into(), "1000000000000.0".parse().unwrap()),
+                // Wrapped token with a rate of 1.1 main tokens.
+                ausdc => token(500_000_000_u128.into(), "1100000000000.0".parse().unwrap()),
+                bpt => token(U256::exp10(33), Bfp::one()),
+            },
+            main_token: usdc,
+            wrapped_token: ausdc,
+            lower_target: 1_000_000_000_u128.into(),
+            upper_target: 2_000_000_000_u128.into(),
+            virtual_supply: U256::exp10(18) * 2050,
+        }
+    }
+
+    #[test]
+    fn linear_get_amount_out() {
+        let pool = create_linear_pool();
+        let (usdc, ausdc, bpt) = (pool.main_token, pool.wrapped_token, pool.bpt());
+
+        // The pool's invariant is 1500 + 500 * 1.1 = 2050 nominal USDC for a
+        // virtual supply of 2050 BPT.
+        assert_eq!(
+            pool.get_amount_out(bpt, (100_000_000.into(), usdc)),
+            Some(U256::exp10(20))
+        );
+        assert_eq!(
+            pool.get_amount_out(ausdc, (110_000_000.into(), usdc)),
+            Some(100_000_000.into())
+        );
+        assert_eq!(
+            pool.get_amount_out(usdc, (U256::exp10(20), bpt)),
+            Some(100_000_000.into())
+        );
+        assert_eq!(pool.get_amount_out(usdc, (U256::exp10(20), usdc)), None);
+    }
+
+    #[test]
+    fn linear_get_amount_in() {
+        let pool = create_linear_pool();
+        let (usdc, ausdc, bpt) = (pool.main_token, pool.wrapped_token, pool.bpt());
+
+        assert_eq!(
+            pool.get_amount_in(usdc, (U256::exp10(20), bpt)),
+            Some(100_000_000.into())
+        );
+        assert_eq!(
+            pool.get_amount_in(ausdc, (110_000_000.into(), usdc)),
+            Some(100_000_000.into())
+        );
+        assert_eq!(
+            pool.get_amount_in(bpt, (100_000_000.into(), usdc)),
+            Some(U256::exp10(20))
+        );
+    }
 }
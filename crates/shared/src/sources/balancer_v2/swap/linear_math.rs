@@ -0,0 +1,452 @@
+//! Module emulating the functions in the Balancer LinearMath.sol smart
+//! contract. The original contract code can be found at:
+//! https://github.com/balancer-labs/balancer-v2-monorepo/blob/linear-deployment/pkg/pool-linear/contracts/LinearMath.sol
+//!
+//! All balances and amounts are upscaled 18-decimal fixed point values. BPT
+//! amounts are in terms of the pool's virtual supply.
+
+use super::{error::Error, fixed_point::Bfp, math::BalU256};
+
+/// Parameters of a linear pool's fee curve.
+#[derive(Clone, Copy, Debug)]
+pub struct Params {
+    pub fee: Bfp,
+    pub lower_target: Bfp,
+    pub upper_target: Bfp,
+}
+
+pub fn calc_bpt_out_per_main_in(
+    main_in: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount out, so we round down overall.
+    if bpt_supply.is_zero() {
+        return to_nominal(main_in, params);
+    }
+
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let after_nominal_main = to_nominal(main_balance.add(main_in)?, params)?;
+    let delta_nominal_main = after_nominal_main.sub(previous_nominal_main)?;
+    let invariant = calc_invariant(previous_nominal_main, wrapped_balance)?;
+    mul_div_down(bpt_supply, delta_nominal_main, invariant)
+}
+
+pub fn calc_bpt_in_per_main_out(
+    main_out: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount in, so we round up overall.
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let after_nominal_main = to_nominal(main_balance.sub(main_out)?, params)?;
+    let delta_nominal_main = previous_nominal_main.sub(after_nominal_main)?;
+    let invariant = calc_invariant(previous_nominal_main, wrapped_balance)?;
+    mul_div_up(bpt_supply, delta_nominal_main, invariant)
+}
+
+pub fn calc_wrapped_out_per_main_in(
+    main_in: Bfp,
+    main_balance: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount out, so we round down overall.
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let after_nominal_main = to_nominal(main_balance.add(main_in)?, params)?;
+    after_nominal_main.sub(previous_nominal_main)
+}
+
+pub fn calc_wrapped_in_per_main_out(
+    main_out: Bfp,
+    main_balance: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount in, so we round up overall.
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let after_nominal_main = to_nominal(main_balance.sub(main_out)?, params)?;
+    previous_nominal_main.sub(after_nominal_main)
+}
+
+pub fn calc_main_in_per_bpt_out(
+    bpt_out: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount in, so we round up overall.
+    if bpt_supply.is_zero() {
+        return from_nominal(bpt_out, params);
+    }
+
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let invariant = calc_invariant(previous_nominal_main, wrapped_balance)?;
+    let delta_nominal_main = mul_div_up(invariant, bpt_out, bpt_supply)?;
+    let after_nominal_main = previous_nominal_main.add(delta_nominal_main)?;
+    let new_main_balance = from_nominal(after_nominal_main, params)?;
+    new_main_balance.sub(main_balance)
+}
+
+pub fn calc_main_out_per_bpt_in(
+    bpt_in: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount out, so we round down overall.
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let invariant = calc_invariant(previous_nominal_main, wrapped_balance)?;
+    let delta_nominal_main = mul_div_down(invariant, bpt_in, bpt_supply)?;
+    let after_nominal_main = previous_nominal_main.sub(delta_nominal_main)?;
+    let new_main_balance = from_nominal(after_nominal_main, params)?;
+    main_balance.sub(new_main_balance)
+}
+
+pub fn calc_main_out_per_wrapped_in(
+    wrapped_in: Bfp,
+    main_balance: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount out, so we round down overall.
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let after_nominal_main = previous_nominal_main.sub(wrapped_in)?;
+    let new_main_balance = from_nominal(after_nominal_main, params)?;
+    main_balance.sub(new_main_balance)
+}
+
+pub fn calc_main_in_per_wrapped_out(
+    wrapped_out: Bfp,
+    main_balance: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount in, so we round up overall.
+    let previous_nominal_main = to_nominal(main_balance, params)?;
+    let after_nominal_main = previous_nominal_main.add(wrapped_out)?;
+    let new_main_balance = from_nominal(after_nominal_main, params)?;
+    new_main_balance.sub(main_balance)
+}
+
+pub fn calc_bpt_out_per_wrapped_in(
+    wrapped_in: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount out, so we round down overall.
+    if bpt_supply.is_zero() {
+        // Return nominal DAI
+        return Ok(wrapped_in);
+    }
+
+    let nominal_main = to_nominal(main_balance, params)?;
+    let previous_invariant = calc_invariant(nominal_main, wrapped_balance)?;
+    let new_wrapped_balance = wrapped_balance.add(wrapped_in)?;
+    let new_invariant = calc_invariant(nominal_main, new_wrapped_balance)?;
+    let new_bpt_balance = mul_div_down(bpt_supply, new_invariant, previous_invariant)?;
+    new_bpt_balance.sub(bpt_supply)
+}
+
+pub fn calc_bpt_in_per_wrapped_out(
+    wrapped_out: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount in, so we round up overall.
+    let nominal_main = to_nominal(main_balance, params)?;
+    let previous_invariant = calc_invariant(nominal_main, wrapped_balance)?;
+    let new_wrapped_balance = wrapped_balance.sub(wrapped_out)?;
+    let new_invariant = calc_invariant(nominal_main, new_wrapped_balance)?;
+    let new_bpt_balance = mul_div_down(bpt_supply, new_invariant, previous_invariant)?;
+    bpt_supply.sub(new_bpt_balance)
+}
+
+pub fn calc_wrapped_in_per_bpt_out(
+    bpt_out: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount in, so we round up overall.
+    if bpt_supply.is_zero() {
+        // Return nominal DAI
+        return Ok(bpt_out);
+    }
+
+    let nominal_main = to_nominal(main_balance, params)?;
+    let previous_invariant = calc_invariant(nominal_main, wrapped_balance)?;
+    let new_bpt_balance = bpt_supply.add(bpt_out)?;
+    let new_wrapped_balance =
+        mul_div_up(new_bpt_balance, previous_invariant, bpt_supply)?.sub(nominal_main)?;
+    new_wrapped_balance.sub(wrapped_balance)
+}
+
+pub fn calc_wrapped_out_per_bpt_in(
+    bpt_in: Bfp,
+    main_balance: Bfp,
+    wrapped_balance: Bfp,
+    bpt_supply: Bfp,
+    params: &Params,
+) -> Result<Bfp, Error> {
+    // Amount out, so we round down overall.
+    let nominal_main = to_nominal(main_balance, params)?;
+    let previous_invariant = calc_invariant(nominal_main, wrapped_balance)?;
+    let new_bpt_balance = bpt_supply.sub(bpt_in)?;
+    let new_wrapped_balance =
+        mul_div_up(new_bpt_balance, previous_invariant, bpt_supply)?.sub(nominal_main)?;
+    wrapped_balance.sub(new_wrapped_balance)
+}
+
+fn calc_invariant(nominal_main_balance: Bfp, wrapped_balance: Bfp) -> Result<Bfp, Error> {
+    nominal_main_balance.add(wrapped_balance)
+}
+
+fn to_nominal(real: Bfp, params: &Params) -> Result<Bfp, Error> {
+    // Fees are always rounded down: either direction would work but we need
+    // to be consistent, and rounding down uses less gas.
+    if real < params.lower_target {
+        let fees = params.lower_target.sub(real)?.mul_down(params.fee)?;
+        real.sub(fees)
+    } else if real <= params.upper_target {
+        Ok(real)
+    } else {
+        let fees = real.sub(params.upper_target)?.mul_down(params.fee)?;
+        real.sub(fees)
+    }
+}
+
+fn from_nominal(nominal: Bfp, params: &Params) -> Result<Bfp, Error> {
+    // Since real = nominal + fees, rounding down fees is equivalent to
+    // rounding down real.
+    if nominal < params.lower_target {
+        nominal
+            .add(params.fee.mul_down(params.lower_target)?)?
+            .div_down(Bfp::one().add(params.fee)?)
+    } else if nominal <= params.upper_target {
+        Ok(nominal)
+    } else {
+        nominal
+            .sub(params.fee.mul_down(params.upper_target)?)?
+            .div_down(Bfp::one().sub(params.fee)?)
+    }
+}
+
+/// Computes `a * b / c` rounding down, equivalent to `Math.divDown(Math.mul(a,
+/// b), c)` in the contract code.
+fn mul_div_down(a: Bfp, b: Bfp, c: Bfp) -> Result<Bfp, Error> {
+    Ok(Bfp::from_wei(
+        a.as_uint256()
+            .bmul(b.as_uint256())?
+            .bdiv_down(c.as_uint256())?,
+    ))
+}
+
+/// Computes `a * b / c` rounding up, equivalent to `Math.divUp(Math.mul(a, b),
+/// c)` in the contract code.
+fn mul_div_up(a: Bfp, b: Bfp, c: Bfp) -> Result<Bfp, Error> {
+    Ok(Bfp::from_wei(
+        a.as_uint256()
+            .bmul(b.as_uint256())?
+            .bdiv_up(c.as_uint256())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> Params {
+        Params {
+            fee: bfp!("0.01"),
+            lower_target: bfp!("1000.0"),
+            upper_target: bfp!("2000.0"),
+        }
+    }
+
+    #[test]
+    fn nominal_conversions() {
+        let params = params();
+
+        // Within the targets, real and nominal values are the same.
+        assert_eq!(to_nominal(bfp!("1500.0"), &params).unwrap(), bfp!("1500.0"));
+        assert_eq!(
+            from_nominal(bfp!("1500.0"), &params).unwrap(),
+            bfp!("1500.0")
+        );
+
+        // Below the lower target, fees are charged on the difference.
+        assert_eq!(to_nominal(bfp!("500.0"), &params).unwrap(), bfp!("495.0"));
+        assert_eq!(from_nominal(bfp!("495.0"), &params).unwrap(), bfp!("500.0"));
+
+        // Above the upper target, fees are charged on the excess.
+        assert_eq!(to_nominal(bfp!("3000.0"), &params).unwrap(), bfp!("2990.0"));
+        assert_eq!(
+            from_nominal(bfp!("2990.0"), &params).unwrap(),
+            bfp!("3000.0")
+        );
+    }
+
+    #[test]
+    fn main_wrapped_swaps() {
+        let params = params();
+
+        // Swapping main tokens in while within the targets is fee-less.
+        assert_eq!(
+            calc_wrapped_out_per_main_in(bfp!("100.0"), bfp!("1500.0"), &params).unwrap(),
+            bfp!("100.0")
+        );
+        assert_eq!(
+            calc_main_in_per_wrapped_out(bfp!("100.0"), bfp!("1500.0"), &params).unwrap(),
+            bfp!("100.0")
+        );
+
+        // Moving the main balance below the lower target is charged a fee
+        // which is returned when moving back towards the targets.
+        assert_eq!(
+            calc_main_out_per_wrapped_in(bfp!("601.0"), bfp!("1500.0"), &params).unwrap(),
+            bfp!("600.0")
+        );
+        assert_eq!(
+            calc_wrapped_in_per_main_out(bfp!("600.0"), bfp!("1500.0"), &params).unwrap(),
+            bfp!("601.0")
+        );
+        assert_eq!(
+            calc_wrapped_out_per_main_in(bfp!("600.0"), bfp!("900.0"), &params).unwrap(),
+            bfp!("601.0")
+        );
+    }
+
+    #[test]
+    fn bpt_swaps() {
+        let params = params();
+        let (main_balance, wrapped_balance, bpt_supply) =
+            (bfp!("1500.0"), bfp!("500.0"), bfp!("1000.0"));
+
+        // The invariant is 2000 nominal main tokens for 1000 BPT, so each BPT
+        // is worth 2 nominal main tokens.
+        assert_eq!(
+            calc_bpt_out_per_main_in(
+                bfp!("100.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("50.0")
+        );
+        assert_eq!(
+            calc_main_in_per_bpt_out(
+                bfp!("50.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("100.0")
+        );
+        assert_eq!(
+            calc_main_out_per_bpt_in(
+                bfp!("50.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("100.0")
+        );
+        assert_eq!(
+            calc_bpt_in_per_main_out(
+                bfp!("100.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("50.0")
+        );
+        assert_eq!(
+            calc_bpt_out_per_wrapped_in(
+                bfp!("100.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("50.0")
+        );
+        assert_eq!(
+            calc_wrapped_in_per_bpt_out(
+                bfp!("50.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("100.0")
+        );
+        assert_eq!(
+            calc_wrapped_out_per_bpt_in(
+                bfp!("50.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("100.0")
+        );
+        assert_eq!(
+            calc_bpt_in_per_wrapped_out(
+                bfp!("100.0"),
+                main_balance,
+                wrapped_balance,
+                bpt_supply,
+                &params
+            )
+            .unwrap(),
+            bfp!("50.0")
+        );
+    }
+
+    #[test]
+    fn bpt_swaps_without_supply() {
+        let params = params();
+
+        assert_eq!(
+            calc_bpt_out_per_main_in(
+                bfp!("500.0"),
+                Bfp::zero(),
+                Bfp::zero(),
+                Bfp::zero(),
+                &params
+            )
+            .unwrap(),
+            bfp!("495.0")
+        );
+        assert_eq!(
+            calc_bpt_out_per_wrapped_in(
+                bfp!("500.0"),
+                Bfp::zero(),
+                Bfp::zero(),
+                Bfp::zero(),
+                &params
+            )
+            .unwrap(),
+            bfp!("500.0")
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Curve stable-swap liquidity source.
+//!
+//! Pools are discovered once through the on-chain Curve registry, which also
+//! provides their block-dependent state (balances, amplification parameter and
+//! fees). The state is cached per block with a `RecentBlockCache`.
+//!
+//! Only plain pools, i.e. pools whose coins are exchanged at a fixed rate
+//! instead of at a lending rate, are indexed.
+
+pub mod pool_fetching;
+pub mod swap;
@@ -0,0 +1,354 @@
+//! Discovery of Curve pools through the on-chain registry and fetching of
+//! their block-dependent state.
+
+use crate::{
+    current_block::CurrentBlockStream,
+    maintenance::Maintaining,
+    recent_block_cache::{Block, CacheConfig, CacheFetching, CacheKey, RecentBlockCache},
+    transport::MAX_BATCH_SIZE,
+    Web3, Web3CallBatch,
+};
+use anyhow::{ensure, Result};
+use contracts::CurveRegistry;
+use ethcontract::{BlockId, H160, U256};
+use futures::future;
+use model::TokenPair;
+use std::collections::{HashMap, HashSet};
+
+/// The maximum number of coins a pool can have according to the registry.
+const MAX_COINS: usize = 8;
+
+/// A Curve pool with its state at a specific block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pool {
+    pub address: H160,
+    pub tokens: Vec<H160>,
+    pub balances: Vec<U256>,
+    /// The rates that normalize the balances to 18 decimals, scaled by 1e18.
+    /// This corresponds to the `RATES` constant of the pool contracts.
+    pub rates: Vec<U256>,
+    pub amplification_parameter: U256,
+    /// The swap fee in units of `swap::FEE_DENOMINATOR`.
+    pub fee: U256,
+}
+
+impl Pool {
+    /// Returns all token pairs that can be traded with this pool.
+    pub fn token_pairs(&self) -> Vec<TokenPair> {
+        token_pairs(&self.tokens)
+    }
+}
+
+fn token_pairs(tokens: &[H160]) -> Vec<TokenPair> {
+    tokens
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &token_a)| {
+            tokens[i + 1..]
+                .iter()
+                .filter_map(move |&token_b| TokenPair::new(token_a, token_b))
+        })
+        .collect()
+}
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait CurvePoolFetching: Send + Sync {
+    async fn fetch(&self, token_pairs: HashSet<TokenPair>, at_block: Block) -> Result<Vec<Pool>>;
+}
+
+/// Static information about a Curve pool.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PoolInfo {
+    address: H160,
+    tokens: Vec<H160>,
+    rates: Vec<U256>,
+}
+
+pub struct CurvePoolFetcher {
+    pools_by_token_pair: HashMap<TokenPair, HashSet<H160>>,
+    cache: RecentBlockCache<H160, Pool, PoolStateFetcher>,
+}
+
+impl CurvePoolFetcher {
+    /// Discovers all plain pools of the Curve registry and creates a fetcher
+    /// that caches their state.
+    pub async fn new(
+        web3: &Web3,
+        config: CacheConfig,
+        block_stream: CurrentBlockStream,
+    ) -> Result<Self> {
+        let registry = CurveRegistry::deployed(web3).await?;
+        let pools = fetch_pool_infos(web3, &registry).await?;
+        tracing::debug!(pools = %pools.len(), "initialized Curve pools");
+
+        let mut pools_by_token_pair: HashMap<TokenPair, HashSet<H160>> = HashMap::new();
+        for pool in &pools {
+            for pair in token_pairs(&pool.tokens) {
+                pools_by_token_pair
+                    .entry(pair)
+                    .or_default()
+                    .insert(pool.address);
+            }
+        }
+
+        let fetcher = PoolStateFetcher {
+            web3: web3.clone(),
+            registry,
+            pools: pools.into_iter().map(|pool| (pool.address, pool)).collect(),
+        };
+        Ok(Self {
+            pools_by_token_pair,
+            cache: RecentBlockCache::new(config, fetcher, block_stream, "curve")?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl CurvePoolFetching for CurvePoolFetcher {
+    async fn fetch(&self, token_pairs: HashSet<TokenPair>, at_block: Block) -> Result<Vec<Pool>> {
+        let pool_addresses = token_pairs
+            .iter()
+            .filter_map(|pair| self.pools_by_token_pair.get(pair))
+            .flatten()
+            .copied()
+            .collect::<HashSet<_>>();
+        self.cache.fetch(pool_addresses, at_block).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Maintaining for CurvePoolFetcher {
+    async fn run_maintenance(&self) -> Result<()> {
+        self.cache.update_cache().await
+    }
+}
+
+impl CacheKey<Pool> for H160 {
+    fn first_ord() -> Self {
+        H160::zero()
+    }
+
+    fn for_value(pool: &Pool) -> Self {
+        pool.address
+    }
+}
+
+struct PoolStateFetcher {
+    web3: Web3,
+    registry: CurveRegistry,
+    pools: HashMap<H160, PoolInfo>,
+}
+
+#[async_trait::async_trait]
+impl CacheFetching<H160, Pool> for PoolStateFetcher {
+    async fn fetch_values(&self, pool_addresses: HashSet<H160>, block: Block) -> Result<Vec<Pool>> {
+        let mut batch = Web3CallBatch::new(self.web3.transport().clone());
+        let block = BlockId::Number(block.into());
+        let futures = pool_addresses
+            .iter()
+            .filter_map(|address| self.pools.get(address))
+            .map(|pool| {
+                (
+                    pool,
+                    self.registry
+                        .get_balances(pool.address)
+                        .block(block)
+                        .batch_call(&mut batch),
+                    self.registry
+                        .get_a(pool.address)
+                        .block(block)
+                        .batch_call(&mut batch),
+                    self.registry
+                        .get_fees(pool.address)
+                        .block(block)
+                        .batch_call(&mut batch),
+                )
+            })
+            .collect::<Vec<_>>();
+        batch.execute_all(MAX_BATCH_SIZE).await;
+
+        let mut pools = Vec::with_capacity(futures.len());
+        for (pool, balances, amplification_parameter, fees) in futures {
+            let balances = balances.await?;
+            pools.push(Pool {
+                address: pool.address,
+                tokens: pool.tokens.clone(),
+                balances: balances[..pool.tokens.len()].to_vec(),
+                rates: pool.rates.clone(),
+                amplification_parameter: amplification_parameter.await?,
+                fee: fees.await?[0],
+            });
+        }
+        Ok(pools)
+    }
+}
+
+/// Retrieves the static information of all plain pools in the registry.
+async fn fetch_pool_infos(web3: &Web3, registry: &CurveRegistry) -> Result<Vec<PoolInfo>> {
+    let pool_count = registry.pool_count().call().await?.as_usize();
+
+    let mut batch = Web3CallBatch::new(web3.transport().clone());
+    let addresses = (0..pool_count)
+        .map(|i| registry.pool_list(i.into()).batch_call(&mut batch))
+        .collect::<Vec<_>>();
+    batch.execute_all(MAX_BATCH_SIZE).await;
+    let addresses = future::try_join_all(addresses).await?;
+
+    let mut batch = Web3CallBatch::new(web3.transport().clone());
+    let futures = addresses
+        .iter()
+        .map(|&address| {
+            (
+                address,
+                registry.get_n_coins(address).batch_call(&mut batch),
+                registry.get_coins(address).batch_call(&mut batch),
+                registry.get_decimals(address).batch_call(&mut batch),
+                registry.get_rates(address).batch_call(&mut batch),
+            )
+        })
+        .collect::<Vec<_>>();
+    batch.execute_all(MAX_BATCH_SIZE).await;
+
+    let mut pools = Vec::new();
+    for (address, n_coins, coins, decimals, rates) in futures {
+        let pool = pool_info(
+            address,
+            n_coins.await?,
+            coins.await?,
+            decimals.await?,
+            rates.await?,
+        );
+        match pool {
+            Ok(pool) => pools.push(pool),
+            Err(err) => tracing::debug!(?address, ?err, "ignoring Curve pool"),
+        }
+    }
+    Ok(pools)
+}
+
+fn pool_info(
+    address: H160,
+    [n_coins, _]: [U256; 2],
+    coins: [H160; MAX_COINS],
+    decimals: [U256; MAX_COINS],
+    rates: [U256; MAX_COINS],
+) -> Result<PoolInfo> {
+    let n_coins = n_coins.as_usize();
+    ensure!(
+        (2..=MAX_COINS).contains(&n_coins),
+        "unsupported number of coins {}",
+        n_coins
+    );
+    let tokens = coins[..n_coins].to_vec();
+    ensure!(
+        tokens.iter().collect::<HashSet<_>>().len() == n_coins,
+        "duplicate coins"
+    );
+    // Lending pools exchange their coins at a variable rate, which is not
+    // supported.
+    ensure!(
+        rates[..n_coins].iter().all(|rate| *rate == U256::exp10(18)),
+        "not a plain pool"
+    );
+    let rates = decimals[..n_coins]
+        .iter()
+        .map(|decimals| {
+            ensure!(*decimals <= 18.into(), "unsupported decimals {}", decimals);
+            Ok(U256::exp10(36 - decimals.as_usize()))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(PoolInfo {
+        address,
+        tokens,
+        rates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses<const N: usize>(seeds: [u8; N]) -> [H160; MAX_COINS] {
+        let mut result = [H160::zero(); MAX_COINS];
+        for (address, seed) in result.iter_mut().zip(seeds) {
+            *address = H160([seed; 20]);
+        }
+        result
+    }
+
+    fn amounts<const N: usize>(values: [u64; N]) -> [U256; MAX_COINS] {
+        let mut result = [U256::zero(); MAX_COINS];
+        for (amount, value) in result.iter_mut().zip(values) {
+            *amount = value.into();
+        }
+        result
+    }
+
+    #[test]
+    fn computes_rates_from_decimals() {
+        let one = 1_000_000_000_000_000_000;
+        let pool = pool_info(
+            H160([0x01; 20]),
+            [3.into(), 0.into()],
+            addresses([0x0d, 0x0c, 0x0e]),
+            amounts([18, 6, 6]),
+            amounts([one, one, one]),
+        )
+        .unwrap();
+        assert_eq!(
+            pool,
+            PoolInfo {
+                address: H160([0x01; 20]),
+                tokens: vec![H160([0x0d; 20]), H160([0x0c; 20]), H160([0x0e; 20])],
+                rates: vec![U256::exp10(18), U256::exp10(30), U256::exp10(30)],
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_unsupported_pools() {
+        let one = 1_000_000_000_000_000_000;
+        // Lending pool with a variable rate.
+        assert!(pool_info(
+            H160([0x01; 20]),
+            [2.into(), 0.into()],
+            addresses([0x0d, 0x0c]),
+            amounts([8, 8]),
+            amounts([one, 1_020_000_000_000_000_000]),
+        )
+        .is_err());
+        // Duplicate coins.
+        assert!(pool_info(
+            H160([0x01; 20]),
+            [2.into(), 0.into()],
+            addresses([0x0d, 0x0d]),
+            amounts([18, 18]),
+            amounts([one, one]),
+        )
+        .is_err());
+        // Too many decimals.
+        assert!(pool_info(
+            H160([0x01; 20]),
+            [2.into(), 0.into()],
+            addresses([0x0d, 0x0c]),
+            amounts([18, 24]),
+            amounts([one, one]),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn enumerates_token_pairs() {
+        let pairs = token_pairs(&[H160([0x01; 20]), H160([0x02; 20]), H160([0x03; 20])]);
+        assert_eq!(
+            pairs,
+            vec![
+                TokenPair::new(H160([0x01; 20]), H160([0x02; 20])).unwrap(),
+                TokenPair::new(H160([0x01; 20]), H160([0x03; 20])).unwrap(),
+                TokenPair::new(H160([0x02; 20]), H160([0x03; 20])).unwrap(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Local Curve StableSwap math.
+//!
+//! This is a port of the `get_D`, `get_y` and `get_dy` functions of the Curve
+//! plain pool contracts:
+//! https://github.com/curvefi/curve-contract/blob/b0bbf77f8f93c9c5f4e415bce9cd71f0cdee960e/contracts/pools/3pool/StableSwap3Pool.vy
+//!
+//! The contracts have no `get_dx`, so buy amounts are computed by inverting
+//! the invariant and rounding in favour of the pool.
+
+use super::pool_fetching::Pool;
+use crate::{baseline_solver::BaselineSolvable, price_estimation::gas::GAS_PER_CURVE_SWAP};
+use ethcontract::{H160, U256};
+
+/// The fee denominator of Curve pools.
+pub const FEE_DENOMINATOR: u64 = 10_000_000_000;
+
+/// The number of Newton iterations after which the invariant computations
+/// give up.
+const MAX_ITERATIONS: usize = 255;
+
+fn precision() -> U256 {
+    U256::exp10(18)
+}
+
+fn within_one(a: U256, b: U256) -> bool {
+    if a > b {
+        a - b <= U256::one()
+    } else {
+        b - a <= U256::one()
+    }
+}
+
+/// Computes the StableSwap invariant `D` for the normalized balances `xp`.
+fn get_d(xp: &[U256], amp: U256) -> Option<U256> {
+    let n = U256::from(xp.len());
+    let s = xp
+        .iter()
+        .try_fold(U256::zero(), |sum, x| sum.checked_add(*x))?;
+    if s.is_zero() {
+        return Some(U256::zero());
+    }
+
+    let ann = amp.checked_mul(n)?;
+    let mut d = s;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp {
+            d_p = d_p.checked_mul(d)?.checked_div(x.checked_mul(n)?)?;
+        }
+        let d_prev = d;
+        let numerator = ann
+            .checked_mul(s)?
+            .checked_add(d_p.checked_mul(n)?)?
+            .checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(1.into())?
+            .checked_mul(d)?
+            .checked_add(n.checked_add(1.into())?.checked_mul(d_p)?)?;
+        d = numerator.checked_div(denominator)?;
+        if within_one(d, d_prev) {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Computes the normalized balance of token `j` after the normalized balance
+/// of token `i` changes to `x`, keeping the invariant constant.
+fn get_y(i: usize, j: usize, x: U256, xp: &[U256], amp: U256) -> Option<U256> {
+    let n = U256::from(xp.len());
+    let d = get_d(xp, amp)?;
+    let ann = amp.checked_mul(n)?;
+
+    let mut c = d;
+    let mut s = U256::zero();
+    for (k, &xp_k) in xp.iter().enumerate() {
+        let x_k = if k == i {
+            x
+        } else if k != j {
+            xp_k
+        } else {
+            continue;
+        };
+        s = s.checked_add(x_k)?;
+        c = c.checked_mul(d)?.checked_div(x_k.checked_mul(n)?)?;
+    }
+    c = c.checked_mul(d)?.checked_div(ann.checked_mul(n)?)?;
+    let b = s.checked_add(d.checked_div(ann)?)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        y = y
+            .checked_mul(y)?
+            .checked_add(c)?
+            .checked_div(y.checked_mul(2.into())?.checked_add(b)?.checked_sub(d)?)?;
+        if within_one(y, y_prev) {
+            return Some(y);
+        }
+    }
+    None
+}
+
+fn ceil_div(a: U256, b: U256) -> Option<U256> {
+    let (quotient, remainder) = a.checked_div(b).map(|q| (q, a % b))?;
+    if remainder.is_zero() {
+        Some(quotient)
+    } else {
+        quotient.checked_add(1.into())
+    }
+}
+
+impl Pool {
+    /// Returns the balances normalized to 18 decimals.
+    fn xp(&self) -> Option<Vec<U256>> {
+        self.balances
+            .iter()
+            .zip(&self.rates)
+            .map(|(balance, rate)| balance.checked_mul(*rate)?.checked_div(precision()))
+            .collect()
+    }
+
+    fn token_index(&self, token: H160) -> Option<usize> {
+        self.tokens.iter().position(|t| *t == token)
+    }
+
+    /// Returns the amount of token `j` received for selling `dx` of token `i`.
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Option<U256> {
+        let xp = self.xp()?;
+        let x = xp
+            .get(i)?
+            .checked_add(dx.checked_mul(self.rates[i])?.checked_div(precision())?)?;
+        let y = get_y(i, j, x, &xp, self.amplification_parameter)?;
+        let dy = xp
+            .get(j)?
+            .checked_sub(y)?
+            .checked_sub(1.into())?
+            .checked_mul(precision())?
+            .checked_div(self.rates[j])?;
+        let fee = self
+            .fee
+            .checked_mul(dy)?
+            .checked_div(FEE_DENOMINATOR.into())?;
+        dy.checked_sub(fee)
+    }
+
+    /// Returns the amount of token `i` that needs to be sold in order to
+    /// receive `dy` of token `j`.
+    pub fn get_dx(&self, i: usize, j: usize, dy: U256) -> Option<U256> {
+        let xp = self.xp()?;
+        let fee_denominator = U256::from(FEE_DENOMINATOR);
+        // Fees are deducted from the output amount, so gross it up first.
+        let dy_with_fee = ceil_div(
+            dy.checked_mul(fee_denominator)?,
+            fee_denominator.checked_sub(self.fee)?,
+        )?;
+        let y = xp.get(j)?.checked_sub(ceil_div(
+            dy_with_fee
+                .checked_add(1.into())?
+                .checked_mul(self.rates[j])?,
+            precision(),
+        )?)?;
+        let x = get_y(j, i, y, &xp, self.amplification_parameter)?;
+        ceil_div(
+            x.checked_sub(*xp.get(i)?)?.checked_mul(precision())?,
+            self.rates[i],
+        )?
+        .checked_add(1.into())
+    }
+}
+
+impl BaselineSolvable for Pool {
+    fn get_amount_out(&self, out_token: H160, (in_amount, in_token): (U256, H160)) -> Option<U256> {
+        let i = self.token_index(in_token)?;
+        let j = self.token_index(out_token)?;
+        if i == j {
+            return None;
+        }
+        self.get_dy(i, j, in_amount)
+    }
+
+    fn get_amount_in(&self, in_token: H160, (out_amount, out_token): (U256, H160)) -> Option<U256> {
+        let i = self.token_index(in_token)?;
+        let j = self.token_index(out_token)?;
+        if i == j {
+            return None;
+        }
+        self.get_dx(i, j, out_amount)
+    }
+
+    fn gas_cost(&self) -> usize {
+        GAS_PER_CURVE_SWAP as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn three_pool() -> Pool {
+        Pool {
+            address: H160([0x01; 20]),
+            tokens: vec![H160([0x0d; 20]), H160([0x0c; 20]), H160([0x0e; 20])],
+            balances: vec![
+                U256::exp10(24),
+                U256::from(1_100_000) * U256::exp10(6),
+                U256::from(900_000) * U256::exp10(6),
+            ],
+            rates: vec![U256::exp10(18), U256::exp10(30), U256::exp10(30)],
+            amplification_parameter: 2000.into(),
+            fee: 4_000_000.into(),
+        }
+    }
+
+    // Expected amounts were computed with a Python port of the Vyper contract.
+
+    #[test]
+    fn computes_sell_amounts() {
+        let pool = three_pool();
+        assert_eq!(
+            pool.get_dy(0, 1, U256::from(1000) * U256::exp10(18)),
+            Some(999_645_411.into())
+        );
+        assert_eq!(
+            pool.get_dy(2, 0, U256::from(1000) * U256::exp10(6)),
+            Some(U256::from_dec_str("999655498797128167603").unwrap())
+        );
+    }
+
+    #[test]
+    fn computes_buy_amounts() {
+        let pool = three_pool();
+
+        let dx = pool.get_dx(0, 1, 1_000_000_000.into()).unwrap();
+        assert_eq!(dx, U256::from_dec_str("1000354716775954884760").unwrap());
+        assert_eq!(pool.get_dy(0, 1, dx), Some(1_000_000_002.into()));
+
+        let dx = pool.get_dx(1, 2, 12_345_678.into()).unwrap();
+        assert_eq!(dx, 12_351_881.into());
+        assert!(pool.get_dy(1, 2, dx).unwrap() >= 12_345_678.into());
+    }
+
+    #[test]
+    fn baseline_solvable_uses_token_indices() {
+        let pool = three_pool();
+        let amount = U256::from(1000) * U256::exp10(18);
+        assert_eq!(
+            pool.get_amount_out(pool.tokens[1], (amount, pool.tokens[0])),
+            pool.get_dy(0, 1, amount),
+        );
+        assert_eq!(
+            pool.get_amount_in(pool.tokens[0], (1_000_000_000.into(), pool.tokens[1])),
+            pool.get_dx(0, 1, 1_000_000_000.into()),
+        );
+        assert_eq!(
+            pool.get_amount_out(pool.tokens[0], (amount, pool.tokens[0])),
+            None
+        );
+        assert_eq!(
+            pool.get_amount_out(H160([0xff; 20]), (amount, pool.tokens[0])),
+            None
+        );
+    }
+
+    #[test]
+    fn buying_more_than_the_balance_fails() {
+        let pool = three_pool();
+        assert_eq!(
+            pool.get_dx(0, 1, U256::from(1_100_001) * U256::exp10(6)),
+            None
+        );
+    }
+}
@@ -1,3 +1,5 @@
 //! Uniswap V3 baseline liquidity source implementation.
+pub mod event_fetching;
 pub mod graph_api;
 pub mod pool_fetching;
+pub mod swap;
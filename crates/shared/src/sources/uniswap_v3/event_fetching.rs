@@ -0,0 +1,714 @@
+//! Uniswap V3 pool indexer that maintains pool state from on-chain events.
+//!
+//! The indexer follows `Initialize`, `Mint`, `Burn` and `Swap` events of all
+//! tracked pools and keeps their tick maps and active liquidity in memory. The
+//! subgraph is only used to get the initial list of pools (with their tokens
+//! and fee tiers).
+//!
+//! Events that are older than the last `MAX_REORG_BLOCK_COUNT` blocks are folded
+//! into a checkpoint, which can optionally be persisted to disk so that restarts
+//! do not need to replay the full event history. More recent events are kept
+//! around so that a reorg can be handled by replaying them on top of the
+//! checkpoint.
+
+use super::{
+    graph_api::{Token, UniV3SubgraphClient},
+    pool_fetching::{PoolFetching, PoolInfo, PoolState, PoolStats},
+};
+use crate::{
+    current_block::BlockRetrieving,
+    event_handling::{BlockNumber, EventIndex, EventRetrieving, MAX_REORG_BLOCK_COUNT},
+    maintenance::Maintaining,
+    Web3,
+};
+use anyhow::{anyhow, Context, Result};
+use contracts::{iuniswap_v3_pool::Event as UniswapV3PoolEvent, IUniswapV3Factory, IUniswapV3Pool};
+use ethcontract::{
+    common::DeploymentInformation, contract::AllEventsBuilder, dyns::DynTransport,
+    Event as EthcontractEvent, H160, U256,
+};
+use futures::StreamExt;
+use model::TokenPair;
+use num::{rational::Ratio, BigInt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+/// The number of blocks the checkpoint needs to advance before it gets written
+/// to disk again.
+const CHECKPOINT_PERSIST_INTERVAL: u64 = 100;
+
+/// The maximum number of blocks whose events are fetched at once when catching up.
+const SYNC_BLOCK_RANGE: u64 = 10_000;
+
+/// Pool state as indexed from events.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexedPool {
+    pub sqrt_price: U256,
+    pub liquidity: u128,
+    pub tick: i32,
+    /// The net liquidity change when crossing an initialized tick from left to
+    /// right. Ticks whose net liquidity drops to zero are removed.
+    pub liquidity_net: BTreeMap<i32, i128>,
+}
+
+impl IndexedPool {
+    fn apply(&mut self, event: &UniswapV3PoolEvent) {
+        match event {
+            UniswapV3PoolEvent::Initialize(initialize) => {
+                self.sqrt_price = initialize.sqrt_price_x96;
+                self.tick = initialize.tick;
+            }
+            UniswapV3PoolEvent::Mint(mint) => {
+                self.update_position(mint.tick_lower, mint.tick_upper, mint.amount as i128)
+            }
+            UniswapV3PoolEvent::Burn(burn) => {
+                self.update_position(burn.tick_lower, burn.tick_upper, -(burn.amount as i128))
+            }
+            UniswapV3PoolEvent::Swap(swap) => {
+                self.sqrt_price = swap.sqrt_price_x96;
+                self.liquidity = swap.liquidity;
+                self.tick = swap.tick;
+            }
+            _ => {}
+        }
+    }
+
+    fn update_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) {
+        for (tick, delta) in [
+            (tick_lower, liquidity_delta),
+            (tick_upper, -liquidity_delta),
+        ] {
+            let liquidity_net = self.liquidity_net.entry(tick).or_default();
+            *liquidity_net += delta;
+            if *liquidity_net == 0 {
+                self.liquidity_net.remove(&tick);
+            }
+        }
+
+        // Only positions whose range contains the current price contribute to
+        // the active liquidity.
+        if tick_lower <= self.tick && self.tick < tick_upper {
+            self.liquidity = if liquidity_delta >= 0 {
+                self.liquidity.saturating_add(liquidity_delta as u128)
+            } else {
+                self.liquidity
+                    .saturating_sub(liquidity_delta.unsigned_abs())
+            };
+        }
+    }
+
+    fn is_initialized(&self) -> bool {
+        !self.sqrt_price.is_zero()
+    }
+}
+
+/// Finalized pool states. The state includes all events strictly before
+/// `block_number`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub block_number: u64,
+    pub pools: HashMap<H160, IndexedPool>,
+}
+
+impl Checkpoint {
+    fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = fs::File::open(path)?;
+        Ok(Some(serde_json::from_reader(std::io::BufReader::new(
+            file,
+        ))?))
+    }
+
+    fn store(&self, path: &Path) -> Result<()> {
+        // Write to a temporary file first so that a crash while writing never
+        // leaves a corrupted checkpoint behind.
+        let tmp = path.with_extension("tmp");
+        serde_json::to_writer(std::io::BufWriter::new(fs::File::create(&tmp)?), self)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PoolEvent {
+    index: EventIndex,
+    pool: H160,
+    event: UniswapV3PoolEvent,
+}
+
+impl TryFrom<EthcontractEvent<UniswapV3PoolEvent>> for PoolEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(event: EthcontractEvent<UniswapV3PoolEvent>) -> Result<Self> {
+        let meta = event
+            .meta
+            .ok_or_else(|| anyhow!("event missing metadata"))?;
+        Ok(Self {
+            index: EventIndex::from(&meta),
+            pool: meta.address,
+            event: event.data,
+        })
+    }
+}
+
+/// In-memory storage of the indexed pool states.
+pub struct PoolEventStore {
+    checkpoint: Checkpoint,
+    /// Events at or after the checkpoint block by pool, which may still get reorged.
+    recent_events: HashMap<H160, Vec<PoolEvent>>,
+    /// Current pool states, i.e. the checkpoint with all recent events applied.
+    pools: HashMap<H160, Arc<IndexedPool>>,
+    checkpoint_path: Option<PathBuf>,
+    persisted_block: u64,
+}
+
+impl PoolEventStore {
+    pub fn new(checkpoint: Checkpoint, checkpoint_path: Option<PathBuf>) -> Self {
+        Self {
+            pools: checkpoint
+                .pools
+                .iter()
+                .map(|(address, pool)| (*address, Arc::new(pool.clone())))
+                .collect(),
+            persisted_block: checkpoint.block_number,
+            checkpoint,
+            recent_events: Default::default(),
+            checkpoint_path,
+        }
+    }
+
+    pub fn pool(&self, address: &H160) -> Option<&IndexedPool> {
+        self.pools.get(address).map(|pool| pool.as_ref())
+    }
+
+    /// Applies the finalized events up to (excluding) `block_number` to the checkpoint and advances
+    /// it to that block. Recent events before that block are dropped because `events` replaces
+    /// them.
+    fn finalize(&mut self, events: Vec<PoolEvent>, block_number: u64) {
+        let mut touched = HashSet::new();
+        for (pool, pool_events) in &mut self.recent_events {
+            let len = pool_events.len();
+            pool_events.retain(|event| event.index.block_number >= block_number);
+            if pool_events.len() != len {
+                touched.insert(*pool);
+            }
+        }
+        self.recent_events
+            .retain(|_, pool_events| !pool_events.is_empty());
+
+        for event in events {
+            // Events before the checkpoint are already part of its state.
+            if event.index.block_number < self.checkpoint.block_number {
+                continue;
+            }
+            touched.insert(event.pool);
+            self.checkpoint
+                .pools
+                .entry(event.pool)
+                .or_default()
+                .apply(&event.event);
+        }
+        self.checkpoint.block_number = self.checkpoint.block_number.max(block_number);
+        self.rebuild(touched);
+    }
+
+    /// Replaces all recent events with the specified events at or after the checkpoint block.
+    fn replace_recent(&mut self, events: Vec<PoolEvent>) {
+        let mut touched = std::mem::take(&mut self.recent_events)
+            .into_keys()
+            .collect::<HashSet<_>>();
+        for event in events {
+            if event.index.block_number < self.checkpoint.block_number {
+                continue;
+            }
+            touched.insert(event.pool);
+            self.recent_events
+                .entry(event.pool)
+                .or_default()
+                .push(event);
+        }
+        self.rebuild(touched);
+    }
+
+    /// Recomputes the current state of the specified pools by replaying their recent events on
+    /// top of the checkpoint.
+    fn rebuild(&mut self, pools: HashSet<H160>) {
+        for pool in pools {
+            let mut state = self
+                .checkpoint
+                .pools
+                .get(&pool)
+                .cloned()
+                .unwrap_or_default();
+            for event in self.recent_events.get(&pool).into_iter().flatten() {
+                state.apply(&event.event);
+            }
+            self.pools.insert(pool, Arc::new(state));
+        }
+    }
+
+    async fn persist_checkpoint(&mut self) {
+        let path = match &self.checkpoint_path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        if self.checkpoint.block_number < self.persisted_block + CHECKPOINT_PERSIST_INTERVAL {
+            return;
+        }
+        let checkpoint = self.checkpoint.clone();
+        let block_number = checkpoint.block_number;
+        let result = tokio::task::spawn_blocking(move || checkpoint.store(&path))
+            .await
+            .context("checkpoint task panicked")
+            .and_then(|result| result);
+        match result {
+            Ok(()) => {
+                tracing::debug!(block = %block_number, "stored Uniswap V3 checkpoint");
+                self.persisted_block = block_number;
+            }
+            // Failing to persist is not fatal, we will just replay more events
+            // on the next restart.
+            Err(err) => tracing::warn!(?err, "failed to store Uniswap V3 checkpoint"),
+        }
+    }
+}
+
+/// Retrieves the events of a set of Uniswap V3 pools.
+pub struct UniswapV3PoolEvents {
+    contract: IUniswapV3Pool,
+    pools: Vec<H160>,
+}
+
+impl EventRetrieving for UniswapV3PoolEvents {
+    type Event = UniswapV3PoolEvent;
+
+    fn get_events(&self) -> AllEventsBuilder<DynTransport, Self::Event> {
+        let mut events = self.contract.all_events();
+        events.filter = events.filter.address(self.pools.clone());
+        events
+    }
+}
+
+impl UniswapV3PoolEvents {
+    async fn fetch(&self, from_block: u64, to_block: BlockNumber) -> Result<Vec<PoolEvent>> {
+        let events = self
+            .get_events()
+            .from_block(from_block.into())
+            .to_block(to_block.block_number())
+            .block_page_size(500)
+            .query_paginated()
+            .await?;
+        futures::pin_mut!(events);
+        let mut pool_events = Vec::new();
+        while let Some(event) = events.next().await {
+            pool_events.push(PoolEvent::try_from(event?)?);
+        }
+        Ok(pool_events)
+    }
+
+    /// Calls `handle` with the events in `from_block..to_block` in chunks of at most
+    /// `SYNC_BLOCK_RANGE` blocks, along with the block the chunk ends before.
+    async fn fetch_finalized(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        mut handle: impl FnMut(Vec<PoolEvent>, u64),
+    ) -> Result<()> {
+        let mut chunk_start = from_block;
+        while chunk_start < to_block {
+            let chunk_end = to_block.min(chunk_start.saturating_add(SYNC_BLOCK_RANGE));
+            tracing::debug!(from = %chunk_start, to = %chunk_end, "fetching Uniswap V3 events");
+            let events = self
+                .fetch(chunk_start, BlockNumber::Specific(chunk_end - 1))
+                .await?;
+            handle(events, chunk_end);
+            chunk_start = chunk_end;
+        }
+        Ok(())
+    }
+}
+
+/// Static information about a tracked pool.
+struct PoolMetadata {
+    tokens: Vec<Token>,
+    fee: Ratio<u32>,
+}
+
+/// Uniswap V3 pool fetcher that indexes pool state from events instead of
+/// querying the subgraph.
+pub struct UniswapV3PoolIndexer {
+    web3: Web3,
+    events: UniswapV3PoolEvents,
+    pools: HashMap<H160, PoolMetadata>,
+    pools_by_token_pair: HashMap<TokenPair, HashSet<H160>>,
+    /// Only used by maintenance, which holds it while fetching events, so that updates don't
+    /// overlap.
+    store: Mutex<PoolEventStore>,
+    /// The current pool states that get swapped in after every update, so that fetching pools
+    /// never waits for the events to be indexed.
+    current_pools: std::sync::Mutex<Arc<HashMap<H160, Arc<IndexedPool>>>>,
+}
+
+impl UniswapV3PoolIndexer {
+    /// Creates a new indexer for all pools registered in the subgraph.
+    ///
+    /// If a checkpoint exists at `checkpoint_path`, indexing resumes from it.
+    /// Otherwise (and for pools that are missing from the checkpoint) events
+    /// get replayed from the Uniswap V3 factory deployment.
+    pub async fn new(
+        chain_id: u64,
+        client: Client,
+        web3: Web3,
+        checkpoint_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        let graph_api = UniV3SubgraphClient::for_chain(chain_id, client)?;
+        let registered_pools = graph_api.get_registered_pools().await?;
+        tracing::debug!(
+            block = %registered_pools.fetched_block_number, pools = %registered_pools.pools.len(),
+            "initialized registered pools",
+        );
+
+        let mut pools = HashMap::new();
+        let mut pools_by_token_pair: HashMap<TokenPair, HashSet<H160>> = HashMap::new();
+        for pool in registered_pools.pools {
+            let token0 = pool.token0.context("token0 does not exist")?;
+            let token1 = pool.token1.context("token1 does not exist")?;
+            let fee = pool.fee_tier.context("fee does not exist")?;
+
+            let pair = TokenPair::new(token0.id, token1.id).context("cant create pair")?;
+            pools_by_token_pair.entry(pair).or_default().insert(pool.id);
+            pools.insert(
+                pool.id,
+                PoolMetadata {
+                    tokens: vec![token0, token1],
+                    fee: Ratio::new(fee.as_u32(), 1_000_000u32),
+                },
+            );
+        }
+
+        let checkpoint = match &checkpoint_path {
+            Some(path) => Checkpoint::load(path).context("failed to load Uniswap V3 checkpoint")?,
+            None => None,
+        };
+        let events = UniswapV3PoolEvents {
+            contract: IUniswapV3Pool::at(&web3, H160::zero()),
+            pools: pools.keys().copied().collect(),
+        };
+        let mut checkpoint = match checkpoint {
+            Some(mut checkpoint) => {
+                let missing = pools
+                    .keys()
+                    .filter(|pool| !checkpoint.pools.contains_key(pool))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if !missing.is_empty() {
+                    tracing::debug!(pools = %missing.len(), "backfilling new Uniswap V3 pools");
+                    backfill(&mut checkpoint, &web3, missing).await?;
+                }
+                checkpoint
+            }
+            None => Checkpoint {
+                block_number: deployment_block(&web3).await?,
+                pools: Default::default(),
+            },
+        };
+        // Track every pool in the checkpoint, even the ones without events yet,
+        // so that they are not backfilled again on the next restart.
+        for pool in &events.pools {
+            checkpoint.pools.entry(*pool).or_default();
+        }
+
+        let indexer = Self {
+            web3,
+            events,
+            pools,
+            pools_by_token_pair,
+            store: Mutex::new(PoolEventStore::new(checkpoint, checkpoint_path)),
+            current_pools: Default::default(),
+        };
+        // Catch up before returning so that the indexer never serves outdated pool states.
+        indexer.update().await?;
+        Ok(indexer)
+    }
+
+    /// Indexes the events since the last update. Events that can no longer be reorged are folded
+    /// into the checkpoint chunk by chunk, so that only the last `MAX_REORG_BLOCK_COUNT` blocks of
+    /// events are kept in memory.
+    async fn update(&self) -> Result<()> {
+        let mut store = self.store.lock().await;
+        let current_block = self.web3.current_block_number().await?;
+        let finalized_block = current_block.saturating_sub(MAX_REORG_BLOCK_COUNT);
+
+        self.events
+            .fetch_finalized(
+                store.checkpoint.block_number,
+                finalized_block,
+                |events, block_number| store.finalize(events, block_number),
+            )
+            .await?;
+        let recent_events = self
+            .events
+            .fetch(
+                store.checkpoint.block_number,
+                BlockNumber::Latest(current_block),
+            )
+            .await?;
+        store.replace_recent(recent_events);
+        store.persist_checkpoint().await;
+
+        *self.current_pools.lock().unwrap() = Arc::new(store.pools.clone());
+        Ok(())
+    }
+}
+
+/// Replays the events of the specified pools from the factory deployment up to
+/// the checkpoint block.
+async fn backfill(checkpoint: &mut Checkpoint, web3: &Web3, pools: Vec<H160>) -> Result<()> {
+    let events = UniswapV3PoolEvents {
+        contract: IUniswapV3Pool::at(web3, H160::zero()),
+        pools,
+    };
+    events
+        .fetch_finalized(
+            deployment_block(web3).await?,
+            checkpoint.block_number,
+            |events, _| {
+                for event in events {
+                    checkpoint
+                        .pools
+                        .entry(event.pool)
+                        .or_default()
+                        .apply(&event.event);
+                }
+            },
+        )
+        .await
+}
+
+async fn deployment_block(web3: &Web3) -> Result<u64> {
+    let factory = IUniswapV3Factory::deployed(web3).await?;
+    match factory.deployment_information() {
+        Some(DeploymentInformation::BlockNumber(block)) => Ok(block),
+        _ => Err(anyhow!(
+            "missing Uniswap V3 factory deployment block, a checkpoint is required"
+        )),
+    }
+}
+
+fn pool_info(address: H160, metadata: &PoolMetadata, pool: &IndexedPool) -> PoolInfo {
+    PoolInfo {
+        address,
+        tokens: metadata.tokens.clone(),
+        state: PoolState {
+            sqrt_price: pool.sqrt_price,
+            liquidity: U256::from(pool.liquidity),
+            tick: BigInt::from(pool.tick),
+            liquidity_net: pool
+                .liquidity_net
+                .iter()
+                .map(|(tick, liquidity_net)| (BigInt::from(*tick), BigInt::from(*liquidity_net)))
+                .collect(),
+            fee: metadata.fee,
+        },
+        gas_stats: PoolStats {
+            mean_gas: U256::from(300_000), // todo: hardcoded for testing purposes
+        },
+    }
+}
+
+#[async_trait::async_trait]
+impl PoolFetching for UniswapV3PoolIndexer {
+    async fn fetch(&self, token_pairs: &HashSet<TokenPair>) -> Result<Vec<PoolInfo>> {
+        let current_pools = self.current_pools.lock().unwrap().clone();
+        Ok(token_pairs
+            .iter()
+            .filter_map(|pair| self.pools_by_token_pair.get(pair))
+            .flatten()
+            .filter_map(|address| {
+                let metadata = self.pools.get(address)?;
+                let pool = current_pools
+                    .get(address)
+                    .filter(|pool| pool.is_initialized())?;
+                Some(pool_info(*address, metadata, pool))
+            })
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Maintaining for UniswapV3PoolIndexer {
+    async fn run_maintenance(&self) -> Result<()> {
+        self.update().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::iuniswap_v3_pool::event_data::{Burn, Initialize, Mint, Swap};
+    use maplit::btreemap;
+
+    fn initialize(tick: i32) -> UniswapV3PoolEvent {
+        UniswapV3PoolEvent::Initialize(Initialize {
+            sqrt_price_x96: 1.into(),
+            tick,
+        })
+    }
+
+    fn mint(tick_lower: i32, tick_upper: i32, amount: u128) -> UniswapV3PoolEvent {
+        UniswapV3PoolEvent::Mint(Mint {
+            tick_lower,
+            tick_upper,
+            amount,
+            ..Default::default()
+        })
+    }
+
+    fn burn(tick_lower: i32, tick_upper: i32, amount: u128) -> UniswapV3PoolEvent {
+        UniswapV3PoolEvent::Burn(Burn {
+            tick_lower,
+            tick_upper,
+            amount,
+            ..Default::default()
+        })
+    }
+
+    fn swap(tick: i32, liquidity: u128) -> UniswapV3PoolEvent {
+        UniswapV3PoolEvent::Swap(Swap {
+            sqrt_price_x96: 2.into(),
+            liquidity,
+            tick,
+            ..Default::default()
+        })
+    }
+
+    fn pool_event(block_number: u64, pool: H160, event: UniswapV3PoolEvent) -> PoolEvent {
+        PoolEvent {
+            index: EventIndex::new(block_number, 0),
+            pool,
+            event,
+        }
+    }
+
+    #[test]
+    fn applies_pool_events() {
+        let mut pool = IndexedPool::default();
+        for event in [
+            initialize(0),
+            mint(-10, 10, 100),
+            mint(10, 20, 50),
+            burn(-10, 10, 40),
+        ] {
+            pool.apply(&event);
+        }
+        assert_eq!(pool.sqrt_price, 1.into());
+        assert_eq!(pool.liquidity, 60);
+        assert_eq!(
+            pool.liquidity_net,
+            btreemap! { -10 => 60, 10 => -10, 20 => -50 }
+        );
+
+        pool.apply(&swap(15, 50));
+        pool.apply(&burn(10, 20, 50));
+        assert_eq!(pool.sqrt_price, 2.into());
+        assert_eq!(pool.tick, 15);
+        assert_eq!(pool.liquidity, 0);
+        assert_eq!(pool.liquidity_net, btreemap! { -10 => 60, 10 => -60 });
+    }
+
+    #[test]
+    fn replays_recent_events_on_reorg() {
+        let address = H160([1; 20]);
+        let mut store = PoolEventStore::new(Checkpoint::default(), None);
+        let events = vec![
+            pool_event(1, address, initialize(0)),
+            pool_event(2, address, mint(-10, 10, 100)),
+        ];
+        store.replace_recent(
+            [
+                events.clone(),
+                vec![pool_event(3, address, mint(-10, 10, 100))],
+            ]
+            .concat(),
+        );
+        assert_eq!(store.pool(&address).unwrap().liquidity, 200);
+
+        // Block 3 gets reorged and replaced by a block with a burn.
+        store.replace_recent(
+            [
+                events.clone(),
+                vec![pool_event(3, address, burn(-10, 10, 30))],
+            ]
+            .concat(),
+        );
+        assert_eq!(store.pool(&address).unwrap().liquidity, 70);
+
+        // Block 3 gets reorged again without any events.
+        store.replace_recent(events);
+        assert_eq!(store.pool(&address).unwrap().liquidity, 100);
+    }
+
+    #[test]
+    fn finalizes_events_into_checkpoint() {
+        let address = H160([1; 20]);
+        let mut store = PoolEventStore::new(Checkpoint::default(), None);
+        store.replace_recent(vec![
+            pool_event(1, address, initialize(0)),
+            pool_event(2, address, mint(-10, 10, 100)),
+            pool_event(3, address, mint(-10, 10, 100)),
+        ]);
+
+        store.finalize(
+            vec![
+                pool_event(1, address, initialize(0)),
+                pool_event(2, address, mint(-10, 10, 100)),
+            ],
+            3,
+        );
+        assert_eq!(store.checkpoint.block_number, 3);
+        assert_eq!(store.checkpoint.pools[&address].liquidity, 100);
+        assert_eq!(store.recent_events[&address].len(), 1);
+        assert_eq!(store.pool(&address).unwrap().liquidity, 200);
+
+        // Replayed events that are already part of the checkpoint are ignored.
+        store.finalize(vec![pool_event(2, address, mint(-10, 10, 100))], 3);
+        store.replace_recent(vec![
+            pool_event(2, address, mint(-10, 10, 100)),
+            pool_event(3, address, mint(-10, 10, 100)),
+        ]);
+        assert_eq!(store.pool(&address).unwrap().liquidity, 200);
+    }
+
+    #[test]
+    fn converts_indexed_pool_to_pool_info() {
+        let metadata = PoolMetadata {
+            tokens: vec![],
+            fee: Ratio::new(3000, 1_000_000),
+        };
+        let pool = IndexedPool {
+            sqrt_price: 1.into(),
+            liquidity: 100,
+            tick: 5,
+            liquidity_net: btreemap! { -10 => 100, 10 => -100 },
+        };
+        let info = pool_info(H160([1; 20]), &metadata, &pool);
+        assert_eq!(info.state.liquidity, 100.into());
+        assert_eq!(info.state.tick, BigInt::from(5));
+        assert_eq!(
+            info.state.liquidity_net,
+            vec![
+                (BigInt::from(-10), BigInt::from(100)),
+                (BigInt::from(10), BigInt::from(-100))
+            ]
+        );
+    }
+}
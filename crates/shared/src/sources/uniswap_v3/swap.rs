@@ -0,0 +1,555 @@
+//! Local Uniswap V3 swap math.
+//!
+//! This is a port of the `TickMath`, `SqrtPriceMath` and `SwapMath` libraries of the Uniswap V3
+//! core contracts:
+//! https://github.com/Uniswap/v3-core/tree/ed88be38ab2032d82bf10ac6f8d03aa631889d48/contracts/libraries
+//!
+//! The main difference to the contracts is that swaps step directly from one initialized tick to
+//! the next instead of stepping through the tick bitmap word by word. This only affects rounding
+//! of the intermediate steps and makes the results very close but not always identical to the
+//! amounts computed on-chain.
+
+use super::pool_fetching::PoolInfo;
+use crate::baseline_solver::BaselineSolvable;
+use ethcontract::{H160, U256};
+use num::ToPrimitive;
+use primitive_types::U512;
+
+// See https://dune.com/queries/1044812 for the cost of single pool swaps through the router.
+const SWAP_GAS_COST: usize = 106_000;
+
+const MIN_TICK: i32 = -887_272;
+const MAX_TICK: i32 = -MIN_TICK;
+
+/// The fee denominator of Uniswap V3 pools, fees are expressed in hundredths of a basis point.
+const FEE_DENOMINATOR: u32 = 1_000_000;
+
+fn q96() -> U256 {
+    U256::one() << 96
+}
+
+fn min_sqrt_ratio() -> U256 {
+    U256::from(4_295_128_739u64)
+}
+
+fn max_sqrt_ratio() -> U256 {
+    U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap()
+}
+
+fn mul_div(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    U256::try_from(a.full_mul(b) / U512::from(denominator)).ok()
+}
+
+fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let product = a.full_mul(b);
+    let denominator = U512::from(denominator);
+    let mut result = product / denominator;
+    if !(product % denominator).is_zero() {
+        result += U512::one();
+    }
+    U256::try_from(result).ok()
+}
+
+fn div_rounding_up(a: U256, b: U256) -> Option<U256> {
+    let (quotient, remainder) = a.checked_div(b).map(|quotient| (quotient, a % b))?;
+    if remainder.is_zero() {
+        Some(quotient)
+    } else {
+        quotient.checked_add(U256::one())
+    }
+}
+
+/// Computes `sqrt(1.0001^tick) * 2^96` exactly like `TickMath.getSqrtRatioAtTick`.
+fn sqrt_ratio_at_tick(tick: i32) -> Option<U256> {
+    const FACTORS: [(u32, u128); 19] = [
+        (0x2, 0xfff97272373d413259a46990580e213a),
+        (0x4, 0xfff2e50f5f656932ef12357cf3c7fdcc),
+        (0x8, 0xffe5caca7e10e4e61c3624eaa0941cd0),
+        (0x10, 0xffcb9843d60f6159c9db58835c926644),
+        (0x20, 0xff973b41fa98c081472e6896dfb254c0),
+        (0x40, 0xff2ea16466c96a3843ec78b326b52861),
+        (0x80, 0xfe5dee046a99a2a811c461f1969c3053),
+        (0x100, 0xfcbe86c7900a88aedcffc83b479aa3a4),
+        (0x200, 0xf987a7253ac413176f2b074cf7815e54),
+        (0x400, 0xf3392b0822b70005940c7a398e4b70f3),
+        (0x800, 0xe7159475a2c29b7443b29c7fa6e889d9),
+        (0x1000, 0xd097f3bdfd2022b8845ad8f792aa5825),
+        (0x2000, 0xa9f746462d870fdf8a65dc1f90e061e5),
+        (0x4000, 0x70d869a156d2a1b890bb3df62baf32f7),
+        (0x8000, 0x31be135f97d08fd981231505542fcfa6),
+        (0x10000, 0x9aa508b5b7a84e1c677de54f3e99bc9),
+        (0x20000, 0x5d6af8dedb81196699c329225ee604),
+        (0x40000, 0x2216e584f5fa1ea926041bedfe98),
+        (0x80000, 0x48a170391f7dc42444e8fa2),
+    ];
+
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        return None;
+    }
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+    } else {
+        U256::one() << 128
+    };
+    for (bit, factor) in FACTORS {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * U256::from(factor)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Round up when converting from Q128.128 to Q64.96 so that the ratio is never lower than the
+    // exact value.
+    let rounding = if (ratio & U256::from(u32::MAX)).is_zero() {
+        U256::zero()
+    } else {
+        U256::one()
+    };
+    Some((ratio >> 32) + rounding)
+}
+
+/// `SqrtPriceMath.getAmount0Delta`
+fn amount0_delta(
+    sqrt_ratio_a: U256,
+    sqrt_ratio_b: U256,
+    liquidity: U256,
+    round_up: bool,
+) -> Option<U256> {
+    let (lower, upper) = if sqrt_ratio_a > sqrt_ratio_b {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    } else {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    };
+    if lower.is_zero() {
+        return None;
+    }
+    let numerator1 = liquidity.checked_mul(q96())?;
+    let numerator2 = upper - lower;
+    if round_up {
+        div_rounding_up(mul_div_rounding_up(numerator1, numerator2, upper)?, lower)
+    } else {
+        Some(mul_div(numerator1, numerator2, upper)? / lower)
+    }
+}
+
+/// `SqrtPriceMath.getAmount1Delta`
+fn amount1_delta(
+    sqrt_ratio_a: U256,
+    sqrt_ratio_b: U256,
+    liquidity: U256,
+    round_up: bool,
+) -> Option<U256> {
+    let difference = if sqrt_ratio_a > sqrt_ratio_b {
+        sqrt_ratio_a - sqrt_ratio_b
+    } else {
+        sqrt_ratio_b - sqrt_ratio_a
+    };
+    if round_up {
+        mul_div_rounding_up(liquidity, difference, q96())
+    } else {
+        mul_div(liquidity, difference, q96())
+    }
+}
+
+/// `SqrtPriceMath.getNextSqrtPriceFromAmount0RoundingUp`
+fn next_sqrt_price_from_amount0(
+    sqrt_price: U256,
+    liquidity: U256,
+    amount: U256,
+    add: bool,
+) -> Option<U256> {
+    if amount.is_zero() {
+        return Some(sqrt_price);
+    }
+    let numerator1 = liquidity.checked_mul(q96())?;
+    let product = amount.full_mul(sqrt_price);
+    let denominator = if add {
+        U512::from(numerator1) + product
+    } else {
+        U512::from(numerator1)
+            .checked_sub(product)
+            .filter(|denominator| !denominator.is_zero())?
+    };
+    let numerator = U512::from(numerator1) * U512::from(sqrt_price);
+    let result = (numerator + denominator - U512::one()) / denominator;
+    U256::try_from(result).ok()
+}
+
+/// `SqrtPriceMath.getNextSqrtPriceFromAmount1RoundingDown`
+fn next_sqrt_price_from_amount1(
+    sqrt_price: U256,
+    liquidity: U256,
+    amount: U256,
+    add: bool,
+) -> Option<U256> {
+    if add {
+        let quotient = mul_div(amount, q96(), liquidity)?;
+        sqrt_price.checked_add(quotient)
+    } else {
+        let quotient = mul_div_rounding_up(amount, q96(), liquidity)?;
+        sqrt_price
+            .checked_sub(quotient)
+            .filter(|price| !price.is_zero())
+    }
+}
+
+/// The result of a single swap step within a range of constant liquidity.
+struct SwapStep {
+    sqrt_price_next: U256,
+    amount_in: U256,
+    amount_out: U256,
+    fee_amount: U256,
+}
+
+/// `SwapMath.computeSwapStep`
+///
+/// The amount remaining is the input amount for exact input swaps and the output amount for exact
+/// output swaps.
+fn compute_swap_step(
+    sqrt_price_current: U256,
+    sqrt_price_target: U256,
+    liquidity: U256,
+    amount_remaining: U256,
+    exact_in: bool,
+    fee_pips: u32,
+) -> Option<SwapStep> {
+    let zero_for_one = sqrt_price_current >= sqrt_price_target;
+    let fee_pips = U256::from(fee_pips);
+    let fee_complement = U256::from(FEE_DENOMINATOR) - fee_pips;
+
+    let (sqrt_price_next, max_amount) = if exact_in {
+        let amount_remaining_less_fee =
+            mul_div(amount_remaining, fee_complement, FEE_DENOMINATOR.into())?;
+        let amount_in = if zero_for_one {
+            amount0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+        } else {
+            amount1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)?
+        };
+        let sqrt_price_next = if amount_remaining_less_fee >= amount_in {
+            sqrt_price_target
+        } else if zero_for_one {
+            next_sqrt_price_from_amount0(
+                sqrt_price_current,
+                liquidity,
+                amount_remaining_less_fee,
+                true,
+            )?
+        } else {
+            next_sqrt_price_from_amount1(
+                sqrt_price_current,
+                liquidity,
+                amount_remaining_less_fee,
+                true,
+            )?
+        };
+        (sqrt_price_next, amount_in)
+    } else {
+        let amount_out = if zero_for_one {
+            amount1_delta(sqrt_price_target, sqrt_price_current, liquidity, false)?
+        } else {
+            amount0_delta(sqrt_price_current, sqrt_price_target, liquidity, false)?
+        };
+        let sqrt_price_next = if amount_remaining >= amount_out {
+            sqrt_price_target
+        } else if zero_for_one {
+            next_sqrt_price_from_amount1(sqrt_price_current, liquidity, amount_remaining, false)?
+        } else {
+            next_sqrt_price_from_amount0(sqrt_price_current, liquidity, amount_remaining, false)?
+        };
+        (sqrt_price_next, amount_out)
+    };
+
+    let max = sqrt_price_target == sqrt_price_next;
+    let (amount_in, mut amount_out) = if zero_for_one {
+        (
+            if max && exact_in {
+                max_amount
+            } else {
+                amount0_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?
+            },
+            if max && !exact_in {
+                max_amount
+            } else {
+                amount1_delta(sqrt_price_next, sqrt_price_current, liquidity, false)?
+            },
+        )
+    } else {
+        (
+            if max && exact_in {
+                max_amount
+            } else {
+                amount1_delta(sqrt_price_current, sqrt_price_next, liquidity, true)?
+            },
+            if max && !exact_in {
+                max_amount
+            } else {
+                amount0_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?
+            },
+        )
+    };
+
+    // Cap the output amount to not exceed the remaining output amount.
+    if !exact_in && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    let fee_amount = if exact_in && sqrt_price_next != sqrt_price_target {
+        // We didn't reach the target, so take the remainder of the maximum input as fee.
+        amount_remaining.checked_sub(amount_in)?
+    } else {
+        mul_div_rounding_up(amount_in, fee_pips, fee_complement)?
+    };
+
+    Some(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+impl PoolInfo {
+    /// Simulates a swap of `amount` crossing initialized ticks as needed.
+    ///
+    /// Returns the computed amount, i.e. the output amount for exact input swaps and the input
+    /// amount including fees for exact output swaps. Returns `None` if the pool doesn't have
+    /// enough liquidity to fully execute the swap.
+    fn swap(&self, zero_for_one: bool, amount: U256, exact_in: bool) -> Option<U256> {
+        let fee_pips = (self.state.fee * FEE_DENOMINATOR).to_integer();
+        let mut ticks = self
+            .state
+            .liquidity_net
+            .iter()
+            .map(|(tick, liquidity_net)| Some((tick.to_i32()?, liquidity_net.to_i128()?)))
+            .collect::<Option<Vec<_>>>()?;
+        ticks.sort_unstable_by_key(|(tick, _)| *tick);
+
+        let mut sqrt_price = self.state.sqrt_price;
+        let mut tick = self.state.tick.to_i32()?;
+        let mut liquidity = self.state.liquidity;
+        let mut amount_remaining = amount;
+        let mut amount_calculated = U256::zero();
+
+        while !amount_remaining.is_zero() {
+            // The next initialized tick in the swap direction, like in the contract's tick bitmap
+            // search the current tick is included when moving down.
+            let next_tick = if zero_for_one {
+                ticks.iter().rev().find(|(tick_, _)| *tick_ <= tick)
+            } else {
+                ticks.iter().find(|(tick_, _)| *tick_ > tick)
+            };
+            let sqrt_price_target = match next_tick {
+                Some((tick, _)) => sqrt_ratio_at_tick(*tick)?,
+                None if zero_for_one => min_sqrt_ratio() + 1,
+                None => max_sqrt_ratio() - 1,
+            };
+            let step = compute_swap_step(
+                sqrt_price,
+                sqrt_price_target,
+                liquidity,
+                amount_remaining,
+                exact_in,
+                fee_pips,
+            )?;
+            if exact_in {
+                amount_remaining =
+                    amount_remaining.checked_sub(step.amount_in + step.fee_amount)?;
+                amount_calculated = amount_calculated.checked_add(step.amount_out)?;
+            } else {
+                amount_remaining = amount_remaining.checked_sub(step.amount_out)?;
+                amount_calculated =
+                    amount_calculated.checked_add(step.amount_in + step.fee_amount)?;
+            }
+            sqrt_price = step.sqrt_price_next;
+
+            if sqrt_price != sqrt_price_target {
+                // The remaining amount was used up before reaching the next tick.
+                break;
+            }
+            let (next_tick, liquidity_net) = match next_tick {
+                Some(next_tick) => *next_tick,
+                // Swapped all the way to the price limit without using up the amount.
+                None => return None,
+            };
+            liquidity = if zero_for_one {
+                apply_liquidity_delta(liquidity, -liquidity_net)?
+            } else {
+                apply_liquidity_delta(liquidity, liquidity_net)?
+            };
+            tick = if zero_for_one {
+                next_tick - 1
+            } else {
+                next_tick
+            };
+        }
+
+        Some(amount_calculated)
+    }
+
+    /// Returns whether swapping from `in_token` to `out_token` is swapping token0 for token1.
+    fn zero_for_one(&self, in_token: H160, out_token: H160) -> Option<bool> {
+        match self.tokens.as_slice() {
+            [token0, token1] if token0.id == in_token && token1.id == out_token => Some(true),
+            [token0, token1] if token1.id == in_token && token0.id == out_token => Some(false),
+            _ => None,
+        }
+    }
+}
+
+fn apply_liquidity_delta(liquidity: U256, delta: i128) -> Option<U256> {
+    if delta < 0 {
+        liquidity.checked_sub(delta.unsigned_abs().into())
+    } else {
+        liquidity.checked_add((delta as u128).into())
+    }
+}
+
+impl BaselineSolvable for PoolInfo {
+    fn get_amount_out(&self, out_token: H160, (in_amount, in_token): (U256, H160)) -> Option<U256> {
+        let zero_for_one = self.zero_for_one(in_token, out_token)?;
+        self.swap(zero_for_one, in_amount, true)
+            .filter(|amount| !amount.is_zero())
+    }
+
+    fn get_amount_in(&self, in_token: H160, (out_amount, out_token): (U256, H160)) -> Option<U256> {
+        let zero_for_one = self.zero_for_one(in_token, out_token)?;
+        self.swap(zero_for_one, out_amount, false)
+    }
+
+    fn gas_cost(&self) -> usize {
+        SWAP_GAS_COST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::uniswap_v3::{
+        graph_api::Token,
+        pool_fetching::{PoolState, PoolStats},
+    };
+    use num::{rational::Ratio, BigInt};
+
+    fn pool(
+        sqrt_price: U256,
+        tick: i32,
+        liquidity: u128,
+        liquidity_net: &[(i32, i128)],
+    ) -> PoolInfo {
+        PoolInfo {
+            address: H160::from_low_u64_be(1),
+            tokens: vec![
+                Token {
+                    id: H160::from_low_u64_be(2),
+                    symbol: "A".to_string(),
+                    decimals: 18,
+                },
+                Token {
+                    id: H160::from_low_u64_be(3),
+                    symbol: "B".to_string(),
+                    decimals: 18,
+                },
+            ],
+            state: PoolState {
+                sqrt_price,
+                liquidity: liquidity.into(),
+                tick: tick.into(),
+                liquidity_net: liquidity_net
+                    .iter()
+                    .map(|(tick, net)| (BigInt::from(*tick), BigInt::from(*net)))
+                    .collect(),
+                fee: Ratio::new(3, 1000),
+            },
+            gas_stats: PoolStats {
+                mean_gas: 300_000.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn sqrt_ratio_at_tick_matches_contract() {
+        assert_eq!(sqrt_ratio_at_tick(0).unwrap(), q96());
+        assert_eq!(sqrt_ratio_at_tick(MIN_TICK).unwrap(), min_sqrt_ratio());
+        assert_eq!(sqrt_ratio_at_tick(MAX_TICK).unwrap(), max_sqrt_ratio());
+        assert_eq!(
+            sqrt_ratio_at_tick(1).unwrap(),
+            U256::from_dec_str("79232123823359799118286999568").unwrap()
+        );
+        assert!(sqrt_ratio_at_tick(MIN_TICK - 1).is_none());
+        assert!(sqrt_ratio_at_tick(MAX_TICK + 1).is_none());
+    }
+
+    #[test]
+    fn swap_within_single_tick_range() {
+        let token_a = H160::from_low_u64_be(2);
+        let token_b = H160::from_low_u64_be(3);
+        // Price 1 with liquidity in the range [-600, 600].
+        let pool = pool(
+            q96(),
+            0,
+            10u128.pow(18),
+            &[(-600, 10i128.pow(18)), (600, -(10i128.pow(18)))],
+        );
+
+        // Like a constant product pool with 1e18 of each token for small amounts.
+        let out = pool
+            .get_amount_out(token_b, (10u128.pow(15).into(), token_a))
+            .unwrap();
+        assert_eq!(out, U256::from_dec_str("996006981039903").unwrap());
+        let out = pool
+            .get_amount_out(token_a, (10u128.pow(15).into(), token_b))
+            .unwrap();
+        assert_eq!(out, U256::from_dec_str("996006981039903").unwrap());
+
+        let amount_in = pool.get_amount_in(token_a, (out, token_b)).unwrap();
+        assert_eq!(amount_in, 10u128.pow(15).into());
+    }
+
+    #[test]
+    fn swap_crosses_ticks() {
+        let token_a = H160::from_low_u64_be(2);
+        let token_b = H160::from_low_u64_be(3);
+        let liquidity = 10u128.pow(18);
+        // A narrow range with liquidity around the current price and a wider one with more
+        // liquidity below.
+        let pool = pool(
+            q96(),
+            0,
+            liquidity,
+            &[
+                (-6000, 4 * liquidity as i128),
+                (-60, -3 * liquidity as i128),
+                (60, -(liquidity as i128)),
+            ],
+        );
+
+        let amount = U256::from(10u128.pow(17));
+        let out = pool.get_amount_out(token_b, (amount, token_a)).unwrap();
+        assert_eq!(out, U256::from_dec_str("96850541916706555").unwrap());
+        let amount_in = pool.get_amount_in(token_a, (out, token_b)).unwrap();
+        assert!(amount_in <= amount && amount - amount_in < 10.into());
+
+        // The range above the current price only holds a limited amount of token A.
+        assert!(pool.get_amount_out(token_a, (amount, token_b)).is_none());
+        assert!(pool.get_amount_in(token_b, (amount, token_a)).is_none());
+    }
+
+    #[test]
+    fn swap_requires_pool_tokens() {
+        let pool = pool(q96(), 0, 10u128.pow(18), &[]);
+        let token_a = H160::from_low_u64_be(2);
+        let other = H160::from_low_u64_be(4);
+        assert!(pool.get_amount_out(other, (1.into(), token_a)).is_none());
+        assert!(pool.get_amount_in(other, (1.into(), token_a)).is_none());
+    }
+}
@@ -1,11 +1,12 @@
 use crate::{
     settlement_access_list::AccessListEstimatorType,
+    settlement_rater::SettlementScoring,
     solver::{ExternalSolverArg, SolverAccountArg, SolverType},
 };
 use primitive_types::H160;
 use reqwest::Url;
 use shared::arguments::{display_list, display_option};
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 #[derive(clap::Parser)]
 pub struct Arguments {
@@ -83,7 +84,10 @@ pub struct Arguments {
     )]
     pub solver_accounts: Option<Vec<SolverAccountArg>>,
 
-    /// List of external solvers in the form of `name|url|account`.
+    /// List of external solvers in the form of `name|url|account` or
+    /// `name|url|account|version`, where the optional version of the batch
+    /// auction instance format sent to the solver is either `v1` (default) or
+    /// `v2`.
     #[clap(long, env, use_value_delimiter = true)]
     pub external_solvers: Option<Vec<ExternalSolverArg>>,
 
@@ -149,6 +153,12 @@ pub struct Arguments {
     #[clap(long, env)]
     pub oneinch_max_slippage_in_eth: Option<f64>,
 
+    /// Whether the ring trade solver may close the residuals of rings whose amounts don't line up
+    /// exactly by trading through AMMs. If disabled, only rings that can be settled purely from
+    /// the traders' funds are used.
+    #[clap(long, env, parse(try_from_str), default_value = "true")]
+    pub ring_trade_solver_close_residuals: bool,
+
     /// How to to submit settlement transactions.
     /// Expected to contain either:
     /// 1. One value equal to TransactionStrategyArg::DryRun or
@@ -277,6 +287,51 @@ pub struct Arguments {
     /// in the settlement are checked for price deviation.
     #[clap(long, env, use_value_delimiter = true)]
     pub token_list_restriction_for_price_checks: Option<Vec<H160>>,
+
+    /// How to rank settlements in the solver competition.
+    /// `ObjectiveValue`: surplus plus fees minus gas cost.
+    /// `RiskAdjusted`: like `ObjectiveValue` but surplus and fees are weighted by the probability
+    /// of the settlement not reverting, estimated from the outcomes of past submissions.
+    #[clap(
+        long,
+        env,
+        default_value = "ObjectiveValue",
+        arg_enum,
+        ignore_case = true
+    )]
+    pub settlement_scoring: SettlementScoring,
+
+    /// If set, a self-contained snapshot of every auction passed to the solvers is written to this
+    /// directory. Snapshots can be solved again offline with the `solver_benchmark` binary.
+    #[clap(long, env)]
+    pub auction_snapshot_dir: Option<PathBuf>,
+
+    /// Rate settlements by the surplus observed when tracing their simulation instead of the
+    /// surplus they claim, which accounts for fee-on-transfer tokens and similar. Requires a node
+    /// supporting the trace_callMany API.
+    #[clap(long, env)]
+    pub trace_settlement_balance_changes: bool,
+
+    /// If set, every user trade of a settlement is compared against the baseline price estimate
+    /// for the same amount (EBBO) and violations are reported per solver. The value is the
+    /// tolerated price deviation as a fraction, e.g. 0.01 for 1%.
+    #[clap(long, env, parse(try_from_str = shared::arguments::parse_percentage_factor))]
+    pub ebbo_tolerance: Option<f64>,
+
+    /// Drop settlements violating EBBO instead of only reporting them. Has no effect unless
+    /// `--ebbo-tolerance` is set.
+    #[clap(long, env)]
+    pub reject_ebbo_violations: bool,
+
+    /// Drop AMM swaps of winning settlements whose output can be paid from the settlement
+    /// contract's buffers instead. Only swaps between `--trusted-buffer-tokens` are internalized
+    /// and settlements of Quasimodo and Mip only use the buffers if they are configured to.
+    #[clap(long, env)]
+    pub internalize_buffers: bool,
+
+    /// The tokens the settlement contract's buffers may be traded in when internalizing AMM swaps.
+    #[clap(long, env, use_value_delimiter = true)]
+    pub trusted_buffer_tokens: Vec<H160>,
 }
 
 impl std::fmt::Display for Arguments {
@@ -306,6 +361,11 @@ impl std::fmt::Display for Arguments {
         writeln!(f, "paraswap_slippage_bps: {}", self.paraswap_slippage_bps)?;
         writeln!(f, "zeroex_slippage_bps: {}", self.zeroex_slippage_bps)?;
         writeln!(f, "oneinch_slippage_bps: {}", self.oneinch_slippage_bps)?;
+        writeln!(
+            f,
+            "ring_trade_solver_close_residuals: {}",
+            self.ring_trade_solver_close_residuals
+        )?;
         writeln!(f, "transaction_strategy: {:?}", self.transaction_strategy)?;
         writeln!(
             f,
@@ -375,6 +435,19 @@ impl std::fmt::Display for Arguments {
             "token_list_restriction_for_price_checks: {:?}",
             self.token_list_restriction_for_price_checks
         )?;
+        writeln!(f, "settlement_scoring: {:?}", self.settlement_scoring)?;
+        writeln!(f, "auction_snapshot_dir: {:?}", self.auction_snapshot_dir)?;
+        writeln!(
+            f,
+            "trace_settlement_balance_changes: {}",
+            self.trace_settlement_balance_changes
+        )?;
+        write!(f, "ebbo_tolerance: ")?;
+        display_option(&self.ebbo_tolerance, f)?;
+        writeln!(f)?;
+        writeln!(f, "reject_ebbo_violations: {}", self.reject_ebbo_violations)?;
+        writeln!(f, "internalize_buffers: {}", self.internalize_buffers)?;
+        writeln!(f, "trusted_buffer_tokens: {:?}", self.trusted_buffer_tokens)?;
         Ok(())
     }
 }
@@ -1,9 +1,56 @@
 //! Submodule containing helper methods to pre-process auction data before passing it on to the solvers.
 
 use crate::liquidity::LimitOrder;
+use chrono::Utc;
+use model::order::Order;
+use std::time::Duration;
 
-// vk: I would like to extend this to also check that the order has minimum age but for this we need
-// access to the creation date which is a more involved change.
 pub fn has_at_least_one_user_order(orders: &[LimitOrder]) -> bool {
     orders.iter().any(|order| !order.is_liquidity_order)
 }
+
+/// Whether any user order is old enough to get settled. Younger orders can only be settled
+/// together with mature ones (see `retain_mature_settlements`), so there is nothing to solve for
+/// otherwise.
+pub fn has_at_least_one_mature_user_order(orders: &[Order], min_order_age: Duration) -> bool {
+    let settle_orders_older_than = Utc::now() - chrono::Duration::from_std(min_order_age).unwrap();
+    orders.iter().any(|order| {
+        !order.metadata.is_liquidity_order
+            && order.metadata.creation_date <= settle_orders_older_than
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::order::OrderMetadata;
+
+    fn order(age: Duration, is_liquidity_order: bool) -> Order {
+        Order {
+            metadata: OrderMetadata {
+                creation_date: Utc::now() - chrono::Duration::from_std(age).unwrap(),
+                is_liquidity_order,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn requires_mature_user_order() {
+        let min_order_age = Duration::from_secs(30);
+        let young = order(Duration::from_secs(10), false);
+        let mature = order(Duration::from_secs(60), false);
+        let mature_liquidity = order(Duration::from_secs(60), true);
+
+        assert!(!has_at_least_one_mature_user_order(&[], min_order_age));
+        assert!(!has_at_least_one_mature_user_order(
+            &[young.clone(), mature_liquidity],
+            min_order_age
+        ));
+        assert!(has_at_least_one_mature_user_order(
+            &[young, mature],
+            min_order_age
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+//! Replaying of past auctions in order to reproduce what solvers would have found for them.
+//!
+//! An auction gets replayed by fetching liquidity at the block at which it was originally
+//! collected, running the solvers against it and simulating the resulting settlements at the block
+//! at which the solver competition was originally simulated. This requires an archive node.
+
+use crate::{
+    driver::solver_settlements::RatedSettlement,
+    liquidity::order_converter::OrderConverter,
+    liquidity_collector::LiquidityCollector,
+    orderbook::OrderBookApi,
+    settlement::{external_prices::ExternalPrices, Settlement},
+    settlement_simulation::simulate_and_estimate_gas,
+    solver::{Auction, Solver, Solvers},
+};
+use anyhow::{anyhow, Context as _, Result};
+use contracts::GPv2Settlement;
+use ethcontract::errors::ExecutionError;
+use futures::future::{join_all, try_join_all};
+use model::{order::Order, solver_competition::SolverCompetitionId};
+use num::BigRational;
+use primitive_types::H160;
+use shared::{recent_block_cache::Block, Web3};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use web3::types::BlockNumber;
+
+/// An auction along with the conditions under which it was originally solved.
+#[derive(Clone, Debug)]
+pub struct RecordedAuction {
+    pub auction: model::auction::Auction,
+    /// The block at which liquidity gets fetched.
+    pub liquidity_block: u64,
+    /// The block on top of which settlements get simulated.
+    pub simulation_block: u64,
+    /// The gas price used for solving and computing objective values.
+    pub gas_price: Option<f64>,
+}
+
+impl RecordedAuction {
+    /// Replays an auction as returned by the orderbook at its own block. The gas price is unknown
+    /// and has to be specified separately.
+    pub fn from_auction(auction: model::auction::Auction) -> Self {
+        Self {
+            liquidity_block: auction.block,
+            simulation_block: auction.block,
+            gas_price: None,
+            auction,
+        }
+    }
+
+    /// Loads the auction of a recorded solver competition.
+    ///
+    /// Competitions only store the order UIDs, so the orders get fetched from the orderbook. Since
+    /// the orderbook only knows their current fill state, the orders are replayed as unfilled.
+    pub async fn from_solver_competition(
+        api: &OrderBookApi,
+        id: SolverCompetitionId,
+    ) -> Result<Self> {
+        let competition = api
+            .get_solver_competition(id)
+            .await
+            .context("failed to fetch solver competition")?;
+        let orders = try_join_all(competition.auction.orders.iter().map(|uid| async move {
+            api.get_order(uid)
+                .await
+                .with_context(|| format!("failed to fetch order {}", uid))
+        }))
+        .await?;
+
+        Ok(Self {
+            auction: model::auction::Auction {
+                block: competition.auction_start_block,
+                next_solver_competition: id,
+                orders: orders.into_iter().map(unfilled).collect(),
+                prices: competition.auction.prices,
+                ..Default::default()
+            },
+            liquidity_block: competition.liquidity_collected_block,
+            simulation_block: competition.competition_simulation_block,
+            gas_price: Some(competition.gas_price),
+        })
+    }
+}
+
+fn unfilled(mut order: Order) -> Order {
+    order.metadata.executed_buy_amount = Default::default();
+    order.metadata.executed_sell_amount = Default::default();
+    order.metadata.executed_sell_amount_before_fees = Default::default();
+    order.metadata.executed_fee_amount = Default::default();
+    order
+}
+
+/// The outcome of replaying an auction with a single solver.
+pub struct SolverReplay {
+    pub solver: Arc<dyn Solver>,
+    pub settlements: Result<Vec<ReplayedSettlement>>,
+}
+
+pub enum ReplayedSettlement {
+    /// The settlement simulated successfully and got rated at the recorded gas price.
+    Rated(RatedSettlement),
+    /// The settlement would have reverted.
+    Reverted(Settlement, ExecutionError),
+}
+
+pub struct AuctionReplayer {
+    pub liquidity_collector: LiquidityCollector,
+    pub solvers: Solvers,
+    pub order_converter: OrderConverter,
+    pub native_token: H160,
+    pub settlement_contract: GPv2Settlement,
+    pub web3: Web3,
+    pub solver_time_limit: Duration,
+}
+
+impl AuctionReplayer {
+    pub async fn replay(&self, recorded: RecordedAuction) -> Result<Vec<SolverReplay>> {
+        let gas_price = recorded
+            .gas_price
+            .ok_or_else(|| anyhow!("unknown gas price for replayed auction"))?;
+        let orders = recorded
+            .auction
+            .orders
+            .into_iter()
+            .map(|order| self.order_converter.normalize_limit_order(order))
+            .collect::<Result<Vec<_>>>()?;
+        let external_prices =
+            ExternalPrices::try_from_auction_prices(self.native_token, recorded.auction.prices)
+                .context("malformed auction prices")?;
+        let liquidity = self
+            .liquidity_collector
+            .get_liquidity_for_orders(&orders, Block::Number(recorded.liquidity_block))
+            .await?;
+        tracing::debug!(
+            orders = orders.len(),
+            liquidity = liquidity.len(),
+            block = recorded.liquidity_block,
+            "replaying auction"
+        );
+
+        let auction = Auction {
+            id: recorded.auction.next_solver_competition,
+            run: 0,
+            orders,
+            liquidity,
+            gas_price,
+            deadline: Instant::now() + self.solver_time_limit,
+            external_prices: external_prices.clone(),
+        };
+        let results = join_all(self.solvers.iter().map(|solver| {
+            let auction = auction.clone();
+            async move {
+                let result =
+                    tokio::time::timeout_at(auction.deadline.into(), solver.solve(auction))
+                        .await
+                        .unwrap_or_else(|_| Err(anyhow!("solver timed out")));
+                (solver.clone(), result)
+            }
+        }))
+        .await;
+
+        let gas_price = BigRational::from_float(gas_price).context("invalid gas price")?;
+        let mut next_id = 0;
+        let mut replays = Vec::new();
+        for (solver, result) in results {
+            let settlements = match result {
+                Ok(settlements) => settlements,
+                Err(err) => {
+                    replays.push(SolverReplay {
+                        solver,
+                        settlements: Err(err),
+                    });
+                    continue;
+                }
+            };
+
+            // No gas price is set for the simulation so that it doesn't depend on the solver
+            // account's balance at that block.
+            let simulations = simulate_and_estimate_gas(
+                settlements
+                    .iter()
+                    .map(|settlement| (solver.account().clone(), settlement.clone(), None)),
+                &self.settlement_contract,
+                &self.web3,
+                Default::default(),
+                BlockNumber::Number(recorded.simulation_block.into()),
+            )
+            .await?;
+            let settlements = settlements
+                .into_iter()
+                .zip(simulations)
+                .map(|(settlement, simulation)| match simulation {
+                    Ok(gas_estimate) => {
+                        let id = next_id;
+                        next_id += 1;
+                        ReplayedSettlement::Rated(RatedSettlement {
+                            id,
+                            surplus: settlement.total_surplus(&external_prices),
+                            unscaled_subsidized_fee: settlement
+                                .total_unscaled_subsidized_fees(&external_prices),
+                            scaled_unsubsidized_fee: settlement
+                                .total_scaled_unsubsidized_fees(&external_prices),
+                            settlement,
+                            gas_estimate,
+                            gas_price: gas_price.clone(),
+                            revert_probability: 0.,
+                            deviates_from_simulation: false,
+                        })
+                    }
+                    Err(err) => ReplayedSettlement::Reverted(settlement, err),
+                })
+                .collect();
+            replays.push(SolverReplay {
+                solver,
+                settlements: Ok(settlements),
+            });
+        }
+
+        Ok(replays)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::order::{OrderData, OrderKind, OrderMetadata};
+    use primitive_types::U256;
+
+    #[test]
+    fn competition_orders_are_replayed_unfilled() {
+        let order = Order {
+            data: OrderData {
+                sell_amount: 100.into(),
+                buy_amount: 100.into(),
+                kind: OrderKind::Sell,
+                partially_fillable: true,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                executed_buy_amount: 50u8.into(),
+                executed_sell_amount: 55u8.into(),
+                executed_sell_amount_before_fees: 50.into(),
+                executed_fee_amount: 5.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let remaining = unfilled(order).remaining_amounts().unwrap();
+        assert_eq!(remaining.sell_amount, U256::from(100));
+        assert_eq!(remaining.buy_amount, U256::from(100));
+    }
+}
@@ -0,0 +1,906 @@
+//! Self-contained snapshots of the auctions solved during driver runs.
+//!
+//! Snapshots contain everything the solvers got to see, so that they can be solved again offline
+//! without a node, for example for benchmarking solvers against a corpus of past auctions. Since
+//! there are no contracts to interact with offline, liquidity restored from a snapshot records its
+//! executions as [`OfflineSwap`]s instead of encoding actual interactions.
+//!
+//! All liquidity kinds that are fetched for the solvers are supported, so a snapshot fails to be
+//! taken rather than silently missing liquidity the solvers got to see.
+
+use crate::{
+    driver::solver_settlements::RatedSettlement,
+    encoding::EncodedInteraction,
+    liquidity::{
+        order_converter::OrderConverter, AmmOrderExecution, ComposableStablePoolOrder,
+        ConcentratedLiquidity, ConstantProductOrder, CurvePoolOrder, Exchange, LimitOrder,
+        LinearPoolOrder, Liquidity, Settleable, SettlementHandling, StablePoolOrder,
+        WeightedProductOrder,
+    },
+    settlement::{external_prices::ExternalPrices, Interaction, Settlement, SettlementEncoder},
+    solver::{Auction, Solver},
+};
+use anyhow::{Context as _, Result};
+use contracts::WETH9;
+use model::{
+    order::{Order, OrderKind},
+    solver_competition::SolverCompetitionId,
+    u256_decimal::DecimalU256,
+    TokenPair,
+};
+use num::{rational::Ratio, BigRational};
+use primitive_types::{H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use shared::{
+    dummy_contract,
+    price_estimation::gas::{GAS_PER_ORDER, GAS_PER_UNISWAP, INITIALIZATION_COST, SETTLEMENT},
+    sources::{
+        balancer_v2::{
+            pool_fetching::{
+                AmplificationParameter, CommonPoolState, ComposableStablePool, LinearPool,
+                ScaledTokenState, TokenState, WeightedTokenState,
+            },
+            swap::fixed_point::Bfp,
+        },
+        curve::pool_fetching::Pool as CurvePool,
+        uniswap_v3::pool_fetching::PoolInfo,
+    },
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// A snapshot of an auction as it was passed to the solvers.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuctionSnapshot {
+    pub id: SolverCompetitionId,
+    pub run: u64,
+    pub native_token: H160,
+    /// The scaling factor that was used for converting the orders.
+    pub fee_objective_scaling_factor: f64,
+    pub orders: Vec<Order>,
+    pub liquidity: Vec<LiquiditySnapshot>,
+    #[serde_as(as = "BTreeMap<_, DecimalU256>")]
+    pub prices: BTreeMap<H160, U256>,
+    pub gas_price: f64,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LiquiditySnapshot {
+    #[serde(rename_all = "camelCase")]
+    ConstantProduct {
+        tokens: (H160, H160),
+        #[serde_as(as = "(DisplayFromStr, DisplayFromStr)")]
+        reserves: (u128, u128),
+        #[serde_as(as = "DisplayFromStr")]
+        fee: Ratio<u32>,
+    },
+    #[serde(rename_all = "camelCase")]
+    BalancerWeighted {
+        reserves: BTreeMap<H160, WeightedTokenSnapshot>,
+        #[serde_as(as = "DecimalU256")]
+        fee: U256,
+    },
+    #[serde(rename_all = "camelCase")]
+    BalancerStable {
+        reserves: BTreeMap<H160, TokenSnapshot>,
+        #[serde_as(as = "DisplayFromStr")]
+        fee: BigRational,
+        #[serde_as(as = "DecimalU256")]
+        amplification_factor: U256,
+        #[serde_as(as = "DecimalU256")]
+        amplification_precision: U256,
+    },
+    #[serde(rename_all = "camelCase")]
+    BalancerComposableStable {
+        pool: BalancerPoolSnapshot,
+        reserves: BTreeMap<H160, ScaledTokenSnapshot>,
+        #[serde_as(as = "DecimalU256")]
+        amplification_factor: U256,
+        #[serde_as(as = "DecimalU256")]
+        amplification_precision: U256,
+    },
+    #[serde(rename_all = "camelCase")]
+    BalancerLinear {
+        pool: BalancerPoolSnapshot,
+        reserves: BTreeMap<H160, ScaledTokenSnapshot>,
+        main_token: H160,
+        wrapped_token: H160,
+        #[serde_as(as = "DecimalU256")]
+        lower_target: U256,
+        #[serde_as(as = "DecimalU256")]
+        upper_target: U256,
+        #[serde_as(as = "DecimalU256")]
+        virtual_supply: U256,
+    },
+    /// A 0x limit order.
+    #[serde(rename_all = "camelCase")]
+    ZeroExOrder {
+        id: String,
+        sell_token: H160,
+        buy_token: H160,
+        #[serde_as(as = "DecimalU256")]
+        sell_amount: U256,
+        #[serde_as(as = "DecimalU256")]
+        buy_amount: U256,
+        kind: OrderKind,
+        partially_fillable: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    Concentrated {
+        tokens: (H160, H160),
+        pool: PoolInfo,
+    },
+    #[serde(rename_all = "camelCase")]
+    Curve {
+        address: H160,
+        tokens: Vec<H160>,
+        #[serde_as(as = "Vec<DecimalU256>")]
+        balances: Vec<U256>,
+        #[serde_as(as = "Vec<DecimalU256>")]
+        rates: Vec<U256>,
+        #[serde_as(as = "DecimalU256")]
+        amplification_parameter: U256,
+        #[serde_as(as = "DecimalU256")]
+        fee: U256,
+    },
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalancerPoolSnapshot {
+    pub id: H256,
+    pub address: H160,
+    #[serde_as(as = "DecimalU256")]
+    pub swap_fee: U256,
+    pub paused: bool,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenSnapshot {
+    #[serde_as(as = "DecimalU256")]
+    pub balance: U256,
+    pub scaling_exponent: u8,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightedTokenSnapshot {
+    #[serde(flatten)]
+    pub token: TokenSnapshot,
+    #[serde_as(as = "DecimalU256")]
+    pub weight: U256,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaledTokenSnapshot {
+    #[serde(flatten)]
+    pub token: TokenSnapshot,
+    #[serde_as(as = "DecimalU256")]
+    pub scaling_factor: U256,
+}
+
+impl From<&CommonPoolState> for BalancerPoolSnapshot {
+    fn from(state: &CommonPoolState) -> Self {
+        Self {
+            id: state.id,
+            address: state.address,
+            swap_fee: state.swap_fee.as_uint256(),
+            paused: state.paused,
+        }
+    }
+}
+
+impl From<BalancerPoolSnapshot> for CommonPoolState {
+    fn from(snapshot: BalancerPoolSnapshot) -> Self {
+        Self {
+            id: snapshot.id,
+            address: snapshot.address,
+            swap_fee: Bfp::from_wei(snapshot.swap_fee),
+            paused: snapshot.paused,
+        }
+    }
+}
+
+impl From<&ScaledTokenState> for ScaledTokenSnapshot {
+    fn from(state: &ScaledTokenState) -> Self {
+        Self {
+            token: (&state.common).into(),
+            scaling_factor: state.scaling_factor.as_uint256(),
+        }
+    }
+}
+
+impl From<ScaledTokenSnapshot> for ScaledTokenState {
+    fn from(snapshot: ScaledTokenSnapshot) -> Self {
+        Self {
+            common: snapshot.token.into(),
+            scaling_factor: Bfp::from_wei(snapshot.scaling_factor),
+        }
+    }
+}
+
+impl From<&TokenState> for TokenSnapshot {
+    fn from(state: &TokenState) -> Self {
+        Self {
+            balance: state.balance,
+            scaling_exponent: state.scaling_exponent,
+        }
+    }
+}
+
+impl From<TokenSnapshot> for TokenState {
+    fn from(snapshot: TokenSnapshot) -> Self {
+        Self {
+            balance: snapshot.balance,
+            scaling_exponent: snapshot.scaling_exponent,
+        }
+    }
+}
+
+impl LiquiditySnapshot {
+    pub fn new(liquidity: &Liquidity) -> Result<Self> {
+        Ok(match liquidity {
+            Liquidity::ConstantProduct(amm) => Self::ConstantProduct {
+                tokens: amm.tokens.get(),
+                reserves: amm.reserves,
+                fee: amm.fee,
+            },
+            Liquidity::BalancerWeighted(amm) => Self::BalancerWeighted {
+                reserves: amm
+                    .reserves
+                    .iter()
+                    .map(|(token, state)| {
+                        let snapshot = WeightedTokenSnapshot {
+                            token: (&state.common).into(),
+                            weight: state.weight.as_uint256(),
+                        };
+                        (*token, snapshot)
+                    })
+                    .collect(),
+                fee: amm.fee.as_uint256(),
+            },
+            Liquidity::BalancerStable(amm) => Self::BalancerStable {
+                reserves: amm
+                    .reserves
+                    .iter()
+                    .map(|(token, state)| (*token, state.into()))
+                    .collect(),
+                fee: amm.fee.clone(),
+                amplification_factor: amm.amplification_parameter.factor(),
+                amplification_precision: amm.amplification_parameter.precision(),
+            },
+            Liquidity::BalancerComposableStable(amm) => Self::BalancerComposableStable {
+                pool: (&amm.pool.common).into(),
+                reserves: scaled_reserves(&amm.pool.reserves),
+                amplification_factor: amm.pool.amplification_parameter.factor(),
+                amplification_precision: amm.pool.amplification_parameter.precision(),
+            },
+            Liquidity::BalancerLinear(amm) => Self::BalancerLinear {
+                pool: (&amm.pool.common).into(),
+                reserves: scaled_reserves(&amm.pool.reserves),
+                main_token: amm.pool.main_token,
+                wrapped_token: amm.pool.wrapped_token,
+                lower_target: amm.pool.lower_target,
+                upper_target: amm.pool.upper_target,
+                virtual_supply: amm.pool.virtual_supply,
+            },
+            Liquidity::LimitOrder(order) => {
+                anyhow::ensure!(
+                    order.exchange == Exchange::ZeroEx,
+                    "unsupported {:?} limit order liquidity",
+                    order.exchange
+                );
+                Self::ZeroExOrder {
+                    id: order.id.clone(),
+                    sell_token: order.sell_token,
+                    buy_token: order.buy_token,
+                    sell_amount: order.sell_amount,
+                    buy_amount: order.buy_amount,
+                    kind: order.kind,
+                    partially_fillable: order.partially_fillable,
+                }
+            }
+            Liquidity::Concentrated(amm) => Self::Concentrated {
+                tokens: amm.tokens.get(),
+                pool: amm.pool.clone(),
+            },
+            Liquidity::Curve(amm) => Self::Curve {
+                address: amm.pool.address,
+                tokens: amm.pool.tokens.clone(),
+                balances: amm.pool.balances.clone(),
+                rates: amm.pool.rates.clone(),
+                amplification_parameter: amm.pool.amplification_parameter,
+                fee: amm.pool.fee,
+            },
+        })
+    }
+
+    /// Restores the liquidity with offline settlement handling.
+    pub fn into_liquidity(self) -> Result<Liquidity> {
+        let settlement_handling = Arc::new(OfflineSettlementHandler);
+        Ok(match self {
+            Self::ConstantProduct {
+                tokens,
+                reserves,
+                fee,
+            } => Liquidity::ConstantProduct(ConstantProductOrder {
+                tokens: TokenPair::new(tokens.0, tokens.1).context("invalid token pair")?,
+                reserves,
+                fee,
+                settlement_handling,
+            }),
+            Self::BalancerWeighted { reserves, fee } => {
+                Liquidity::BalancerWeighted(WeightedProductOrder {
+                    reserves: reserves
+                        .into_iter()
+                        .map(|(token, snapshot)| {
+                            let state = WeightedTokenState {
+                                common: snapshot.token.into(),
+                                weight: Bfp::from_wei(snapshot.weight),
+                            };
+                            (token, state)
+                        })
+                        .collect(),
+                    fee: Bfp::from_wei(fee),
+                    settlement_handling,
+                })
+            }
+            Self::BalancerStable {
+                reserves,
+                fee,
+                amplification_factor,
+                amplification_precision,
+            } => Liquidity::BalancerStable(StablePoolOrder {
+                reserves: reserves
+                    .into_iter()
+                    .map(|(token, snapshot)| (token, snapshot.into()))
+                    .collect(),
+                fee,
+                amplification_parameter: AmplificationParameter::new(
+                    amplification_factor,
+                    amplification_precision,
+                )?,
+                settlement_handling,
+            }),
+            Self::BalancerComposableStable {
+                pool,
+                reserves,
+                amplification_factor,
+                amplification_precision,
+            } => Liquidity::BalancerComposableStable(ComposableStablePoolOrder {
+                pool: ComposableStablePool {
+                    common: pool.into(),
+                    reserves: reserves
+                        .into_iter()
+                        .map(|(token, snapshot)| (token, snapshot.into()))
+                        .collect(),
+                    amplification_parameter: AmplificationParameter::new(
+                        amplification_factor,
+                        amplification_precision,
+                    )?,
+                },
+                settlement_handling,
+            }),
+            Self::BalancerLinear {
+                pool,
+                reserves,
+                main_token,
+                wrapped_token,
+                lower_target,
+                upper_target,
+                virtual_supply,
+            } => Liquidity::BalancerLinear(LinearPoolOrder {
+                pool: LinearPool {
+                    common: pool.into(),
+                    reserves: reserves
+                        .into_iter()
+                        .map(|(token, snapshot)| (token, snapshot.into()))
+                        .collect(),
+                    main_token,
+                    wrapped_token,
+                    lower_target,
+                    upper_target,
+                    virtual_supply,
+                },
+                settlement_handling,
+            }),
+            Self::ZeroExOrder {
+                id,
+                sell_token,
+                buy_token,
+                sell_amount,
+                buy_amount,
+                kind,
+                partially_fillable,
+            } => Liquidity::LimitOrder(LimitOrder {
+                id,
+                sell_token,
+                buy_token,
+                sell_amount,
+                buy_amount,
+                kind,
+                partially_fillable,
+                unscaled_subsidized_fee: U256::zero(),
+                scaled_unsubsidized_fee: U256::zero(),
+                is_liquidity_order: true,
+                settlement_handling: Arc::new(OfflineLimitOrderHandler {
+                    sell_token,
+                    buy_token,
+                    sell_amount,
+                    buy_amount,
+                    kind,
+                }),
+                exchange: Exchange::ZeroEx,
+                gpv2_order: None,
+            }),
+            Self::Concentrated { tokens, pool } => Liquidity::Concentrated(ConcentratedLiquidity {
+                tokens: TokenPair::new(tokens.0, tokens.1).context("invalid token pair")?,
+                pool,
+                settlement_handling,
+            }),
+            Self::Curve {
+                address,
+                tokens,
+                balances,
+                rates,
+                amplification_parameter,
+                fee,
+            } => Liquidity::Curve(CurvePoolOrder {
+                pool: CurvePool {
+                    address,
+                    tokens,
+                    balances,
+                    rates,
+                    amplification_parameter,
+                    fee,
+                },
+                settlement_handling,
+            }),
+        })
+    }
+}
+
+fn scaled_reserves(
+    reserves: &HashMap<H160, ScaledTokenState>,
+) -> BTreeMap<H160, ScaledTokenSnapshot> {
+    reserves
+        .iter()
+        .map(|(token, state)| (*token, state.into()))
+        .collect()
+}
+
+impl AuctionSnapshot {
+    pub fn new(
+        auction: &Auction,
+        native_token: H160,
+        fee_objective_scaling_factor: f64,
+    ) -> Result<Self> {
+        Ok(Self {
+            id: auction.id,
+            run: auction.run,
+            native_token,
+            fee_objective_scaling_factor,
+            orders: auction
+                .orders
+                .iter()
+                .filter_map(|order| order.gpv2_order.clone())
+                .collect(),
+            liquidity: auction
+                .liquidity
+                .iter()
+                .map(LiquiditySnapshot::new)
+                .collect::<Result<_>>()?,
+            prices: auction.external_prices.to_native_prices(),
+            gas_price: auction.gas_price,
+        })
+    }
+
+    /// Writes the snapshot to a file in the specified directory named after the auction ID and run.
+    pub fn write_to_dir(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(format!("{}-{}.json", self.id, self.run));
+        let file = File::create(&path).with_context(|| format!("failed to create {:?}", path))?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("failed to open {:?}", path))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Restores the auction with offline settlement handling.
+    pub fn into_auction(self, deadline: Instant) -> Result<Auction> {
+        let order_converter = OrderConverter {
+            native_token: dummy_contract!(WETH9, self.native_token),
+            fee_objective_scaling_factor: self.fee_objective_scaling_factor,
+        };
+        Ok(Auction {
+            id: self.id,
+            run: self.run,
+            orders: self
+                .orders
+                .into_iter()
+                .map(|order| order_converter.normalize_limit_order(order))
+                .collect::<Result<_>>()?,
+            liquidity: self
+                .liquidity
+                .into_iter()
+                .map(LiquiditySnapshot::into_liquidity)
+                .collect::<Result<_>>()?,
+            gas_price: self.gas_price,
+            deadline,
+            external_prices: ExternalPrices::try_from_auction_prices(
+                self.native_token,
+                self.prices,
+            )?,
+        })
+    }
+}
+
+/// An AMM swap of liquidity restored from a snapshot.
+#[derive(Debug)]
+pub struct OfflineSwap(pub AmmOrderExecution);
+
+impl Interaction for OfflineSwap {
+    fn encode(&self) -> Vec<EncodedInteraction> {
+        Vec::new()
+    }
+
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        Some(self.0.clone())
+    }
+}
+
+struct OfflineSettlementHandler;
+
+impl<L> SettlementHandling<L> for OfflineSettlementHandler
+where
+    L: Settleable<Execution = AmmOrderExecution>,
+{
+    fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        encoder.append_to_execution_plan(OfflineSwap(execution));
+        Ok(())
+    }
+}
+
+/// Records fills of limit order liquidity restored from a snapshot as swaps of the buy token for the
+/// sell token of the order at its limit price.
+struct OfflineLimitOrderHandler {
+    sell_token: H160,
+    buy_token: H160,
+    sell_amount: U256,
+    buy_amount: U256,
+    kind: OrderKind,
+}
+
+impl SettlementHandling<LimitOrder> for OfflineLimitOrderHandler {
+    fn encode(&self, executed_amount: U256, encoder: &mut SettlementEncoder) -> Result<()> {
+        let (sold, bought) = match self.kind {
+            OrderKind::Buy => (
+                executed_amount
+                    .checked_mul(self.sell_amount)
+                    .and_then(|amount| amount.checked_div(self.buy_amount)),
+                Some(executed_amount),
+            ),
+            OrderKind::Sell => (
+                Some(executed_amount),
+                executed_amount
+                    .checked_mul(self.buy_amount)
+                    .and_then(|amount| amount.checked_div(self.sell_amount)),
+            ),
+        };
+        encoder.append_to_execution_plan(OfflineSwap(AmmOrderExecution {
+            input: (
+                self.buy_token,
+                bought.context("overflow in limit order execution")?,
+            ),
+            output: (
+                self.sell_token,
+                sold.context("overflow in limit order execution")?,
+            ),
+        }));
+        Ok(())
+    }
+}
+
+/// Estimates the gas used by a settlement of an auction restored from a snapshot, since it can't
+/// be simulated. All swaps are assumed to cost as much as a Uniswap swap.
+pub fn estimate_offline_gas(settlement: &Settlement) -> U256 {
+    let trades =
+        settlement.encoder.order_trades().len() + settlement.encoder.liquidity_order_trades().len();
+    let swaps = settlement
+        .encoder
+        .execution_plan()
+        .iter()
+        .filter(|interaction| interaction.swap_execution().is_some())
+        .count();
+    U256::from(INITIALIZATION_COST + SETTLEMENT)
+        + U256::from(GAS_PER_ORDER) * U256::from(trades)
+        + U256::from(GAS_PER_UNISWAP) * U256::from(swaps)
+}
+
+/// The result of solving a snapshotted auction offline with a single solver.
+pub struct OfflineRun {
+    pub solver: Arc<dyn Solver>,
+    pub runtime: Duration,
+    /// The settlements found by the solver rated with the estimated gas.
+    pub settlements: Result<Vec<RatedSettlement>>,
+}
+
+impl OfflineRun {
+    /// The settlement with the highest objective value.
+    pub fn best_settlement(&self) -> Option<&RatedSettlement> {
+        self.settlements
+            .as_ref()
+            .ok()?
+            .iter()
+            .max_by_key(|settlement| settlement.objective_value())
+    }
+}
+
+/// Solves a snapshotted auction with each of the solvers one after the other, so that their
+/// runtimes don't affect each other.
+pub async fn solve_offline(
+    snapshot: AuctionSnapshot,
+    solvers: &[Arc<dyn Solver>],
+    time_limit: Duration,
+) -> Result<Vec<OfflineRun>> {
+    let gas_price = BigRational::from_float(snapshot.gas_price).context("invalid gas price")?;
+    let mut runs = Vec::new();
+    for solver in solvers {
+        let auction = snapshot.clone().into_auction(Instant::now() + time_limit)?;
+        let external_prices = auction.external_prices.clone();
+
+        let start = Instant::now();
+        let result = tokio::time::timeout_at(auction.deadline.into(), solver.solve(auction))
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("solver timed out")));
+        let runtime = start.elapsed();
+
+        let settlements = result.map(|settlements| {
+            settlements
+                .into_iter()
+                .enumerate()
+                .map(|(id, settlement)| RatedSettlement {
+                    id,
+                    surplus: settlement.total_surplus(&external_prices),
+                    unscaled_subsidized_fee: settlement
+                        .total_unscaled_subsidized_fees(&external_prices),
+                    scaled_unsubsidized_fee: settlement
+                        .total_scaled_unsubsidized_fees(&external_prices),
+                    gas_estimate: estimate_offline_gas(&settlement),
+                    gas_price: gas_price.clone(),
+                    revert_probability: 0.,
+                    deviates_from_simulation: false,
+                    settlement,
+                })
+                .collect()
+        });
+        runs.push(OfflineRun {
+            solver: solver.clone(),
+            runtime,
+            settlements,
+        });
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethcontract::Account;
+    use model::order::{OrderData, OrderKind, OrderMetadata};
+
+    fn snapshot() -> AuctionSnapshot {
+        let native_token = H160::from_low_u64_be(1);
+        let token = H160::from_low_u64_be(2);
+        let order = |sell_token, buy_token, sell_amount: u128, uid| Order {
+            data: OrderData {
+                sell_token,
+                buy_token,
+                sell_amount: sell_amount.into(),
+                buy_amount: (sell_amount * 9 / 10).into(),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: model::order::OrderUid([uid; 56]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        AuctionSnapshot {
+            id: 42,
+            run: 1,
+            native_token,
+            fee_objective_scaling_factor: 1.,
+            // The orders only partially match, so the remainder has to be swapped on the pool.
+            orders: vec![
+                order(native_token, token, 1_000_000_000_000_000_000, 1),
+                order(token, native_token, 500_000_000_000_000_000, 2),
+            ],
+            liquidity: vec![LiquiditySnapshot::ConstantProduct {
+                tokens: (native_token, token),
+                reserves: (1_000_000_000_000_000_000_000, 1_000_000_000_000_000_000_000),
+                fee: Ratio::new(3, 1000),
+            }],
+            prices: [
+                (native_token, 1_000_000_000_000_000_000u128.into()),
+                (token, 1_000_000_000_000_000_000u128.into()),
+            ]
+            .into_iter()
+            .collect(),
+            gas_price: 1e9,
+        }
+    }
+
+    #[test]
+    fn snapshot_roundtrips() {
+        let snapshot = snapshot();
+        let auction = snapshot.clone().into_auction(Instant::now()).unwrap();
+        assert_eq!(
+            AuctionSnapshot::new(&auction, snapshot.native_token, 1.).unwrap(),
+            snapshot
+        );
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_value::<AuctionSnapshot>(json).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn roundtrips_liquidity() {
+        let token_state = TokenState {
+            balance: 1_000.into(),
+            scaling_exponent: 12,
+        };
+        let scaled_reserves = [(
+            H160::from_low_u64_be(3),
+            ScaledTokenSnapshot {
+                token: (&token_state).into(),
+                scaling_factor: 1_000_000_000_000_000_000u128.into(),
+            },
+        )]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+        let pool = BalancerPoolSnapshot {
+            id: H256([0x42; 32]),
+            address: H160([0x42; 20]),
+            swap_fee: 3_000_000_000_000_000u128.into(),
+            paused: false,
+        };
+        let liquidity = [
+            LiquiditySnapshot::BalancerWeighted {
+                reserves: [(
+                    H160::from_low_u64_be(1),
+                    WeightedTokenSnapshot {
+                        token: (&token_state).into(),
+                        weight: 500_000_000_000_000_000u128.into(),
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                fee: 3_000_000_000_000_000u128.into(),
+            },
+            LiquiditySnapshot::BalancerStable {
+                reserves: [(H160::from_low_u64_be(2), (&token_state).into())]
+                    .into_iter()
+                    .collect(),
+                fee: BigRational::new(3.into(), 1000.into()),
+                amplification_factor: 200.into(),
+                amplification_precision: 1000.into(),
+            },
+            LiquiditySnapshot::BalancerComposableStable {
+                pool: pool.clone(),
+                reserves: scaled_reserves.clone(),
+                amplification_factor: 200.into(),
+                amplification_precision: 1000.into(),
+            },
+            LiquiditySnapshot::BalancerLinear {
+                pool,
+                reserves: scaled_reserves,
+                main_token: H160::from_low_u64_be(3),
+                wrapped_token: H160::from_low_u64_be(4),
+                lower_target: 1.into(),
+                upper_target: 2.into(),
+                virtual_supply: 3.into(),
+            },
+            LiquiditySnapshot::ZeroExOrder {
+                id: "order".to_owned(),
+                sell_token: H160::from_low_u64_be(1),
+                buy_token: H160::from_low_u64_be(2),
+                sell_amount: 1_000.into(),
+                buy_amount: 2_000.into(),
+                kind: OrderKind::Buy,
+                partially_fillable: true,
+            },
+            LiquiditySnapshot::Concentrated {
+                tokens: (H160::from_low_u64_be(1), H160::from_low_u64_be(2)),
+                pool: PoolInfo {
+                    address: H160([0x42; 20]),
+                    ..Default::default()
+                },
+            },
+            LiquiditySnapshot::Curve {
+                address: H160([0x42; 20]),
+                tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+                balances: vec![1_000.into(), 2_000.into()],
+                rates: vec![1.into(), 1.into()],
+                amplification_parameter: 200.into(),
+                fee: 4_000_000.into(),
+            },
+        ];
+
+        for snapshot in liquidity {
+            let json = serde_json::to_value(&snapshot).unwrap();
+            let deserialized = serde_json::from_value::<LiquiditySnapshot>(json).unwrap();
+            let restored = deserialized.into_liquidity().unwrap();
+            assert_eq!(LiquiditySnapshot::new(&restored).unwrap(), snapshot);
+        }
+    }
+
+    #[test]
+    fn records_limit_order_fills_as_swaps() {
+        let (sell_token, buy_token) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let order = LiquiditySnapshot::ZeroExOrder {
+            id: "order".to_owned(),
+            sell_token,
+            buy_token,
+            sell_amount: 1_000.into(),
+            buy_amount: 2_000.into(),
+            kind: OrderKind::Buy,
+            partially_fillable: true,
+        };
+        let order = match order.into_liquidity().unwrap() {
+            Liquidity::LimitOrder(order) => order,
+            _ => unreachable!(),
+        };
+
+        let mut encoder = SettlementEncoder::default();
+        order
+            .settlement_handling
+            .encode(500.into(), &mut encoder)
+            .unwrap();
+        assert_eq!(
+            encoder.execution_plan()[0].swap_execution(),
+            Some(AmmOrderExecution {
+                input: (buy_token, 500.into()),
+                output: (sell_token, 250.into()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn solves_snapshots_offline() {
+        let solver = crate::solver::naive_solver(Account::Local(H160::default(), None));
+        let runs = solve_offline(snapshot(), &[solver], Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        let best = runs[0].best_settlement().unwrap();
+        assert_eq!(best.settlement.encoder.order_trades().len(), 2);
+        assert_eq!(
+            best.gas_estimate,
+            U256::from(INITIALIZATION_COST + SETTLEMENT + 2 * GAS_PER_ORDER + GAS_PER_UNISWAP)
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Replays a past auction against the configured solvers and prints the resulting settlements
+//! along with their objective values.
+//!
+//! The auction is either loaded from a recorded solver competition or from a JSON file containing
+//! an auction as returned by the orderbook. Liquidity is fetched at the block at which it was
+//! originally collected, so the node has to be an archive node.
+
+use clap::Parser;
+use model::solver_competition::SolverCompetitionId;
+use num::ToPrimitive;
+use shared::sources::BaselineSource;
+use solver::{
+    auction_replay::{AuctionReplayer, RecordedAuction, ReplayedSettlement},
+    liquidity::order_converter::OrderConverter,
+    metrics::NoopMetrics,
+    orderbook::OrderBookApi,
+    setup::Components,
+};
+use std::{path::PathBuf, sync::Arc};
+
+#[derive(clap::Parser)]
+struct Arguments {
+    #[clap(flatten)]
+    solver: solver::arguments::Arguments,
+
+    /// The ID of the recorded solver competition to replay.
+    #[clap(long, env)]
+    competition_id: Option<SolverCompetitionId>,
+
+    /// A JSON file containing the auction to replay in the format returned by the orderbook's
+    /// auction endpoint. The auction is replayed at its block.
+    #[clap(long, env)]
+    auction_file: Option<PathBuf>,
+
+    /// The gas price in wei used for solving and computing objective values. Defaults to the gas
+    /// price of the recorded solver competition.
+    #[clap(long, env)]
+    replay_gas_price: Option<f64>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Arguments::parse();
+    shared::tracing::initialize(
+        args.solver.shared.log_filter.as_str(),
+        args.solver.shared.log_stderr_threshold,
+    );
+    tracing::info!("replaying auction with arguments:\n{}", args.solver);
+    let solver_args = args.solver;
+
+    let components = Components::new(&solver_args)
+        .await
+        .expect("failed to set up solver components");
+
+    let api = OrderBookApi::new(
+        solver_args.orderbook_url.clone(),
+        components.client.clone(),
+        None,
+    );
+    let mut recorded = match (args.competition_id, args.auction_file) {
+        (Some(id), None) => RecordedAuction::from_solver_competition(&api, id)
+            .await
+            .expect("failed to load solver competition"),
+        (None, Some(path)) => {
+            let file = std::fs::File::open(path).expect("failed to open auction file");
+            let auction = serde_json::from_reader(file).expect("failed to parse auction file");
+            RecordedAuction::from_auction(auction)
+        }
+        _ => panic!("exactly one of COMPETITION_ID or AUCTION_FILE must be set"),
+    };
+    if let Some(gas_price) = args.replay_gas_price {
+        recorded.gas_price = Some(gas_price);
+    }
+
+    let mut baseline_sources = components.baseline_sources.clone();
+    baseline_sources.retain(|source| {
+        let can_be_replayed = !matches!(source, BaselineSource::ZeroEx | BaselineSource::UniswapV3);
+        if !can_be_replayed {
+            tracing::warn!(
+                ?source,
+                "liquidity can't be fetched at past blocks and is ignored"
+            );
+        }
+        can_be_replayed
+    });
+    // The pool fetchers don't need to be maintained because pools are only fetched at past blocks.
+    let liquidity_sources = components
+        .build_liquidity_sources(&solver_args, &baseline_sources)
+        .await
+        .expect("failed to create liquidity sources");
+    let solvers = components
+        .create_solvers(&solver_args, Arc::new(NoopMetrics::default()))
+        .expect("failure creating solvers");
+
+    let replayer = AuctionReplayer {
+        liquidity_collector: liquidity_sources.collector,
+        solvers,
+        order_converter: OrderConverter {
+            native_token: components.native_token_contract.clone(),
+            fee_objective_scaling_factor: solver_args.fee_objective_scaling_factor,
+        },
+        native_token: components.native_token_contract.address(),
+        settlement_contract: components.settlement_contract,
+        web3: components.web3,
+        solver_time_limit: solver_args.solver_time_limit,
+    };
+    let replays = replayer
+        .replay(recorded)
+        .await
+        .expect("failed to replay auction");
+
+    for replay in replays {
+        let settlements = match replay.settlements {
+            Ok(settlements) => settlements,
+            Err(err) => {
+                println!("solver={} error={:?}", replay.solver.name(), err);
+                continue;
+            }
+        };
+        if settlements.is_empty() {
+            println!("solver={} no settlements", replay.solver.name());
+        }
+        for settlement in settlements {
+            match settlement {
+                ReplayedSettlement::Rated(rated) => {
+                    println!(
+                        "solver={} objective={:.2e} surplus={:.2e} \
+                         unscaled_subsidized_fee={:.2e} scaled_unsubsidized_fee={:.2e} \
+                         gas_estimate={} gas_price={:.2e}\n{:#?}",
+                        replay.solver.name(),
+                        rated.objective_value().to_f64().unwrap_or(f64::NAN),
+                        rated.surplus.to_f64().unwrap_or(f64::NAN),
+                        rated.unscaled_subsidized_fee.to_f64().unwrap_or(f64::NAN),
+                        rated.scaled_unsubsidized_fee.to_f64().unwrap_or(f64::NAN),
+                        rated.gas_estimate,
+                        rated.gas_price.to_f64().unwrap_or(f64::NAN),
+                        rated.settlement,
+                    );
+                }
+                ReplayedSettlement::Reverted(settlement, err) => {
+                    println!(
+                        "solver={} reverted error={:?}\n{:#?}",
+                        replay.solver.name(),
+                        err,
+                        settlement
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Solves a corpus of auction snapshots recorded by the driver offline and reports the objective
+//! value, surplus and runtime of the best settlement of every solver per auction and in total.
+//!
+//! Snapshots can be recorded by running the driver with `--auction-snapshot-dir`. No node is
+//! needed, so only solvers that don't depend on external services are supported and gas costs are
+//! estimated instead of simulated.
+
+use clap::Parser;
+use ethcontract::Account;
+use num::ToPrimitive;
+use primitive_types::H160;
+use shared::baseline_solver::BaseTokens;
+use solver::{
+    auction_snapshot::{solve_offline, AuctionSnapshot},
+    solver::SolverType,
+};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tracing::level_filters::LevelFilter;
+
+#[derive(clap::Parser)]
+struct Arguments {
+    /// Snapshot files or directories containing snapshot files.
+    #[clap(required = true)]
+    snapshots: Vec<PathBuf>,
+
+    #[clap(long, env, default_value = "warn")]
+    log_filter: String,
+
+    /// The solvers to benchmark. Only solvers that can run offline are supported.
+    #[clap(
+        long,
+        env,
+        default_values = &["Naive", "Baseline"],
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true
+    )]
+    solvers: Vec<SolverType>,
+
+    /// Base tokens used by the baseline solver for finding multi-hop paths.
+    #[clap(long, env, use_value_delimiter = true)]
+    base_tokens: Vec<H160>,
+
+    /// The maximum number of AMM paths the baseline solver splits an order across.
+    #[clap(long, env, default_value = "1")]
+    baseline_max_split_paths: usize,
+
+    /// The time limit in seconds for every solver run.
+    #[clap(
+        long,
+        env,
+        default_value = "30",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    solver_time_limit: Duration,
+}
+
+#[derive(Default)]
+struct Totals {
+    solved: usize,
+    failed: usize,
+    objective: f64,
+    surplus: f64,
+    runtime: Duration,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Arguments::parse();
+    shared::tracing::initialize(&args.log_filter, LevelFilter::ERROR);
+
+    let mut paths = Vec::new();
+    for path in args.snapshots {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(&path)
+                .expect("failed to read snapshot directory")
+                .map(|entry| entry.expect("failed to read snapshot directory").path())
+                .filter(|path| {
+                    path.extension()
+                        .map_or(false, |extension| extension == "json")
+                })
+                .collect::<Vec<_>>();
+            entries.sort();
+            paths.extend(entries);
+        } else {
+            paths.push(path);
+        }
+    }
+
+    let mut totals = args
+        .solvers
+        .iter()
+        .map(|_| Totals::default())
+        .collect::<Vec<_>>();
+    for path in paths {
+        let snapshot = match AuctionSnapshot::read_from_file(&path) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                println!("snapshot={:?} error={:?}", path, err);
+                continue;
+            }
+        };
+        let (id, run) = (snapshot.id, snapshot.run);
+
+        // The base tokens always include the native token, which may differ between snapshots.
+        // The solvers never sign or submit anything, so the account doesn't matter.
+        let account = Account::Local(H160::zero(), None);
+        let solvers = solver::solver::create_offline(
+            args.solvers
+                .iter()
+                .map(|solver_type| (account.clone(), *solver_type))
+                .collect(),
+            Arc::new(BaseTokens::new(snapshot.native_token, &args.base_tokens)),
+            args.baseline_max_split_paths,
+        )
+        .expect("failed to create solvers");
+        let runs = match solve_offline(snapshot, &solvers, args.solver_time_limit).await {
+            Ok(runs) => runs,
+            Err(err) => {
+                println!("auction={} run={} error={:?}", id, run, err);
+                continue;
+            }
+        };
+
+        for (run_result, totals) in runs.iter().zip(&mut totals) {
+            let name = run_result.solver.name();
+            totals.runtime += run_result.runtime;
+            if let Err(err) = &run_result.settlements {
+                totals.failed += 1;
+                println!(
+                    "auction={} run={} solver={} runtime={:?} error={:?}",
+                    id, run, name, run_result.runtime, err
+                );
+                continue;
+            }
+            match run_result.best_settlement() {
+                Some(best) => {
+                    let objective = best.objective_value().to_f64().unwrap_or(f64::NAN);
+                    let surplus = best.surplus.to_f64().unwrap_or(f64::NAN);
+                    totals.solved += 1;
+                    totals.objective += objective;
+                    totals.surplus += surplus;
+                    println!(
+                        "auction={} run={} solver={} runtime={:?} objective={:.2e} \
+                         surplus={:.2e} gas_estimate={}",
+                        id, run, name, run_result.runtime, objective, surplus, best.gas_estimate,
+                    );
+                }
+                None => println!(
+                    "auction={} run={} solver={} runtime={:?} no settlements",
+                    id, run, name, run_result.runtime
+                ),
+            }
+        }
+    }
+
+    println!();
+    for (solver_type, totals) in args.solvers.iter().zip(totals) {
+        println!(
+            "solver={:?} solved={} failed={} total_objective={:.2e} total_surplus={:.2e} \
+             total_runtime={:?}",
+            solver_type,
+            totals.solved,
+            totals.failed,
+            totals.objective,
+            totals.surplus,
+            totals.runtime,
+        );
+    }
+}
@@ -3,17 +3,26 @@ pub mod solver_settlements;
 use self::solver_settlements::RatedSettlement;
 use crate::{
     analytics, auction_preprocessing,
+    auction_snapshot::AuctionSnapshot,
     in_flight_orders::InFlightOrders,
     liquidity::order_converter::OrderConverter,
     liquidity_collector::LiquidityCollector,
     metrics::{SolverMetrics, SolverRunOutcome},
     orderbook::OrderBookApi,
     settlement::{external_prices::ExternalPrices, PriceCheckTokens, Settlement},
-    settlement_post_processing::PostProcessingPipeline,
-    settlement_rater::{SettlementRater, SettlementRating},
+    settlement_ebbo::EbboValidator,
+    settlement_post_processing::{BufferInternalization, PostProcessingPipeline},
+    settlement_rater::{
+        balance_changes::TraceCallBalanceChangeTracer,
+        revert_risk::{RevertRiskEstimator, SettlementFeatures},
+        SettlementRater, SettlementRating, SettlementScoring,
+    },
     settlement_simulation::{self, simulate_before_after_access_list, TenderlyApi},
     settlement_submission::{SolutionSubmitter, SubmissionError},
-    solver::{Auction, SettlementWithError, Solver, Solvers},
+    solver::{
+        http_solver::buffers::{BufferRetriever, BufferRetrieving},
+        Auction, SettlementWithError, Solver, Solvers,
+    },
 };
 use anyhow::{Context, Result};
 use contracts::GPv2Settlement;
@@ -37,11 +46,13 @@ use shared::{
     Web3,
 };
 use std::{
+    collections::HashMap,
+    path::PathBuf,
     sync::Arc,
     time::{Duration, Instant},
 };
 use tracing::{Instrument as _, Span};
-use web3::types::{AccessList, TransactionReceipt};
+use web3::types::{AccessList, BlockNumber, TransactionReceipt};
 
 pub struct Driver {
     settlement_contract: GPv2Settlement,
@@ -71,6 +82,12 @@ pub struct Driver {
     token_list_restriction_for_price_checks: PriceCheckTokens,
     tenderly: Option<TenderlyApi>,
     settlement_rater: Box<dyn SettlementRating>,
+    settlement_scoring: SettlementScoring,
+    revert_risk: Arc<RevertRiskEstimator>,
+    buffer_retriever: Arc<dyn BufferRetrieving>,
+    auction_snapshot_dir: Option<PathBuf>,
+    ebbo_validator: Option<EbboValidator>,
+    reject_ebbo_violations: bool,
 }
 impl Driver {
     #[allow(clippy::too_many_arguments)]
@@ -99,20 +116,36 @@ impl Driver {
         max_settlement_price_deviation: Option<Ratio<BigInt>>,
         token_list_restriction_for_price_checks: PriceCheckTokens,
         tenderly: Option<TenderlyApi>,
+        settlement_scoring: SettlementScoring,
+        auction_snapshot_dir: Option<PathBuf>,
+        trace_settlement_balance_changes: bool,
+        ebbo_validator: Option<EbboValidator>,
+        reject_ebbo_violations: bool,
+        buffer_internalization: Option<BufferInternalization>,
     ) -> Self {
         let post_processing_pipeline = PostProcessingPipeline::new(
             native_token,
             web3.clone(),
             weth_unwrap_factor,
             settlement_contract.clone(),
+            buffer_internalization,
         );
 
+        let revert_risk = Arc::new(RevertRiskEstimator::default());
         let settlement_rater = Box::new(SettlementRater {
             access_list_estimator: solution_submitter.access_list_estimator.clone(),
             settlement_contract: settlement_contract.clone(),
             web3: web3.clone(),
+            revert_risk: revert_risk.clone(),
+            balance_change_tracer: trace_settlement_balance_changes
+                .then(|| Arc::new(TraceCallBalanceChangeTracer { web3: web3.clone() }) as Arc<_>),
         });
 
+        let buffer_retriever = Arc::new(BufferRetriever::new(
+            web3.clone(),
+            settlement_contract.address(),
+        ));
+
         Self {
             settlement_contract,
             liquidity_collector,
@@ -142,10 +175,28 @@ impl Driver {
             token_list_restriction_for_price_checks,
             tenderly,
             settlement_rater,
+            settlement_scoring,
+            revert_risk,
+            buffer_retriever,
+            auction_snapshot_dir,
+            ebbo_validator,
+            reject_ebbo_violations,
         }
     }
 
     pub async fn run_forever(&mut self) -> ! {
+        match self.api.get_auction().await {
+            Ok(auction) => {
+                self.revert_risk
+                    .seed_from_solver_competitions(
+                        &self.api,
+                        &self.web3,
+                        auction.next_solver_competition,
+                    )
+                    .await
+            }
+            Err(err) => tracing::warn!(?err, "failed to seed revert risk history"),
+        }
         loop {
             match self.single_run().await {
                 Ok(()) => tracing::debug!("single run finished ok"),
@@ -285,6 +336,49 @@ impl Driver {
         Ok(())
     }
 
+    /// Merges the settlements of different solvers using the current settlement contract
+    /// buffers for re-pricing trades with incompatible clearing prices.
+    async fn merge_settlements_across_solvers(
+        &self,
+        external_prices: &ExternalPrices,
+        settlements: &[(Arc<dyn Solver>, Settlement)],
+    ) -> Option<solver_settlements::MergedSettlement> {
+        if self.max_merged_settlements <= 1 || settlements.len() <= 1 {
+            return None;
+        }
+
+        let tokens = settlements
+            .iter()
+            .flat_map(|(_, settlement)| settlement.clearing_prices().keys().copied())
+            .unique()
+            .collect::<Vec<_>>();
+        let buffers = self
+            .buffer_retriever
+            .get_buffers(&tokens)
+            .await
+            .into_iter()
+            .filter_map(|(token, buffer)| match buffer {
+                Ok(buffer) => Some((token, buffer)),
+                Err(err) => {
+                    tracing::debug!(?token, ?err, "failed to get buffer for merging");
+                    None
+                }
+            })
+            .collect();
+
+        let merged = solver_settlements::merge_settlements_across_solvers(
+            self.max_merged_settlements,
+            external_prices,
+            settlements,
+            buffers,
+        )?;
+        tracing::debug!(
+            solvers = ?merged.solvers, settlement = ?merged.settlement,
+            "merged settlements across solvers",
+        );
+        Some(merged)
+    }
+
     async fn can_settle_without_liquidity(
         &self,
         solver: Arc<dyn Solver>,
@@ -302,7 +396,7 @@ impl Driver {
             return Ok(false);
         }
 
-        let simulations = settlement_simulation::simulate_and_estimate_gas_at_current_block(
+        let simulations = settlement_simulation::simulate_and_estimate_gas(
             std::iter::once((
                 solver.account().clone(),
                 settlement.settlement.without_onchain_liquidity(),
@@ -311,6 +405,7 @@ impl Driver {
             &self.settlement_contract,
             &self.web3,
             gas_price,
+            BlockNumber::Latest,
         )
         .await
         .context("failed to simulate settlement")?;
@@ -495,6 +590,24 @@ impl Driver {
             deadline: Instant::now() + self.solver_time_limit,
             external_prices: external_prices.clone(),
         };
+        if let Some(dir) = &self.auction_snapshot_dir {
+            match AuctionSnapshot::new(
+                &auction,
+                self.native_token,
+                self.order_converter.fee_objective_scaling_factor,
+            ) {
+                Ok(snapshot) => {
+                    // Serializing and writing the snapshot blocks, so do it off the run loop.
+                    let dir = dir.clone();
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(err) = snapshot.write_to_dir(&dir) {
+                            tracing::warn!(?err, "failed to write auction snapshot");
+                        }
+                    });
+                }
+                Err(err) => tracing::error!(?err, "failed to take auction snapshot"),
+            }
+        }
 
         tracing::debug!(deadline =? auction.deadline, "solving auction");
         let run_solver_results = self.run_solvers(auction).await;
@@ -561,11 +674,12 @@ impl Driver {
 
             // Keep at most this many settlements. This is important in case where a solver produces
             // a large number of settlements which would hold up the driver logic when simulating
-            // them.
-            // Shuffle first so that in the case a buggy solver keeps returning some amount of
-            // invalid settlements first we have a chance to make progress.
-            settlements.shuffle(&mut rand::thread_rng());
+            // them. Solvers return their settlements ranked by preference, so the most preferred
+            // ones are kept.
             settlements.truncate(self.max_settlements_per_solver);
+            // Shuffle the remaining settlements so that they get merged in a different order every
+            // run.
+            settlements.shuffle(&mut rand::thread_rng());
 
             solver_settlements::merge_settlements(
                 self.max_merged_settlements,
@@ -581,9 +695,19 @@ impl Driver {
         }
 
         // filters out all non-mature settlements
-        let solver_settlements =
+        let mut solver_settlements =
             solver_settlements::retain_mature_settlements(self.min_order_age, solver_settlements);
 
+        // Try to combine the settlements of different solvers into an additional candidate that
+        // competes on its own.
+        let merged_settlement = self
+            .merge_settlements_across_solvers(&external_prices, &solver_settlements)
+            .await
+            .map(|merged| {
+                solver_settlements.push((merged.solver, merged.settlement));
+                (solver_settlements.len() - 1, merged.solvers)
+            });
+
         // log considered settlements. While we already log all found settlements, this additonal
         // statement allows us to figure out which settlements were filtered out and which ones are
         // going to be simulated and considered for competition.
@@ -616,11 +740,40 @@ impl Driver {
             self.metrics.settlement_simulation_succeeded(solver.name());
         }
 
+        // Check that no user gets a worse price than trading directly on chain would give them at
+        // the block the solvers got their liquidity from. Violations are reported in the solver
+        // competition and violating settlements can't win if they get rejected.
+        let mut ebbo_violations = HashMap::new();
+        if let Some(ebbo_validator) = &self.ebbo_validator {
+            let violations = join_all(rated_settlements.iter().map(
+                |(solver, rated_settlement, _)| {
+                    ebbo_validator.violations(
+                        solver.name(),
+                        &rated_settlement.settlement,
+                        current_block_during_liquidity_fetch,
+                    )
+                },
+            ))
+            .await;
+            for ((_, rated_settlement, _), violations) in rated_settlements.iter().zip(violations) {
+                if !violations.is_empty() {
+                    ebbo_violations.insert(
+                        rated_settlement.id,
+                        violations
+                            .into_iter()
+                            .map(|violation| violation.order_uid)
+                            .collect::<Vec<_>>(),
+                    );
+                }
+            }
+        }
+
         // Before sorting, make sure to shuffle the settlements. This is to make sure we don't give
         // preference to any specific solver when there is an objective value tie.
         rated_settlements.shuffle(&mut rand::thread_rng());
 
-        rated_settlements.sort_by(|a, b| a.1.objective_value().cmp(&b.1.objective_value()));
+        rated_settlements
+            .sort_by_cached_key(|(_, settlement, _)| settlement.score(self.settlement_scoring));
         print_settlements(&rated_settlements, &self.fee_objective_scaling_factor);
 
         // Report solver competition data to the api.
@@ -635,6 +788,10 @@ impl Driver {
                 .iter()
                 .map(|(solver, rated_settlement, _)| SolverSettlement {
                     solver: solver.name().to_string(),
+                    merged_solvers: match &merged_settlement {
+                        Some((id, solvers)) if *id == rated_settlement.id => solvers.clone(),
+                        _ => Vec::new(),
+                    },
                     objective: Objective {
                         total: rated_settlement
                             .objective_value()
@@ -648,6 +805,7 @@ impl Driver {
                         cost: rated_settlement.gas_estimate.to_f64_lossy()
                             * rated_settlement.gas_price.to_f64().unwrap_or(f64::NAN),
                         gas: rated_settlement.gas_estimate.low_u64(),
+                        revert_probability: rated_settlement.revert_probability,
                     },
                     clearing_prices: rated_settlement
                         .settlement
@@ -666,10 +824,27 @@ impl Driver {
                     call_data: settlement_simulation::call_data(
                         rated_settlement.settlement.clone().into(),
                     ),
+                    ebbo_violations: ebbo_violations
+                        .get(&rated_settlement.id)
+                        .cloned()
+                        .unwrap_or_default(),
                 })
                 .collect(),
         };
 
+        if self.reject_ebbo_violations {
+            rated_settlements.retain(|(solver, rated_settlement, _)| {
+                let violates = ebbo_violations.contains_key(&rated_settlement.id);
+                if violates {
+                    tracing::debug!(
+                        solver_name = %solver.name(), id = rated_settlement.id,
+                        "settlement filtered for violating EBBO",
+                    );
+                }
+                !violates
+            });
+        }
+
         if let Some((winning_solver, mut winning_settlement, access_list)) = rated_settlements.pop()
         {
             // If we have enough buffer in the settlement contract to not use on-chain interactions, remove those
@@ -701,12 +876,15 @@ impl Driver {
                     winning_settlement.settlement,
                     access_list,
                     winning_solver.account().clone(),
+                    winning_solver.name(),
                     gas_price,
                 )
                 .await;
 
             self.metrics
                 .complete_runloop_until_transaction(start.elapsed());
+            let features =
+                SettlementFeatures::new(winning_solver.name(), &winning_settlement.settlement);
             let start = Instant::now();
             match self
                 .submit_settlement(winning_solver.clone(), winning_settlement.clone())
@@ -736,9 +914,11 @@ impl Driver {
                     }
 
                     solver_competition.transaction_hash = Some(receipt.transaction_hash);
+                    self.revert_risk.record_outcome(&features, false);
                 }
                 Err(SubmissionError::Revert(hash)) => {
                     solver_competition.transaction_hash = Some(hash);
+                    self.revert_risk.record_outcome(&features, true);
                 }
                 _ => (),
             }
@@ -802,7 +982,8 @@ fn print_settlements(
              objective={:.2e} surplus={:.2e} \
              gas_estimate={:.2e} gas_price={:.2e} \
              unscaled_unsubsidized_fee={:.2e} unscaled_subsidized_fee={:.2e} \
-             access_list_addreses={}",
+             access_list_addreses={} revert_probability={:.4} \
+             deviates_from_simulation={}",
             settlement.id,
             solver.name(),
             settlement.objective_value().to_f64().unwrap_or(f64::NAN),
@@ -816,7 +997,9 @@ fn print_settlements(
                 .unscaled_subsidized_fee
                 .to_f64()
                 .unwrap_or(f64::NAN),
-            access_list.clone().unwrap_or_default().len()
+            access_list.clone().unwrap_or_default().len(),
+            settlement.revert_probability,
+            settlement.deviates_from_simulation,
         )
         .unwrap();
     }
@@ -909,6 +1092,8 @@ mod tests {
                     scaled_unsubsidized_fee: BigRational::new(3u8.into(), 1u8.into()),
                     gas_estimate: 4.into(),
                     gas_price: BigRational::new(5u8.into(), 1u8.into()),
+                    revert_probability: 0.,
+                    deviates_from_simulation: false,
                 },
                 None,
             ),
@@ -922,6 +1107,8 @@ mod tests {
                     scaled_unsubsidized_fee: BigRational::new(9u8.into(), 1u8.into()),
                     gas_estimate: 10.into(),
                     gas_price: BigRational::new(11u8.into(), 1u8.into()),
+                    revert_probability: 0.5,
+                    deviates_from_simulation: true,
                 },
                 None,
             ),
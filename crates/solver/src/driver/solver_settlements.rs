@@ -1,11 +1,16 @@
 use crate::{
     settlement::{external_prices::ExternalPrices, Settlement},
+    settlement_rater::SettlementScoring,
     solver::Solver,
 };
-use ethcontract::U256;
+use ethcontract::{H160, U256};
 use num::BigRational;
 use shared::conversions::U256Ext as _;
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 pub fn has_user_order(settlement: &Settlement) -> bool {
     !settlement.encoder.order_trades().is_empty()
@@ -22,6 +27,10 @@ pub struct RatedSettlement {
     pub scaled_unsubsidized_fee: BigRational, // In wei.
     pub gas_estimate: U256,                   // In gas units.
     pub gas_price: BigRational,               // In wei per gas unit.
+    pub revert_probability: f64,
+    // Whether the balance changes observed when tracing the simulation differ from the claimed
+    // ones. Always false if tracing is disabled.
+    pub deviates_from_simulation: bool,
 }
 
 // Helper function for RatedSettlement to allow unit testing objective value computation
@@ -36,6 +45,21 @@ fn compute_objective_value(
     surplus + solver_fees - cost
 }
 
+// Like the objective value but only counts the surplus and fees if the settlement doesn't revert.
+// The gas cost has to be paid either way.
+fn compute_expected_value(
+    surplus: &BigRational,
+    solver_fees: &BigRational,
+    gas_estimate: &BigRational,
+    gas_price: &BigRational,
+    revert_probability: f64,
+) -> BigRational {
+    let success_probability =
+        BigRational::from_float(1. - revert_probability.clamp(0., 1.)).unwrap_or_else(num::zero);
+    let cost = gas_estimate * gas_price;
+    (surplus + solver_fees) * success_probability - cost
+}
+
 impl RatedSettlement {
     pub fn objective_value(&self) -> BigRational {
         let gas_estimate = self.gas_estimate.to_big_rational();
@@ -46,6 +70,24 @@ impl RatedSettlement {
             &self.gas_price,
         )
     }
+
+    pub fn expected_value(&self) -> BigRational {
+        let gas_estimate = self.gas_estimate.to_big_rational();
+        compute_expected_value(
+            &self.surplus,
+            &self.scaled_unsubsidized_fee,
+            &gas_estimate,
+            &self.gas_price,
+            self.revert_probability,
+        )
+    }
+
+    pub fn score(&self, scoring: SettlementScoring) -> BigRational {
+        match scoring {
+            SettlementScoring::ObjectiveValue => self.objective_value(),
+            SettlementScoring::RiskAdjusted => self.expected_value(),
+        }
+    }
 }
 
 // Takes the settlements of a single solver and adds a merged settlement.
@@ -92,6 +134,70 @@ fn merge_at_most_settlements(
     }
 }
 
+/// A settlement combining settlements found by different solvers.
+pub struct MergedSettlement {
+    /// The solver submitting the merged settlement. This is the solver of the merged settlement
+    /// with the most surplus.
+    pub solver: Arc<dyn Solver>,
+    pub settlement: Settlement,
+    /// The names of all solvers whose settlements were merged.
+    pub solvers: Vec<String>,
+}
+
+// Takes the settlements of all solvers and tries to merge the settlements of different solvers
+// into a single one. Settlements are merged in order of surplus, with every solver contributing at
+// most one settlement. Settlements are merged if their orders are disjoint and their clearing
+// prices are compatible, or otherwise if their trades can be re-priced using the settlement
+// contract's buffers.
+pub fn merge_settlements_across_solvers(
+    max_merged_settlements: usize,
+    prices: &ExternalPrices,
+    settlements: &[(Arc<dyn Solver>, Settlement)],
+    mut buffers: HashMap<H160, U256>,
+) -> Option<MergedSettlement> {
+    let mut settlements = settlements.to_vec();
+    settlements.sort_by_cached_key(|(_, settlement)| -settlement.total_surplus(prices));
+
+    let mut settlements = settlements.into_iter();
+    let (solver, mut merged) = settlements.next()?;
+    let mut solvers = vec![solver.name().to_string()];
+    for (next_solver, next) in settlements {
+        if solvers.len() >= max_merged_settlements {
+            break;
+        }
+        if solvers.iter().any(|name| name == next_solver.name()) {
+            continue;
+        }
+
+        let result = merged
+            .clone()
+            .merge(next.clone())
+            .or_else(|_| merged.clone().merge_with_repricing(next, &mut buffers));
+        match result {
+            Ok(settlement) => {
+                merged = settlement;
+                solvers.push(next_solver.name().to_string());
+            }
+            Err(err) => {
+                tracing::debug!(
+                    solver_name = %next_solver.name(), ?err,
+                    "failed to merge settlement across solvers",
+                );
+            }
+        }
+    }
+
+    if solvers.len() > 1 {
+        Some(MergedSettlement {
+            solver,
+            settlement: merged,
+            solvers,
+        })
+    } else {
+        None
+    }
+}
+
 /// Filters out all settlements without any user order which is mature by age or mature by association.
 /// Any user order older than `min_order_age` is considered to be mature by age.
 /// Any younger user order in a settlement containing a user order mature by age or mature by association
@@ -366,6 +472,7 @@ mod tests {
                 ..Default::default()
             },
             buy_token_index: 1,
+            ..Default::default()
         };
         let settlement = |executed_amount: U256, order_uid: u8| {
             Settlement::with_trades(
@@ -392,6 +499,78 @@ mod tests {
         }));
     }
 
+    struct NamedSolver(&'static str);
+
+    #[async_trait::async_trait]
+    impl Solver for NamedSolver {
+        async fn solve(&self, _: crate::solver::Auction) -> anyhow::Result<Vec<Settlement>> {
+            unimplemented!()
+        }
+
+        fn account(&self) -> &ethcontract::Account {
+            unimplemented!()
+        }
+
+        fn name(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn merges_settlements_of_different_solvers() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+        let token2 = H160::from_low_u64_be(2);
+        let external_prices = externalprices! {
+            native_token: token0,
+            token1 => BigRational::one(),
+            token2 => BigRational::one(),
+        };
+        let settlement =
+            |prices, uid: u8| Settlement::with_trades(prices, vec![trade(Utc::now(), uid)], vec![]);
+
+        let solver_a: Arc<dyn Solver> = Arc::new(NamedSolver("a"));
+        let solver_b: Arc<dyn Solver> = Arc::new(NamedSolver("b"));
+        let solver_c: Arc<dyn Solver> = Arc::new(NamedSolver("c"));
+        let settlements = vec![
+            (
+                solver_a.clone(),
+                settlement(hashmap! { token0 => 1.into(), token1 => 1.into() }, 1),
+            ),
+            // Same solver as above, so it doesn't get merged.
+            (
+                solver_a,
+                settlement(hashmap! { token0 => 1.into(), token2 => 1.into() }, 2),
+            ),
+            // Disjoint orders with compatible prices.
+            (
+                solver_b,
+                settlement(hashmap! { token1 => 1.into(), token2 => 1.into() }, 3),
+            ),
+            // Uses the same order as the first settlement.
+            (
+                solver_c,
+                settlement(hashmap! { token0 => 1.into(), token1 => 1.into() }, 1),
+            ),
+        ];
+
+        let merged =
+            merge_settlements_across_solvers(3, &external_prices, &settlements, HashMap::new())
+                .unwrap();
+        let mut solvers = merged.solvers.clone();
+        solvers.sort();
+        assert_eq!(solvers, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(merged.settlement.traded_orders().count(), 2);
+
+        assert!(merge_settlements_across_solvers(
+            1,
+            &external_prices,
+            &settlements,
+            HashMap::new()
+        )
+        .is_none());
+    }
+
     #[test]
     fn merge_continues_on_error() {
         let token0 = H160::from_low_u64_be(0);
@@ -507,6 +686,35 @@ mod tests {
         assert!(obj_value1 > obj_value2);
     }
 
+    #[test]
+    fn compute_expected_value() {
+        // Surplus is 1.003 ETH
+        let surplus = BigRational::from_integer(1_003_000_000_000_000_000_u128.into());
+        // Fees is 0.001 ETH
+        let solver_fees = BigRational::from_integer(1_000_000_000_000_000_u128.into());
+        let gas_estimate = BigRational::from_integer(300_000.into());
+        // Gas price is 10 gwei
+        let gas_price = BigRational::from_integer(10_000_000_000_u128.into());
+
+        // Without revert risk the expected value is the objective value.
+        assert_eq!(
+            super::compute_expected_value(&surplus, &solver_fees, &gas_estimate, &gas_price, 0.),
+            super::compute_objective_value(&surplus, &solver_fees, &gas_estimate, &gas_price),
+        );
+
+        // Expected value is 1.004 * 0.5 - 3e5 * 10e-9 = 0.499 ETH
+        assert_eq!(
+            super::compute_expected_value(&surplus, &solver_fees, &gas_estimate, &gas_price, 0.5),
+            BigRational::from_integer(499_000_000_000_000_000_u128.into())
+        );
+
+        // A settlement that certainly reverts only costs gas.
+        assert_eq!(
+            super::compute_expected_value(&surplus, &solver_fees, &gas_estimate, &gas_price, 1.),
+            -BigRational::from_integer(3_000_000_000_000_000_u128.into())
+        );
+    }
+
     #[test]
     fn has_user_order_() {
         let settlement = Settlement::with_trades(Default::default(), vec![], vec![]);
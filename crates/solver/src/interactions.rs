@@ -1,14 +1,16 @@
 pub mod allowances;
 pub mod balancer_v2;
 pub mod block_coinbase;
+mod curve;
 mod erc20;
 mod uniswap_v2;
 mod uniswap_v3;
 mod weth;
 pub mod zeroex;
 
-pub use balancer_v2::BalancerSwapGivenOutInteraction;
-pub use erc20::Erc20ApproveInteraction;
+pub use balancer_v2::{BalancerBatchSwapInteraction, BalancerSwapGivenOutInteraction};
+pub use curve::CurveExchangeInteraction;
+pub use erc20::{Erc20ApproveInteraction, Erc20TransferInteraction};
 pub use uniswap_v2::UniswapInteraction;
 pub use uniswap_v3::ExactOutputSingleParams;
 pub use uniswap_v3::UniswapV3Interaction;
@@ -1,6 +1,7 @@
-use crate::{encoding::EncodedInteraction, settlement::Interaction};
+use crate::{encoding::EncodedInteraction, liquidity::AmmOrderExecution, settlement::Interaction};
+use anyhow::{ensure, Context as _, Result};
 use contracts::{BalancerV2Vault, GPv2Settlement};
-use ethcontract::{Bytes, H160, H256};
+use ethcontract::{Bytes, H160, H256, I256};
 use primitive_types::U256;
 
 #[derive(Clone, Debug)]
@@ -12,9 +13,12 @@ pub struct BalancerSwapGivenOutInteraction {
     pub asset_out: H160,
     pub amount_out: U256,
     pub amount_in_max: U256,
+    /// The input amount the swap is expected to use, without slippage.
+    pub amount_in: U256,
     pub user_data: Bytes<Vec<u8>>,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub enum SwapKind {
     GivenIn = 0,
@@ -51,6 +55,214 @@ impl Interaction for BalancerSwapGivenOutInteraction {
         let calldata = method.tx.data.expect("no calldata").0;
         vec![(self.vault.address(), 0.into(), Bytes(calldata))]
     }
+
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        Some(AmmOrderExecution {
+            input: (self.asset_in, self.amount_in),
+            output: (self.asset_out, self.amount_out),
+        })
+    }
+}
+
+/// A single swap of a Balancer batch swap. Assets are referenced by their
+/// index in the batch swap's asset list.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchSwapStep {
+    pub pool_id: H256,
+    pub asset_in_index: usize,
+    pub asset_out_index: usize,
+    /// The swap amount. An amount of 0 uses the output of the previous step
+    /// as the input, which is how multi-hop swaps are expressed.
+    pub amount: U256,
+    pub user_data: Bytes<Vec<u8>>,
+}
+
+/// A Balancer Vault `batchSwap` interaction.
+#[derive(Clone, Debug)]
+pub struct BalancerBatchSwapInteraction {
+    pub settlement: GPv2Settlement,
+    pub vault: BalancerV2Vault,
+    pub kind: SwapKind,
+    pub swaps: Vec<BatchSwapStep>,
+    pub assets: Vec<H160>,
+    /// The maximum amount of each asset that can be sent to the Vault
+    /// (positive values) or the minimum amount that must be received from it
+    /// (negative values).
+    pub limits: Vec<I256>,
+    /// The net amount of each asset that is expected to be sent to the Vault
+    /// (positive values) or received from it (negative values), without
+    /// slippage. Only known for given out batch swaps.
+    pub expected_deltas: Vec<I256>,
+}
+
+impl BalancerBatchSwapInteraction {
+    /// Creates a multi-hop batch swap selling exactly `amount_in` of
+    /// `asset_in` through the specified `(pool_id, asset_out)` hops, requiring
+    /// at least `min_amount_out` of the final asset to be received.
+    pub fn given_in_path(
+        settlement: GPv2Settlement,
+        vault: BalancerV2Vault,
+        (asset_in, amount_in): (H160, U256),
+        hops: &[(H256, H160)],
+        min_amount_out: U256,
+    ) -> Result<Self> {
+        ensure!(!hops.is_empty(), "empty Balancer batch swap path");
+
+        let swaps = hops
+            .iter()
+            .enumerate()
+            .map(|(index, (pool_id, _))| BatchSwapStep {
+                pool_id: *pool_id,
+                asset_in_index: index,
+                asset_out_index: index + 1,
+                amount: if index == 0 { amount_in } else { U256::zero() },
+                user_data: Default::default(),
+            })
+            .collect();
+        let assets = std::iter::once(asset_in)
+            .chain(hops.iter().map(|(_, asset_out)| *asset_out))
+            .collect::<Vec<_>>();
+
+        let mut limits = vec![I256::zero(); assets.len()];
+        limits[0] = I256::try_from(amount_in).context("batch swap amount in overflow")?;
+        limits[assets.len() - 1] =
+            -I256::try_from(min_amount_out).context("batch swap minimum amount out overflow")?;
+
+        Ok(Self {
+            settlement,
+            vault,
+            kind: SwapKind::GivenIn,
+            swaps,
+            assets,
+            limits,
+            expected_deltas: Vec::new(),
+        })
+    }
+
+    /// Creates a batch swap executing all of the specified single pool swaps.
+    ///
+    /// Each swap becomes a step with an explicit output amount, and the limits
+    /// are the net asset deltas of the swaps' own limits. This way, the Vault
+    /// only transfers the net amount of each asset instead of transferring
+    /// intermediate tokens in and out for every swap.
+    pub fn given_out(swaps: &[BalancerSwapGivenOutInteraction]) -> Result<Self> {
+        let first = swaps.first().context("empty Balancer batch swap")?;
+        let mut batch = Self {
+            settlement: first.settlement.clone(),
+            vault: first.vault.clone(),
+            kind: SwapKind::GivenOut,
+            swaps: Vec::with_capacity(swaps.len()),
+            assets: Vec::new(),
+            limits: Vec::new(),
+            expected_deltas: Vec::new(),
+        };
+
+        for swap in swaps {
+            ensure!(
+                swap.settlement.address() == batch.settlement.address()
+                    && swap.vault.address() == batch.vault.address(),
+                "cannot batch Balancer swaps for different contracts",
+            );
+
+            let asset_in_index = batch.asset_index(swap.asset_in);
+            let asset_out_index = batch.asset_index(swap.asset_out);
+            batch.limits[asset_in_index] = batch.limits[asset_in_index]
+                .checked_add(
+                    I256::try_from(swap.amount_in_max).context("batch swap amount in overflow")?,
+                )
+                .context("batch swap limit overflow")?;
+            batch.expected_deltas[asset_in_index] = batch.expected_deltas[asset_in_index]
+                .checked_add(
+                    I256::try_from(swap.amount_in).context("batch swap amount in overflow")?,
+                )
+                .context("batch swap delta overflow")?;
+            let amount_out =
+                I256::try_from(swap.amount_out).context("batch swap amount out overflow")?;
+            batch.limits[asset_out_index] = batch.limits[asset_out_index]
+                .checked_sub(amount_out)
+                .context("batch swap limit overflow")?;
+            batch.expected_deltas[asset_out_index] = batch.expected_deltas[asset_out_index]
+                .checked_sub(amount_out)
+                .context("batch swap delta overflow")?;
+
+            batch.swaps.push(BatchSwapStep {
+                pool_id: swap.pool_id,
+                asset_in_index,
+                asset_out_index,
+                amount: swap.amount_out,
+                user_data: swap.user_data.clone(),
+            });
+        }
+
+        Ok(batch)
+    }
+
+    /// Returns the index of an asset in the batch swap, adding it with an
+    /// empty limit and delta if it is not yet part of it.
+    fn asset_index(&mut self, asset: H160) -> usize {
+        match self.assets.iter().position(|&existing| existing == asset) {
+            Some(index) => index,
+            None => {
+                self.assets.push(asset);
+                self.limits.push(I256::zero());
+                self.expected_deltas.push(I256::zero());
+                self.assets.len() - 1
+            }
+        }
+    }
+}
+
+impl Interaction for BalancerBatchSwapInteraction {
+    fn encode(&self) -> Vec<EncodedInteraction> {
+        let method = self.vault.batch_swap(
+            self.kind as _,
+            self.swaps
+                .iter()
+                .map(|swap| {
+                    (
+                        Bytes(swap.pool_id.0),
+                        swap.asset_in_index.into(),
+                        swap.asset_out_index.into(),
+                        swap.amount,
+                        swap.user_data.clone(),
+                    )
+                })
+                .collect(),
+            self.assets.clone(),
+            (
+                self.settlement.address(), // sender
+                false,                     // fromInternalBalance
+                self.settlement.address(), // recipient
+                false,                     // toInternalBalance
+            ),
+            self.limits.clone(),
+            *NEVER,
+        );
+        let calldata = method.tx.data.expect("no calldata").0;
+        vec![(self.vault.address(), 0.into(), Bytes(calldata))]
+    }
+
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        // Only given out batch swaps have exact output amounts, and only batch swaps with a
+        // single net input and output asset are equivalent to a single swap.
+        if self.kind != SwapKind::GivenOut {
+            return None;
+        }
+        let mut input = None;
+        let mut output = None;
+        for (asset, delta) in self.assets.iter().zip(&self.expected_deltas) {
+            if delta.is_positive() && input.replace((*asset, delta.into_raw())).is_some() {
+                return None;
+            }
+            if delta.is_negative() && output.replace((*asset, (-*delta).into_raw())).is_some() {
+                return None;
+            }
+        }
+        Some(AmmOrderExecution {
+            input: input?,
+            output: output?,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -69,6 +281,7 @@ mod tests {
             asset_out: H160([0x05; 20]),
             amount_out: U256::from(42_000_000_000_000_000_000u128),
             amount_in_max: U256::from(1_337_000_000_000_000_000_000u128),
+            amount_in: U256::from(1_000_000_000_000_000_000_000u128),
             user_data: Bytes::default(),
         };
 
@@ -121,4 +334,265 @@ mod tests {
             )]
         );
     }
+
+    #[test]
+    fn encode_multi_hop_batch_swap() {
+        let vault = dummy_contract!(BalancerV2Vault, [0x01; 20]);
+        let interaction = BalancerBatchSwapInteraction::given_in_path(
+            dummy_contract!(GPv2Settlement, [0x02; 20]),
+            vault.clone(),
+            (H160([0x04; 20]), U256::exp10(18)),
+            &[
+                (H256([0x03; 32]), H160([0x05; 20])),
+                (H256([0x06; 32]), H160([0x07; 20])),
+            ],
+            U256::exp10(18) * 2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            interaction.swaps,
+            vec![
+                BatchSwapStep {
+                    pool_id: H256([0x03; 32]),
+                    asset_in_index: 0,
+                    asset_out_index: 1,
+                    amount: U256::exp10(18),
+                    user_data: Default::default(),
+                },
+                BatchSwapStep {
+                    pool_id: H256([0x06; 32]),
+                    asset_in_index: 1,
+                    asset_out_index: 2,
+                    amount: U256::zero(),
+                    user_data: Default::default(),
+                },
+            ]
+        );
+        assert_eq!(
+            interaction.limits,
+            vec![
+                I256::try_from(U256::exp10(18)).unwrap(),
+                I256::zero(),
+                -I256::try_from(U256::exp10(18) * 2).unwrap(),
+            ]
+        );
+
+        // Computed using Ethers.js:
+        // ```js
+        // vault.interface.encodeFunctionData("batchSwap", [
+        //   0,
+        //   [
+        //     {
+        //       poolId: "0x0303030303030303030303030303030303030303030303030303030303030303",
+        //       assetInIndex: 0,
+        //       assetOutIndex: 1,
+        //       amount: ethers.utils.parseEther("1.0"),
+        //       userData: "0x",
+        //     },
+        //     {
+        //       poolId: "0x0606060606060606060606060606060606060606060606060606060606060606",
+        //       assetInIndex: 1,
+        //       assetOutIndex: 2,
+        //       amount: 0,
+        //       userData: "0x",
+        //     },
+        //   ],
+        //   [
+        //     "0x0404040404040404040404040404040404040404",
+        //     "0x0505050505050505050505050505050505050505",
+        //     "0x0707070707070707070707070707070707070707",
+        //   ],
+        //   {
+        //     sender: "0x0202020202020202020202020202020202020202",
+        //     fromInternalBalance: false,
+        //     recipient: "0x0202020202020202020202020202020202020202",
+        //     toInternalBalance: false,
+        //   },
+        //   [ethers.utils.parseEther("1.0"), 0, ethers.utils.parseEther("-2.0")],
+        //   "0x8000000000000000000000000000000000000000000000000000000000000000",
+        // ])
+        // ```
+        assert_eq!(
+            interaction.encode(),
+            vec![(
+                vault.address(),
+                0.into(),
+                Bytes(
+                    hex::decode(
+                        "945bcec9\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         0000000000000000000000000000000000000000000000000000000000000120\
+                         0000000000000000000000000000000000000000000000000000000000000300\
+                         0000000000000000000000000202020202020202020202020202020202020202\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         0000000000000000000000000202020202020202020202020202020202020202\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         0000000000000000000000000000000000000000000000000000000000000380\
+                         8000000000000000000000000000000000000000000000000000000000000000\
+                         0000000000000000000000000000000000000000000000000000000000000002\
+                         0000000000000000000000000000000000000000000000000000000000000040\
+                         0000000000000000000000000000000000000000000000000000000000000100\
+                         0303030303030303030303030303030303030303030303030303030303030303\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         0000000000000000000000000000000000000000000000000000000000000001\
+                         0000000000000000000000000000000000000000000000000de0b6b3a7640000\
+                         00000000000000000000000000000000000000000000000000000000000000a0\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         0606060606060606060606060606060606060606060606060606060606060606\
+                         0000000000000000000000000000000000000000000000000000000000000001\
+                         0000000000000000000000000000000000000000000000000000000000000002\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         00000000000000000000000000000000000000000000000000000000000000a0\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         0000000000000000000000000000000000000000000000000000000000000003\
+                         0000000000000000000000000404040404040404040404040404040404040404\
+                         0000000000000000000000000505050505050505050505050505050505050505\
+                         0000000000000000000000000707070707070707070707070707070707070707\
+                         0000000000000000000000000000000000000000000000000000000000000003\
+                         0000000000000000000000000000000000000000000000000de0b6b3a7640000\
+                         0000000000000000000000000000000000000000000000000000000000000000\
+                         ffffffffffffffffffffffffffffffffffffffffffffffffe43e9298b1380000"
+                    )
+                    .unwrap()
+                ),
+            )]
+        );
+    }
+
+    #[test]
+    fn batch_swap_requires_hops() {
+        assert!(BalancerBatchSwapInteraction::given_in_path(
+            dummy_contract!(GPv2Settlement, [0x02; 20]),
+            dummy_contract!(BalancerV2Vault, [0x01; 20]),
+            (H160([0x04; 20]), U256::exp10(18)),
+            &[],
+            U256::exp10(18),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn batch_swap_nets_given_out_swaps() {
+        let settlement = dummy_contract!(GPv2Settlement, [0x02; 20]);
+        let vault = dummy_contract!(BalancerV2Vault, [0x01; 20]);
+        let swap = |pool: u8, asset_in: u8, asset_out: u8, amount_in_max: u64, amount_out: u64| {
+            BalancerSwapGivenOutInteraction {
+                amount_in: (amount_in_max - 1).into(),
+                settlement: settlement.clone(),
+                vault: vault.clone(),
+                pool_id: H256([pool; 32]),
+                asset_in: H160([asset_in; 20]),
+                asset_out: H160([asset_out; 20]),
+                amount_out: amount_out.into(),
+                amount_in_max: amount_in_max.into(),
+                user_data: Default::default(),
+            }
+        };
+
+        let interaction = BalancerBatchSwapInteraction::given_out(&[
+            swap(0x10, 0x04, 0x05, 101, 200),
+            swap(0x11, 0x05, 0x06, 202, 300),
+            swap(0x12, 0x07, 0x06, 50, 25),
+        ])
+        .unwrap();
+
+        assert_eq!(interaction.kind, SwapKind::GivenOut);
+        assert_eq!(
+            interaction.assets,
+            vec![
+                H160([0x04; 20]),
+                H160([0x05; 20]),
+                H160([0x06; 20]),
+                H160([0x07; 20]),
+            ]
+        );
+        assert_eq!(
+            interaction
+                .swaps
+                .iter()
+                .map(|swap| (
+                    swap.pool_id,
+                    swap.asset_in_index,
+                    swap.asset_out_index,
+                    swap.amount
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (H256([0x10; 32]), 0, 1, 200.into()),
+                (H256([0x11; 32]), 1, 2, 300.into()),
+                (H256([0x12; 32]), 3, 2, 25.into()),
+            ]
+        );
+        assert_eq!(
+            interaction.limits,
+            vec![
+                I256::from(101_i128),
+                I256::from(2_i128),
+                I256::from(-325_i128),
+                I256::from(50_i128),
+            ]
+        );
+        assert_eq!(
+            interaction.expected_deltas,
+            vec![
+                I256::from(100_i128),
+                I256::from(1_i128),
+                I256::from(-325_i128),
+                I256::from(49_i128),
+            ]
+        );
+    }
+
+    #[test]
+    fn batch_swap_execution_ignores_slippage() {
+        let swap = |asset_in: u8, asset_out: u8, amount_in: u64, amount_out: u64| {
+            BalancerSwapGivenOutInteraction {
+                settlement: dummy_contract!(GPv2Settlement, [0x02; 20]),
+                vault: dummy_contract!(BalancerV2Vault, [0x01; 20]),
+                pool_id: H256([0x10; 32]),
+                asset_in: H160([asset_in; 20]),
+                asset_out: H160([asset_out; 20]),
+                amount_out: amount_out.into(),
+                amount_in_max: (amount_in + 1).into(),
+                amount_in: amount_in.into(),
+                user_data: Default::default(),
+            }
+        };
+
+        // The intermediate asset has a positive limit because of slippage, but
+        // it is not expected to be sent to the Vault.
+        let interaction = BalancerBatchSwapInteraction::given_out(&[
+            swap(0x04, 0x05, 100, 200),
+            swap(0x05, 0x06, 200, 300),
+        ])
+        .unwrap();
+
+        assert_eq!(interaction.limits[1], I256::from(1_i128));
+        assert_eq!(
+            interaction.swap_execution(),
+            Some(AmmOrderExecution {
+                input: (H160([0x04; 20]), 100.into()),
+                output: (H160([0x06; 20]), 300.into()),
+            })
+        );
+    }
+
+    #[test]
+    fn batch_swap_requires_same_contracts() {
+        let swap = |vault: u8| BalancerSwapGivenOutInteraction {
+            settlement: dummy_contract!(GPv2Settlement, [0x02; 20]),
+            vault: dummy_contract!(BalancerV2Vault, [vault; 20]),
+            pool_id: H256([0x10; 32]),
+            asset_in: H160([0x04; 20]),
+            asset_out: H160([0x05; 20]),
+            amount_out: 1.into(),
+            amount_in_max: 1.into(),
+            amount_in: 1.into(),
+            user_data: Default::default(),
+        };
+
+        assert!(BalancerBatchSwapInteraction::given_out(&[]).is_err());
+        assert!(BalancerBatchSwapInteraction::given_out(&[swap(0x01), swap(0x03)]).is_err());
+    }
 }
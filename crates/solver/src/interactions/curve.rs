@@ -0,0 +1,59 @@
+use crate::{encoding::EncodedInteraction, settlement::Interaction};
+use contracts::CurvePool;
+use ethcontract::Bytes;
+use primitive_types::U256;
+
+/// An `exchange` of two coins of a Curve pool. Curve pools only support
+/// swapping exact input amounts, so slippage is bounded by the minimum output.
+#[derive(Clone, Debug)]
+pub struct CurveExchangeInteraction {
+    pub pool: CurvePool,
+    pub i: i128,
+    pub j: i128,
+    pub dx: U256,
+    pub min_dy: U256,
+}
+
+impl Interaction for CurveExchangeInteraction {
+    fn encode(&self) -> Vec<EncodedInteraction> {
+        let method = self.pool.exchange(self.i, self.j, self.dx, self.min_dy);
+        let calldata = method.tx.data.expect("no calldata").0;
+        vec![(self.pool.address(), 0.into(), Bytes(calldata))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use primitive_types::H160;
+    use shared::dummy_contract;
+
+    #[test]
+    fn encode_exchange() {
+        let pool = dummy_contract!(CurvePool, H160([0x01; 20]));
+        let interaction = CurveExchangeInteraction {
+            pool: pool.clone(),
+            i: 1,
+            j: 2,
+            dx: 3.into(),
+            min_dy: 4.into(),
+        };
+
+        let encoded = interaction.encode();
+        assert_eq!(encoded.len(), 1);
+        let (target, value, calldata) = &encoded[0];
+        assert_eq!(*target, pool.address());
+        assert_eq!(*value, 0.into());
+        assert_eq!(
+            calldata.0,
+            hex!(
+                "3df02124
+                 0000000000000000000000000000000000000000000000000000000000000001
+                 0000000000000000000000000000000000000000000000000000000000000002
+                 0000000000000000000000000000000000000000000000000000000000000003
+                 0000000000000000000000000000000000000000000000000000000000000004"
+            )
+        );
+    }
+}
@@ -2,9 +2,16 @@
 
 use crate::{encoding::EncodedInteraction, settlement::Interaction};
 use contracts::ERC20;
-use ethcontract::Bytes;
+use ethcontract::{
+    common::abi::{self, Token},
+    Bytes,
+};
+use hex_literal::hex;
 use primitive_types::{H160, U256};
 
+/// The `transfer(address,uint256)` function selector.
+const TRANSFER_SELECTOR: [u8; 4] = hex!("a9059cbb");
+
 #[derive(Debug)]
 pub struct Erc20ApproveInteraction {
     pub token: ERC20,
@@ -26,10 +33,37 @@ impl Interaction for Erc20ApproveInteraction {
     }
 }
 
+/// An ERC20 transfer from the settlement contract's buffers.
+///
+/// Unlike approvals, transfers are encoded while building settlements where no contract instance
+/// is available, so the calldata is encoded directly from the token address.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Erc20TransferInteraction {
+    pub token: H160,
+    pub recipient: H160,
+    pub amount: U256,
+}
+
+impl Erc20TransferInteraction {
+    pub fn as_encoded(&self) -> EncodedInteraction {
+        let mut calldata = TRANSFER_SELECTOR.to_vec();
+        calldata.extend(abi::encode(&[
+            Token::Address(self.recipient),
+            Token::Uint(self.amount),
+        ]));
+        (self.token, 0.into(), Bytes(calldata))
+    }
+}
+
+impl Interaction for Erc20TransferInteraction {
+    fn encode(&self) -> Vec<EncodedInteraction> {
+        vec![self.as_encoded()]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hex_literal::hex;
     use shared::dummy_contract;
 
     #[test]
@@ -52,4 +86,25 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn encode_erc20_transfer() {
+        let transfer = Erc20TransferInteraction {
+            token: H160([0x01; 20]),
+            recipient: H160([0x02; 20]),
+            amount: U256::from_big_endian(&[0x03; 32]),
+        };
+
+        let (target, value, calldata) = transfer.as_encoded();
+        assert_eq!(target, transfer.token);
+        assert_eq!(value, 0.into());
+        assert_eq!(
+            calldata.0,
+            hex!(
+                "a9059cbb
+                 0000000000000000000000000202020202020202020202020202020202020202
+                 0303030303030303030303030303030303030303030303030303030303030303"
+            )
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{encoding::EncodedInteraction, settlement::Interaction};
+use crate::{encoding::EncodedInteraction, liquidity::AmmOrderExecution, settlement::Interaction};
 use contracts::{GPv2Settlement, IUniswapLikeRouter};
 use ethcontract::Bytes;
 use primitive_types::{H160, U256};
@@ -9,6 +9,8 @@ pub struct UniswapInteraction {
     pub settlement: GPv2Settlement,
     pub amount_out: U256,
     pub amount_in_max: U256,
+    /// The input amount the swap is expected to use, without slippage.
+    pub amount_in: U256,
     pub token_in: H160,
     pub token_out: H160,
 }
@@ -17,6 +19,13 @@ impl Interaction for UniswapInteraction {
     fn encode(&self) -> Vec<EncodedInteraction> {
         vec![self.encode_swap()]
     }
+
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        Some(AmmOrderExecution {
+            input: (self.token_in, self.amount_in),
+            output: (self.token_out, self.amount_out),
+        })
+    }
 }
 
 impl UniswapInteraction {
@@ -61,6 +70,7 @@ mod tests {
             settlement,
             amount_out: amount_out.into(),
             amount_in_max: amount_in_max.into(),
+            amount_in: 5.into(),
             token_in,
             token_out: H160::from_low_u64_be(token_out as u64),
         };
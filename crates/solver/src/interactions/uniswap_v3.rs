@@ -1,4 +1,4 @@
-use crate::{encoding::EncodedInteraction, settlement::Interaction};
+use crate::{encoding::EncodedInteraction, liquidity::AmmOrderExecution, settlement::Interaction};
 use contracts::UniswapV3SwapRouter;
 use ethcontract::Bytes;
 use primitive_types::{H160, U256};
@@ -7,6 +7,8 @@ use primitive_types::{H160, U256};
 pub struct UniswapV3Interaction {
     pub router: UniswapV3SwapRouter,
     pub params: ExactOutputSingleParams,
+    /// The input amount the swap is expected to use, without slippage.
+    pub amount_in: U256,
 }
 
 #[derive(Debug)]
@@ -35,6 +37,13 @@ impl Interaction for UniswapV3Interaction {
         let calldata = method.tx.data.expect("no calldata").0;
         vec![(self.router.address(), 0.into(), Bytes(calldata))]
     }
+
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        Some(AmmOrderExecution {
+            input: (self.params.token_in, self.amount_in),
+            output: (self.params.token_out, self.params.amount_out),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -72,6 +81,7 @@ mod tests {
                 amount_in_max: amount_in_max.into(),
                 sqrt_price_limit_x96: U256::zero(),
             },
+            amount_in: 5.into(),
         };
         let interactions = interaction.encode();
 
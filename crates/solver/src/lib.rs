@@ -1,6 +1,8 @@
 mod analytics;
 pub mod arguments;
-mod auction_preprocessing;
+pub mod auction_preprocessing;
+pub mod auction_replay;
+pub mod auction_snapshot;
 pub mod driver;
 pub mod encoding;
 pub mod in_flight_orders;
@@ -11,10 +13,12 @@ pub mod metrics;
 pub mod orderbook;
 pub mod settlement;
 pub mod settlement_access_list;
+pub mod settlement_ebbo;
 pub mod settlement_post_processing;
 pub mod settlement_rater;
 pub mod settlement_simulation;
 pub mod settlement_submission;
+pub mod setup;
 pub mod solver;
 #[cfg(test)]
 mod test;
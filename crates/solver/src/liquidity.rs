@@ -1,4 +1,5 @@
 pub mod balancer_v2;
+pub mod curve;
 pub mod order_converter;
 pub mod slippage;
 pub mod uniswap_v2;
@@ -9,18 +10,24 @@ use crate::settlement::SettlementEncoder;
 use anyhow::Result;
 #[cfg(test)]
 use derivative::Derivative;
-#[cfg(test)]
-use model::order::Order;
-use model::{order::OrderKind, TokenPair};
+use model::{
+    order::{Order, OrderKind},
+    TokenPair,
+};
 use num::{rational::Ratio, BigRational};
 use primitive_types::{H160, U256};
+use shared::conversions::U256Ext as _;
 #[cfg(test)]
 use shared::sources::uniswap_v2::pool_fetching::Pool;
 use shared::sources::{
     balancer_v2::{
-        pool_fetching::{AmplificationParameter, TokenState, WeightedTokenState},
+        pool_fetching::{
+            AmplificationParameter, ComposableStablePool, LinearPool, TokenState,
+            WeightedTokenState,
+        },
         swap::fixed_point::Bfp,
     },
+    curve::pool_fetching::Pool as CurvePool,
     uniswap_v3::pool_fetching::PoolInfo,
 };
 use std::collections::HashMap;
@@ -34,8 +41,11 @@ pub enum Liquidity {
     ConstantProduct(ConstantProductOrder),
     BalancerWeighted(WeightedProductOrder),
     BalancerStable(StablePoolOrder),
+    BalancerComposableStable(ComposableStablePoolOrder),
+    BalancerLinear(LinearPoolOrder),
     LimitOrder(LimitOrder),
     Concentrated(ConcentratedLiquidity),
+    Curve(CurvePoolOrder),
 }
 
 impl Liquidity {
@@ -45,10 +55,13 @@ impl Liquidity {
             Liquidity::ConstantProduct(amm) => vec![amm.tokens],
             Liquidity::BalancerWeighted(amm) => token_pairs(&amm.reserves),
             Liquidity::BalancerStable(amm) => token_pairs(&amm.reserves),
+            Liquidity::BalancerComposableStable(amm) => token_pairs(&amm.pool.reserves),
+            Liquidity::BalancerLinear(amm) => token_pairs(&amm.pool.reserves),
             Liquidity::LimitOrder(order) => TokenPair::new(order.sell_token, order.buy_token)
                 .map(|pair| vec![pair])
                 .unwrap_or_default(),
             Liquidity::Concentrated(amm) => vec![amm.tokens],
+            Liquidity::Curve(amm) => amm.pool.token_pairs(),
         }
     }
 }
@@ -101,6 +114,9 @@ pub struct LimitOrder {
     #[cfg_attr(test, derivative(PartialEq = "ignore"))]
     pub settlement_handling: Arc<dyn SettlementHandling<Self>>,
     pub exchange: Exchange,
+    /// The GPv2 order this limit order was created from. This is `None` for
+    /// limit orders of other exchanges.
+    pub gpv2_order: Option<Order>,
 }
 
 impl std::fmt::Debug for LimitOrder {
@@ -117,6 +133,73 @@ impl LimitOrder {
             OrderKind::Buy => self.buy_amount,
         }
     }
+
+    /// Returns a copy of a partially fillable order that only executes the
+    /// specified amount, at the same limit price.
+    ///
+    /// Returns `None` if the order is not partially fillable, or if the amount
+    /// is zero or exceeds the order's full execution amount.
+    pub fn partial_fill(&self, execution_amount: U256) -> Option<LimitOrder> {
+        let full_execution_amount = self.full_execution_amount();
+        if !self.partially_fillable
+            || execution_amount.is_zero()
+            || execution_amount > full_execution_amount
+        {
+            return None;
+        }
+
+        let scale_down =
+            |amount: U256| Some(amount.checked_mul(execution_amount)? / full_execution_amount);
+        let (sell_amount, buy_amount) = match self.kind {
+            // Round the limit amounts in favour of the trader, so that the
+            // partial fill never has a better limit price than the order.
+            OrderKind::Sell => (
+                execution_amount,
+                self.buy_amount
+                    .checked_mul(execution_amount)?
+                    .checked_ceil_div(&full_execution_amount)?,
+            ),
+            OrderKind::Buy => (scale_down(self.sell_amount)?, execution_amount),
+        };
+
+        Some(LimitOrder {
+            sell_amount,
+            buy_amount,
+            unscaled_subsidized_fee: scale_down(self.unscaled_subsidized_fee)?,
+            scaled_unsubsidized_fee: scale_down(self.scaled_unsubsidized_fee)?,
+            ..self.clone()
+        })
+    }
+
+    /// Searches the largest execution amount of a partially fillable order for
+    /// which `is_valid` holds. This assumes that if an execution amount is
+    /// valid, then all smaller amounts are valid as well.
+    ///
+    /// The search stops once the amount is within 0.01% of the largest valid
+    /// amount in order to bound the number of (potentially expensive) validity
+    /// checks. Returns `None` if no valid partial execution was found.
+    pub fn max_partial_execution(&self, mut is_valid: impl FnMut(U256) -> bool) -> Option<U256> {
+        if !self.partially_fillable {
+            return None;
+        }
+
+        let full_execution_amount = self.full_execution_amount();
+        let tolerance = (full_execution_amount / 10_000).max(U256::one());
+        let (mut valid, mut invalid) = (U256::zero(), full_execution_amount);
+        if is_valid(invalid) {
+            return Some(invalid);
+        }
+        while invalid - valid > tolerance {
+            let amount = valid + (invalid - valid) / 2;
+            if is_valid(amount) {
+                valid = amount;
+            } else {
+                invalid = amount;
+            }
+        }
+
+        Some(valid).filter(|amount| !amount.is_zero())
+    }
 }
 
 impl Settleable for LimitOrder {
@@ -152,6 +235,7 @@ impl Default for LimitOrder {
             is_liquidity_order: false,
             id: Default::default(),
             exchange: Exchange::GnosisProtocol,
+            gpv2_order: None,
         }
     }
 }
@@ -220,6 +304,44 @@ impl std::fmt::Debug for StablePoolOrder {
     }
 }
 
+/// Balancer V2 stable pool whose tokens may have rates (e.g. wrapped tokens)
+/// and that holds its own pool token.
+#[derive(Clone)]
+#[cfg_attr(test, derive(Derivative))]
+#[cfg_attr(test, derivative(PartialEq))]
+pub struct ComposableStablePoolOrder {
+    pub pool: ComposableStablePool,
+    #[cfg_attr(test, derivative(PartialEq = "ignore"))]
+    pub settlement_handling: Arc<dyn SettlementHandling<Self>>,
+}
+
+impl std::fmt::Debug for ComposableStablePoolOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Composable Stable Pool AMM {:?}",
+            self.pool.reserves.keys()
+        )
+    }
+}
+
+/// Balancer V2 linear pool trading a main token, its wrapped version and the
+/// pool token itself.
+#[derive(Clone)]
+#[cfg_attr(test, derive(Derivative))]
+#[cfg_attr(test, derivative(PartialEq))]
+pub struct LinearPoolOrder {
+    pub pool: LinearPool,
+    #[cfg_attr(test, derivative(PartialEq = "ignore"))]
+    pub settlement_handling: Arc<dyn SettlementHandling<Self>>,
+}
+
+impl std::fmt::Debug for LinearPoolOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Linear Pool AMM {:?}", self.pool.reserves.keys())
+    }
+}
+
 pub fn token_pairs<T>(reserves: &HashMap<H160, T>) -> Vec<TokenPair> {
     // The `HashMap` docs specifically say that we can't rely on ordering
     // of keys (even across multiple calls). So, first collect all tokens
@@ -273,6 +395,22 @@ impl Settleable for StablePoolOrder {
     }
 }
 
+impl Settleable for ComposableStablePoolOrder {
+    type Execution = AmmOrderExecution;
+
+    fn settlement_handling(&self) -> &dyn SettlementHandling<Self> {
+        &*self.settlement_handling
+    }
+}
+
+impl Settleable for LinearPoolOrder {
+    type Execution = AmmOrderExecution;
+
+    fn settlement_handling(&self) -> &dyn SettlementHandling<Self> {
+        &*self.settlement_handling
+    }
+}
+
 /// Concentrated type of liquidity with ticks (e.g. UniswapV3)
 #[derive(Clone)]
 #[cfg_attr(test, derive(Derivative))]
@@ -298,6 +436,30 @@ impl Settleable for ConcentratedLiquidity {
     }
 }
 
+/// Multi-token stable swap automated market maker with a shared invariant (e.g. Curve)
+#[derive(Clone)]
+#[cfg_attr(test, derive(Derivative))]
+#[cfg_attr(test, derivative(PartialEq))]
+pub struct CurvePoolOrder {
+    pub pool: CurvePool,
+    #[cfg_attr(test, derivative(PartialEq = "ignore"))]
+    pub settlement_handling: Arc<dyn SettlementHandling<Self>>,
+}
+
+impl std::fmt::Debug for CurvePoolOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Curve Pool AMM {:?}", self.pool.address)
+    }
+}
+
+impl Settleable for CurvePoolOrder {
+    type Execution = AmmOrderExecution;
+
+    fn settlement_handling(&self) -> &dyn SettlementHandling<Self> {
+        &*self.settlement_handling
+    }
+}
+
 #[cfg(test)]
 impl Default for ConstantProductOrder {
     fn default() -> Self {
@@ -333,6 +495,23 @@ impl Default for StablePoolOrder {
     }
 }
 
+#[cfg(test)]
+impl Default for CurvePoolOrder {
+    fn default() -> Self {
+        CurvePoolOrder {
+            pool: CurvePool {
+                address: Default::default(),
+                tokens: Default::default(),
+                balances: Default::default(),
+                rates: Default::default(),
+                amplification_parameter: Default::default(),
+                fee: Default::default(),
+            },
+            settlement_handling: tests::CapturingSettlementHandler::arc(),
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -409,6 +588,75 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn limit_order_partial_fill() {
+        let order = LimitOrder {
+            sell_amount: 100.into(),
+            buy_amount: 33.into(),
+            kind: OrderKind::Sell,
+            partially_fillable: true,
+            unscaled_subsidized_fee: 10.into(),
+            scaled_unsubsidized_fee: 20.into(),
+            ..Default::default()
+        };
+
+        let partial = order.partial_fill(50.into()).unwrap();
+        assert_eq!(
+            (
+                partial.sell_amount,
+                partial.buy_amount,
+                partial.unscaled_subsidized_fee,
+                partial.scaled_unsubsidized_fee,
+            ),
+            (50.into(), 17.into(), 5.into(), 10.into()),
+        );
+
+        let buy_order = LimitOrder {
+            kind: OrderKind::Buy,
+            ..order.clone()
+        };
+        let partial = buy_order.partial_fill(10.into()).unwrap();
+        assert_eq!(
+            (partial.sell_amount, partial.buy_amount),
+            (30.into(), 10.into())
+        );
+
+        assert!(order.partial_fill(0.into()).is_none());
+        assert!(order.partial_fill(101.into()).is_none());
+        let fill_or_kill = LimitOrder {
+            partially_fillable: false,
+            ..order
+        };
+        assert!(fill_or_kill.partial_fill(50.into()).is_none());
+    }
+
+    #[test]
+    fn limit_order_max_partial_execution() {
+        let order = LimitOrder {
+            sell_amount: 1_000_000.into(),
+            kind: OrderKind::Sell,
+            partially_fillable: true,
+            ..Default::default()
+        };
+
+        let amount = order
+            .max_partial_execution(|amount| amount <= 424_242.into())
+            .unwrap();
+        assert!(amount <= 424_242.into());
+        assert!(amount >= (424_242 - 100).into());
+
+        assert_eq!(
+            order.max_partial_execution(|_| true),
+            Some(1_000_000.into())
+        );
+        assert_eq!(order.max_partial_execution(|_| false), None);
+        let fill_or_kill = LimitOrder {
+            partially_fillable: false,
+            ..order
+        };
+        assert_eq!(fill_or_kill.max_partial_execution(|_| true), None);
+    }
+
     #[test]
     fn enumerate_token_pairs() {
         let token_map: HashMap<_, Option<u32>> = hashmap! {
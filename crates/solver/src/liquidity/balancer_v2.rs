@@ -6,8 +6,8 @@ use crate::{
         BalancerSwapGivenOutInteraction,
     },
     liquidity::{
-        slippage, AmmOrderExecution, LimitOrder, SettlementHandling, StablePoolOrder,
-        WeightedProductOrder,
+        slippage, AmmOrderExecution, ComposableStablePoolOrder, LimitOrder, LinearPoolOrder,
+        Liquidity, SettlementHandling, StablePoolOrder, WeightedProductOrder,
     },
     settlement::SettlementEncoder,
 };
@@ -21,7 +21,7 @@ use shared::{
 };
 use std::sync::Arc;
 
-/// A liquidity provider for Balancer V2 weighted pools.
+/// A liquidity provider for Balancer V2 pools.
 pub struct BalancerV2Liquidity {
     settlement: GPv2Settlement,
     vault: BalancerV2Vault,
@@ -48,13 +48,12 @@ impl BalancerV2Liquidity {
         }
     }
 
-    /// Returns relevant Balancer V2 weighted pools given a list of off-chain
-    /// orders.
+    /// Returns relevant Balancer V2 pools given a list of off-chain orders.
     pub async fn get_liquidity(
         &self,
         orders: &[LimitOrder],
         block: Block,
-    ) -> Result<(Vec<StablePoolOrder>, Vec<WeightedProductOrder>)> {
+    ) -> Result<Vec<Liquidity>> {
         let pairs = self.base_tokens.relevant_pairs(
             &mut orders
                 .iter()
@@ -69,37 +68,48 @@ impl BalancerV2Liquidity {
                 .await?,
         );
 
-        let weighted_product_orders = pools
-            .weighted_pools
-            .into_iter()
-            .map(|pool| WeightedProductOrder {
+        let settlement_handler = |pool_id| {
+            Arc::new(SettlementHandler {
+                pool_id,
+                settlement: self.settlement.clone(),
+                vault: self.vault.clone(),
+                allowances: allowances.clone(),
+            })
+        };
+
+        let weighted_product_orders = pools.weighted_pools.into_iter().map(|pool| {
+            Liquidity::BalancerWeighted(WeightedProductOrder {
                 reserves: pool.reserves,
                 fee: pool.common.swap_fee,
-                settlement_handling: Arc::new(SettlementHandler {
-                    pool_id: pool.common.id,
-                    settlement: self.settlement.clone(),
-                    vault: self.vault.clone(),
-                    allowances: allowances.clone(),
-                }),
+                settlement_handling: settlement_handler(pool.common.id),
             })
-            .collect();
-        let stable_pool_orders = pools
-            .stable_pools
-            .into_iter()
-            .map(|pool| StablePoolOrder {
+        });
+        let stable_pool_orders = pools.stable_pools.into_iter().map(|pool| {
+            Liquidity::BalancerStable(StablePoolOrder {
                 reserves: pool.reserves,
                 fee: pool.common.swap_fee.into(),
                 amplification_parameter: pool.amplification_parameter,
-                settlement_handling: Arc::new(SettlementHandler {
-                    pool_id: pool.common.id,
-                    settlement: self.settlement.clone(),
-                    vault: self.vault.clone(),
-                    allowances: allowances.clone(),
-                }),
+                settlement_handling: settlement_handler(pool.common.id),
             })
-            .collect();
+        });
+        let composable_stable_pool_orders = pools.composable_stable_pools.into_iter().map(|pool| {
+            Liquidity::BalancerComposableStable(ComposableStablePoolOrder {
+                settlement_handling: settlement_handler(pool.common.id),
+                pool,
+            })
+        });
+        let linear_pool_orders = pools.linear_pools.into_iter().map(|pool| {
+            Liquidity::BalancerLinear(LinearPoolOrder {
+                settlement_handling: settlement_handler(pool.common.id),
+                pool,
+            })
+        });
 
-        Ok((stable_pool_orders, weighted_product_orders))
+        Ok(weighted_product_orders
+            .chain(stable_pool_orders)
+            .chain(composable_stable_pool_orders)
+            .chain(linear_pool_orders)
+            .collect())
     }
 }
 
@@ -139,6 +149,18 @@ impl SettlementHandling<StablePoolOrder> for SettlementHandler {
     }
 }
 
+impl SettlementHandling<ComposableStablePoolOrder> for SettlementHandler {
+    fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        self.inner_encode(execution, encoder)
+    }
+}
+
+impl SettlementHandling<LinearPoolOrder> for SettlementHandler {
+    fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        self.inner_encode(execution, encoder)
+    }
+}
+
 impl SettlementHandler {
     fn inner_encode(
         &self,
@@ -148,22 +170,23 @@ impl SettlementHandler {
         let (asset_in, amount_in) = execution.input;
         let (asset_out, amount_out) = execution.output;
 
-        encoder.append_to_execution_plan(self.allowances.approve_token(asset_in, amount_in)?);
-        encoder.append_to_execution_plan(BalancerSwapGivenOutInteraction {
-            settlement: self.settlement.clone(),
-            vault: self.vault.clone(),
-            pool_id: self.pool_id,
-            asset_in,
-            asset_out,
-            amount_out,
-            amount_in_max: slippage::amount_plus_max_slippage(amount_in),
-            // Balancer pools allow passing additional user data in order to
-            // control pool behaviour for swaps. That being said, weighted pools
-            // do not seem to make use of this at the moment so leave it empty.
-            user_data: Default::default(),
-        });
-
-        Ok(())
+        encoder.append_balancer_swap(
+            self.allowances.approve_token(asset_in, amount_in)?,
+            BalancerSwapGivenOutInteraction {
+                settlement: self.settlement.clone(),
+                vault: self.vault.clone(),
+                pool_id: self.pool_id,
+                asset_in,
+                asset_out,
+                amount_out,
+                amount_in_max: slippage::amount_plus_max_slippage(amount_in),
+                amount_in,
+                // Balancer pools allow passing additional user data in order to
+                // control pool behaviour for swaps. That being said, none of the
+                // supported pools make use of this at the moment so leave it empty.
+                user_data: Default::default(),
+            },
+        )
     }
 }
 
@@ -171,7 +194,10 @@ impl SettlementHandler {
 mod tests {
     use super::*;
     use crate::{
-        interactions::allowances::{Approval, MockAllowanceManaging},
+        interactions::{
+            allowances::{Approval, MockAllowanceManaging},
+            BalancerBatchSwapInteraction,
+        },
         settlement::Interaction,
     };
     use maplit::{hashmap, hashset};
@@ -304,6 +330,7 @@ mod tests {
                     Ok(FetchedBalancerPools {
                         stable_pools: stable_pools.clone(),
                         weighted_pools: weighted_pools.clone(),
+                        ..Default::default()
                     })
                 }
             });
@@ -331,7 +358,7 @@ mod tests {
             allowance_manager: Box::new(allowance_manager),
             base_tokens,
         };
-        let (stable_orders, weighted_orders) = liquidity_provider
+        let liquidity = liquidity_provider
             .get_liquidity(
                 &[
                     LimitOrder {
@@ -355,6 +382,15 @@ mod tests {
             .await
             .unwrap();
 
+        let (mut stable_orders, mut weighted_orders) = (Vec::new(), Vec::new());
+        for liquidity in liquidity {
+            match liquidity {
+                Liquidity::BalancerStable(order) => stable_orders.push(order),
+                Liquidity::BalancerWeighted(order) => weighted_orders.push(order),
+                _ => panic!("unexpected liquidity {:?}", liquidity),
+            }
+        }
+
         assert_eq!(weighted_orders.len(), 2);
         assert_eq!(stable_orders.len(), 1);
 
@@ -411,6 +447,31 @@ mod tests {
         )
         .unwrap();
 
+        let swaps = [
+            BalancerSwapGivenOutInteraction {
+                settlement: settlement.clone(),
+                vault: vault.clone(),
+                pool_id: H256([0x90; 32]),
+                asset_in: H160([0x70; 20]),
+                asset_out: H160([0x71; 20]),
+                amount_out: 11.into(),
+                amount_in_max: slippage::amount_plus_max_slippage(10.into()),
+                amount_in: 10.into(),
+                user_data: Default::default(),
+            },
+            BalancerSwapGivenOutInteraction {
+                settlement,
+                vault: vault.clone(),
+                pool_id: H256([0x90; 32]),
+                asset_in: H160([0x71; 20]),
+                asset_out: H160([0x72; 20]),
+                amount_out: 13.into(),
+                amount_in_max: slippage::amount_plus_max_slippage(12.into()),
+                amount_in: 12.into(),
+                user_data: Default::default(),
+            },
+        ];
+
         let [_, interactions, _] = encoder.finish().interactions;
         assert_eq!(
             interactions,
@@ -420,29 +481,10 @@ mod tests {
                     spender: vault.address(),
                 }
                 .encode(),
-                BalancerSwapGivenOutInteraction {
-                    settlement: settlement.clone(),
-                    vault: vault.clone(),
-                    pool_id: H256([0x90; 32]),
-                    asset_in: H160([0x70; 20]),
-                    asset_out: H160([0x71; 20]),
-                    amount_out: 11.into(),
-                    amount_in_max: slippage::amount_plus_max_slippage(10.into()),
-                    user_data: Default::default(),
-                }
-                .encode(),
                 Approval::AllowanceSufficient.encode(),
-                BalancerSwapGivenOutInteraction {
-                    settlement,
-                    vault,
-                    pool_id: H256([0x90; 32]),
-                    asset_in: H160([0x71; 20]),
-                    asset_out: H160([0x72; 20]),
-                    amount_out: 13.into(),
-                    amount_in_max: slippage::amount_plus_max_slippage(12.into()),
-                    user_data: Default::default(),
-                }
-                .encode(),
+                BalancerBatchSwapInteraction::given_out(&swaps)
+                    .unwrap()
+                    .encode(),
             ]
             .concat(),
         );
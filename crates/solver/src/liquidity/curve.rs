@@ -0,0 +1,300 @@
+//! Module for providing Curve pool liquidity to the solvers.
+
+use crate::{
+    interactions::{
+        allowances::{AllowanceManager, AllowanceManaging, Allowances},
+        CurveExchangeInteraction,
+    },
+    liquidity::{slippage, AmmOrderExecution, CurvePoolOrder, LimitOrder, SettlementHandling},
+    settlement::SettlementEncoder,
+};
+use anyhow::{Context as _, Result};
+use contracts::{CurvePool, GPv2Settlement};
+use futures::future;
+use model::TokenPair;
+use primitive_types::H160;
+use shared::{
+    baseline_solver::BaseTokens, recent_block_cache::Block,
+    sources::curve::pool_fetching::CurvePoolFetching, Web3,
+};
+use std::sync::Arc;
+
+/// A liquidity provider for Curve plain pools.
+pub struct CurveLiquidity {
+    web3: Web3,
+    pool_fetcher: Arc<dyn CurvePoolFetching>,
+    allowance_manager: Box<dyn AllowanceManaging>,
+    base_tokens: Arc<BaseTokens>,
+}
+
+impl CurveLiquidity {
+    pub fn new(
+        web3: Web3,
+        pool_fetcher: Arc<dyn CurvePoolFetching>,
+        base_tokens: Arc<BaseTokens>,
+        settlement: GPv2Settlement,
+    ) -> Self {
+        let allowance_manager = AllowanceManager::new(web3.clone(), settlement.address());
+        Self {
+            web3,
+            pool_fetcher,
+            allowance_manager: Box::new(allowance_manager),
+            base_tokens,
+        }
+    }
+
+    /// Returns relevant Curve pools given a list of off-chain orders.
+    pub async fn get_liquidity(
+        &self,
+        orders: &[LimitOrder],
+        block: Block,
+    ) -> Result<Vec<CurvePoolOrder>> {
+        let pairs = self.base_tokens.relevant_pairs(
+            &mut orders
+                .iter()
+                .flat_map(|order| TokenPair::new(order.buy_token, order.sell_token)),
+        );
+        let pools = self.pool_fetcher.fetch(pairs, block).await?;
+
+        // Curve pools pull the sell token themselves, so each pool is its own
+        // spender.
+        let allowances = future::try_join_all(pools.iter().map(|pool| {
+            self.allowance_manager
+                .get_allowances(pool.tokens.iter().copied().collect(), pool.address)
+        }))
+        .await?;
+
+        Ok(pools
+            .into_iter()
+            .zip(allowances)
+            .map(|(pool, allowances)| CurvePoolOrder {
+                settlement_handling: Arc::new(SettlementHandler {
+                    pool: CurvePool::at(&self.web3, pool.address),
+                    tokens: pool.tokens.clone(),
+                    allowances,
+                }),
+                pool,
+            })
+            .collect())
+    }
+}
+
+pub struct SettlementHandler {
+    pool: CurvePool,
+    tokens: Vec<H160>,
+    allowances: Allowances,
+}
+
+impl SettlementHandler {
+    fn token_index(&self, token: H160) -> Result<i128> {
+        let index = self
+            .tokens
+            .iter()
+            .position(|t| *t == token)
+            .with_context(|| format!("token {:?} not in Curve pool", token))?;
+        Ok(index as i128)
+    }
+}
+
+impl SettlementHandling<CurvePoolOrder> for SettlementHandler {
+    fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        let (token_in, amount_in) = execution.input;
+        let (token_out, amount_out) = execution.output;
+        let i = self.token_index(token_in)?;
+        let j = self.token_index(token_out)?;
+
+        encoder.append_to_execution_plan(self.allowances.approve_token(token_in, amount_in)?);
+        encoder.append_to_execution_plan(CurveExchangeInteraction {
+            pool: self.pool.clone(),
+            i,
+            j,
+            dx: amount_in,
+            min_dy: slippage::amount_minus_max_slippage(amount_out),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interactions::allowances::{Approval, MockAllowanceManaging},
+        settlement::Interaction,
+    };
+    use maplit::{hashmap, hashset};
+    use mockall::predicate::*;
+    use primitive_types::U256;
+    use shared::{
+        dummy_contract,
+        sources::curve::pool_fetching::{MockCurvePoolFetching, Pool},
+        transport::dummy::DummyTransport,
+        Web3Transport,
+    };
+
+    fn pool(address: u8, tokens: &[u8]) -> Pool {
+        Pool {
+            address: H160([address; 20]),
+            tokens: tokens.iter().map(|seed| H160([*seed; 20])).collect(),
+            balances: vec![U256::exp10(24); tokens.len()],
+            rates: vec![U256::exp10(18); tokens.len()],
+            amplification_parameter: 100.into(),
+            fee: 4_000_000.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_liquidity() {
+        let mut pool_fetcher = MockCurvePoolFetching::new();
+        let mut allowance_manager = MockAllowanceManaging::new();
+
+        let pools = vec![pool(0x90, &[0x70, 0x71, 0xb0]), pool(0x91, &[0x70, 0x72])];
+
+        pool_fetcher
+            .expect_fetch()
+            .with(
+                eq(hashset![
+                    TokenPair::new(H160([0x70; 20]), H160([0x71; 20])).unwrap(),
+                    TokenPair::new(H160([0x70; 20]), H160([0xb0; 20])).unwrap(),
+                    TokenPair::new(H160([0x71; 20]), H160([0xb0; 20])).unwrap(),
+                ]),
+                always(),
+            )
+            .returning({
+                let pools = pools.clone();
+                move |_, _| Ok(pools.clone())
+            });
+        allowance_manager
+            .expect_get_allowances()
+            .with(
+                eq(hashset![
+                    H160([0x70; 20]),
+                    H160([0x71; 20]),
+                    H160([0xb0; 20])
+                ]),
+                eq(H160([0x90; 20])),
+            )
+            .returning(|_, spender| Ok(Allowances::empty(spender)));
+        allowance_manager
+            .expect_get_allowances()
+            .with(
+                eq(hashset![H160([0x70; 20]), H160([0x72; 20])]),
+                eq(H160([0x91; 20])),
+            )
+            .returning(|_, spender| Ok(Allowances::empty(spender)));
+
+        let liquidity_provider = CurveLiquidity {
+            web3: Web3::new(Web3Transport::new(DummyTransport)),
+            pool_fetcher: Arc::new(pool_fetcher),
+            allowance_manager: Box::new(allowance_manager),
+            base_tokens: Arc::new(BaseTokens::new(H160([0xb0; 20]), &[])),
+        };
+        let orders = liquidity_provider
+            .get_liquidity(
+                &[LimitOrder {
+                    sell_token: H160([0x70; 20]),
+                    buy_token: H160([0x71; 20]),
+                    ..Default::default()
+                }],
+                Block::Recent,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            orders.iter().map(|order| &order.pool).collect::<Vec<_>>(),
+            pools.iter().collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn encodes_exchanges_in_settlement() {
+        let pool = dummy_contract!(CurvePool, H160([0x90; 20]));
+        let handler = SettlementHandler {
+            pool: pool.clone(),
+            tokens: vec![H160([0x70; 20]), H160([0x71; 20]), H160([0x72; 20])],
+            allowances: Allowances::new(
+                pool.address(),
+                hashmap! {
+                    H160([0x70; 20]) => 0.into(),
+                    H160([0x72; 20]) => 100.into(),
+                },
+            ),
+        };
+
+        let mut encoder = SettlementEncoder::new(Default::default());
+        handler
+            .encode(
+                AmmOrderExecution {
+                    input: (H160([0x70; 20]), 10.into()),
+                    output: (H160([0x71; 20]), 11.into()),
+                },
+                &mut encoder,
+            )
+            .unwrap();
+        handler
+            .encode(
+                AmmOrderExecution {
+                    input: (H160([0x72; 20]), 12.into()),
+                    output: (H160([0x70; 20]), 13.into()),
+                },
+                &mut encoder,
+            )
+            .unwrap();
+
+        let [_, interactions, _] = encoder.finish().interactions;
+        assert_eq!(
+            interactions,
+            [
+                Approval::Approve {
+                    token: H160([0x70; 20]),
+                    spender: pool.address(),
+                }
+                .encode(),
+                CurveExchangeInteraction {
+                    pool: pool.clone(),
+                    i: 0,
+                    j: 1,
+                    dx: 10.into(),
+                    min_dy: slippage::amount_minus_max_slippage(11.into()),
+                }
+                .encode(),
+                Approval::AllowanceSufficient.encode(),
+                CurveExchangeInteraction {
+                    pool,
+                    i: 2,
+                    j: 0,
+                    dx: 12.into(),
+                    min_dy: slippage::amount_minus_max_slippage(13.into()),
+                }
+                .encode(),
+            ]
+            .concat(),
+        );
+    }
+
+    #[test]
+    fn fails_to_encode_unknown_tokens() {
+        let pool = dummy_contract!(CurvePool, H160([0x90; 20]));
+        let handler = SettlementHandler {
+            pool: pool.clone(),
+            tokens: vec![H160([0x70; 20]), H160([0x71; 20])],
+            allowances: Allowances::new(
+                pool.address(),
+                hashmap! { H160([0x70; 20]) => 100.into() },
+            ),
+        };
+
+        let mut encoder = SettlementEncoder::new(Default::default());
+        assert!(handler
+            .encode(
+                AmmOrderExecution {
+                    input: (H160([0x70; 20]), 10.into()),
+                    output: (H160([0x72; 20]), 11.into()),
+                },
+                &mut encoder,
+            )
+            .is_err());
+    }
+}
@@ -50,6 +50,7 @@ impl OrderConverter {
             unscaled_subsidized_fee: remaining.fee_amount,
             scaled_unsubsidized_fee: scaled_fee_amount,
             is_liquidity_order,
+            gpv2_order: Some(order.clone()),
             settlement_handling: Arc::new(OrderSettlementHandler {
                 order,
                 native_token,
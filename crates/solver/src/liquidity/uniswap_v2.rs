@@ -11,10 +11,12 @@ use contracts::{GPv2Settlement, IUniswapLikeRouter};
 use model::TokenPair;
 use primitive_types::{H160, U256};
 use shared::{
-    baseline_solver::BaseTokens, recent_block_cache::Block,
-    sources::uniswap_v2::pool_fetching::PoolFetching, Web3,
+    baseline_solver::BaseTokens,
+    recent_block_cache::Block,
+    sources::{uniswap_v2::pool_fetching::PoolFetching, BaselineSource},
+    Web3,
 };
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
 pub struct UniswapLikeLiquidity {
@@ -136,6 +138,7 @@ impl Inner {
                 // Apply fixed slippage tolerance in case balances change between solution finding and mining
                 amount_out,
                 amount_in_max: amount_in_with_slippage,
+                amount_in,
                 token_in,
                 token_out,
             },
@@ -153,6 +156,52 @@ impl SettlementHandling<ConstantProductOrder> for Inner {
     }
 }
 
+/// Creates the liquidity of the Uniswap-like baseline sources, ignoring other kinds of sources.
+pub async fn build_amm_artifacts(
+    sources: &HashMap<BaselineSource, Arc<dyn PoolFetching>>,
+    settlement_contract: GPv2Settlement,
+    base_tokens: Arc<BaseTokens>,
+    web3: Web3,
+) -> Vec<UniswapLikeLiquidity> {
+    let mut res = vec![];
+    for (source, pool_fetcher) in sources {
+        let router_address = match source {
+            BaselineSource::UniswapV2 => contracts::UniswapV2Router02::deployed(&web3)
+                .await
+                .expect("couldn't load deployed UniswapV2 router")
+                .address(),
+            BaselineSource::SushiSwap => contracts::SushiSwapRouter::deployed(&web3)
+                .await
+                .expect("couldn't load deployed SushiSwap router")
+                .address(),
+            BaselineSource::Honeyswap => contracts::HoneyswapRouter::deployed(&web3)
+                .await
+                .expect("couldn't load deployed Honeyswap router")
+                .address(),
+            BaselineSource::Baoswap => contracts::BaoswapRouter::deployed(&web3)
+                .await
+                .expect("couldn't load deployed Baoswap router")
+                .address(),
+            BaselineSource::Swapr => contracts::SwaprRouter::deployed(&web3)
+                .await
+                .expect("couldn't load deployed Swapr router")
+                .address(),
+            BaselineSource::BalancerV2 => continue,
+            BaselineSource::ZeroEx => continue,
+            BaselineSource::UniswapV3 => continue,
+            BaselineSource::Curve => continue,
+        };
+        res.push(UniswapLikeLiquidity::new(
+            IUniswapLikeRouter::at(&web3, router_address),
+            settlement_contract.clone(),
+            base_tokens.clone(),
+            web3.clone(),
+            pool_fetcher.clone(),
+        ));
+    }
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
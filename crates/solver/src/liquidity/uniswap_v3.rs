@@ -165,6 +165,7 @@ impl UniswapV3SettlementHandler {
                     amount_in_max: amount_in_with_slippage,
                     sqrt_price_limit_x96: U256::zero(),
                 },
+                amount_in,
             },
         )
     }
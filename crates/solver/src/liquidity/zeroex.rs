@@ -85,6 +85,7 @@ impl ZeroExLiquidity {
                 zeroex: self.zeroex.clone(),
             }),
             exchange: Exchange::ZeroEx,
+            gpv2_order: None,
         };
         Some(Liquidity::LimitOrder(limit_order))
     }
@@ -1,18 +1,43 @@
 use crate::{
     liquidity::Liquidity,
     liquidity::{
-        balancer_v2::BalancerV2Liquidity, uniswap_v2::UniswapLikeLiquidity,
-        uniswap_v3::UniswapV3Liquidity, zeroex::ZeroExLiquidity, LimitOrder,
+        balancer_v2::BalancerV2Liquidity,
+        curve::CurveLiquidity,
+        uniswap_v2::{build_amm_artifacts, UniswapLikeLiquidity},
+        uniswap_v3::UniswapV3Liquidity,
+        zeroex::ZeroExLiquidity,
+        LimitOrder,
     },
 };
 use anyhow::{Context, Result};
-use shared::recent_block_cache::Block;
+use contracts::{GPv2Settlement, UniswapV3SwapRouter};
+use ethcontract::H256;
+use reqwest::Client;
+use shared::{
+    baseline_solver::BaseTokens,
+    current_block::CurrentBlockStream,
+    maintenance::Maintaining,
+    recent_block_cache::{Block, CacheConfig},
+    sources::{
+        self,
+        balancer_v2::{pool_fetching::BalancerContracts, BalancerFactoryKind, BalancerPoolFetcher},
+        curve::pool_fetching::CurvePoolFetcher,
+        uniswap_v2::{pool_cache::PoolCache, pool_fetching::PoolFetching},
+        uniswap_v3::event_fetching::UniswapV3PoolIndexer,
+        BaselineSource,
+    },
+    token_info::TokenInfoFetching,
+    zeroex_api::ZeroExApi,
+    Web3,
+};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 pub struct LiquidityCollector {
     pub uniswap_like_liquidity: Vec<UniswapLikeLiquidity>,
     pub balancer_v2_liquidity: Option<BalancerV2Liquidity>,
     pub zeroex_liquidity: Option<ZeroExLiquidity>,
     pub uniswap_v3_liquidity: Option<UniswapV3Liquidity>,
+    pub curve_liquidity: Option<CurveLiquidity>,
 }
 
 impl LiquidityCollector {
@@ -38,13 +63,12 @@ impl LiquidityCollector {
             );
         }
         if let Some(balancer_v2_liquidity) = self.balancer_v2_liquidity.as_ref() {
-            let (stable_orders, weighted_orders) = balancer_v2_liquidity
-                .get_liquidity(&user_orders, at_block)
-                .await
-                .context("failed to get Balancer liquidity")?;
-
-            amms.extend(weighted_orders.into_iter().map(Liquidity::BalancerWeighted));
-            amms.extend(stable_orders.into_iter().map(Liquidity::BalancerStable));
+            amms.extend(
+                balancer_v2_liquidity
+                    .get_liquidity(&user_orders, at_block)
+                    .await
+                    .context("failed to get Balancer liquidity")?,
+            );
         }
         if let Some(zeroex_liquidity) = self.zeroex_liquidity.as_ref() {
             amms.append(&mut zeroex_liquidity.get_liquidity(limit_orders).await?)
@@ -59,8 +83,164 @@ impl LiquidityCollector {
                     .map(Liquidity::Concentrated),
             )
         }
+        if let Some(curve_liquidity) = self.curve_liquidity.as_ref() {
+            amms.extend(
+                curve_liquidity
+                    .get_liquidity(&user_orders, at_block)
+                    .await
+                    .context("failed to get Curve liquidity")?
+                    .into_iter()
+                    .map(Liquidity::Curve),
+            )
+        }
         tracing::debug!("got {} AMMs", amms.len());
 
         Ok(amms)
     }
 }
+
+/// A `LiquidityCollector` for the configured baseline sources together with the pool fetchers it
+/// uses.
+pub struct LiquiditySources {
+    pub collector: LiquidityCollector,
+    /// The cached pool fetchers of the uniswap-like sources.
+    pub pool_caches: HashMap<BaselineSource, Arc<PoolCache>>,
+    /// The pool fetchers that need to be maintained on every new block.
+    pub maintainers: Vec<Arc<dyn Maintaining>>,
+}
+
+/// Creates the liquidity collector and pool fetchers for the specified baseline sources.
+#[allow(clippy::too_many_arguments)]
+pub async fn build_liquidity_sources(
+    web3: &Web3,
+    client: &Client,
+    chain_id: u64,
+    baseline_sources: &[BaselineSource],
+    cache_config: CacheConfig,
+    current_block_stream: CurrentBlockStream,
+    token_info_fetcher: Arc<dyn TokenInfoFetching>,
+    base_tokens: Arc<BaseTokens>,
+    settlement_contract: &GPv2Settlement,
+    zeroex_api: Arc<dyn ZeroExApi>,
+    balancer_factories: Option<Vec<BalancerFactoryKind>>,
+    balancer_pool_deny_list: Vec<H256>,
+    uniswap_v3_checkpoint_path: Option<PathBuf>,
+) -> Result<LiquiditySources> {
+    let mut maintainers = Vec::<Arc<dyn Maintaining>>::new();
+
+    let mut pool_caches = HashMap::new();
+    for (source, (_, pool_fetcher)) in
+        sources::uniswap_like_liquidity_sources(web3, baseline_sources)
+            .await
+            .context("failed to load baseline source uniswap liquidity")?
+    {
+        let pool_cache = Arc::new(
+            PoolCache::new(cache_config, pool_fetcher, current_block_stream.clone())
+                .context("failed to create pool cache")?,
+        );
+        maintainers.push(pool_cache.clone());
+        pool_caches.insert(source, pool_cache);
+    }
+    let uniswap_like_liquidity = build_amm_artifacts(
+        &pool_caches
+            .iter()
+            .map(|(source, pool_cache)| (*source, pool_cache.clone() as Arc<dyn PoolFetching>))
+            .collect(),
+        settlement_contract.clone(),
+        base_tokens.clone(),
+        web3.clone(),
+    )
+    .await;
+
+    let balancer_v2_liquidity = if baseline_sources.contains(&BaselineSource::BalancerV2) {
+        let factories =
+            balancer_factories.unwrap_or_else(|| BalancerFactoryKind::for_chain(chain_id));
+        let contracts = BalancerContracts::new(web3, factories).await?;
+        let balancer_pool_fetcher = Arc::new(
+            BalancerPoolFetcher::new(
+                chain_id,
+                token_info_fetcher,
+                cache_config,
+                current_block_stream.clone(),
+                client.clone(),
+                &contracts,
+                balancer_pool_deny_list,
+            )
+            .await
+            .context("failed to create Balancer pool fetcher")?,
+        );
+        maintainers.push(balancer_pool_fetcher.clone());
+        Some(BalancerV2Liquidity::new(
+            web3.clone(),
+            balancer_pool_fetcher,
+            base_tokens.clone(),
+            settlement_contract.clone(),
+            contracts.vault,
+        ))
+    } else {
+        None
+    };
+
+    let zeroex_liquidity = if baseline_sources.contains(&BaselineSource::ZeroEx) {
+        Some(ZeroExLiquidity {
+            api: zeroex_api,
+            zeroex: contracts::IZeroEx::deployed(web3).await?,
+            base_tokens: base_tokens.clone(),
+            gpv2: settlement_contract.clone(),
+        })
+    } else {
+        None
+    };
+
+    let uniswap_v3_liquidity = if baseline_sources.contains(&BaselineSource::UniswapV3) {
+        let uniswap_v3_pool_fetcher = Arc::new(
+            UniswapV3PoolIndexer::new(
+                chain_id,
+                client.clone(),
+                web3.clone(),
+                uniswap_v3_checkpoint_path,
+            )
+            .await
+            .context("failed to create UniswapV3 pool fetcher")?,
+        );
+        maintainers.push(uniswap_v3_pool_fetcher.clone());
+        Some(UniswapV3Liquidity::new(
+            UniswapV3SwapRouter::deployed(web3).await?,
+            settlement_contract.clone(),
+            base_tokens.clone(),
+            web3.clone(),
+            uniswap_v3_pool_fetcher,
+        ))
+    } else {
+        None
+    };
+
+    let curve_liquidity = if baseline_sources.contains(&BaselineSource::Curve) {
+        let curve_pool_fetcher = Arc::new(
+            CurvePoolFetcher::new(web3, cache_config, current_block_stream)
+                .await
+                .context("failed to create Curve pool fetcher")?,
+        );
+        maintainers.push(curve_pool_fetcher.clone());
+        Some(CurveLiquidity::new(
+            web3.clone(),
+            curve_pool_fetcher,
+            base_tokens,
+            settlement_contract.clone(),
+        ))
+    } else {
+        None
+    };
+
+    Ok(LiquiditySources {
+        collector: LiquidityCollector {
+            uniswap_like_liquidity,
+            balancer_v2_liquidity,
+            zeroex_liquidity,
+            uniswap_v3_liquidity,
+            curve_liquidity,
+        },
+        pool_caches,
+        maintainers,
+    })
+}
@@ -1,38 +1,19 @@
 use anyhow::Context;
 use clap::Parser;
-use contracts::{BalancerV2Vault, IUniswapLikeRouter, UniswapV3SwapRouter, WETH9};
 use num::rational::Ratio;
-use primitive_types::U256;
 use shared::{
-    baseline_solver::BaseTokens,
-    current_block::current_block_stream,
-    maintenance::{Maintaining, ServiceMaintenance},
-    metrics::serve_metrics,
-    network::network_name,
-    recent_block_cache::CacheConfig,
-    sources::{
-        self,
-        balancer_v2::{pool_fetching::BalancerContracts, BalancerFactoryKind, BalancerPoolFetcher},
-        uniswap_v2::pool_cache::PoolCache,
-        uniswap_v3::pool_fetching::AutoUpdatingUniswapV3PoolFetcher,
-        BaselineSource,
-    },
-    token_info::{CachedTokenInfoFetcher, TokenInfoFetcher},
-    token_list::TokenList,
-    transport::http::HttpTransport,
-    zeroex_api::DefaultZeroExApi,
-    Web3Transport,
+    maintenance::ServiceMaintenance, metrics::serve_metrics,
+    sources::uniswap_v2::pool_fetching::PoolFetching, token_list::TokenList,
+    transport::http::HttpTransport, Web3Transport,
 };
 use solver::{
     arguments::TransactionStrategyArg,
     driver::Driver,
-    liquidity::{
-        balancer_v2::BalancerV2Liquidity, order_converter::OrderConverter,
-        uniswap_v2::UniswapLikeLiquidity, uniswap_v3::UniswapV3Liquidity, zeroex::ZeroExLiquidity,
-    },
-    liquidity_collector::LiquidityCollector,
+    liquidity::order_converter::OrderConverter,
     metrics::Metrics,
     orderbook::OrderBookApi,
+    settlement_ebbo::EbboValidator,
+    settlement_post_processing::BufferInternalization,
     settlement_simulation::TenderlyApi,
     settlement_submission::{
         submitter::{
@@ -41,8 +22,9 @@ use solver::{
         },
         GlobalTxPool, SolutionSubmitter, StrategyArgs, TransactionStrategy,
     },
+    setup::Components,
 };
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() {
@@ -56,41 +38,29 @@ async fn main() {
     global_metrics::setup_metrics_registry(Some("gp_v2_solver".into()), None);
     let metrics = Arc::new(Metrics::new().expect("Couldn't register metrics"));
 
-    let client = shared::http_client(args.shared.http_timeout);
-
-    let transport = Web3Transport::new(HttpTransport::new(
-        client.clone(),
-        args.shared.node_url,
-        "base".to_string(),
-    ));
-    let web3 = web3::Web3::new(transport);
-    let chain_id = web3
-        .eth()
-        .chain_id()
+    let components = Components::new(&args)
         .await
-        .expect("Could not get chainId")
-        .as_u64();
-    let network_id = web3
-        .net()
-        .version()
+        .expect("failed to set up solver components");
+    tracing::info!(baseline_sources = ?components.baseline_sources, "using baseline sources");
+    let solver = components
+        .create_solvers(&args, metrics.clone())
+        .expect("failure creating solvers");
+    let liquidity_sources = components
+        .build_liquidity_sources(&args, &components.baseline_sources)
         .await
-        .expect("failed to get network id");
-    let network_name = network_name(&network_id, chain_id);
-    let settlement_contract = solver::get_settlement_contract(&web3)
-        .await
-        .expect("couldn't load deployed settlement");
-    let vault_contract = BalancerV2Vault::deployed(&web3).await.ok();
-    let native_token_contract = WETH9::deployed(&web3)
-        .await
-        .expect("couldn't load deployed native token");
-    let base_tokens = Arc::new(BaseTokens::new(
-        native_token_contract.address(),
-        &args.shared.base_tokens,
-    ));
+        .expect("failed to create liquidity sources");
+    let Components {
+        client,
+        web3,
+        chain_id,
+        network_id,
+        settlement_contract,
+        native_token_contract,
+        base_tokens,
+        current_block_stream,
+        ..
+    } = components;
 
-    let token_info_fetcher = Arc::new(CachedTokenInfoFetcher::new(Box::new(TokenInfoFetcher {
-        web3: web3.clone(),
-    })));
     let gas_price_estimator = Arc::new(
         shared::gas_price_estimation::create_priority_estimator(
             client.clone(),
@@ -101,187 +71,6 @@ async fn main() {
         .await
         .expect("failed to create gas price estimator"),
     );
-
-    let current_block_stream =
-        current_block_stream(web3.clone(), args.shared.block_stream_poll_interval_seconds)
-            .await
-            .unwrap();
-
-    let cache_config = CacheConfig {
-        number_of_blocks_to_cache: args.shared.pool_cache_blocks,
-        // 0 because we don't make use of the auto update functionality as we always fetch
-        // for specific blocks
-        number_of_entries_to_auto_update: 0,
-        maximum_recent_block_age: args.shared.pool_cache_maximum_recent_block_age,
-        max_retries: args.shared.pool_cache_maximum_retries,
-        delay_between_retries: args.shared.pool_cache_delay_between_retries_seconds,
-    };
-    let baseline_sources = args.shared.baseline_sources.unwrap_or_else(|| {
-        sources::defaults_for_chain(chain_id).expect("failed to get default baseline sources")
-    });
-    tracing::info!(?baseline_sources, "using baseline sources");
-    let pool_caches: HashMap<BaselineSource, Arc<PoolCache>> =
-        sources::uniswap_like_liquidity_sources(&web3, &baseline_sources)
-            .await
-            .expect("failed to load baseline source uniswap liquidity")
-            .into_iter()
-            .map(|(source, (_, pool_fetcher))| {
-                let pool_cache =
-                    PoolCache::new(cache_config, pool_fetcher, current_block_stream.clone())
-                        .expect("failed to create pool cache");
-                (source, Arc::new(pool_cache))
-            })
-            .collect();
-
-    let (balancer_pool_maintainer, balancer_v2_liquidity) =
-        if baseline_sources.contains(&BaselineSource::BalancerV2) {
-            let factories = args
-                .shared
-                .balancer_factories
-                .unwrap_or_else(|| BalancerFactoryKind::for_chain(chain_id));
-            let contracts = BalancerContracts::new(&web3, factories).await.unwrap();
-            let balancer_pool_fetcher = Arc::new(
-                BalancerPoolFetcher::new(
-                    chain_id,
-                    token_info_fetcher.clone(),
-                    cache_config,
-                    current_block_stream.clone(),
-                    client.clone(),
-                    &contracts,
-                    args.shared.balancer_pool_deny_list,
-                )
-                .await
-                .expect("failed to create Balancer pool fetcher"),
-            );
-            (
-                Some(balancer_pool_fetcher.clone() as Arc<dyn Maintaining>),
-                Some(BalancerV2Liquidity::new(
-                    web3.clone(),
-                    balancer_pool_fetcher,
-                    base_tokens.clone(),
-                    settlement_contract.clone(),
-                    contracts.vault,
-                )),
-            )
-        } else {
-            (None, None)
-        };
-
-    let uniswap_like_liquidity = build_amm_artifacts(
-        &pool_caches,
-        settlement_contract.clone(),
-        base_tokens.clone(),
-        web3.clone(),
-    )
-    .await;
-
-    let solvers = {
-        if let Some(solver_accounts) = args.solver_accounts {
-            assert!(
-                solver_accounts.len() == args.solvers.len(),
-                "number of solvers ({}) does not match the number of accounts ({})",
-                args.solvers.len(),
-                solver_accounts.len()
-            );
-
-            solver_accounts
-                .into_iter()
-                .map(|account_arg| account_arg.into_account(chain_id))
-                .zip(args.solvers)
-                .collect()
-        } else if let Some(account_arg) = args.solver_account {
-            std::iter::repeat(account_arg.into_account(chain_id))
-                .zip(args.solvers)
-                .collect()
-        } else {
-            panic!("either SOLVER_ACCOUNTS or SOLVER_ACCOUNT must be set")
-        }
-    };
-
-    let zeroex_api = Arc::new(
-        DefaultZeroExApi::new(
-            args.shared
-                .zeroex_url
-                .as_deref()
-                .unwrap_or(DefaultZeroExApi::DEFAULT_URL),
-            args.shared.zeroex_api_key,
-            client.clone(),
-        )
-        .unwrap(),
-    );
-
-    let solver = solver::solver::create(
-        web3.clone(),
-        solvers,
-        base_tokens.clone(),
-        native_token_contract.address(),
-        args.mip_solver_url,
-        args.cow_dex_ag_solver_url,
-        args.quasimodo_solver_url,
-        args.balancer_sor_url,
-        &settlement_contract,
-        vault_contract.as_ref(),
-        token_info_fetcher,
-        network_name.to_string(),
-        chain_id,
-        args.shared.disabled_one_inch_protocols,
-        args.paraswap_slippage_bps,
-        args.shared.disabled_paraswap_dexs,
-        args.shared.paraswap_partner,
-        client.clone(),
-        metrics.clone(),
-        zeroex_api.clone(),
-        args.zeroex_slippage_bps,
-        args.shared.disabled_zeroex_sources,
-        args.oneinch_slippage_bps,
-        args.shared.quasimodo_uses_internal_buffers,
-        args.shared.mip_uses_internal_buffers,
-        args.shared.one_inch_url,
-        args.external_solvers.unwrap_or_default(),
-        args.oneinch_max_slippage_in_eth
-            .map(|float| U256::from_f64_lossy(float * 1e18)),
-    )
-    .expect("failure creating solvers");
-
-    let zeroex_liquidity = if baseline_sources.contains(&BaselineSource::ZeroEx) {
-        Some(ZeroExLiquidity {
-            api: zeroex_api,
-            zeroex: contracts::IZeroEx::deployed(&web3).await.unwrap(),
-            base_tokens: base_tokens.clone(),
-            gpv2: settlement_contract.clone(),
-        })
-    } else {
-        None
-    };
-
-    let uniswap_v3_liquidity = if baseline_sources.contains(&BaselineSource::UniswapV3) {
-        let uniswap_v3_pool_fetcher = Arc::new(
-            AutoUpdatingUniswapV3PoolFetcher::new(
-                chain_id,
-                args.shared.liquidity_fetcher_max_age_update,
-                client.clone(),
-            )
-            .await
-            .expect("failed to create UniswapV3 pool fetcher in solver"),
-        );
-
-        Some(UniswapV3Liquidity::new(
-            UniswapV3SwapRouter::deployed(&web3).await.unwrap(),
-            settlement_contract.clone(),
-            base_tokens.clone(),
-            web3.clone(),
-            uniswap_v3_pool_fetcher,
-        ))
-    } else {
-        None
-    };
-
-    let liquidity_collector = LiquidityCollector {
-        uniswap_like_liquidity,
-        balancer_v2_liquidity,
-        zeroex_liquidity,
-        uniswap_v3_liquidity,
-    };
     let market_makable_token_list =
         TokenList::from_url(&args.market_makable_token_list, chain_id, client.clone())
             .await
@@ -412,10 +201,36 @@ async fn main() {
         .tenderly_url
         .zip(args.tenderly_api_key)
         .and_then(|(url, api_key)| TenderlyApi::new(url, client.clone(), &api_key).ok());
+    let ebbo_validator = args.ebbo_tolerance.map(|tolerance| {
+        EbboValidator::baseline(
+            liquidity_sources
+                .pool_caches
+                .values()
+                .map(|pool_cache| pool_cache.clone() as Arc<dyn PoolFetching>)
+                .collect(),
+            gas_price_estimator.clone(),
+            base_tokens.clone(),
+            native_token_contract.address(),
+            args.shared.baseline_max_split_paths,
+            tolerance,
+        )
+    });
+
+    let buffer_internalization = args.internalize_buffers.then(|| BufferInternalization {
+        trusted_tokens: args.trusted_buffer_tokens.iter().copied().collect(),
+        excluded_solvers: [
+            ("Quasimodo", args.shared.quasimodo_uses_internal_buffers),
+            ("Mip", args.shared.mip_uses_internal_buffers),
+        ]
+        .into_iter()
+        .filter(|(_, uses_internal_buffers)| !uses_internal_buffers)
+        .map(|(name, _)| name.to_string())
+        .collect(),
+    });
 
     let mut driver = Driver::new(
         settlement_contract,
-        liquidity_collector,
+        liquidity_sources.collector,
         solver,
         gas_price_estimator,
         args.settle_interval,
@@ -439,61 +254,19 @@ async fn main() {
             .map(|max_price_deviation| Ratio::from_float(max_price_deviation).unwrap()),
         args.token_list_restriction_for_price_checks.into(),
         tenderly,
+        args.settlement_scoring,
+        args.auction_snapshot_dir,
+        args.trace_settlement_balance_changes,
+        ebbo_validator,
+        args.reject_ebbo_violations,
+        buffer_internalization,
     );
 
     let maintainer = ServiceMaintenance {
-        maintainers: pool_caches
-            .into_iter()
-            .map(|(_, cache)| cache as Arc<dyn Maintaining>)
-            .chain(balancer_pool_maintainer)
-            .collect(),
+        maintainers: liquidity_sources.maintainers,
     };
     tokio::task::spawn(maintainer.run_maintenance_on_new_block(current_block_stream));
 
     serve_metrics(metrics, ([0, 0, 0, 0], args.metrics_port).into());
     driver.run_forever().await;
 }
-
-async fn build_amm_artifacts(
-    sources: &HashMap<BaselineSource, Arc<PoolCache>>,
-    settlement_contract: contracts::GPv2Settlement,
-    base_tokens: Arc<BaseTokens>,
-    web3: shared::Web3,
-) -> Vec<UniswapLikeLiquidity> {
-    let mut res = vec![];
-    for (source, pool_cache) in sources {
-        let router_address = match source {
-            BaselineSource::UniswapV2 => contracts::UniswapV2Router02::deployed(&web3)
-                .await
-                .expect("couldn't load deployed UniswapV2 router")
-                .address(),
-            BaselineSource::SushiSwap => contracts::SushiSwapRouter::deployed(&web3)
-                .await
-                .expect("couldn't load deployed SushiSwap router")
-                .address(),
-            BaselineSource::Honeyswap => contracts::HoneyswapRouter::deployed(&web3)
-                .await
-                .expect("couldn't load deployed Honeyswap router")
-                .address(),
-            BaselineSource::Baoswap => contracts::BaoswapRouter::deployed(&web3)
-                .await
-                .expect("couldn't load deployed Baoswap router")
-                .address(),
-            BaselineSource::Swapr => contracts::SwaprRouter::deployed(&web3)
-                .await
-                .expect("couldn't load deployed Swapr router")
-                .address(),
-            BaselineSource::BalancerV2 => continue,
-            BaselineSource::ZeroEx => continue,
-            BaselineSource::UniswapV3 => continue,
-        };
-        res.push(UniswapLikeLiquidity::new(
-            IUniswapLikeRouter::at(&web3, router_address),
-            settlement_contract.clone(),
-            base_tokens.clone(),
-            web3.clone(),
-            pool_cache.clone(),
-        ));
-    }
-    res
-}
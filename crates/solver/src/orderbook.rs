@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use model::{
     auction::Auction,
+    order::{Order, OrderUid},
     solver_competition::{SolverCompetition, SolverCompetitionId},
 };
 use reqwest::{Client, Url};
@@ -27,6 +28,37 @@ impl OrderBookApi {
         Ok(auction)
     }
 
+    pub async fn get_order(&self, uid: &OrderUid) -> Result<Order> {
+        let url = self.base.join(&format!("api/v1/orders/{}", uid))?;
+        let order = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(order)
+    }
+
+    pub async fn get_solver_competition(
+        &self,
+        id: SolverCompetitionId,
+    ) -> Result<SolverCompetition> {
+        let url = self
+            .base
+            .join(&format!("api/v1/solver_competition/{}", id))?;
+        let competition = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(competition)
+    }
+
     pub async fn send_solver_competition(
         &self,
         body: &SolverCompetition,
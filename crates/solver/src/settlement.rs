@@ -5,7 +5,7 @@ use self::external_prices::ExternalPrices;
 pub use self::settlement_encoder::{verify_executed_amount, SettlementEncoder};
 use crate::{
     encoding::{self, EncodedInteraction, EncodedSettlement, EncodedTrade},
-    liquidity::Settleable,
+    liquidity::{AmmOrderExecution, Settleable},
 };
 use anyhow::Result;
 use itertools::Itertools;
@@ -30,6 +30,9 @@ pub struct Trade {
 pub struct OrderTrade {
     pub trade: Trade,
     pub buy_token_index: usize,
+    // The buy token price at which the trade is settled instead of the uniform clearing price.
+    // Orders paying a partner fee buy at a worse price so that the difference covers the fee.
+    pub custom_buy_token_price: Option<U256>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -80,12 +83,30 @@ pub fn trade_surplus_in_native_token(
 ) -> Option<BigRational> {
     let sell_token_clearing_price = clearing_prices
         .get(&order.data.sell_token)
-        .expect("Solution with trade but without price for sell token")
-        .to_big_rational();
+        .expect("Solution with trade but without price for sell token");
     let buy_token_clearing_price = clearing_prices
         .get(&order.data.buy_token)
-        .expect("Solution with trade but without price for buy token")
-        .to_big_rational();
+        .expect("Solution with trade but without price for buy token");
+    trade_surplus_in_native_token_at_prices(
+        order,
+        executed_amount,
+        external_prices,
+        *sell_token_clearing_price,
+        *buy_token_clearing_price,
+    )
+}
+
+/// Like `trade_surplus_in_native_token` but for a trade settled at the specified prices, which
+/// can differ from the uniform clearing prices.
+pub fn trade_surplus_in_native_token_at_prices(
+    order: &Order,
+    executed_amount: U256,
+    external_prices: &ExternalPrices,
+    sell_token_price: U256,
+    buy_token_price: U256,
+) -> Option<BigRational> {
+    let sell_token_clearing_price = sell_token_price.to_big_rational();
+    let buy_token_clearing_price = buy_token_price.to_big_rational();
 
     if match order.data.kind {
         OrderKind::Sell => &buy_token_clearing_price,
@@ -232,6 +253,13 @@ pub trait Interaction: std::fmt::Debug + Send + Sync {
     // never fail. Then the question becomes whether interactions should be allowed to fail encoding
     // for other reasons.
     fn encode(&self) -> Vec<EncodedInteraction>;
+
+    /// The tokens sent from and received by the settlement contract if this interaction is a
+    /// single exact output swap. Such interactions can be replaced by using the settlement
+    /// contract's buffers instead.
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        None
+    }
 }
 
 impl Interaction for EncodedInteraction {
@@ -337,16 +365,18 @@ impl Settlement {
         user_orders.chain(liquidity_orders)
     }
 
-    /// Returns an iterator of all executed trades.
+    /// Returns an iterator of all executed trades. The buy amounts of orders paying a partner fee
+    /// are net of the fee.
     pub fn executed_trades(&self) -> impl Iterator<Item = (&'_ Trade, TradeExecution)> + '_ {
         let order_trades = self.encoder.order_trades().iter().map(move |order_trade| {
             let order = &order_trade.trade.order.data;
+            let buy_price = match order_trade.custom_buy_token_price {
+                Some(price) => price,
+                None => self.clearing_price(order.buy_token)?,
+            };
             order_trade
                 .trade
-                .executed_amounts(
-                    self.clearing_price(order.sell_token)?,
-                    self.clearing_price(order.buy_token)?,
-                )
+                .executed_amounts(self.clearing_price(order.sell_token)?, buy_price)
                 .map(|execution| (&order_trade.trade, execution))
         });
         let liquidity_order_trades =
@@ -488,6 +518,16 @@ impl Settlement {
         Ok(Self { encoder: merged })
     }
 
+    /// See SettlementEncoder::merge_with_repricing
+    pub fn merge_with_repricing(
+        self,
+        other: Self,
+        buffers: &mut HashMap<H160, U256>,
+    ) -> Result<Self> {
+        let merged = self.encoder.merge_with_repricing(other.encoder, buffers)?;
+        Ok(Self { encoder: merged })
+    }
+
     // Calculates the risk level for settlement to be reverted
     pub fn revertable(&self) -> Revertable {
         if self.encoder.execution_plan().is_empty() {
@@ -10,6 +10,7 @@ use ethcontract::{H160, U256};
 use lazy_static::lazy_static;
 use model::order::BUY_ETH_ADDRESS;
 use num::{BigInt, BigRational, One as _, ToPrimitive as _};
+use number_conversions::big_int_to_u256;
 use shared::conversions::U256Ext as _;
 use std::collections::{BTreeMap, HashMap};
 
@@ -96,6 +97,20 @@ impl ExternalPrices {
         prices.remove(&BUY_ETH_ADDRESS);
         prices
     }
+
+    /// Converts a set of external prices back into native prices as used by
+    /// the orderbook API `/auction` endpoint, omitting `BUY_ETH_ADDRESS` like
+    /// [`Self::into_http_solver_prices`].
+    pub fn to_native_prices(&self) -> BTreeMap<H160, U256> {
+        self.0
+            .iter()
+            .filter(|(token, _)| **token != BUY_ETH_ADDRESS)
+            .filter_map(|(token, xrate)| {
+                let price = (xrate * &*UNIT).to_integer();
+                Some((*token, big_int_to_u256(&price).ok()?))
+            })
+            .collect()
+    }
 }
 
 impl Default for ExternalPrices {
@@ -163,6 +178,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn converts_exchange_rates_back_to_native_prices() {
+        let native_token = H160([42; 20]);
+        let prices = btreemap! {
+            H160([1; 20]) => U256::from(100_000_000_000_000_000_u128),
+        };
+        assert_eq!(
+            ExternalPrices::try_from_auction_prices(native_token, prices)
+                .unwrap()
+                .to_native_prices(),
+            btreemap! {
+                H160([1; 20]) => U256::from(100_000_000_000_000_000_u128),
+                native_token => U256::from(1_000_000_000_000_000_000_u128),
+            },
+        );
+    }
+
     #[test]
     fn augments_price_map_with_native_token_prices() {
         let native_token = H160([42; 20]);
@@ -1,13 +1,16 @@
 use super::{ExternalPrices, Interaction, LiquidityOrderTrade, OrderTrade, Trade, TradeExecution};
 use crate::{
     encoding::{EncodedSettlement, EncodedTrade},
-    interactions::UnwrapWethInteraction,
-    settlement::trade_surplus_in_native_token,
+    interactions::{
+        allowances::Approval, BalancerBatchSwapInteraction, BalancerSwapGivenOutInteraction,
+        Erc20TransferInteraction, UnwrapWethInteraction,
+    },
+    settlement::trade_surplus_in_native_token_at_prices,
 };
 use anyhow::{bail, ensure, Context as _, Result};
-use model::order::{Order, OrderKind};
-use num::{BigRational, One};
-use number_conversions::big_rational_to_u256;
+use model::order::{Order, OrderKind, BUY_ETH_ADDRESS};
+use num::{BigInt, BigRational, One, Signed as _};
+use number_conversions::{big_int_to_u256, big_rational_to_u256};
 use primitive_types::{H160, U256};
 use shared::conversions::U256Ext;
 use std::{
@@ -33,7 +36,9 @@ pub struct SettlementEncoder {
     clearing_prices: HashMap<H160, U256>,
     // Order trades are trades of usual user orders. They will be settled using the
     // uniform clearing prices. Hence, every trade's buy and sell token has an entry
-    // in clearing_prices
+    // in clearing_prices. Trades of orders paying a partner fee are settled at a custom
+    // buy token price that gets appended to the clearing price vector like the ones of
+    // liquidity orders.
     order_trades: Vec<OrderTrade>,
     // Liquidity orders will be settled at their limit prices.
     // In order to represent the limit price, the sell_price is taken from the uniform
@@ -44,7 +49,14 @@ pub struct SettlementEncoder {
     // would make the trait not be object safe which prevents using it through `dyn`.
     // TODO: Can we fix this in a better way?
     execution_plan: Vec<Arc<dyn Interaction>>,
+    // The Balancer swaps of the most recently added Balancer batch swap along with its index in
+    // the execution plan. Balancer swaps that directly follow it are merged into the same batch
+    // swap, so that the Vault only transfers the net token amounts.
+    balancer_swaps: Option<(usize, Vec<BalancerSwapGivenOutInteraction>)>,
     unwraps: Vec<UnwrapWethInteraction>,
+    // Partner fees of order trades, paid out of the buy tokens that the custom prices of these
+    // trades withhold from their receivers after all trades have been executed.
+    partner_fee_transfers: Vec<Erc20TransferInteraction>,
 }
 
 impl Default for SettlementEncoder {
@@ -76,7 +88,9 @@ impl SettlementEncoder {
             order_trades: Vec::new(),
             liquidity_order_trades: Vec::new(),
             execution_plan: Vec::new(),
+            balancer_swaps: None,
             unwraps: Vec::new(),
+            partner_fee_transfers: Vec::new(),
         }
     }
 
@@ -100,7 +114,9 @@ impl SettlementEncoder {
             order_trades: self.order_trades.clone(),
             liquidity_order_trades: self.liquidity_order_trades.clone(),
             execution_plan: Vec::new(),
+            balancer_swaps: None,
             unwraps: self.unwraps.clone(),
+            partner_fee_transfers: self.partner_fee_transfers.clone(),
         }
     }
 
@@ -120,7 +136,17 @@ impl SettlementEncoder {
         &self.execution_plan
     }
 
+    pub fn partner_fee_transfers(&self) -> &[Erc20TransferInteraction] {
+        &self.partner_fee_transfers
+    }
+
     // Fails if any used token doesn't have a price or if executed amount is impossible.
+    //
+    // If the order specifies a partner fee, it is paid by the trader: sell orders receive the fee
+    // less than at the clearing prices and buy orders pay for the bought amount plus the fee. The
+    // returned execution is net of the fee and a transfer of the fee in the buy token is added to
+    // the settlement. Interactions provide the buy tokens as for any other trade at the clearing
+    // prices.
     pub fn add_trade(
         &mut self,
         order: Order,
@@ -144,7 +170,7 @@ impl SettlementEncoder {
             .token_index(order.data.buy_token)
             .expect("missing buy token with price");
 
-        let order_trade = OrderTrade {
+        let mut order_trade = OrderTrade {
             trade: Trade {
                 order,
                 sell_token_index,
@@ -152,13 +178,17 @@ impl SettlementEncoder {
                 scaled_unsubsidized_fee,
             },
             buy_token_index,
+            custom_buy_token_price: None,
         };
         let execution = order_trade
             .trade
             .executed_amounts(*sell_price, *buy_price)
             .context("impossible trade execution")?;
+        let (execution, partner_fee_transfer) =
+            apply_partner_fee(&mut order_trade, *sell_price, *buy_price, execution)?;
 
         self.order_trades.push(order_trade);
+        self.partner_fee_transfers.extend(partner_fee_transfer);
         Ok(execution)
     }
 
@@ -235,6 +265,42 @@ impl SettlementEncoder {
         self.execution_plan.push(Arc::new(interaction));
     }
 
+    /// Appends a Balancer swap along with its token approval to the execution
+    /// plan.
+    ///
+    /// If the previous interaction of the execution plan is also a Balancer
+    /// swap, both are merged into a single `batchSwap` call. The approval is
+    /// then added before the batch swap, which is fine since approvals don't
+    /// depend on any token balances.
+    pub fn append_balancer_swap(
+        &mut self,
+        approval: Approval,
+        swap: BalancerSwapGivenOutInteraction,
+    ) -> Result<()> {
+        let last_index = self.execution_plan.len().checked_sub(1);
+        match &mut self.balancer_swaps {
+            Some((index, swaps)) if Some(*index) == last_index => {
+                let merged_swaps = swaps
+                    .iter()
+                    .cloned()
+                    .chain(iter::once(swap))
+                    .collect::<Vec<_>>();
+                let batch_swap = BalancerBatchSwapInteraction::given_out(&merged_swaps)?;
+                self.execution_plan.insert(*index, Arc::new(approval));
+                *index += 1;
+                self.execution_plan[*index] = Arc::new(batch_swap);
+                *swaps = merged_swaps;
+            }
+            _ => {
+                self.execution_plan.push(Arc::new(approval));
+                self.execution_plan.push(Arc::new(swap.clone()));
+                self.balancer_swaps = Some((self.execution_plan.len() - 1, vec![swap]));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn add_unwrap(&mut self, unwrap: UnwrapWethInteraction) {
         for existing_unwrap in self.unwraps.iter_mut() {
             if existing_unwrap.merge(&unwrap).is_ok() {
@@ -310,11 +376,16 @@ impl SettlementEncoder {
         self.order_trades
             .iter()
             .fold(Some(num::zero()), |acc, order_trade| {
-                let normalized_surplus = trade_surplus_in_native_token(
-                    &order_trade.trade.order,
+                let order = &order_trade.trade.order;
+                let buy_price = order_trade
+                    .custom_buy_token_price
+                    .unwrap_or(self.clearing_prices[&order.data.buy_token]);
+                let normalized_surplus = trade_surplus_in_native_token_at_prices(
+                    order,
                     order_trade.trade.executed_amount,
                     external_prices,
-                    &self.clearing_prices,
+                    self.clearing_prices[&order.data.sell_token],
+                    buy_price,
                 )?;
                 Some(acc? + normalized_surplus)
             })
@@ -377,7 +448,14 @@ impl SettlementEncoder {
         let mut trades: Vec<EncodedTrade> = self
             .order_trades
             .into_iter()
-            .map(|trade| trade.encode())
+            .map(|mut trade| {
+                if let Some(price) = trade.custom_buy_token_price {
+                    trade.buy_token_index = tokens.len();
+                    tokens.push(trade.trade.order.data.buy_token);
+                    clearing_prices.push(price);
+                }
+                trade.encode()
+            })
             .collect();
         let mut liquidity_order_trades: Vec<EncodedTrade> = self
             .liquidity_order_trades
@@ -399,7 +477,10 @@ impl SettlementEncoder {
                     )
                     .chain(self.unwraps.iter().flat_map(|unwrap| unwrap.encode()))
                     .collect(),
-                Vec::new(),
+                self.partner_fee_transfers
+                    .iter()
+                    .flat_map(|transfer| transfer.encode())
+                    .collect(),
             ],
         }
     }
@@ -467,6 +548,13 @@ impl SettlementEncoder {
             );
         }
 
+        for order_trade in &mut other.order_trades {
+            if let Some(price) = &mut order_trade.custom_buy_token_price {
+                *price = big_rational_to_u256(&(price.to_big_rational() * &scaling_factor))
+                    .context("Invalid price scaling factor")?;
+            }
+        }
+
         other.modify_token_index_for_liquidity_orders_after_change(
             self.liquidity_order_trades.len(),
         );
@@ -475,15 +563,131 @@ impl SettlementEncoder {
         self.order_trades.append(&mut other.order_trades);
         self.sort_tokens_and_update_indices();
 
-        self.execution_plan.append(&mut other.execution_plan);
+        // If the execution plan of `self` ends with a Balancer batch swap and the one of `other`
+        // starts with one, both are merged into a single batch swap. This is the case when the
+        // only interactions before the batch swap of `other` are its approvals, one per swap,
+        // which can be moved before the merged batch swap. Otherwise only the Balancer batch swap
+        // of the settlement that ends up last in the execution plan can still be extended.
+        let offset = self.execution_plan.len();
+        match (self.balancer_swaps.take(), other.balancer_swaps.take()) {
+            (Some((index, swaps)), Some((other_index, other_swaps)))
+                if index + 1 == offset && other_index == other_swaps.len() =>
+            {
+                let merged_swaps = swaps.into_iter().chain(other_swaps).collect::<Vec<_>>();
+                let batch_swap = BalancerBatchSwapInteraction::given_out(&merged_swaps)?;
+                let mut other_plan = std::mem::take(&mut other.execution_plan).into_iter();
+                let approvals = other_plan.by_ref().take(other_index).collect::<Vec<_>>();
+                // Skip the batch swap of `other`, it is part of the merged one.
+                other_plan.next();
+                self.execution_plan.splice(index..index, approvals);
+                let index = index + other_index;
+                self.execution_plan[index] = Arc::new(batch_swap);
+                self.execution_plan.extend(other_plan);
+                self.balancer_swaps = Some((index, merged_swaps));
+            }
+            (swaps, other_swaps) => {
+                self.execution_plan.append(&mut other.execution_plan);
+                self.balancer_swaps = other_swaps
+                    .map(|(index, swaps)| (index + offset, swaps))
+                    .or(swaps);
+            }
+        }
 
         for unwrap in other.unwraps {
             self.add_unwrap(unwrap);
         }
 
+        // Partner fees are denominated in token amounts and are therefore unaffected by the price
+        // scaling. Scaling the custom prices doesn't change the executed amounts either.
+        self.partner_fee_transfers
+            .append(&mut other.partner_fee_transfers);
+
         Ok(self)
     }
 
+    /// Merges `other` into `self` like `merge`, but allows the clearing prices of tokens that are
+    /// part of both settlements to differ. The order trades of `other` are re-priced at the
+    /// clearing prices of `self`. Any additional tokens that the re-priced trades pay out compared
+    /// to the original ones are taken from the settlement contract's `buffers`, which get reduced
+    /// by the used amounts.
+    ///
+    /// Fails if a re-priced trade violates its limit price, if the buffers are insufficient, or if
+    /// `other` encodes anything that depends on its executed trade amounts (liquidity order
+    /// trades, native token unwraps and partner fee transfers).
+    pub fn merge_with_repricing(
+        self,
+        mut other: Self,
+        buffers: &mut HashMap<H160, U256>,
+    ) -> Result<Self> {
+        ensure!(
+            other.liquidity_order_trades.is_empty()
+                && other.unwraps.is_empty()
+                && other.partner_fee_transfers.is_empty(),
+            "settlement depending on executed amounts can't be re-priced"
+        );
+
+        let scaling_factor = self.price_scaling_factor(&other);
+        let mut prices = HashMap::new();
+        for (token, price) in &other.clearing_prices {
+            let price = match self.clearing_prices.get(token) {
+                Some(price) => *price,
+                None => big_rational_to_u256(&(price.to_big_rational() * &scaling_factor))
+                    .context("Invalid price scaling factor")?,
+            };
+            prices.insert(*token, price);
+        }
+
+        // Positive amounts are paid out of the buffers, negative ones are added to them.
+        let mut buffer_usage = HashMap::<H160, BigInt>::new();
+        for order_trade in &other.order_trades {
+            let trade = &order_trade.trade;
+            let order = &trade.order.data;
+            let (sell_price, buy_price) = (prices[&order.sell_token], prices[&order.buy_token]);
+            ensure!(
+                order.sell_amount.full_mul(sell_price) >= order.buy_amount.full_mul(buy_price),
+                "re-priced trade violates limit price"
+            );
+
+            let original = trade
+                .executed_amounts(
+                    other.clearing_prices[&order.sell_token],
+                    other.clearing_prices[&order.buy_token],
+                )
+                .context("impossible trade execution")?;
+            let repriced = trade
+                .executed_amounts(sell_price, buy_price)
+                .context("impossible trade execution")?;
+            *buffer_usage.entry(order.buy_token).or_default() +=
+                repriced.buy_amount.to_big_int() - original.buy_amount.to_big_int();
+            *buffer_usage.entry(order.sell_token).or_default() +=
+                original.sell_amount.to_big_int() - repriced.sell_amount.to_big_int();
+        }
+
+        let buffer_usage = buffer_usage
+            .into_iter()
+            .filter(|(_, usage)| usage.is_positive())
+            .map(|(token, usage)| {
+                let usage = big_int_to_u256(&usage)?;
+                ensure!(
+                    usage <= buffers.get(&token).copied().unwrap_or_default(),
+                    "insufficient buffer to re-price trades"
+                );
+                Ok((token, usage))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // The token set doesn't change, so the token indices of the trades remain valid.
+        other.clearing_prices = prices;
+        let merged = self.merge(other)?;
+
+        for (token, usage) in buffer_usage {
+            if let Some(buffer) = buffers.get_mut(&token) {
+                *buffer -= usage;
+            }
+        }
+        Ok(merged)
+    }
+
     fn price_scaling_factor(&self, other: &Self) -> BigRational {
         let self_keys: HashSet<_> = self.clearing_prices().keys().collect();
         let other_keys: HashSet<_> = other.clearing_prices().keys().collect();
@@ -512,6 +716,25 @@ impl SettlementEncoder {
         self.unwraps.retain(|unwrap| unwrap.weth.address() != token);
     }
 
+    /// Removes the interactions at the specified indices from the execution plan.
+    pub fn drop_interactions(&mut self, indices: &HashSet<usize>) {
+        self.balancer_swaps = self
+            .balancer_swaps
+            .take()
+            .filter(|(index, _)| !indices.contains(index))
+            .map(|(index, swaps)| {
+                let dropped_before = indices.iter().filter(|dropped| **dropped < index).count();
+                (index - dropped_before, swaps)
+            });
+
+        let mut index = 0;
+        self.execution_plan.retain(|_| {
+            let keep = !indices.contains(&index);
+            index += 1;
+            keep
+        });
+    }
+
     /// Calculates how much of a given token this settlement will unwrap during the execution.
     pub fn amount_to_unwrap(&self, token: H160) -> U256 {
         self.unwraps.iter().fold(U256::zero(), |sum, unwrap| {
@@ -525,6 +748,88 @@ impl SettlementEncoder {
     }
 }
 
+/// Applies the partner fee of an order to its trade by settling it at a custom buy token price
+/// that withholds the fee from the trader, respecting the order's limit price. Returns the
+/// execution at that price and the transfer paying the withheld tokens to the partner: bought
+/// tokens for sell orders and additionally sold tokens for buy orders.
+fn apply_partner_fee(
+    order_trade: &mut OrderTrade,
+    sell_price: U256,
+    buy_price: U256,
+    execution: TradeExecution,
+) -> Result<(TradeExecution, Option<Erc20TransferInteraction>)> {
+    let order = &order_trade.trade.order;
+    let partner_fee = match order.partner_fee().context("invalid partner fee")? {
+        Some(partner_fee) => partner_fee,
+        None => return Ok((execution, None)),
+    };
+    ensure!(
+        order.data.buy_token != BUY_ETH_ADDRESS,
+        "partner fees are not supported for orders buying native token"
+    );
+    let fee = partner_fee
+        .fee_amount(execution.buy_amount)
+        .context("partner fee overflow")?;
+    if fee.is_zero() {
+        return Ok((execution, None));
+    }
+
+    // The contract computes the bought amount of sell orders and the sold amount of buy orders
+    // from the prices, so the fee is charged by raising the buy token price accordingly.
+    let buy_price = match order.data.kind {
+        OrderKind::Sell => {
+            let net_buy_amount = execution
+                .buy_amount
+                .checked_sub(fee)
+                .filter(|amount| !amount.is_zero())
+                .context("partner fee exceeds bought amount")?;
+            execution
+                .sell_amount
+                .checked_mul(sell_price)
+                .and_then(|value| value.checked_ceil_div(&net_buy_amount))
+        }
+        OrderKind::Buy => execution
+            .buy_amount
+            .checked_add(fee)
+            .and_then(|gross_buy_amount| buy_price.checked_mul(gross_buy_amount))
+            .map(|value| value / execution.buy_amount),
+    }
+    .context("partner fee price overflow")?;
+    ensure!(
+        order.data.sell_amount.full_mul(sell_price) >= order.data.buy_amount.full_mul(buy_price),
+        "partner fee violates limit price"
+    );
+
+    let net_execution = order_trade
+        .trade
+        .executed_amounts(sell_price, buy_price)
+        .context("impossible trade execution")?;
+    // The contract rounds the traded amounts, so the withheld amount can differ from the fee by a
+    // rounding error. Paying out exactly the withheld amount in the token it was withheld in keeps
+    // the settlement balanced.
+    let (token, fee) = match order.data.kind {
+        OrderKind::Sell => (
+            order.data.buy_token,
+            execution
+                .buy_amount
+                .saturating_sub(net_execution.buy_amount),
+        ),
+        OrderKind::Buy => (
+            order.data.sell_token,
+            net_execution
+                .sell_amount
+                .saturating_sub(execution.sell_amount),
+        ),
+    };
+    let transfer = Erc20TransferInteraction {
+        token,
+        recipient: partner_fee.recipient,
+        amount: fee,
+    };
+    order_trade.custom_buy_token_price = Some(buy_price);
+    Ok((net_execution, Some(transfer)))
+}
+
 pub fn verify_executed_amount(order: &Order, executed_amount: U256) -> Result<()> {
     let remaining_amounts = order.remaining_amounts()?;
     let valid_executed_amount = match (order.data.partially_fillable, order.data.kind) {
@@ -541,10 +846,11 @@ pub fn verify_executed_amount(order: &Order, executed_amount: U256) -> Result<()
 pub mod tests {
     use super::*;
     use crate::{encoding::EncodedInteraction, settlement::NoopInteraction};
-    use contracts::WETH9;
-    use ethcontract::Bytes;
-    use maplit::hashmap;
-    use model::order::{OrderBuilder, OrderData};
+    use contracts::{BalancerV2Vault, GPv2Settlement, WETH9};
+    use ethcontract::{Bytes, H256};
+    use maplit::{hashmap, hashset};
+    use model::order::{OrderBuilder, OrderData, OrderMetadata, OrderUid};
+    use num::Zero as _;
     use shared::dummy_contract;
 
     #[test]
@@ -605,6 +911,288 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn settlement_encodes_partner_fee_transfers() {
+        let order = |uid: u8, full_app_data: &str| Order {
+            data: OrderData {
+                sell_token: token(0),
+                sell_amount: 1_000.into(),
+                buy_token: token(1),
+                buy_amount: 1_000.into(),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([uid; 56]),
+                full_app_data: Some(full_app_data.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let prices = maplit::hashmap! {
+            token(0) => U256::exp10(18) * 2,
+            token(1) => U256::exp10(18),
+        };
+        let fee_recipient = H160([0xfe; 20]);
+
+        let mut encoder = SettlementEncoder::new(prices.clone());
+        encoder
+            .add_trade(
+                order(
+                    1,
+                    r#"{"metadata":{"partnerFee":{"recipient":"0xfefefefefefefefefefefefefefefefefefefefe","bps":50}}}"#,
+                ),
+                1_000.into(),
+                0.into(),
+            )
+            .unwrap();
+        // Orders without partner fee don't add any transfers.
+        encoder
+            .add_trade(order(2, "{}"), 1_000.into(), 0.into())
+            .unwrap();
+
+        let transfer = Erc20TransferInteraction {
+            token: token(1),
+            recipient: fee_recipient,
+            amount: 10.into(),
+        };
+        assert_eq!(encoder.partner_fee_transfers(), [transfer.clone()]);
+
+        // The trade paying the fee is settled at a custom buy token price.
+        let finished = encoder.clone().finish();
+        assert_eq!(finished.tokens, [token(0), token(1), token(1)]);
+        assert_eq!(
+            finished.clearing_prices[2],
+            U256::from(1_005_025_125_628_140_704_u128)
+        );
+        assert_eq!(finished.trades[0].1, 2.into());
+        assert_eq!(finished.trades[1].1, 1.into());
+
+        // Partner fee transfers are kept when merging settlements.
+        let mut other = SettlementEncoder::new(prices);
+        other
+            .add_trade(
+                order(
+                    3,
+                    r#"{"metadata":{"partnerFee":{"recipient":"0xfefefefefefefefefefefefefefefefefefefefe","bps":50}}}"#,
+                ),
+                1_000.into(),
+                0.into(),
+            )
+            .unwrap();
+        let merged = encoder.merge(other).unwrap();
+
+        assert_eq!(
+            merged.finish().interactions[2],
+            [transfer.encode(), transfer.encode()].concat(),
+        );
+    }
+
+    fn partner_fee_order(kind: OrderKind, buy_amount: u64, bps: u64) -> Order {
+        Order {
+            data: OrderData {
+                sell_token: token(0),
+                sell_amount: 1_000.into(),
+                buy_token: token(1),
+                buy_amount: buy_amount.into(),
+                kind,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([1; 56]),
+                full_app_data: Some(format!(
+                    r#"{{"metadata":{{"partnerFee":{{"recipient":"0xfefefefefefefefefefefefefefefefefefefefe","bps":{}}}}}}}"#,
+                    bps
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn partner_fee_is_paid_by_trader() {
+        let prices = maplit::hashmap! {
+            token(0) => U256::exp10(18),
+            token(1) => U256::exp10(18),
+        };
+        let mut encoder = SettlementEncoder::new(prices);
+        let execution = encoder
+            .add_trade(
+                partner_fee_order(OrderKind::Sell, 900, 100),
+                1_000.into(),
+                0.into(),
+            )
+            .unwrap();
+        assert_eq!(execution.sell_amount, 1_000.into());
+        assert_eq!(execution.buy_amount, 990.into());
+
+        // A counter order without partner fee settles at the uniform clearing prices.
+        let counter_order = Order {
+            data: OrderData {
+                sell_token: token(1),
+                sell_amount: 1_000.into(),
+                buy_token: token(0),
+                buy_amount: 900.into(),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([2; 56]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        encoder
+            .add_trade(counter_order, 1_000.into(), 0.into())
+            .unwrap();
+
+        // The settlement contract's token balances net to zero with the fee.
+        let settlement = crate::settlement::Settlement { encoder };
+        let mut balances = HashMap::<H160, BigInt>::new();
+        for (_, execution) in settlement.executed_trades() {
+            *balances.entry(execution.sell_token).or_default() +=
+                execution.sell_amount.to_big_int();
+            *balances.entry(execution.buy_token).or_default() -= execution.buy_amount.to_big_int();
+        }
+        for transfer in settlement.encoder.partner_fee_transfers() {
+            assert_eq!(transfer.amount, 10.into());
+            *balances.entry(transfer.token).or_default() -= transfer.amount.to_big_int();
+        }
+        assert!(balances.values().all(|balance| balance.is_zero()));
+
+        // The fee reduces the surplus of the trader paying it.
+        let external_prices = crate::settlement::external_prices::externalprices! {
+            native_token: token(0),
+            token(1) => BigRational::one(),
+        };
+        let surplus = settlement.total_surplus(&external_prices);
+        assert!(
+            (surplus - BigRational::from_integer(190.into())).abs()
+                < BigRational::new(1.into(), 1_000.into())
+        );
+    }
+
+    #[test]
+    fn partner_fee_of_buy_order_is_added_to_sold_amount() {
+        let prices = maplit::hashmap! {
+            token(0) => U256::exp10(18),
+            token(1) => U256::exp10(18),
+        };
+        let mut encoder = SettlementEncoder::new(prices);
+        let execution = encoder
+            .add_trade(
+                partner_fee_order(OrderKind::Buy, 900, 100),
+                900.into(),
+                0.into(),
+            )
+            .unwrap();
+        assert_eq!(execution.buy_amount, 900.into());
+        assert_eq!(execution.sell_amount, 909.into());
+        assert_eq!(
+            encoder.partner_fee_transfers(),
+            [Erc20TransferInteraction {
+                token: token(0),
+                recipient: H160([0xfe; 20]),
+                amount: 9.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn partner_fee_of_buy_order_keeps_settlement_balanced() {
+        let prices = maplit::hashmap! {
+            token(0) => U256::exp10(18),
+            token(1) => U256::exp10(18),
+        };
+        let mut encoder = SettlementEncoder::new(prices);
+        encoder
+            .add_trade(
+                partner_fee_order(OrderKind::Buy, 900, 100),
+                900.into(),
+                0.into(),
+            )
+            .unwrap();
+
+        // A counter order without partner fee settles at the uniform clearing prices and provides
+        // exactly the bought tokens.
+        let counter_order = Order {
+            data: OrderData {
+                sell_token: token(1),
+                sell_amount: 900.into(),
+                buy_token: token(0),
+                buy_amount: 900.into(),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([2; 56]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        encoder
+            .add_trade(counter_order, 900.into(), 0.into())
+            .unwrap();
+
+        // The settlement contract's token balances net to zero with the fee.
+        let settlement = crate::settlement::Settlement { encoder };
+        let mut balances = HashMap::<H160, BigInt>::new();
+        for (_, execution) in settlement.executed_trades() {
+            *balances.entry(execution.sell_token).or_default() +=
+                execution.sell_amount.to_big_int();
+            *balances.entry(execution.buy_token).or_default() -= execution.buy_amount.to_big_int();
+        }
+        for transfer in settlement.encoder.partner_fee_transfers() {
+            *balances.entry(transfer.token).or_default() -= transfer.amount.to_big_int();
+        }
+        assert!(balances.values().all(|balance| balance.is_zero()));
+    }
+
+    #[test]
+    fn partner_fee_respects_limit_price() {
+        let prices = maplit::hashmap! {
+            token(0) => U256::exp10(18),
+            token(1) => U256::exp10(18),
+        };
+        let mut encoder = SettlementEncoder::new(prices);
+        // Without the fee, the order would be executed exactly at its limit price.
+        assert!(encoder
+            .add_trade(
+                partner_fee_order(OrderKind::Sell, 1_000, 100),
+                1_000.into(),
+                0.into()
+            )
+            .is_err());
+        assert!(encoder.order_trades().is_empty());
+        assert!(encoder.partner_fee_transfers().is_empty());
+    }
+
+    #[test]
+    fn settlement_rejects_invalid_partner_fee() {
+        let mut encoder = SettlementEncoder::new(maplit::hashmap! {
+            token(0) => 1.into(),
+            token(1) => 1.into(),
+        });
+        let order = Order {
+            data: OrderData {
+                sell_token: token(0),
+                sell_amount: 1.into(),
+                buy_token: token(1),
+                buy_amount: 1.into(),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                full_app_data: Some("not json".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(encoder.add_trade(order, 1.into(), 0.into()).is_err());
+        assert!(encoder.order_trades().is_empty());
+    }
+
     #[test]
     fn settlement_reflects_different_price_for_normal_and_liquidity_order() {
         let mut settlement = SettlementEncoder::new(maplit::hashmap! {
@@ -729,6 +1317,167 @@ pub mod tests {
         );
     }
 
+    fn balancer_swap(asset_in: u8, asset_out: u8) -> BalancerSwapGivenOutInteraction {
+        BalancerSwapGivenOutInteraction {
+            settlement: dummy_contract!(GPv2Settlement, [0x01; 20]),
+            vault: dummy_contract!(BalancerV2Vault, [0x02; 20]),
+            pool_id: H256([asset_in ^ asset_out; 32]),
+            asset_in: H160([asset_in; 20]),
+            asset_out: H160([asset_out; 20]),
+            amount_out: 1.into(),
+            amount_in_max: 1.into(),
+            amount_in: 1.into(),
+            user_data: Default::default(),
+        }
+    }
+
+    #[test]
+    fn settlement_merges_consecutive_balancer_swaps() {
+        let approval = Approval::Approve {
+            token: H160([0x03; 20]),
+            spender: H160([0x02; 20]),
+        };
+        let swaps = [
+            balancer_swap(0x03, 0x04),
+            balancer_swap(0x04, 0x05),
+            balancer_swap(0x06, 0x05),
+        ];
+
+        let mut encoder = SettlementEncoder::new(HashMap::new());
+        encoder
+            .append_balancer_swap(approval, swaps[0].clone())
+            .unwrap();
+        encoder
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[1].clone())
+            .unwrap();
+        encoder
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[2].clone())
+            .unwrap();
+
+        assert_eq!(
+            encoder.finish().interactions[1],
+            [
+                approval.encode(),
+                BalancerBatchSwapInteraction::given_out(&swaps)
+                    .unwrap()
+                    .encode(),
+            ]
+            .concat(),
+        );
+    }
+
+    #[test]
+    fn drop_interactions_keeps_merging_balancer_swaps() {
+        let interaction: EncodedInteraction = (H160([0x01; 20]), 0.into(), Bytes(Vec::new()));
+        let swaps = [balancer_swap(0x03, 0x04), balancer_swap(0x04, 0x05)];
+
+        let mut encoder = SettlementEncoder::new(HashMap::new());
+        encoder.append_to_execution_plan(interaction.clone());
+        encoder.append_to_execution_plan(interaction.clone());
+        encoder
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[0].clone())
+            .unwrap();
+        encoder.drop_interactions(&hashset! {0, 1});
+        encoder
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[1].clone())
+            .unwrap();
+
+        assert_eq!(encoder.execution_plan().len(), 3);
+        assert_eq!(
+            encoder.finish().interactions[1],
+            BalancerBatchSwapInteraction::given_out(&swaps)
+                .unwrap()
+                .encode(),
+        );
+    }
+
+    #[test]
+    fn settlement_does_not_merge_balancer_swaps_around_other_interactions() {
+        let interaction: EncodedInteraction = (H160([0x01; 20]), 0.into(), Bytes(Vec::new()));
+        let swaps = [balancer_swap(0x03, 0x04), balancer_swap(0x05, 0x06)];
+
+        let mut encoder = SettlementEncoder::new(HashMap::new());
+        encoder
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[0].clone())
+            .unwrap();
+        encoder.append_to_execution_plan(interaction.clone());
+        encoder
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[1].clone())
+            .unwrap();
+
+        assert_eq!(
+            encoder.finish().interactions[1],
+            [swaps[0].encode(), interaction.encode(), swaps[1].encode(),].concat(),
+        );
+    }
+
+    #[test]
+    fn merged_settlements_merge_balancer_swaps() {
+        let approvals = [
+            Approval::Approve {
+                token: H160([0x03; 20]),
+                spender: H160([0x02; 20]),
+            },
+            Approval::Approve {
+                token: H160([0x05; 20]),
+                spender: H160([0x02; 20]),
+            },
+        ];
+        let swaps = [
+            balancer_swap(0x03, 0x04),
+            balancer_swap(0x05, 0x06),
+            balancer_swap(0x06, 0x07),
+        ];
+
+        let mut encoder0 = SettlementEncoder::new(HashMap::new());
+        encoder0
+            .append_balancer_swap(approvals[0], swaps[0].clone())
+            .unwrap();
+        let mut encoder1 = SettlementEncoder::new(HashMap::new());
+        encoder1
+            .append_balancer_swap(approvals[1], swaps[1].clone())
+            .unwrap();
+        let mut merged = encoder0.merge(encoder1).unwrap();
+        // The merged batch swap can still be extended.
+        merged
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[2].clone())
+            .unwrap();
+
+        assert_eq!(
+            merged.finish().interactions[1],
+            [
+                approvals[0].encode(),
+                approvals[1].encode(),
+                BalancerBatchSwapInteraction::given_out(&swaps)
+                    .unwrap()
+                    .encode(),
+            ]
+            .concat(),
+        );
+    }
+
+    #[test]
+    fn merged_settlements_keep_balancer_swaps_around_other_interactions() {
+        let interaction: EncodedInteraction = (H160([0x01; 20]), 0.into(), Bytes(Vec::new()));
+        let swaps = [balancer_swap(0x03, 0x04), balancer_swap(0x05, 0x06)];
+
+        let mut encoder0 = SettlementEncoder::new(HashMap::new());
+        encoder0
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[0].clone())
+            .unwrap();
+        let mut encoder1 = SettlementEncoder::new(HashMap::new());
+        encoder1.append_to_execution_plan(interaction.clone());
+        encoder1
+            .append_balancer_swap(Approval::AllowanceSufficient, swaps[1].clone())
+            .unwrap();
+        let merged = encoder0.merge(encoder1).unwrap();
+
+        assert_eq!(
+            merged.finish().interactions[1],
+            [swaps[0].encode(), interaction.encode(), swaps[1].encode()].concat(),
+        );
+    }
+
     #[test]
     fn settlement_encoder_add_token_equivalency() {
         let token_a = H160([0x00; 20]);
@@ -974,6 +1723,50 @@ pub mod tests {
         assert!(encoder0.merge(encoder1).is_err());
     }
 
+    #[test]
+    fn merge_with_repricing_uses_buffers() {
+        let encoder0 =
+            SettlementEncoder::new(hashmap! { token(1) => 1.into(), token(2) => 1.into() });
+        let repriced_encoder = |buy_amount: u64| {
+            let order = OrderBuilder::default()
+                .with_sell_token(token(1))
+                .with_sell_amount(100.into())
+                .with_buy_token(token(2))
+                .with_buy_amount(buy_amount.into())
+                .with_kind(OrderKind::Sell)
+                .build();
+            let mut encoder =
+                SettlementEncoder::new(hashmap! { token(1) => 1.into(), token(2) => 2.into() });
+            encoder.add_trade(order, 100.into(), 0.into()).unwrap();
+            encoder
+        };
+
+        // The trade originally buys 50 of token 2, and 100 when re-priced.
+        let mut buffers = hashmap! { token(2) => 49.into() };
+        assert!(encoder0
+            .clone()
+            .merge_with_repricing(repriced_encoder(40), &mut buffers)
+            .is_err());
+        assert_eq!(buffers, hashmap! { token(2) => 49.into() });
+
+        let mut buffers = hashmap! { token(2) => 60.into() };
+        let merged = encoder0
+            .clone()
+            .merge_with_repricing(repriced_encoder(40), &mut buffers)
+            .unwrap();
+        assert_eq!(
+            merged.clearing_prices,
+            hashmap! { token(1) => 1.into(), token(2) => 1.into() }
+        );
+        assert_eq!(buffers, hashmap! { token(2) => 10.into() });
+
+        // Re-pricing can't violate the order's limit price.
+        let mut buffers = hashmap! { token(2) => 1_000.into() };
+        assert!(encoder0
+            .merge_with_repricing(repriced_encoder(101), &mut buffers)
+            .is_err());
+    }
+
     #[test]
     fn encoding_strips_unnecessary_tokens_and_prices() {
         let prices = hashmap! {token(1) => 7.into(), token(2) => 2.into(),
@@ -0,0 +1,397 @@
+//! Fairness check making sure that solvers don't give users worse prices than they would get by
+//! trading directly on the best available on-chain liquidity (EBBO, the "ethereum best bid and
+//! offer").
+//!
+//! Every user trade of a settlement gets compared against a price estimate for the same amount at
+//! the block the settlement was computed for. Liquidity orders are not checked since they are not
+//! owned by users.
+
+use crate::settlement::Settlement;
+use futures::{stream::BoxStream, StreamExt as _};
+use gas_estimation::GasPriceEstimating;
+use model::order::{OrderKind, OrderUid, BUY_ETH_ADDRESS};
+use primitive_types::{H160, U256};
+use shared::{
+    baseline_solver::BaseTokens,
+    price_estimation::{baseline::BaselinePriceEstimator, PriceEstimateResult, Query},
+    rate_limiter::RateLimiter,
+    recent_block_cache::Block,
+    sources::{uniswap_v2::pool_fetching::PoolFetching, PoolAggregator},
+};
+use std::sync::Arc;
+
+/// Price estimation on the liquidity of a specific block.
+#[cfg_attr(test, mockall::automock)]
+pub trait BlockPriceEstimating: Send + Sync + 'static {
+    /// Returns one result for each query in arbitrary order. The usize is the index into the
+    /// queries slice.
+    fn estimates_at_block<'a>(
+        &'a self,
+        queries: &'a [Query],
+        block: Block,
+    ) -> BoxStream<'_, (usize, PriceEstimateResult)>;
+}
+
+impl BlockPriceEstimating for BaselinePriceEstimator {
+    fn estimates_at_block<'a>(
+        &'a self,
+        queries: &'a [Query],
+        block: Block,
+    ) -> BoxStream<'_, (usize, PriceEstimateResult)> {
+        BaselinePriceEstimator::estimates_at_block(self, queries, block)
+    }
+}
+
+/// A user trade that got executed at a worse price than the estimated one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EbboViolation {
+    pub order_uid: OrderUid,
+    pub kind: OrderKind,
+    /// The executed buy amount for sell orders or the executed sell amount for buy orders.
+    pub executed_amount: U256,
+    /// The estimated buy amount for sell orders or the estimated sell amount for buy orders.
+    pub estimated_amount: U256,
+}
+
+pub struct EbboValidator {
+    price_estimator: Arc<dyn BlockPriceEstimating>,
+    native_token: H160,
+    /// The fraction by which an executed price may be worse than the estimated one.
+    tolerance: f64,
+}
+
+impl EbboValidator {
+    pub fn new(
+        price_estimator: Arc<dyn BlockPriceEstimating>,
+        native_token: H160,
+        tolerance: f64,
+    ) -> Self {
+        Self {
+            price_estimator,
+            native_token,
+            tolerance,
+        }
+    }
+
+    /// Creates a validator estimating prices with the baseline price estimator on the pools of
+    /// the specified fetchers.
+    pub fn baseline(
+        pool_fetchers: Vec<Arc<dyn PoolFetching>>,
+        gas_price_estimator: Arc<dyn GasPriceEstimating>,
+        base_tokens: Arc<BaseTokens>,
+        native_token: H160,
+        max_split_paths: usize,
+        tolerance: f64,
+    ) -> Self {
+        let price_estimator = BaselinePriceEstimator::new(
+            Arc::new(PoolAggregator { pool_fetchers }),
+            None,
+            gas_price_estimator,
+            base_tokens,
+            native_token,
+            U256::exp10(18),
+            Arc::new(RateLimiter::from_strategy(
+                Default::default(),
+                "ebbo_estimator".into(),
+            )),
+            max_split_paths,
+        );
+        Self::new(Arc::new(price_estimator), native_token, tolerance)
+    }
+
+    /// Returns all user trades of the settlement that violate EBBO at the specified block and
+    /// records them for the solver that proposed the settlement.
+    ///
+    /// Trades are compared at the uniform clearing prices, i.e. before partner fees are withheld,
+    /// since the fees are not part of the price the solver found. Trades for which no price could
+    /// be estimated are not considered violations.
+    pub async fn violations(
+        &self,
+        solver: &str,
+        settlement: &Settlement,
+        block: u64,
+    ) -> Vec<EbboViolation> {
+        let trades = settlement
+            .encoder
+            .order_trades()
+            .iter()
+            .map(|order_trade| &order_trade.trade)
+            .filter(|trade| !trade.order.metadata.is_liquidity_order)
+            .filter_map(|trade| {
+                let execution = trade.executed_amounts(
+                    settlement.clearing_price(trade.order.data.sell_token)?,
+                    settlement.clearing_price(trade.order.data.buy_token)?,
+                )?;
+                Some((trade, execution))
+            })
+            .map(|(trade, execution)| {
+                let (in_amount, executed_amount) = match trade.order.data.kind {
+                    OrderKind::Sell => (execution.sell_amount, execution.buy_amount),
+                    OrderKind::Buy => (execution.buy_amount, execution.sell_amount),
+                };
+                let query = Query {
+                    sell_token: execution.sell_token,
+                    buy_token: self.normalize_token(execution.buy_token),
+                    in_amount,
+                    kind: trade.order.data.kind,
+                };
+                (trade.order.metadata.uid, query, executed_amount)
+            })
+            .filter(|(_, query, _)| !query.in_amount.is_zero())
+            .collect::<Vec<_>>();
+        if trades.is_empty() {
+            return Vec::new();
+        }
+
+        let queries = trades
+            .iter()
+            .map(|(_, query, _)| *query)
+            .collect::<Vec<_>>();
+        let mut estimates = vec![None; queries.len()];
+        let mut stream = self
+            .price_estimator
+            .estimates_at_block(&queries, Block::Number(block));
+        while let Some((index, estimate)) = stream.next().await {
+            estimates[index] = Some(estimate);
+        }
+        let violations = trades
+            .into_iter()
+            .zip(estimates)
+            .filter_map(|((order_uid, query, executed_amount), estimate)| {
+                let estimate = match estimate? {
+                    Ok(estimate) => estimate,
+                    Err(err) => {
+                        tracing::debug!(%order_uid, ?err, "skipping EBBO check for trade");
+                        return None;
+                    }
+                };
+                let violated = match query.kind {
+                    OrderKind::Sell => {
+                        executed_amount < scale(estimate.out_amount, 1. - self.tolerance)
+                    }
+                    OrderKind::Buy => {
+                        executed_amount > scale(estimate.out_amount, 1. + self.tolerance)
+                    }
+                };
+                violated.then(|| EbboViolation {
+                    order_uid,
+                    kind: query.kind,
+                    executed_amount,
+                    estimated_amount: estimate.out_amount,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !violations.is_empty() {
+            tracing::debug!(%solver, ?violations, "settlement violates EBBO");
+            Metrics::instance(global_metrics::get_metric_storage_registry())
+                .expect("unexpected error getting metrics instance")
+                .ebbo_violations
+                .with_label_values(&[solver])
+                .inc_by(violations.len() as u64);
+        }
+        violations
+    }
+
+    /// Price estimators don't know about the special buy ETH address but the native token is
+    /// priced the same.
+    fn normalize_token(&self, token: H160) -> H160 {
+        if token == BUY_ETH_ADDRESS {
+            self.native_token
+        } else {
+            token
+        }
+    }
+}
+
+fn scale(amount: U256, factor: f64) -> U256 {
+    U256::from_f64_lossy(amount.to_f64_lossy() * factor)
+}
+
+#[derive(prometheus_metric_storage::MetricStorage, Clone, Debug)]
+#[metric(subsystem = "settlement_ebbo")]
+struct Metrics {
+    /// Number of user trades that got executed at a worse price than the EBBO estimate.
+    #[metric(labels("solver"))]
+    ebbo_violations: prometheus::IntCounterVec,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::{OrderTrade, Trade};
+    use maplit::hashmap;
+    use model::order::{Order, OrderData, OrderMetadata};
+    use shared::price_estimation::{Estimate, PriceEstimationError};
+
+    const BLOCK: u64 = 42;
+
+    fn address(value: u64) -> H160 {
+        H160::from_low_u64_be(value)
+    }
+
+    fn trade(uid: u8, kind: OrderKind, buy_token: H160, executed_amount: U256) -> OrderTrade {
+        OrderTrade {
+            trade: Trade {
+                order: Order {
+                    data: OrderData {
+                        sell_token: address(1),
+                        buy_token,
+                        sell_amount: 1000.into(),
+                        buy_amount: 1.into(),
+                        kind,
+                        ..Default::default()
+                    },
+                    metadata: OrderMetadata {
+                        uid: OrderUid([uid; 56]),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                executed_amount,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn estimator(out_amounts: Vec<Option<u64>>) -> Arc<dyn BlockPriceEstimating> {
+        let mut estimator = MockBlockPriceEstimating::new();
+        estimator
+            .expect_estimates_at_block()
+            .returning(move |queries, block| {
+                assert_eq!(queries.len(), out_amounts.len());
+                assert_eq!(block, Block::Number(BLOCK));
+                futures::stream::iter(out_amounts.clone().into_iter().map(|out_amount| {
+                    out_amount
+                        .map(|out_amount| Estimate {
+                            out_amount: out_amount.into(),
+                            gas: 0,
+                        })
+                        .ok_or(PriceEstimationError::NoLiquidity)
+                }))
+                .enumerate()
+                .boxed()
+            });
+        Arc::new(estimator)
+    }
+
+    // Token 1 trades 1:2 for token 2.
+    fn settlement(trades: Vec<OrderTrade>) -> Settlement {
+        Settlement::with_trades(
+            hashmap! { address(1) => 2.into(), address(2) => 1.into() },
+            trades,
+            vec![],
+        )
+    }
+
+    #[tokio::test]
+    async fn flags_trades_worse_than_estimate() {
+        let validator = EbboValidator::new(estimator(vec![Some(200), Some(210)]), address(2), 0.);
+        let violations = validator
+            .violations(
+                "solver",
+                &settlement(vec![
+                    trade(1, OrderKind::Sell, address(2), 100.into()),
+                    trade(2, OrderKind::Sell, address(2), 100.into()),
+                ]),
+                BLOCK,
+            )
+            .await;
+        assert_eq!(
+            violations,
+            vec![EbboViolation {
+                order_uid: OrderUid([2; 56]),
+                kind: OrderKind::Sell,
+                executed_amount: 200.into(),
+                estimated_amount: 210.into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn compares_amounts_before_partner_fee() {
+        let validator = EbboValidator::new(estimator(vec![Some(200)]), address(2), 0.);
+        // The partner fee halves the bought amount.
+        let trade = OrderTrade {
+            custom_buy_token_price: Some(2.into()),
+            ..trade(1, OrderKind::Sell, address(2), 100.into())
+        };
+        let settlement = settlement(vec![trade]);
+        assert_eq!(
+            settlement.executed_trades().next().unwrap().1.buy_amount,
+            100.into()
+        );
+        assert!(validator
+            .violations("solver", &settlement, BLOCK)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn buy_orders_must_not_sell_more_than_estimate() {
+        // Buying 100 of token 2 costs 50 of token 1.
+        let validator = EbboValidator::new(estimator(vec![Some(50), Some(40)]), address(2), 0.1);
+        let violations = validator
+            .violations(
+                "solver",
+                &settlement(vec![
+                    trade(1, OrderKind::Buy, address(2), 100.into()),
+                    trade(2, OrderKind::Buy, address(2), 100.into()),
+                ]),
+                BLOCK,
+            )
+            .await;
+        assert_eq!(
+            violations,
+            vec![EbboViolation {
+                order_uid: OrderUid([2; 56]),
+                kind: OrderKind::Buy,
+                executed_amount: 50.into(),
+                estimated_amount: 40.into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn tolerates_small_deviations_and_missing_estimates() {
+        let validator = EbboValidator::new(estimator(vec![Some(210), None]), address(2), 0.05);
+        let violations = validator
+            .violations(
+                "solver",
+                &settlement(vec![
+                    trade(1, OrderKind::Sell, address(2), 100.into()),
+                    trade(2, OrderKind::Sell, address(2), 100.into()),
+                ]),
+                BLOCK,
+            )
+            .await;
+        assert!(violations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn queries_native_token_instead_of_buy_eth() {
+        let mut estimator = MockBlockPriceEstimating::new();
+        estimator
+            .expect_estimates_at_block()
+            .returning(|queries, _| {
+                assert_eq!(queries[0].buy_token, address(2));
+                futures::stream::iter([Ok(Estimate {
+                    out_amount: 200.into(),
+                    gas: 0,
+                })])
+                .enumerate()
+                .boxed()
+            });
+        let validator = EbboValidator::new(Arc::new(estimator), address(2), 0.);
+        let settlement = Settlement::with_trades(
+            hashmap! { address(1) => 2.into(), BUY_ETH_ADDRESS => 1.into() },
+            vec![trade(1, OrderKind::Sell, BUY_ETH_ADDRESS, 100.into())],
+            vec![],
+        );
+        assert!(validator
+            .violations("solver", &settlement, BLOCK)
+            .await
+            .is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+use super::SettlementSimulating;
+use crate::liquidity::AmmOrderExecution;
+use crate::settlement::Settlement;
+use crate::solver::http_solver::buffers::BufferRetrieving;
+use itertools::Itertools;
+use primitive_types::{H160, U256};
+use std::collections::{HashMap, HashSet};
+use web3::types::AccessList;
+
+/// Drops AMM swaps whose output can be paid with the settlement contract's own token balances
+/// instead. The settlement contract then keeps the input tokens of these swaps, which is only done
+/// for swaps between trusted tokens and if the input is worth at least as much as the output at
+/// the settlement's clearing prices.
+/// Since solvers may already rely on the buffers themselves, both variants of the settlement get
+/// simulated and the cheaper one that succeeds is kept.
+pub async fn internalize_buffers(
+    settlement: Settlement,
+    access_list: Option<AccessList>,
+    settlement_simulator: &impl SettlementSimulating,
+    buffer_retriever: &impl BufferRetrieving,
+    trusted_tokens: &HashSet<H160>,
+) -> Settlement {
+    let swaps = settlement
+        .encoder
+        .execution_plan()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, interaction)| Some((index, interaction.swap_execution()?)))
+        .filter(|(_, swap)| {
+            trusted_tokens.contains(&swap.input.0) && trusted_tokens.contains(&swap.output.0)
+        })
+        .filter(|(_, swap)| is_at_least_clearing_price(swap, settlement.clearing_prices()))
+        .collect::<Vec<_>>();
+    if swaps.is_empty() {
+        return settlement;
+    }
+
+    let tokens = swaps
+        .iter()
+        .map(|(_, swap)| swap.output.0)
+        .unique()
+        .collect::<Vec<_>>();
+    let mut buffers = buffer_retriever
+        .get_buffers(&tokens)
+        .await
+        .into_iter()
+        .filter_map(|(token, buffer)| Some((token, buffer.ok()?)))
+        .collect::<HashMap<_, _>>();
+
+    let mut internalized = HashSet::new();
+    for (index, swap) in swaps {
+        let (token_out, amount_out) = swap.output;
+        match buffers.get_mut(&token_out) {
+            Some(buffer) if *buffer >= amount_out => {
+                *buffer -= amount_out;
+                internalized.insert(index);
+            }
+            _ => (),
+        }
+    }
+    if internalized.is_empty() {
+        return settlement;
+    }
+
+    let mut optimized_settlement = settlement.clone();
+    optimized_settlement
+        .encoder
+        .drop_interactions(&internalized);
+
+    let (original_gas, optimized_gas) = futures::join!(
+        settlement_simulator.estimate_gas(settlement.clone(), access_list.clone()),
+        settlement_simulator.estimate_gas(optimized_settlement.clone(), access_list),
+    );
+    match (original_gas, optimized_gas) {
+        (Some(original_gas), Some(optimized_gas)) if optimized_gas >= original_gas => settlement,
+        (_, Some(optimized_gas)) => {
+            tracing::debug!(
+                ?original_gas,
+                ?optimized_gas,
+                internalized = internalized.len(),
+                "use internal buffers for AMM swaps"
+            );
+            optimized_settlement
+        }
+        (_, None) => settlement,
+    }
+}
+
+/// Checks whether the input of the swap is worth at least as much as its output at the clearing
+/// prices. Swaps of tokens without clearing prices are never internalized.
+fn is_at_least_clearing_price(swap: &AmmOrderExecution, prices: &HashMap<H160, U256>) -> bool {
+    let ((token_in, amount_in), (token_out, amount_out)) = (swap.input, swap.output);
+    match (prices.get(&token_in), prices.get(&token_out)) {
+        (Some(price_in), Some(price_out)) => {
+            amount_in.full_mul(*price_in) >= amount_out.full_mul(*price_out)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::NoopInteraction;
+    use crate::settlement_post_processing::MockSettlementSimulating;
+    use crate::solver::http_solver::buffers::{BufferRetrievalError, MockBufferRetrieving};
+    use crate::{encoding::EncodedInteraction, settlement::Interaction};
+    use maplit::hashmap;
+
+    #[derive(Debug)]
+    struct Swap(AmmOrderExecution);
+
+    impl Interaction for Swap {
+        fn encode(&self) -> Vec<EncodedInteraction> {
+            Vec::new()
+        }
+
+        fn swap_execution(&self) -> Option<AmmOrderExecution> {
+            Some(self.0.clone())
+        }
+    }
+
+    fn swap(token_in: u64, amount_in: u64, token_out: u64, amount_out: u64) -> Swap {
+        Swap(AmmOrderExecution {
+            input: (H160::from_low_u64_be(token_in), amount_in.into()),
+            output: (H160::from_low_u64_be(token_out), amount_out.into()),
+        })
+    }
+
+    fn settlement_with_swaps(swaps: impl IntoIterator<Item = Swap>) -> Settlement {
+        let mut settlement = Settlement::new(hashmap! {
+            H160::from_low_u64_be(1) => 1.into(),
+            H160::from_low_u64_be(2) => 2.into(),
+        });
+        settlement.encoder.append_to_execution_plan(NoopInteraction);
+        for swap in swaps {
+            settlement.encoder.append_to_execution_plan(swap);
+        }
+        settlement
+    }
+
+    fn buffers(buffers: &[(u64, u64)]) -> MockBufferRetrieving {
+        let buffers = buffers
+            .iter()
+            .map(|(token, amount)| (H160::from_low_u64_be(*token), Ok((*amount).into())))
+            .collect::<HashMap<_, Result<U256, BufferRetrievalError>>>();
+        let mut buffer_retriever = MockBufferRetrieving::new();
+        buffer_retriever
+            .expect_get_buffers()
+            .returning(move |_| buffers.clone());
+        buffer_retriever
+    }
+
+    fn trusted_tokens() -> HashSet<H160> {
+        (1..=3).map(H160::from_low_u64_be).collect()
+    }
+
+    fn simulator_preferring_fewer_interactions() -> MockSettlementSimulating {
+        let mut settlement_simulator = MockSettlementSimulating::new();
+        settlement_simulator
+            .expect_estimate_gas()
+            .returning(|settlement, _| {
+                Some(U256::from(settlement.encoder.execution_plan().len()) * 100_000)
+            });
+        settlement_simulator
+    }
+
+    #[tokio::test]
+    async fn internalizes_swaps_covered_by_buffers() {
+        let settlement = internalize_buffers(
+            settlement_with_swaps([swap(1, 20, 2, 10), swap(1, 20, 2, 10)]),
+            None,
+            &simulator_preferring_fewer_interactions(),
+            &buffers(&[(2, 15)]),
+            &trusted_tokens(),
+        )
+        .await;
+        // Only one of the swaps fits into the buffer.
+        assert_eq!(settlement.encoder.execution_plan().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_internalize_swaps_of_untrusted_tokens() {
+        let mut settlement_simulator = MockSettlementSimulating::new();
+        settlement_simulator.expect_estimate_gas().never();
+
+        let settlement = internalize_buffers(
+            settlement_with_swaps([swap(1, 20, 2, 10)]),
+            None,
+            &settlement_simulator,
+            &buffers(&[(2, 100)]),
+            &[H160::from_low_u64_be(2)].into_iter().collect(),
+        )
+        .await;
+        assert_eq!(settlement.encoder.execution_plan().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_internalize_swaps_worse_than_clearing_price() {
+        let mut settlement_simulator = MockSettlementSimulating::new();
+        settlement_simulator.expect_estimate_gas().never();
+
+        let settlement = internalize_buffers(
+            settlement_with_swaps([swap(1, 19, 2, 10), swap(3, 1, 2, 1)]),
+            None,
+            &settlement_simulator,
+            &buffers(&[(2, 100)]),
+            &trusted_tokens(),
+        )
+        .await;
+        assert_eq!(settlement.encoder.execution_plan().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn keeps_original_settlement_if_cheaper_or_optimized_reverts() {
+        let mut settlement_simulator = MockSettlementSimulating::new();
+        settlement_simulator
+            .expect_estimate_gas()
+            .returning(
+                |settlement, _| match settlement.encoder.execution_plan().len() {
+                    2 => Some(100_000.into()),
+                    _ => Some(200_000.into()),
+                },
+            );
+        let settlement = internalize_buffers(
+            settlement_with_swaps([swap(1, 20, 2, 10)]),
+            None,
+            &settlement_simulator,
+            &buffers(&[(2, 100)]),
+            &trusted_tokens(),
+        )
+        .await;
+        assert_eq!(settlement.encoder.execution_plan().len(), 2);
+
+        let mut settlement_simulator = MockSettlementSimulating::new();
+        settlement_simulator
+            .expect_estimate_gas()
+            .returning(
+                |settlement, _| match settlement.encoder.execution_plan().len() {
+                    2 => Some(100_000.into()),
+                    _ => None,
+                },
+            );
+        let settlement = internalize_buffers(
+            settlement_with_swaps([swap(1, 20, 2, 10)]),
+            None,
+            &settlement_simulator,
+            &buffers(&[(2, 100)]),
+            &trusted_tokens(),
+        )
+        .await;
+        assert_eq!(settlement.encoder.execution_plan().len(), 2);
+    }
+}
@@ -1,15 +1,18 @@
+pub mod internalize_buffers;
 pub mod optimize_unwrapping;
 
 use crate::settlement::Settlement;
-use crate::settlement_simulation::simulate_and_estimate_gas_at_current_block;
+use crate::settlement_simulation::simulate_and_estimate_gas;
 use crate::solver::http_solver::buffers::BufferRetriever;
 use contracts::{GPv2Settlement, WETH9};
 use ethcontract::Account;
 use gas_estimation::GasPrice1559;
+use internalize_buffers::internalize_buffers;
 use optimize_unwrapping::optimize_unwrapping;
-use primitive_types::H160;
+use primitive_types::{H160, U256};
 use shared::Web3;
-use web3::types::AccessList;
+use std::collections::HashSet;
+use web3::types::{AccessList, BlockNumber};
 
 /// Determines whether a settlement would be executed successfully.
 #[cfg_attr(test, mockall::automock)]
@@ -20,6 +23,13 @@ pub trait SettlementSimulating: Send + Sync {
         settlement: Settlement,
         access_list: Option<AccessList>,
     ) -> bool;
+
+    /// Returns the gas used by the settlement or `None` if it would revert.
+    async fn estimate_gas(
+        &self,
+        settlement: Settlement,
+        access_list: Option<AccessList>,
+    ) -> Option<U256>;
 }
 
 pub struct SettlementSimulator {
@@ -36,23 +46,47 @@ impl SettlementSimulating for SettlementSimulator {
         settlement: Settlement,
         access_list: Option<AccessList>,
     ) -> bool {
-        let result = simulate_and_estimate_gas_at_current_block(
+        self.estimate_gas(settlement, access_list).await.is_some()
+    }
+
+    async fn estimate_gas(
+        &self,
+        settlement: Settlement,
+        access_list: Option<AccessList>,
+    ) -> Option<U256> {
+        let result = simulate_and_estimate_gas(
             std::iter::once((self.solver_account.clone(), settlement, access_list)),
             &self.settlement_contract,
             &self.web3,
             self.gas_price,
+            BlockNumber::Latest,
         )
         .await;
-        matches!(result, Ok(results) if results[0].is_ok())
+        match result {
+            Ok(results) => results.into_iter().next()?.ok(),
+            Err(_) => None,
+        }
     }
 }
 
+/// Configures which AMM swaps of winning settlements may be paid from the settlement contract's
+/// buffers instead.
+#[derive(Clone, Debug, Default)]
+pub struct BufferInternalization {
+    /// Only swaps between these tokens get internalized, so the buffers never end up holding
+    /// tokens nobody vetted.
+    pub trusted_tokens: HashSet<H160>,
+    /// The names of solvers whose settlements must not use the buffers.
+    pub excluded_solvers: HashSet<String>,
+}
+
 pub struct PostProcessingPipeline {
     web3: Web3,
     settlement_contract: GPv2Settlement,
     unwrap_factor: f64,
     weth: WETH9,
     buffer_retriever: BufferRetriever,
+    buffer_internalization: Option<BufferInternalization>,
 }
 
 impl PostProcessingPipeline {
@@ -61,6 +95,7 @@ impl PostProcessingPipeline {
         web3: Web3,
         unwrap_factor: f64,
         settlement_contract: GPv2Settlement,
+        buffer_internalization: Option<BufferInternalization>,
     ) -> Self {
         let weth = WETH9::at(&web3, native_token);
         let buffer_retriever = BufferRetriever::new(web3.clone(), settlement_contract.address());
@@ -71,6 +106,7 @@ impl PostProcessingPipeline {
             unwrap_factor,
             weth,
             buffer_retriever,
+            buffer_internalization,
         }
     }
 
@@ -79,6 +115,7 @@ impl PostProcessingPipeline {
         settlement: Settlement,
         access_list: Option<AccessList>,
         solver_account: Account,
+        solver_name: &str,
         gas_price: GasPrice1559,
     ) -> Settlement {
         let simulator = SettlementSimulator {
@@ -89,14 +126,28 @@ impl PostProcessingPipeline {
         };
 
         // an error will leave the settlement unmodified
-        optimize_unwrapping(
+        let optimized_settlement = optimize_unwrapping(
             settlement,
-            access_list,
+            access_list.clone(),
             &simulator,
             &self.buffer_retriever,
             &self.weth,
             self.unwrap_factor,
         )
-        .await
+        .await;
+
+        match &self.buffer_internalization {
+            Some(config) if !config.excluded_solvers.contains(solver_name) => {
+                internalize_buffers(
+                    optimized_settlement,
+                    access_list,
+                    &simulator,
+                    &self.buffer_retriever,
+                    &config.trusted_tokens,
+                )
+                .await
+            }
+            _ => optimized_settlement,
+        }
     }
 }
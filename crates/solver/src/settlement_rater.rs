@@ -1,18 +1,37 @@
+pub mod balance_changes;
+pub mod revert_risk;
+
+use self::{
+    balance_changes::{simulated_outcome, BalanceChangeTracing, SimulatedOutcome},
+    revert_risk::{RevertRiskEstimator, SettlementFeatures},
+};
 use crate::{
     driver::solver_settlements::RatedSettlement,
     settlement::{external_prices::ExternalPrices, Settlement},
     settlement_access_list::AccessListEstimating,
-    settlement_simulation::{settle_method, simulate_and_estimate_gas_at_current_block},
+    settlement_simulation::{call_data, settle_method, simulate_and_estimate_gas},
     solver::{SettlementWithError, SettlementWithSolver, Solver},
 };
 use anyhow::{Context, Result};
 use contracts::GPv2Settlement;
+use futures::future::join_all;
 use gas_estimation::GasPrice1559;
 use itertools::{Either, Itertools};
 use num::BigRational;
 use shared::Web3;
 use std::sync::Arc;
-use web3::types::AccessList;
+use web3::types::{AccessList, BlockNumber, Bytes, CallRequest};
+
+/// How rated settlements are ranked against each other.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ArgEnum)]
+#[clap(rename_all = "verbatim")]
+pub enum SettlementScoring {
+    /// Rank by objective value, i.e. surplus plus fees minus gas cost.
+    ObjectiveValue,
+    /// Rank by expected value, i.e. the objective value taking into account the estimated
+    /// probability of the settlement reverting, in which case we pay the gas cost but get nothing.
+    RiskAdjusted,
+}
 
 type SolverSettlement = (Arc<dyn Solver>, Settlement);
 type RatedSolverSettlement = (Arc<dyn Solver>, RatedSettlement, Option<AccessList>);
@@ -33,6 +52,9 @@ pub struct SettlementRater {
     pub access_list_estimator: Arc<dyn AccessListEstimating>,
     pub settlement_contract: GPv2Settlement,
     pub web3: Web3,
+    pub revert_risk: Arc<RevertRiskEstimator>,
+    /// If set, settlements are rated by the surplus observed when tracing their simulation.
+    pub balance_change_tracer: Option<Arc<dyn BalanceChangeTracing>>,
 }
 
 impl SettlementRater {
@@ -69,6 +91,38 @@ impl SettlementRater {
             })
             .collect()
     }
+
+    /// Traces the settlement if enabled. Failing to trace only gets logged, in which case the
+    /// settlement gets rated by its claimed surplus.
+    async fn simulated_outcome(
+        &self,
+        solver: &dyn Solver,
+        settlement: &Settlement,
+        prices: &ExternalPrices,
+    ) -> Option<SimulatedOutcome> {
+        let tracer = self.balance_change_tracer.as_ref()?;
+        let settlement_tx = CallRequest {
+            from: Some(solver.account().address()),
+            to: Some(self.settlement_contract.address()),
+            data: Some(Bytes(call_data(settlement.clone().into()))),
+            ..Default::default()
+        };
+        match simulated_outcome(
+            tracer.as_ref(),
+            settlement_tx,
+            settlement,
+            self.settlement_contract.address(),
+            prices,
+        )
+        .await
+        {
+            Ok(outcome) => Some(outcome),
+            Err(err) => {
+                tracing::warn!(?err, solver = solver.name(), "failed to trace settlement");
+                None
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -81,7 +135,7 @@ impl SettlementRating for SettlementRater {
     ) -> Result<(Vec<RatedSolverSettlement>, Vec<SettlementWithError>)> {
         let settlements = self.append_access_lists(settlements, gas_price).await;
 
-        let simulations = simulate_and_estimate_gas_at_current_block(
+        let simulations = simulate_and_estimate_gas(
             settlements.iter().map(|settlement| {
                 (
                     settlement.0.account().clone(),
@@ -92,15 +146,46 @@ impl SettlementRating for SettlementRater {
             &self.settlement_contract,
             &self.web3,
             gas_price,
+            BlockNumber::Latest,
         )
         .await
         .context("failed to simulate settlements")?;
 
+        self.revert_risk
+            .record_gas_price(gas_price.effective_gas_price());
         let gas_price =
             BigRational::from_float(gas_price.effective_gas_price()).expect("Invalid gas price.");
 
-        let rate_settlement = |id, settlement: Settlement, gas_estimate| {
-            let surplus = settlement.total_surplus(prices);
+        let outcomes = join_all(settlements.iter().zip(&simulations).map(
+            |((solver, settlement, _), simulation)| async move {
+                simulation.as_ref().ok()?;
+                self.simulated_outcome(solver.as_ref(), settlement, prices)
+                    .await
+            },
+        ))
+        .await;
+
+        let rate_settlement = |id: usize,
+                               solver: &dyn Solver,
+                               settlement: Settlement,
+                               gas_estimate,
+                               outcome: Option<SimulatedOutcome>| {
+            let revert_probability = self
+                .revert_risk
+                .revert_probability(&SettlementFeatures::new(solver.name(), &settlement));
+            let (surplus, deviates_from_simulation) = match outcome {
+                Some(outcome) => {
+                    if outcome.deviates {
+                        tracing::warn!(
+                            id,
+                            solver = solver.name(),
+                            "simulated settlement outcome deviates from its claims"
+                        );
+                    }
+                    (outcome.surplus, outcome.deviates)
+                }
+                None => (settlement.total_surplus(prices), false),
+            };
             let scaled_solver_fees = settlement.total_scaled_unsubsidized_fees(prices);
             let unscaled_subsidized_fee = settlement.total_unscaled_subsidized_fees(prices);
             RatedSettlement {
@@ -111,20 +196,25 @@ impl SettlementRating for SettlementRater {
                 scaled_unsubsidized_fee: scaled_solver_fees,
                 gas_estimate,
                 gas_price: gas_price.clone(),
+                revert_probability,
+                deviates_from_simulation,
             }
         };
 
-        Ok(
-            (settlements.into_iter().zip(simulations).enumerate()).partition_map(
-                |(i, ((solver, settlement, access_list), result))| match result {
+        Ok(settlements
+            .into_iter()
+            .zip(simulations)
+            .zip(outcomes)
+            .enumerate()
+            .partition_map(
+                |(i, (((solver, settlement, access_list), result), outcome))| match result {
                     Ok(gas_estimate) => Either::Left((
                         solver.clone(),
-                        rate_settlement(i, settlement, gas_estimate),
+                        rate_settlement(i, solver.as_ref(), settlement, gas_estimate, outcome),
                         access_list,
                     )),
                     Err(err) => Either::Right((solver, settlement, access_list, err)),
                 },
-            ),
-        )
+            ))
     }
 }
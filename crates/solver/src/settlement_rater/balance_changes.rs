@@ -0,0 +1,480 @@
+//! Surplus accounting based on the token balance changes observed when simulating a settlement.
+//!
+//! The surplus a settlement claims is computed from its clearing prices assuming that all token
+//! transfers and interactions behave as modeled. Fee-on-transfer or rebasing tokens and AMM state
+//! that drifted since the solution was computed make the actual outcome differ. We trace the
+//! settlement between ERC20 balance queries of its traders and of the settlement contract, compare
+//! the observed balance changes with the claimed ones and value the differences at external prices.
+
+use crate::settlement::{external_prices::ExternalPrices, Settlement, Trade, TradeExecution};
+use anyhow::{ensure, Context, Result};
+use contracts::ERC20;
+use model::order::{BuyTokenDestination, SellTokenSource, BUY_ETH_ADDRESS};
+use num::{BigInt, BigRational, Signed as _, Zero as _};
+use primitive_types::{H160, U256};
+use shared::{conversions::U256Ext as _, trace_many, Web3};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use web3::types::{Action, BlockTrace, CallRequest};
+
+/// The ERC20 token balance of an account.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Balance {
+    pub owner: H160,
+    pub token: H160,
+}
+
+#[mockall::automock]
+#[async_trait::async_trait]
+pub trait BalanceChangeTracing: Send + Sync {
+    /// Simulates the settlement transaction at the latest block and returns by how much each of
+    /// the balances changed. Fails if the settlement reverts.
+    async fn trace_balance_changes(
+        &self,
+        settlement_tx: CallRequest,
+        balances: Vec<Balance>,
+    ) -> Result<Vec<BigInt>>;
+
+    /// Simulates the settlement transaction at the latest block and returns all tokens whose
+    /// `transfer` or `transferFrom` functions it calls.
+    async fn transferred_tokens(&self, settlement_tx: CallRequest) -> Result<Vec<H160>>;
+}
+
+/// The selectors of ERC20 `transfer(address,uint256)` and `transferFrom(address,address,uint256)`.
+const TRANSFER_SELECTORS: [[u8; 4]; 2] = [[0xa9, 0x05, 0x9c, 0xbb], [0x23, 0xb8, 0x72, 0xdd]];
+
+/// Traces balance changes with the `trace_callMany` API that only some nodes support.
+pub struct TraceCallBalanceChangeTracer {
+    pub web3: Web3,
+}
+
+#[async_trait::async_trait]
+impl BalanceChangeTracing for TraceCallBalanceChangeTracer {
+    async fn trace_balance_changes(
+        &self,
+        settlement_tx: CallRequest,
+        balances: Vec<Balance>,
+    ) -> Result<Vec<BigInt>> {
+        let balance_requests = balances
+            .iter()
+            .map(|balance| {
+                let tx = ERC20::at(&self.web3, balance.token)
+                    .balance_of(balance.owner)
+                    .m
+                    .tx;
+                CallRequest {
+                    to: Some(balance.token),
+                    data: tx.data,
+                    ..Default::default()
+                }
+            })
+            .collect::<Vec<_>>();
+        let requests = balance_requests
+            .iter()
+            .cloned()
+            .chain(std::iter::once(settlement_tx))
+            .chain(balance_requests.iter().cloned())
+            .collect();
+
+        let traces = trace_many::trace_many(requests, &self.web3)
+            .await
+            .context("failed to trace settlement")?;
+        ensure!(
+            traces.len() == 2 * balances.len() + 1,
+            "unexpected number of traces"
+        );
+        let (before, rest) = traces.split_at(balances.len());
+        let (settlement, after) = rest.split_at(1);
+        ensure!(
+            trace_many::all_calls_succeeded(settlement)?,
+            "settlement reverted"
+        );
+
+        before
+            .iter()
+            .zip(after)
+            .map(|(before, after)| {
+                Ok(decode_u256(after)?.to_big_int() - decode_u256(before)?.to_big_int())
+            })
+            .collect()
+    }
+
+    async fn transferred_tokens(&self, settlement_tx: CallRequest) -> Result<Vec<H160>> {
+        let traces = trace_many::trace_many(vec![settlement_tx], &self.web3)
+            .await
+            .context("failed to trace settlement")?;
+        let tokens = traces
+            .iter()
+            .flat_map(|trace| trace.trace.iter().flatten())
+            .filter_map(|trace| match &trace.action {
+                Action::Call(call) if is_transfer(&call.input.0) => Some(call.to),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+        Ok(tokens.into_iter().collect())
+    }
+}
+
+fn is_transfer(input: &[u8]) -> bool {
+    input.get(..4).map_or(false, |selector| {
+        TRANSFER_SELECTORS
+            .iter()
+            .any(|transfer| transfer == selector)
+    })
+}
+
+fn decode_u256(trace: &BlockTrace) -> Result<U256> {
+    let bytes = trace.output.0.as_slice();
+    ensure!(bytes.len() == 32, "invalid balance length");
+    Ok(U256::from_big_endian(bytes))
+}
+
+/// The balance changes a settlement claims for the traders of its orders. Balances that aren't
+/// ERC20 balances of the traders, like native ETH and Balancer Vault internal balances, are
+/// ignored.
+pub fn claimed_balance_changes(settlement: &Settlement) -> BTreeMap<Balance, BigInt> {
+    trader_balance_changes(settlement, |_, execution| {
+        Some((
+            execution.sell_amount.to_big_int() + execution.fee_amount.to_big_int(),
+            execution.buy_amount.to_big_int(),
+        ))
+    })
+}
+
+/// Sums the balance changes of the traders of a settlement where `amounts` returns the sold amount
+/// (including the fee) and the bought amount of each executed trade, or `None` to skip the trade.
+/// Like in `claimed_balance_changes`, balances that aren't ERC20 balances of the traders are
+/// ignored.
+pub fn trader_balance_changes(
+    settlement: &Settlement,
+    mut amounts: impl FnMut(&Trade, &TradeExecution) -> Option<(BigInt, BigInt)>,
+) -> BTreeMap<Balance, BigInt> {
+    let mut changes = BTreeMap::new();
+    for (trade, execution) in settlement.executed_trades() {
+        let (sold, bought) = match amounts(trade, &execution) {
+            Some(amounts) => amounts,
+            None => continue,
+        };
+        let order = &trade.order;
+        if order.data.sell_token_balance != SellTokenSource::Internal {
+            let balance = Balance {
+                owner: order.metadata.owner,
+                token: execution.sell_token,
+            };
+            *changes.entry(balance).or_insert_with(BigInt::zero) -= sold;
+        }
+        if order.data.buy_token_balance == BuyTokenDestination::Erc20
+            && execution.buy_token != BUY_ETH_ADDRESS
+        {
+            let balance = Balance {
+                owner: order.data.receiver.unwrap_or(order.metadata.owner),
+                token: execution.buy_token,
+            };
+            *changes.entry(balance).or_insert_with(BigInt::zero) += bought;
+        }
+    }
+    changes
+}
+
+/// Traces the settlement and returns the observed changes of the specified trader balances and of
+/// the settlement contract's buffers of the specified tokens.
+pub async fn observed_balance_changes(
+    tracer: &dyn BalanceChangeTracing,
+    settlement_tx: CallRequest,
+    settlement_contract: H160,
+    traders: impl IntoIterator<Item = Balance>,
+    buffer_tokens: impl IntoIterator<Item = H160>,
+) -> Result<HashMap<Balance, BigInt>> {
+    let buffers = buffer_tokens
+        .into_iter()
+        .filter(|token| *token != BUY_ETH_ADDRESS)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|token| Balance {
+            owner: settlement_contract,
+            token,
+        });
+    let balances = traders.into_iter().chain(buffers).collect::<Vec<_>>();
+
+    let changes = tracer
+        .trace_balance_changes(settlement_tx, balances.clone())
+        .await?;
+    ensure!(
+        changes.len() == balances.len(),
+        "unexpected number of balance changes"
+    );
+    Ok(balances.into_iter().zip(changes).collect())
+}
+
+/// The outcome of a settlement as observed when simulating it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedOutcome {
+    /// The claimed surplus corrected by the value of the differences between the claimed and the
+    /// observed balance changes of the traders, minus the value of the buffers the settlement
+    /// contract lost beyond the fees it collected (in wei).
+    pub surplus: BigRational,
+    /// Whether the observed balance changes of the traders differ from the claimed ones.
+    pub deviates: bool,
+}
+
+/// Traces the settlement and computes its surplus from the observed balance changes.
+pub async fn simulated_outcome(
+    tracer: &dyn BalanceChangeTracing,
+    settlement_tx: CallRequest,
+    settlement: &Settlement,
+    settlement_contract: H160,
+    prices: &ExternalPrices,
+) -> Result<SimulatedOutcome> {
+    let claimed = claimed_balance_changes(settlement);
+    let observed = observed_balance_changes(
+        tracer,
+        settlement_tx,
+        settlement_contract,
+        claimed.keys().copied(),
+        settlement.clearing_prices().keys().copied(),
+    )
+    .await?;
+
+    Ok(evaluate(
+        settlement,
+        prices,
+        &claimed,
+        &observed,
+        settlement_contract,
+    ))
+}
+
+fn evaluate(
+    settlement: &Settlement,
+    prices: &ExternalPrices,
+    claimed: &BTreeMap<Balance, BigInt>,
+    observed: &HashMap<Balance, BigInt>,
+    settlement_contract: H160,
+) -> SimulatedOutcome {
+    // Tokens without external prices can only be bought by liquidity orders and aren't valued.
+    let value = |token: H160, amount: &BigInt| {
+        prices
+            .try_get_native_amount(token, BigRational::from_integer(amount.clone()))
+            .unwrap_or_else(BigRational::zero)
+    };
+
+    let mut deviates = false;
+    let mut shortfall = BigRational::zero();
+    for (balance, claimed_change) in claimed {
+        let observed_change = observed.get(balance).cloned().unwrap_or_default();
+        if observed_change != *claimed_change {
+            deviates = true;
+            shortfall += value(balance.token, &(claimed_change - observed_change));
+        }
+    }
+
+    // Solvers may intentionally leave tokens in or take tokens from the buffers, so only a net
+    // loss counts.
+    let buffer_change = observed
+        .iter()
+        .filter(|(balance, _)| balance.owner == settlement_contract)
+        .map(|(balance, change)| value(balance.token, change))
+        .fold(BigRational::zero(), |total, value| total + value);
+    let buffer_loss = settlement.total_unscaled_subsidized_fees(prices) - buffer_change;
+    if buffer_loss.is_positive() {
+        shortfall += buffer_loss;
+    }
+
+    SimulatedOutcome {
+        surplus: settlement.total_surplus(prices) - shortfall,
+        deviates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::{OrderTrade, Trade};
+    use maplit::{btreemap, hashmap};
+    use model::order::{Order, OrderData, OrderKind, OrderMetadata};
+
+    fn address(value: u64) -> H160 {
+        H160::from_low_u64_be(value)
+    }
+
+    const SETTLEMENT_CONTRACT: u64 = 42;
+
+    // Trader 1 sells 100 of token 1 (plus a fee of 10) for 100 of token 2 and receives the buy
+    // tokens at address 3.
+    fn settlement() -> Settlement {
+        let order = Order {
+            data: OrderData {
+                sell_token: address(1),
+                buy_token: address(2),
+                sell_amount: 100.into(),
+                buy_amount: 90.into(),
+                fee_amount: 10.into(),
+                kind: OrderKind::Sell,
+                receiver: Some(address(3)),
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                owner: address(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Settlement::with_trades(
+            hashmap! { address(1) => 1.into(), address(2) => 1.into() },
+            vec![OrderTrade {
+                trade: Trade {
+                    order,
+                    executed_amount: 100.into(),
+                    scaled_unsubsidized_fee: 10.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            vec![],
+        )
+    }
+
+    fn prices() -> ExternalPrices {
+        ExternalPrices::new(
+            address(0),
+            hashmap! {
+                address(1) => BigRational::from_integer(1.into()),
+                address(2) => BigRational::from_integer(2.into()),
+            },
+        )
+        .unwrap()
+    }
+
+    fn balance(owner: u64, token: u64) -> Balance {
+        Balance {
+            owner: address(owner),
+            token: address(token),
+        }
+    }
+
+    #[test]
+    fn claims_balance_changes_of_owner_and_receiver() {
+        assert_eq!(
+            claimed_balance_changes(&settlement()),
+            btreemap! {
+                balance(1, 1) => BigInt::from(-110),
+                balance(3, 2) => BigInt::from(100),
+            }
+        );
+    }
+
+    #[test]
+    fn matching_outcome_keeps_claimed_surplus() {
+        let settlement = settlement();
+        let claimed = claimed_balance_changes(&settlement);
+        let observed = hashmap! {
+            balance(1, 1) => BigInt::from(-110),
+            balance(3, 2) => BigInt::from(100),
+            balance(SETTLEMENT_CONTRACT, 1) => BigInt::from(20),
+            balance(SETTLEMENT_CONTRACT, 2) => BigInt::from(-5),
+        };
+
+        let outcome = evaluate(
+            &settlement,
+            &prices(),
+            &claimed,
+            &observed,
+            address(SETTLEMENT_CONTRACT),
+        );
+        assert_eq!(
+            outcome,
+            SimulatedOutcome {
+                surplus: settlement.total_surplus(&prices()),
+                deviates: false,
+            }
+        );
+    }
+
+    #[test]
+    fn deducts_trader_shortfall_and_buffer_loss() {
+        let settlement = settlement();
+        let claimed = claimed_balance_changes(&settlement);
+        // The receiver only gets 99 tokens because of a transfer fee and the settlement contract
+        // pays 7 token 2 (worth 14) out of its buffers while only collecting a fee of 10.
+        let observed = hashmap! {
+            balance(1, 1) => BigInt::from(-110),
+            balance(3, 2) => BigInt::from(99),
+            balance(SETTLEMENT_CONTRACT, 1) => BigInt::from(10),
+            balance(SETTLEMENT_CONTRACT, 2) => BigInt::from(-7),
+        };
+
+        let outcome = evaluate(
+            &settlement,
+            &prices(),
+            &claimed,
+            &observed,
+            address(SETTLEMENT_CONTRACT),
+        );
+        assert!(outcome.deviates);
+        assert_eq!(
+            outcome.surplus,
+            settlement.total_surplus(&prices())
+                - BigRational::from_integer(2.into())
+                - BigRational::from_integer(14.into())
+        );
+    }
+
+    #[tokio::test]
+    async fn traces_trader_and_buffer_balances() {
+        let mut tracer = MockBalanceChangeTracing::new();
+        tracer
+            .expect_trace_balance_changes()
+            .withf(|_, balances| {
+                balances
+                    == &[
+                        balance(1, 1),
+                        balance(3, 2),
+                        balance(SETTLEMENT_CONTRACT, 1),
+                        balance(SETTLEMENT_CONTRACT, 2),
+                    ]
+                    || balances
+                        == &[
+                            balance(1, 1),
+                            balance(3, 2),
+                            balance(SETTLEMENT_CONTRACT, 2),
+                            balance(SETTLEMENT_CONTRACT, 1),
+                        ]
+            })
+            .returning(|_, balances| {
+                Ok(balances
+                    .iter()
+                    .map(|balance| match (balance.owner, balance.token) {
+                        (owner, _) if owner == address(1) => BigInt::from(-110),
+                        (owner, _) if owner == address(3) => BigInt::from(100),
+                        _ => BigInt::from(10),
+                    })
+                    .collect())
+            });
+
+        let settlement = settlement();
+        let outcome = simulated_outcome(
+            &tracer,
+            CallRequest::default(),
+            &settlement,
+            address(SETTLEMENT_CONTRACT),
+            &prices(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            outcome,
+            SimulatedOutcome {
+                surplus: settlement.total_surplus(&prices()),
+                deviates: false,
+            }
+        );
+    }
+
+    #[test]
+    fn detects_transfer_calls() {
+        assert!(is_transfer(&[0xa9, 0x05, 0x9c, 0xbb, 0x00]));
+        assert!(is_transfer(&[0x23, 0xb8, 0x72, 0xdd]));
+        // `approve(address,uint256)`
+        assert!(!is_transfer(&[0x09, 0x5e, 0xa7, 0xb3, 0x00]));
+        assert!(!is_transfer(&[0xa9, 0x05]));
+    }
+}
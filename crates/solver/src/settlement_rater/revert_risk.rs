@@ -0,0 +1,403 @@
+//! Estimation of the probability that a settlement reverts on-chain.
+//!
+//! Simulation only tells us that a settlement succeeds at the current block. By the time the
+//! transaction gets mined the state it depends on may have changed, which happens more often for
+//! some solvers, for settlements with many external interactions, for some tokens and when gas
+//! prices are moving a lot. We keep a bounded history of the outcomes of submissions and estimate
+//! the revert probability of new settlements from it. The history is seeded from the settlements
+//! of recent solver competitions stored in the orderbook and afterwards fed with the outcomes of
+//! our own submissions.
+//!
+//! Every feature is categorical. For each feature we compute the smoothed revert rate of past
+//! settlements with the same value and combine them with the overall revert rate by adding up
+//! their log-odds relative to it (naive Bayes).
+
+use crate::{orderbook::OrderBookApi, settlement::Settlement};
+use contracts::GPv2Settlement;
+use ethcontract::common::abi::Token;
+use futures::{future, stream, StreamExt as _};
+use model::solver_competition::{SolverCompetition, SolverCompetitionId};
+use primitive_types::H160;
+use shared::Web3;
+use std::{collections::VecDeque, sync::Mutex};
+use web3::types::TransactionId;
+
+/// The maximum number of submission outcomes that are kept for estimating revert probabilities.
+const MAX_OUTCOMES: usize = 1000;
+
+/// The number of most recent solver competitions the history gets seeded from.
+const SEED_COMPETITIONS: SolverCompetitionId = 200;
+
+/// The number of most recent gas prices used for computing the gas price volatility.
+const MAX_GAS_PRICES: usize = 20;
+
+/// The revert probability assumed when there is no history.
+const PRIOR_REVERT_PROBABILITY: f64 = 0.01;
+
+/// The weight of the prior in number of observations. Revert rates of rarely seen feature values
+/// stay close to the prior.
+const PRIOR_WEIGHT: f64 = 10.;
+
+/// The properties of a settlement that are used for estimating its revert probability.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SettlementFeatures {
+    pub solver: String,
+    /// The number of encoded interactions between the trades.
+    pub interactions: usize,
+    pub tokens: Vec<H160>,
+}
+
+impl SettlementFeatures {
+    pub fn new(solver: &str, settlement: &Settlement) -> Self {
+        Self {
+            solver: solver.to_string(),
+            interactions: settlement
+                .encoder
+                .execution_plan()
+                .iter()
+                .map(|interaction| interaction.encode().len())
+                .sum(),
+            tokens: settlement.clearing_prices().keys().copied().collect(),
+        }
+    }
+
+    /// Recovers the features of the solution of a solver competition that got submitted with the
+    /// specified transaction input.
+    pub fn from_competition(
+        competition: &SolverCompetition,
+        transaction_input: &[u8],
+    ) -> Option<Self> {
+        let solution = competition.solutions.iter().find(|solution| {
+            !solution.call_data.is_empty() && transaction_input.starts_with(&solution.call_data)
+        })?;
+        Some(Self {
+            solver: solution.solver.clone(),
+            interactions: intra_interaction_count(&solution.call_data)?,
+            tokens: solution.clearing_prices.keys().copied().collect(),
+        })
+    }
+}
+
+/// Decodes the number of interactions between the trades from the call data of a settlement.
+fn intra_interaction_count(call_data: &[u8]) -> Option<usize> {
+    let settle = GPv2Settlement::raw_contract().abi.function("settle").ok()?;
+    let params = settle.decode_input(call_data.get(4..)?).ok()?;
+    match params.get(3)? {
+        Token::FixedArray(interactions) => match interactions.get(1)? {
+            Token::Array(intra) => Some(intra.len()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum InteractionCount {
+    None,
+    One,
+    Few,
+    Many,
+}
+
+impl From<usize> for InteractionCount {
+    fn from(count: usize) -> Self {
+        match count {
+            0 => Self::None,
+            1 => Self::One,
+            2..=4 => Self::Few,
+            _ => Self::Many,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GasPriceVolatility {
+    Low,
+    Medium,
+    High,
+}
+
+impl GasPriceVolatility {
+    /// Categorizes the coefficient of variation of recent gas prices.
+    fn from_gas_prices<'a>(gas_prices: impl ExactSizeIterator<Item = &'a f64> + Clone) -> Self {
+        let count = gas_prices.len() as f64;
+        if count < 2. {
+            return Self::Low;
+        }
+        let mean = gas_prices.clone().sum::<f64>() / count;
+        if mean <= 0. {
+            return Self::Low;
+        }
+        let variance = gas_prices.map(|price| (price - mean).powi(2)).sum::<f64>() / count;
+        match variance.sqrt() / mean {
+            cv if cv < 0.05 => Self::Low,
+            cv if cv < 0.2 => Self::Medium,
+            _ => Self::High,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Outcome {
+    solver: String,
+    interactions: InteractionCount,
+    tokens: Vec<H160>,
+    volatility: GasPriceVolatility,
+    reverted: bool,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    outcomes: VecDeque<Outcome>,
+    gas_prices: VecDeque<f64>,
+}
+
+impl History {
+    fn volatility(&self) -> GasPriceVolatility {
+        GasPriceVolatility::from_gas_prices(self.gas_prices.iter())
+    }
+
+    /// The smoothed revert rate of the outcomes matching the filter.
+    fn revert_rate(&self, prior: f64, filter: impl Fn(&Outcome) -> bool) -> f64 {
+        let (total, reverts) = self.outcomes.iter().filter(|outcome| filter(outcome)).fold(
+            (0., 0.),
+            |(total, reverts), outcome| {
+                (total + 1., reverts + f64::from(u8::from(outcome.reverted)))
+            },
+        );
+        (reverts + prior * PRIOR_WEIGHT) / (total + PRIOR_WEIGHT)
+    }
+}
+
+/// Keeps track of the outcomes of submitted settlements in order to estimate revert probabilities.
+#[derive(Debug, Default)]
+pub struct RevertRiskEstimator {
+    history: Mutex<History>,
+}
+
+impl RevertRiskEstimator {
+    /// Seeds the history with the outcomes of the settlements submitted for the solver
+    /// competitions preceding the specified one, so that estimates don't start from scratch after
+    /// every restart.
+    pub async fn seed_from_solver_competitions(
+        &self,
+        api: &OrderBookApi,
+        web3: &Web3,
+        next_competition: SolverCompetitionId,
+    ) {
+        let ids = next_competition.saturating_sub(SEED_COMPETITIONS).max(0)..next_competition;
+        let outcomes = stream::iter(ids)
+            .map(|id| submission_outcome(api, web3, id))
+            .buffered(10)
+            .filter_map(future::ready)
+            .collect::<Vec<_>>()
+            .await;
+        tracing::debug!(
+            count = outcomes.len(),
+            "seeded revert risk history from solver competitions"
+        );
+        for (gas_price, features, reverted) in outcomes {
+            self.record_gas_price(gas_price);
+            self.record_outcome(&features, reverted);
+        }
+    }
+
+    /// Records the gas price at which settlements are currently being rated.
+    pub fn record_gas_price(&self, gas_price: f64) {
+        let mut history = self.history.lock().unwrap();
+        if history.gas_prices.len() >= MAX_GAS_PRICES {
+            history.gas_prices.pop_front();
+        }
+        history.gas_prices.push_back(gas_price);
+    }
+
+    /// Records whether a submitted settlement with the specified features reverted.
+    pub fn record_outcome(&self, features: &SettlementFeatures, reverted: bool) {
+        let mut history = self.history.lock().unwrap();
+        let outcome = Outcome {
+            solver: features.solver.clone(),
+            interactions: features.interactions.into(),
+            tokens: features.tokens.clone(),
+            volatility: history.volatility(),
+            reverted,
+        };
+        if history.outcomes.len() >= MAX_OUTCOMES {
+            history.outcomes.pop_front();
+        }
+        history.outcomes.push_back(outcome);
+    }
+
+    /// Estimates the probability that a settlement with the specified features reverts when
+    /// submitted now.
+    pub fn revert_probability(&self, features: &SettlementFeatures) -> f64 {
+        let history = self.history.lock().unwrap();
+
+        let base = history.revert_rate(PRIOR_REVERT_PROBABILITY, |_| true);
+        let relative_log_odds = |rate: f64| log_odds(rate) - log_odds(base);
+
+        let interaction_count = InteractionCount::from(features.interactions);
+        let current_volatility = history.volatility();
+        let solver = history.revert_rate(base, |outcome| outcome.solver == features.solver);
+        let interactions =
+            history.revert_rate(base, |outcome| outcome.interactions == interaction_count);
+        let volatility =
+            history.revert_rate(base, |outcome| outcome.volatility == current_volatility);
+        // Only the riskiest token is taken into account so that settlements aren't penalized for
+        // merely touching many tokens.
+        let tokens = features
+            .tokens
+            .iter()
+            .map(|token| {
+                relative_log_odds(
+                    history.revert_rate(base, |outcome| outcome.tokens.contains(token)),
+                )
+            })
+            .reduce(f64::max)
+            .unwrap_or_default();
+
+        logistic(
+            log_odds(base)
+                + relative_log_odds(solver)
+                + relative_log_odds(interactions)
+                + relative_log_odds(volatility)
+                + tokens,
+        )
+    }
+}
+
+/// Fetches the gas price, the features and whether the settlement reverted for the settlement that
+/// got submitted for a solver competition, if any.
+async fn submission_outcome(
+    api: &OrderBookApi,
+    web3: &Web3,
+    id: SolverCompetitionId,
+) -> Option<(f64, SettlementFeatures, bool)> {
+    let competition = api.get_solver_competition(id).await.ok()?;
+    let hash = competition.transaction_hash?;
+    let (transaction, receipt) = futures::try_join!(
+        web3.eth().transaction(TransactionId::Hash(hash)),
+        web3.eth().transaction_receipt(hash),
+    )
+    .map_err(|err| tracing::debug!(?err, ?hash, "failed to fetch settlement transaction"))
+    .ok()?;
+    let features = SettlementFeatures::from_competition(&competition, &transaction?.input.0)?;
+    let reverted = receipt?.status?.is_zero();
+    Some((competition.gas_price, features, reverted))
+}
+
+fn log_odds(probability: f64) -> f64 {
+    (probability / (1. - probability)).ln()
+}
+
+fn logistic(log_odds: f64) -> f64 {
+    1. / (1. + (-log_odds).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement_simulation::call_data;
+    use maplit::hashmap;
+    use model::solver_competition::SolverSettlement;
+    use primitive_types::U256;
+
+    fn features(solver: &str, interactions: usize, tokens: &[u64]) -> SettlementFeatures {
+        SettlementFeatures {
+            solver: solver.to_string(),
+            interactions,
+            tokens: tokens.iter().copied().map(H160::from_low_u64_be).collect(),
+        }
+    }
+
+    #[test]
+    fn uses_prior_without_history() {
+        let estimator = RevertRiskEstimator::default();
+        let probability = estimator.revert_probability(&features("a", 3, &[1, 2]));
+        assert!((probability - PRIOR_REVERT_PROBABILITY).abs() < 1e-9);
+    }
+
+    #[test]
+    fn riskier_features_increase_revert_probability() {
+        let estimator = RevertRiskEstimator::default();
+        for _ in 0..50 {
+            estimator.record_outcome(&features("safe", 0, &[1]), false);
+            estimator.record_outcome(&features("risky", 5, &[2]), true);
+            estimator.record_outcome(&features("risky", 0, &[1]), false);
+        }
+
+        let safe = estimator.revert_probability(&features("safe", 0, &[1]));
+        let risky_solver = estimator.revert_probability(&features("risky", 0, &[1]));
+        let risky_interactions = estimator.revert_probability(&features("safe", 5, &[1]));
+        let risky_token = estimator.revert_probability(&features("safe", 0, &[1, 2]));
+        let unknown_solver = estimator.revert_probability(&features("unknown", 0, &[1]));
+
+        assert!(safe < risky_solver);
+        assert!(safe < risky_interactions);
+        assert!(safe < risky_token);
+        assert!(safe < unknown_solver);
+        assert!((0. ..=1.).contains(&risky_token));
+    }
+
+    #[test]
+    fn recovers_features_of_submitted_solution() {
+        let mut settlement = Settlement::new(hashmap! {
+            H160::from_low_u64_be(1) => 1.into(),
+            H160::from_low_u64_be(2) => 2.into(),
+        });
+        settlement.encoder.append_to_execution_plan((
+            H160::zero(),
+            U256::zero(),
+            ethcontract::Bytes(Vec::new()),
+        ));
+        let call_data = call_data(settlement.clone().into());
+        let competition = SolverCompetition {
+            solutions: vec![
+                SolverSettlement {
+                    solver: "a".to_string(),
+                    call_data: vec![1, 2, 3],
+                    ..Default::default()
+                },
+                SolverSettlement {
+                    solver: "b".to_string(),
+                    clearing_prices: settlement
+                        .clearing_prices()
+                        .iter()
+                        .map(|(token, price)| (*token, *price))
+                        .collect(),
+                    call_data: call_data.clone(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Submitted transactions may carry additional data after the settlement call data.
+        let input = [call_data, vec![0xff]].concat();
+        let recovered = SettlementFeatures::from_competition(&competition, &input).unwrap();
+        assert_eq!(recovered, features("b", 1, &[1, 2]));
+        assert_eq!(recovered, SettlementFeatures::new("b", &settlement));
+    }
+
+    #[test]
+    fn categorizes_gas_price_volatility() {
+        let volatility = |prices: &[f64]| GasPriceVolatility::from_gas_prices(prices.iter());
+        assert_eq!(volatility(&[]), GasPriceVolatility::Low);
+        assert_eq!(volatility(&[10., 10.1, 9.9]), GasPriceVolatility::Low);
+        assert_eq!(volatility(&[10., 11., 9.]), GasPriceVolatility::Medium);
+        assert_eq!(volatility(&[10., 20., 5.]), GasPriceVolatility::High);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let estimator = RevertRiskEstimator::default();
+        for _ in 0..MAX_OUTCOMES + 10 {
+            estimator.record_outcome(&features("a", 0, &[]), true);
+        }
+        for _ in 0..MAX_GAS_PRICES + 10 {
+            estimator.record_gas_price(1.);
+        }
+        let history = estimator.history.lock().unwrap();
+        assert_eq!(history.outcomes.len(), MAX_OUTCOMES);
+        assert_eq!(history.gas_prices.len(), MAX_GAS_PRICES);
+    }
+}
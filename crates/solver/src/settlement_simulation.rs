@@ -18,7 +18,7 @@ use reqwest::{
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use shared::Web3;
-use web3::types::{AccessList, BlockId};
+use web3::types::{AccessList, BlockId, BlockNumber, CallRequest};
 
 const SIMULATE_BATCH_SIZE: usize = 10;
 
@@ -38,11 +38,14 @@ const SIMULATE_BATCH_SIZE: usize = 10;
 /// Next [block 12998226](https://etherscan.io/block/12998226) has base fee of `48.771904644` which is an increase of ~12.5%.
 const MAX_BASE_GAS_FEE_INCREASE: f64 = 1.125;
 
-pub async fn simulate_and_estimate_gas_at_current_block(
+/// Estimates the gas used by the settlements on top of the specified block. Estimating on top of
+/// past blocks requires an archive node.
+pub async fn simulate_and_estimate_gas(
     settlements: impl Iterator<Item = (Account, Settlement, Option<AccessList>)>,
     contract: &GPv2Settlement,
     web3: &Web3,
     gas_price: GasPrice1559,
+    block: BlockNumber,
 ) -> Result<Vec<Result<U256, ExecutionError>>> {
     // Collect into Vec to not rely on Itertools::chunk which would make this future !Send.
     let settlements: Vec<_> = settlements.collect();
@@ -56,24 +59,30 @@ pub async fn simulate_and_estimate_gas_at_current_block(
     let web3 = web3::Web3::new(shared::transport::buffered::Buffered::new(
         web3.transport().clone(),
     ));
-    let contract_with_buffered_transport = GPv2Settlement::at(&web3, contract.address());
+    // See `settle_method` for why the gas price gets bumped.
+    let gas_price = gas_price.bump(MAX_BASE_GAS_FEE_INCREASE);
     let mut results = Vec::new();
     for chunk in settlements.chunks(SIMULATE_BATCH_SIZE) {
         let calls = chunk
             .iter()
             .map(|(account, settlement, access_list)| {
-                let tx = settle_method(
-                    gas_price,
-                    &contract_with_buffered_transport,
-                    settlement.clone(),
-                    account.clone(),
-                )
-                .tx;
-                let tx = match access_list {
-                    Some(access_list) => tx.access_list(access_list.clone()),
-                    None => tx,
+                let tx =
+                    settle_method_builder(contract, settlement.clone().into(), account.clone()).tx;
+                let request = CallRequest {
+                    from: Some(account.address()),
+                    to: Some(contract.address()),
+                    data: tx.data,
+                    transaction_type: Some(2.into()),
+                    access_list: access_list.clone(),
+                    max_fee_per_gas: Some(U256::from_f64_lossy(gas_price.max_fee_per_gas)),
+                    max_priority_fee_per_gas: Some(U256::from_f64_lossy(
+                        gas_price.max_priority_fee_per_gas,
+                    )),
+                    ..Default::default()
                 };
-                tx.estimate_gas()
+                web3.eth()
+                    .estimate_gas(request, Some(block))
+                    .map(|result| result.map_err(ExecutionError::from))
             })
             .collect::<Vec<_>>();
         let chuck_results = futures::future::join_all(calls).await;
@@ -380,21 +389,23 @@ mod tests {
         .await;
         let _ = dbg!(result);
 
-        let result = simulate_and_estimate_gas_at_current_block(
+        let result = simulate_and_estimate_gas(
             settlements.iter().cloned(),
             &contract,
             &web3,
             Default::default(),
+            BlockNumber::Latest,
         )
         .await
         .unwrap();
         let _ = dbg!(result);
 
-        let result = simulate_and_estimate_gas_at_current_block(
+        let result = simulate_and_estimate_gas(
             std::iter::empty(),
             &contract,
             &web3,
             Default::default(),
+            BlockNumber::Latest,
         )
         .await
         .unwrap();
@@ -766,11 +777,12 @@ mod tests {
             (account.clone(), Settlement::new(Default::default()), None);
             SIMULATE_BATCH_SIZE + 2
         ];
-        let result = simulate_and_estimate_gas_at_current_block(
+        let result = simulate_and_estimate_gas(
             settlements.iter().cloned(),
             &contract,
             &web3,
             GasPrice1559::default(),
+            BlockNumber::Latest,
         )
         .await
         .unwrap();
@@ -34,7 +34,7 @@ use std::{
     fmt,
     time::{Duration, Instant},
 };
-use web3::types::{AccessList, TransactionReceipt, U64};
+use web3::types::{AccessList, BlockNumber, TransactionReceipt, U64};
 
 /// Minimal gas price replacement factor
 const GAS_PRICE_BUMP: f64 = 1.125;
@@ -705,19 +705,19 @@ mod tests {
         );
 
         let settlement = Settlement::new(Default::default());
-        let gas_estimate =
-            crate::settlement_simulation::simulate_and_estimate_gas_at_current_block(
-                std::iter::once((account.clone(), settlement.clone(), None)),
-                &contract,
-                &web3,
-                Default::default(),
-            )
-            .await
-            .unwrap()
-            .into_iter()
-            .next()
-            .unwrap()
-            .unwrap();
+        let gas_estimate = crate::settlement_simulation::simulate_and_estimate_gas(
+            std::iter::once((account.clone(), settlement.clone(), None)),
+            &contract,
+            &web3,
+            Default::default(),
+            BlockNumber::Latest,
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
 
         let submitted_transactions = Default::default();
 
@@ -0,0 +1,213 @@
+//! Setup that is shared by the solver binaries.
+
+use crate::{
+    arguments::Arguments,
+    liquidity_collector::{self, LiquiditySources},
+    metrics::SolverMetrics,
+    solver::{SolverType, Solvers},
+};
+use anyhow::{bail, ensure, Context as _, Result};
+use contracts::{BalancerV2Vault, GPv2Settlement, WETH9};
+use ethcontract::Account;
+use primitive_types::U256;
+use reqwest::Client;
+use shared::{
+    baseline_solver::BaseTokens,
+    current_block::{current_block_stream, CurrentBlockStream},
+    network::network_name,
+    recent_block_cache::CacheConfig,
+    sources::{self, BaselineSource},
+    token_info::{CachedTokenInfoFetcher, TokenInfoFetcher, TokenInfoFetching},
+    transport::http::HttpTransport,
+    zeroex_api::{DefaultZeroExApi, ZeroExApi},
+    Web3, Web3Transport,
+};
+use std::sync::Arc;
+
+/// The components every solver binary needs, created from the command line arguments.
+pub struct Components {
+    pub client: Client,
+    pub web3: Web3,
+    pub chain_id: u64,
+    pub network_id: String,
+    pub network_name: String,
+    pub settlement_contract: GPv2Settlement,
+    pub vault_contract: Option<BalancerV2Vault>,
+    pub native_token_contract: WETH9,
+    pub base_tokens: Arc<BaseTokens>,
+    pub token_info_fetcher: Arc<dyn TokenInfoFetching>,
+    pub current_block_stream: CurrentBlockStream,
+    pub cache_config: CacheConfig,
+    pub baseline_sources: Vec<BaselineSource>,
+    pub zeroex_api: Arc<dyn ZeroExApi>,
+}
+
+impl Components {
+    pub async fn new(args: &Arguments) -> Result<Self> {
+        let client = shared::http_client(args.shared.http_timeout);
+        let transport = Web3Transport::new(HttpTransport::new(
+            client.clone(),
+            args.shared.node_url.clone(),
+            "base".to_string(),
+        ));
+        let web3 = web3::Web3::new(transport);
+        let chain_id = web3
+            .eth()
+            .chain_id()
+            .await
+            .context("Could not get chainId")?
+            .as_u64();
+        let network_id = web3
+            .net()
+            .version()
+            .await
+            .context("failed to get network id")?;
+        let network_name = network_name(&network_id, chain_id).to_string();
+        let settlement_contract = crate::get_settlement_contract(&web3)
+            .await
+            .context("couldn't load deployed settlement")?;
+        let vault_contract = BalancerV2Vault::deployed(&web3).await.ok();
+        let native_token_contract = WETH9::deployed(&web3)
+            .await
+            .context("couldn't load deployed native token")?;
+        let base_tokens = Arc::new(BaseTokens::new(
+            native_token_contract.address(),
+            &args.shared.base_tokens,
+        ));
+        let token_info_fetcher =
+            Arc::new(CachedTokenInfoFetcher::new(Box::new(TokenInfoFetcher {
+                web3: web3.clone(),
+            })));
+        let current_block_stream =
+            current_block_stream(web3.clone(), args.shared.block_stream_poll_interval_seconds)
+                .await?;
+        let cache_config = CacheConfig {
+            number_of_blocks_to_cache: args.shared.pool_cache_blocks,
+            // 0 because we don't make use of the auto update functionality as we always fetch
+            // for specific blocks
+            number_of_entries_to_auto_update: 0,
+            maximum_recent_block_age: args.shared.pool_cache_maximum_recent_block_age,
+            max_retries: args.shared.pool_cache_maximum_retries,
+            delay_between_retries: args.shared.pool_cache_delay_between_retries_seconds,
+        };
+        let baseline_sources = match &args.shared.baseline_sources {
+            Some(baseline_sources) => baseline_sources.clone(),
+            None => sources::defaults_for_chain(chain_id)
+                .context("failed to get default baseline sources")?,
+        };
+        let zeroex_api = Arc::new(DefaultZeroExApi::new(
+            args.shared
+                .zeroex_url
+                .as_deref()
+                .unwrap_or(DefaultZeroExApi::DEFAULT_URL),
+            args.shared.zeroex_api_key.clone(),
+            client.clone(),
+        )?);
+
+        Ok(Self {
+            client,
+            web3,
+            chain_id,
+            network_id,
+            network_name,
+            settlement_contract,
+            vault_contract,
+            native_token_contract,
+            base_tokens,
+            token_info_fetcher,
+            current_block_stream,
+            cache_config,
+            baseline_sources,
+            zeroex_api,
+        })
+    }
+
+    /// Creates the configured solvers along with their accounts.
+    pub fn create_solvers(
+        &self,
+        args: &Arguments,
+        solver_metrics: Arc<dyn SolverMetrics>,
+    ) -> Result<Solvers> {
+        crate::solver::create(
+            self.web3.clone(),
+            solver_accounts(args, self.chain_id)?,
+            self.base_tokens.clone(),
+            self.native_token_contract.address(),
+            args.mip_solver_url.clone(),
+            args.cow_dex_ag_solver_url.clone(),
+            args.quasimodo_solver_url.clone(),
+            args.balancer_sor_url.clone(),
+            &self.settlement_contract,
+            self.vault_contract.as_ref(),
+            self.token_info_fetcher.clone(),
+            self.network_name.clone(),
+            self.chain_id,
+            args.shared.disabled_one_inch_protocols.clone(),
+            args.paraswap_slippage_bps,
+            args.shared.disabled_paraswap_dexs.clone(),
+            args.shared.paraswap_partner.clone(),
+            self.client.clone(),
+            solver_metrics,
+            self.zeroex_api.clone(),
+            args.zeroex_slippage_bps,
+            args.shared.disabled_zeroex_sources.clone(),
+            args.oneinch_slippage_bps,
+            args.shared.quasimodo_uses_internal_buffers,
+            args.shared.mip_uses_internal_buffers,
+            args.shared.one_inch_url.clone(),
+            args.external_solvers.clone().unwrap_or_default(),
+            args.oneinch_max_slippage_in_eth
+                .map(|float| U256::from_f64_lossy(float * 1e18)),
+            args.ring_trade_solver_close_residuals,
+            args.shared.baseline_max_split_paths,
+        )
+    }
+
+    /// Creates the liquidity collector for the specified baseline sources.
+    pub async fn build_liquidity_sources(
+        &self,
+        args: &Arguments,
+        baseline_sources: &[BaselineSource],
+    ) -> Result<LiquiditySources> {
+        liquidity_collector::build_liquidity_sources(
+            &self.web3,
+            &self.client,
+            self.chain_id,
+            baseline_sources,
+            self.cache_config,
+            self.current_block_stream.clone(),
+            self.token_info_fetcher.clone(),
+            self.base_tokens.clone(),
+            &self.settlement_contract,
+            self.zeroex_api.clone(),
+            args.shared.balancer_factories.clone(),
+            args.shared.balancer_pool_deny_list.clone(),
+            args.shared.uniswap_v3_checkpoint_path.clone(),
+        )
+        .await
+    }
+}
+
+fn solver_accounts(args: &Arguments, chain_id: u64) -> Result<Vec<(Account, SolverType)>> {
+    if let Some(solver_accounts) = &args.solver_accounts {
+        ensure!(
+            solver_accounts.len() == args.solvers.len(),
+            "number of solvers ({}) does not match the number of accounts ({})",
+            args.solvers.len(),
+            solver_accounts.len()
+        );
+        Ok(solver_accounts
+            .iter()
+            .map(|account_arg| account_arg.clone().into_account(chain_id))
+            .zip(args.solvers.iter().copied())
+            .collect())
+    } else if let Some(account_arg) = &args.solver_account {
+        Ok(
+            std::iter::repeat(account_arg.clone().into_account(chain_id))
+                .zip(args.solvers.iter().copied())
+                .collect(),
+        )
+    } else {
+        bail!("either SOLVER_ACCOUNTS or SOLVER_ACCOUNT must be set")
+    }
+}
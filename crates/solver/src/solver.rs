@@ -18,8 +18,9 @@ use num::BigRational;
 use oneinch_solver::OneInchSolver;
 use paraswap_solver::ParaswapSolver;
 use reqwest::{Client, Url};
+use ring_trade_solver::RingTradeSolver;
 use shared::balancer_sor_api::DefaultBalancerSorApi;
-use shared::http_solver::{DefaultHttpSolverApi, SolverConfig};
+use shared::http_solver::{model::InstanceVersion, DefaultHttpSolverApi, SolverConfig};
 use shared::zeroex_api::ZeroExApi;
 use shared::{
     baseline_solver::BaseTokens, conversions::U256Ext, token_info::TokenInfoFetching, Web3,
@@ -39,6 +40,7 @@ pub mod http_solver;
 mod naive_solver;
 mod oneinch_solver;
 mod paraswap_solver;
+mod ring_trade_solver;
 mod single_order_solver;
 pub mod uni_v3_router_solver;
 mod zeroex_solver;
@@ -154,6 +156,7 @@ pub enum SolverType {
     ZeroEx,
     Quasimodo,
     BalancerSor,
+    RingTrade,
 }
 
 #[derive(Debug, Clone)]
@@ -189,11 +192,12 @@ impl FromStr for SolverAccountArg {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ExternalSolverArg {
     pub name: String,
     pub url: Url,
     pub account: SolverAccountArg,
+    pub instance_version: InstanceVersion,
 }
 
 impl FromStr for ExternalSolverArg {
@@ -204,10 +208,16 @@ impl FromStr for ExternalSolverArg {
         let name = parts.next().ok_or_else(|| anyhow!("missing name"))?;
         let url = parts.next().ok_or_else(|| anyhow!("missing url"))?;
         let account = parts.next().ok_or_else(|| anyhow!("missing account"))?;
+        let instance_version = parts
+            .next()
+            .map(|version| version.parse().context("parse instance version"))
+            .transpose()?
+            .unwrap_or_default();
         Ok(Self {
             name: name.to_string(),
             url: url.parse().context("parse url")?,
             account: account.parse().context("parse account")?,
+            instance_version,
         })
     }
 }
@@ -242,6 +252,8 @@ pub fn create(
     one_inch_url: Url,
     external_solvers: Vec<ExternalSolverArg>,
     oneinch_max_slippage_in_wei: Option<U256>,
+    ring_trade_solver_close_residuals: bool,
+    baseline_max_split_paths: usize,
 ) -> Result<Solvers> {
     // Tiny helper function to help out with type inference. Otherwise, all
     // `Box::new(...)` expressions would have to be cast `as Box<dyn Solver>`.
@@ -284,9 +296,11 @@ pub fn create(
         .map(|(account, solver_type)| {
             let solver = match solver_type {
                 SolverType::Naive => Ok(shared(NaiveSolver::new(account))),
-                SolverType::Baseline => {
-                    Ok(shared(BaselineSolver::new(account, base_tokens.clone())))
-                }
+                SolverType::Baseline => Ok(shared(BaselineSolver::new(
+                    account,
+                    base_tokens.clone(),
+                    baseline_max_split_paths,
+                ))),
                 SolverType::Mip => Ok(shared(create_http_solver(
                     account,
                     mip_solver_url.clone(),
@@ -373,6 +387,11 @@ pub fn create(
                     ),
                     solver_metrics.clone(),
                 ))),
+                SolverType::RingTrade => Ok(shared(RingTradeSolver::new(
+                    account,
+                    base_tokens.clone(),
+                    ring_trade_solver_close_residuals,
+                ))),
             };
 
             if let Ok(solver) = &solver {
@@ -393,6 +412,7 @@ pub fn create(
             solver.name,
             SolverConfig {
                 use_internal_buffers: Some(mip_uses_internal_buffers),
+                instance_version: solver.instance_version,
                 ..Default::default()
             },
         ))
@@ -407,6 +427,27 @@ pub fn naive_solver(account: Account) -> Arc<dyn Solver> {
     Arc::new(NaiveSolver::new(account))
 }
 
+/// Creates solvers that don't depend on any external services, so that they can solve auctions
+/// offline. Fails for all other solver types.
+pub fn create_offline(
+    solvers: Vec<(Account, SolverType)>,
+    base_tokens: Arc<BaseTokens>,
+    baseline_max_split_paths: usize,
+) -> Result<Solvers> {
+    solvers
+        .into_iter()
+        .map(|(account, solver_type)| match solver_type {
+            SolverType::Naive => Ok(naive_solver(account)),
+            SolverType::Baseline => Ok(Arc::new(BaselineSolver::new(
+                account,
+                base_tokens.clone(),
+                baseline_max_split_paths,
+            )) as Arc<dyn Solver>),
+            _ => Err(anyhow!("{:?} solver can't run offline", solver_type)),
+        })
+        .collect()
+}
+
 /// A solver that remove limit order below a certain threshold and
 /// passes the remaining liquidity onto an inner solver implementation.
 pub struct SellVolumeFilteringSolver {
@@ -603,5 +644,10 @@ mod tests {
             parsed.account,
             SolverAccountArg::PrivateKey(PrivateKey::from_raw([0x42; 32]).unwrap())
         );
+        assert_eq!(parsed.instance_version, InstanceVersion::V1);
+
+        let parsed = ExternalSolverArg::from_str(&format!("{}|v2", arg)).unwrap();
+        assert_eq!(parsed.instance_version, InstanceVersion::V2);
+        assert!(ExternalSolverArg::from_str(&format!("{}|v3", arg)).is_err());
     }
 }
@@ -1,6 +1,7 @@
 use crate::{
     liquidity::{
-        token_pairs, AmmOrderExecution, ConstantProductOrder, LimitOrder, Liquidity,
+        token_pairs, AmmOrderExecution, ComposableStablePoolOrder, ConcentratedLiquidity,
+        ConstantProductOrder, CurvePoolOrder, LimitOrder, LinearPoolOrder, Liquidity,
         WeightedProductOrder,
     },
     settlement::Settlement,
@@ -11,7 +12,10 @@ use ethcontract::{Account, H160, U256};
 use maplit::hashmap;
 use model::TokenPair;
 use shared::{
-    baseline_solver::{estimate_buy_amount, estimate_sell_amount, BaseTokens, BaselineSolvable},
+    baseline_solver::{
+        estimate_sell_amount, estimate_split_buy_amount, estimate_split_sell_amount, BaseTokens,
+        BaselineSolvable, SplitEstimate,
+    },
     sources::{balancer_v2::swap::WeightedPoolRef, uniswap_v2::pool_fetching::Pool},
 };
 use std::{collections::HashMap, sync::Arc};
@@ -19,6 +23,7 @@ use std::{collections::HashMap, sync::Arc};
 pub struct BaselineSolver {
     account: Account,
     base_tokens: Arc<BaseTokens>,
+    max_split_paths: usize,
 }
 
 #[async_trait::async_trait]
@@ -44,15 +49,22 @@ impl Solver for BaselineSolver {
 /// A type representing all possible AMM orders that are considered as on-chain
 /// liquidity by the baseline solver.
 #[derive(Debug, Clone)]
-struct Amm {
+pub(super) struct Amm {
     tokens: TokenPair,
     order: AmmOrder,
+    /// The index of the liquidity this AMM was created from, which identifies its pool across
+    /// all of the token pairs it is listed under.
+    pool: usize,
 }
 
 #[derive(Debug, Clone)]
 enum AmmOrder {
     ConstantProduct(ConstantProductOrder),
     WeightedProduct(WeightedProductOrder),
+    Concentrated(ConcentratedLiquidity),
+    Curve(CurvePoolOrder),
+    ComposableStable(ComposableStablePoolOrder),
+    Linear(LinearPoolOrder),
 }
 
 impl BaselineSolvable for ConstantProductOrder {
@@ -83,11 +95,71 @@ impl BaselineSolvable for WeightedProductOrder {
     }
 }
 
+impl BaselineSolvable for ConcentratedLiquidity {
+    fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_out(out_token, input)
+    }
+
+    fn get_amount_in(&self, in_token: H160, output: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_in(in_token, output)
+    }
+
+    fn gas_cost(&self) -> usize {
+        self.pool.gas_cost()
+    }
+}
+
+impl BaselineSolvable for CurvePoolOrder {
+    fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_out(out_token, input)
+    }
+
+    fn get_amount_in(&self, in_token: H160, output: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_in(in_token, output)
+    }
+
+    fn gas_cost(&self) -> usize {
+        self.pool.gas_cost()
+    }
+}
+
+impl BaselineSolvable for ComposableStablePoolOrder {
+    fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_out(out_token, input)
+    }
+
+    fn get_amount_in(&self, in_token: H160, output: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_in(in_token, output)
+    }
+
+    fn gas_cost(&self) -> usize {
+        self.pool.gas_cost()
+    }
+}
+
+impl BaselineSolvable for LinearPoolOrder {
+    fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_out(out_token, input)
+    }
+
+    fn get_amount_in(&self, in_token: H160, output: (U256, H160)) -> Option<U256> {
+        self.pool.get_amount_in(in_token, output)
+    }
+
+    fn gas_cost(&self) -> usize {
+        self.pool.gas_cost()
+    }
+}
+
 impl BaselineSolvable for Amm {
     fn get_amount_out(&self, out_token: H160, input: (U256, H160)) -> Option<U256> {
         match &self.order {
             AmmOrder::ConstantProduct(order) => order.get_amount_out(out_token, input),
             AmmOrder::WeightedProduct(order) => order.get_amount_out(out_token, input),
+            AmmOrder::Concentrated(order) => order.get_amount_out(out_token, input),
+            AmmOrder::Curve(order) => order.get_amount_out(out_token, input),
+            AmmOrder::ComposableStable(order) => order.get_amount_out(out_token, input),
+            AmmOrder::Linear(order) => order.get_amount_out(out_token, input),
         }
     }
 
@@ -95,6 +167,10 @@ impl BaselineSolvable for Amm {
         match &self.order {
             AmmOrder::ConstantProduct(order) => order.get_amount_in(in_token, output),
             AmmOrder::WeightedProduct(order) => order.get_amount_in(in_token, output),
+            AmmOrder::Concentrated(order) => order.get_amount_in(in_token, output),
+            AmmOrder::Curve(order) => order.get_amount_in(in_token, output),
+            AmmOrder::ComposableStable(order) => order.get_amount_in(in_token, output),
+            AmmOrder::Linear(order) => order.get_amount_in(in_token, output),
         }
     }
 
@@ -102,15 +178,24 @@ impl BaselineSolvable for Amm {
         match &self.order {
             AmmOrder::ConstantProduct(order) => order.gas_cost(),
             AmmOrder::WeightedProduct(order) => order.gas_cost(),
+            AmmOrder::Concentrated(order) => order.gas_cost(),
+            AmmOrder::Curve(order) => order.gas_cost(),
+            AmmOrder::ComposableStable(order) => order.gas_cost(),
+            AmmOrder::Linear(order) => order.gas_cost(),
         }
     }
+
+    fn pool_id(&self) -> usize {
+        self.pool
+    }
 }
 
 impl BaselineSolver {
-    pub fn new(account: Account, base_tokens: Arc<BaseTokens>) -> Self {
+    pub fn new(account: Account, base_tokens: Arc<BaseTokens>, max_split_paths: usize) -> Self {
         Self {
             account,
             base_tokens,
+            max_split_paths,
         }
     }
 
@@ -121,35 +206,7 @@ impl BaselineSolver {
     ) -> Vec<Settlement> {
         limit_orders.retain(|order| !order.is_liquidity_order);
         let user_orders = limit_orders;
-        let amm_map =
-            liquidity
-                .into_iter()
-                .fold(HashMap::<_, Vec<_>>::new(), |mut amm_map, liquidity| {
-                    match liquidity {
-                        Liquidity::ConstantProduct(order) => {
-                            amm_map.entry(order.tokens).or_default().push(Amm {
-                                tokens: order.tokens,
-                                order: AmmOrder::ConstantProduct(order),
-                            });
-                        }
-                        Liquidity::BalancerWeighted(order) => {
-                            for tokens in token_pairs(&order.reserves) {
-                                amm_map.entry(tokens).or_default().push(Amm {
-                                    tokens,
-                                    order: AmmOrder::WeightedProduct(order.clone()),
-                                });
-                            }
-                        }
-                        Liquidity::BalancerStable(_order) => {
-                            // TODO - https://github.com/cowprotocol/services/issues/80
-                            tracing::debug!("Excluded stable pool from baseline solving.")
-                        }
-                        Liquidity::LimitOrder(_) => {}
-                        Liquidity::Concentrated(_) => {} // not being implemented right now since baseline solver
-                                                         // is not winning anyway
-                    }
-                    amm_map
-                });
+        let amm_map = amm_map(liquidity);
 
         // We assume that individual settlements do not move the amm pools significantly when
         // returning multiple settlements.
@@ -157,9 +214,12 @@ impl BaselineSolver {
 
         // Return a solution for the first settle-able user order
         for order in user_orders {
-            let solution = match self.settle_order(&order, &amm_map) {
-                Some(solution) => solution,
-                None => continue,
+            let (order, solution) = match self.settle_order(&order, &amm_map) {
+                Some(solution) => (order, solution),
+                None => match self.settle_order_partially(&order, &amm_map) {
+                    Some(partial) => partial,
+                    None => continue,
+                },
             };
 
             match solution.into_settlement(&order) {
@@ -180,53 +240,224 @@ impl BaselineSolver {
     ) -> Option<Solution> {
         let candidates = self
             .base_tokens
-            .path_candidates(order.sell_token, order.buy_token);
+            .path_candidates(order.sell_token, order.buy_token)
+            .into_iter()
+            .collect::<Vec<_>>();
 
-        let (path, executed_sell_amount, executed_buy_amount) = match order.kind {
+        let (routes, executed_sell_amount, executed_buy_amount) = match order.kind {
             model::order::OrderKind::Buy => {
-                let best = candidates
-                    .iter()
-                    .filter_map(|path| estimate_sell_amount(order.buy_amount, path, amms))
-                    .filter(|estimate| estimate.value <= order.sell_amount)
-                    // For buy orders we find the best path starting at the buy token ending at the
-                    // sell token. When we turn this into a settlement however we need to go from
-                    // the sell token to the buy token. This reversing of the direction can fail or
-                    // yield different amounts as explained in the BaselineSolvable trait.
-                    .filter(|estimate| {
-                        matches!(
-                            traverse_path_forward(
-                                order.sell_token,
-                                estimate.value,
-                                &estimate.path,
-                            ), Some(amount) if amount >= order.buy_amount
-                        )
-                    })
-                    .min_by_key(|estimate| estimate.value)?;
-                (best.path, best.value, order.buy_amount)
+                if let Some(split) = self.split_buy_order(order, &candidates, amms) {
+                    let routes = split
+                        .routes
+                        .into_iter()
+                        .map(|route| (route.estimate.value, route.estimate.path))
+                        .collect();
+                    (routes, split.value, order.buy_amount)
+                } else {
+                    let best = candidates
+                        .iter()
+                        .filter_map(|path| estimate_sell_amount(order.buy_amount, path, amms))
+                        .filter(|estimate| estimate.value <= order.sell_amount)
+                        // For buy orders we find the best path starting at the buy token ending at
+                        // the sell token. When we turn this into a settlement however we need to
+                        // go from the sell token to the buy token. This reversing of the direction
+                        // can fail or yield different amounts as explained in the
+                        // BaselineSolvable trait.
+                        .filter(|estimate| {
+                            matches!(
+                                traverse_path_forward(
+                                    order.sell_token,
+                                    estimate.value,
+                                    &estimate.path,
+                                ), Some(amount) if amount >= order.buy_amount
+                            )
+                        })
+                        .min_by_key(|estimate| estimate.value)?;
+                    (vec![(best.value, best.path)], best.value, order.buy_amount)
+                }
             }
             model::order::OrderKind::Sell => {
-                let best = candidates
-                    .iter()
-                    .filter_map(|path| estimate_buy_amount(order.sell_amount, path, amms))
-                    .filter(|estimate| estimate.value >= order.buy_amount)
-                    .max_by_key(|estimate| estimate.value)?;
-                (best.path, order.sell_amount, best.value)
+                // Limiting the split to a single path yields the best single path.
+                let best = estimate_split_buy_amount(
+                    order.sell_amount,
+                    &candidates,
+                    amms,
+                    self.max_split_paths,
+                )
+                .filter(|estimate| estimate.value >= order.buy_amount)?;
+                let routes = best
+                    .routes
+                    .into_iter()
+                    .map(|route| (route.amount, route.estimate.path))
+                    .collect();
+                (routes, order.sell_amount, best.value)
             }
         };
         Some(Solution {
-            path: path.into_iter().cloned().collect(),
+            routes: routes
+                .into_iter()
+                .map(|(sell_amount, path): (U256, Vec<&Amm>)| {
+                    (sell_amount, path.into_iter().cloned().collect())
+                })
+                .collect(),
             executed_sell_amount,
             executed_buy_amount,
         })
     }
 
+    /// Settles the largest possible part of a partially fillable order that
+    /// can't be filled completely with the available AMMs without violating
+    /// its limit price. Returns the partially filled order along with its
+    /// solution.
+    fn settle_order_partially(
+        &self,
+        order: &LimitOrder,
+        amms: &HashMap<TokenPair, Vec<Amm>>,
+    ) -> Option<(LimitOrder, Solution)> {
+        let settle_partial_fill = |amount| {
+            let partial_order = order.partial_fill(amount)?;
+            let solution = self.settle_order(&partial_order, amms)?;
+            Some((partial_order, solution))
+        };
+        let amount = order.max_partial_execution(|amount| settle_partial_fill(amount).is_some())?;
+        settle_partial_fill(amount)
+    }
+
+    /// Splits a buy order across multiple paths if that is possible and sells less than the best
+    /// single path. Each route is checked to yield its part of the buy amount when traversed in
+    /// the settlement's direction.
+    fn split_buy_order<'a>(
+        &self,
+        order: &LimitOrder,
+        candidates: &[Vec<H160>],
+        amms: &'a HashMap<TokenPair, Vec<Amm>>,
+    ) -> Option<SplitEstimate<'a, Amm>> {
+        if self.max_split_paths < 2 {
+            return None;
+        }
+        let split =
+            estimate_split_sell_amount(order.buy_amount, candidates, amms, self.max_split_paths)?;
+        let is_valid = split.routes.len() > 1
+            && split.value <= order.sell_amount
+            && split.routes.iter().all(|route| {
+                matches!(
+                    traverse_path_forward(
+                        order.sell_token,
+                        route.estimate.value,
+                        &route.estimate.path,
+                    ), Some(amount) if amount >= route.amount
+                )
+            });
+        if is_valid {
+            Some(split)
+        } else {
+            None
+        }
+    }
+
     #[cfg(test)]
     fn must_solve(&self, orders: Vec<LimitOrder>, liquidity: Vec<Liquidity>) -> Settlement {
         self.solve_(orders, liquidity).into_iter().next().unwrap()
     }
 }
 
-fn traverse_path_forward(
+/// Indexes the AMMs the baseline solver can route through by token pair.
+pub(super) fn amm_map(liquidity: Vec<Liquidity>) -> HashMap<TokenPair, Vec<Amm>> {
+    liquidity.into_iter().enumerate().fold(
+        HashMap::<_, Vec<_>>::new(),
+        |mut amm_map, (pool, liquidity)| {
+            match liquidity {
+                Liquidity::ConstantProduct(order) => {
+                    amm_map.entry(order.tokens).or_default().push(Amm {
+                        tokens: order.tokens,
+                        order: AmmOrder::ConstantProduct(order),
+                        pool,
+                    });
+                }
+                Liquidity::BalancerWeighted(order) => {
+                    for tokens in token_pairs(&order.reserves) {
+                        amm_map.entry(tokens).or_default().push(Amm {
+                            tokens,
+                            order: AmmOrder::WeightedProduct(order.clone()),
+                            pool,
+                        });
+                    }
+                }
+                Liquidity::BalancerStable(_order) => {
+                    // TODO - https://github.com/cowprotocol/services/issues/80
+                    tracing::debug!("Excluded stable pool from baseline solving.")
+                }
+                Liquidity::BalancerComposableStable(order) => {
+                    for tokens in token_pairs(&order.pool.reserves) {
+                        amm_map.entry(tokens).or_default().push(Amm {
+                            tokens,
+                            order: AmmOrder::ComposableStable(order.clone()),
+                            pool,
+                        });
+                    }
+                }
+                Liquidity::BalancerLinear(order) => {
+                    for tokens in token_pairs(&order.pool.reserves) {
+                        amm_map.entry(tokens).or_default().push(Amm {
+                            tokens,
+                            order: AmmOrder::Linear(order.clone()),
+                            pool,
+                        });
+                    }
+                }
+                Liquidity::Concentrated(order) => {
+                    amm_map.entry(order.tokens).or_default().push(Amm {
+                        tokens: order.tokens,
+                        order: AmmOrder::Concentrated(order),
+                        pool,
+                    });
+                }
+                Liquidity::Curve(order) => {
+                    for tokens in order.pool.token_pairs() {
+                        amm_map.entry(tokens).or_default().push(Amm {
+                            tokens,
+                            order: AmmOrder::Curve(order.clone()),
+                            pool,
+                        });
+                    }
+                }
+                Liquidity::LimitOrder(_) => {}
+            }
+            amm_map
+        },
+    )
+}
+
+/// Adds the AMM interactions for trading the specified amount along a path to a settlement.
+pub(super) fn settle_path<'a>(
+    settlement: &mut Settlement,
+    (mut sell_token, mut sell_amount): (H160, U256),
+    path: impl IntoIterator<Item = &'a Amm>,
+) -> Result<()> {
+    for amm in path {
+        let buy_token = amm.tokens.other(&sell_token).expect("Inconsistent path");
+        let buy_amount = amm
+            .get_amount_out(buy_token, (sell_amount, sell_token))
+            .expect("Path was found, so amount must be calculable");
+        let execution = AmmOrderExecution {
+            input: (sell_token, sell_amount),
+            output: (buy_token, buy_amount),
+        };
+        match &amm.order {
+            AmmOrder::ConstantProduct(order) => settlement.with_liquidity(order, execution),
+            AmmOrder::WeightedProduct(order) => settlement.with_liquidity(order, execution),
+            AmmOrder::Concentrated(order) => settlement.with_liquidity(order, execution),
+            AmmOrder::Curve(order) => settlement.with_liquidity(order, execution),
+            AmmOrder::ComposableStable(order) => settlement.with_liquidity(order, execution),
+            AmmOrder::Linear(order) => settlement.with_liquidity(order, execution),
+        }?;
+        sell_amount = buy_amount;
+        sell_token = buy_token;
+    }
+    Ok(())
+}
+
+pub(super) fn traverse_path_forward(
     mut sell_token: H160,
     mut sell_amount: U256,
     path: &[&Amm],
@@ -242,7 +473,8 @@ fn traverse_path_forward(
 
 #[derive(Debug)]
 struct Solution {
-    path: Vec<Amm>,
+    /// The sell amount and AMM path of each route the order is split into.
+    routes: Vec<(U256, Vec<Amm>)>,
     executed_sell_amount: U256,
     executed_buy_amount: U256,
 }
@@ -255,23 +487,8 @@ impl Solution {
         });
 
         settlement.with_liquidity(order, order.full_execution_amount())?;
-
-        let (mut sell_amount, mut sell_token) = (self.executed_sell_amount, order.sell_token);
-        for amm in self.path {
-            let buy_token = amm.tokens.other(&sell_token).expect("Inconsistent path");
-            let buy_amount = amm
-                .get_amount_out(buy_token, (sell_amount, sell_token))
-                .expect("Path was found, so amount must be calculable");
-            let execution = AmmOrderExecution {
-                input: (sell_token, sell_amount),
-                output: (buy_token, buy_amount),
-            };
-            match &amm.order {
-                AmmOrder::ConstantProduct(order) => settlement.with_liquidity(order, execution),
-                AmmOrder::WeightedProduct(order) => settlement.with_liquidity(order, execution),
-            }?;
-            sell_amount = buy_amount;
-            sell_token = buy_token;
+        for (sell_amount, path) in &self.routes {
+            settle_path(&mut settlement, (order.sell_token, *sell_amount), path)?;
         }
 
         Ok(settlement)
@@ -305,7 +522,14 @@ mod tests {
     use shared::sources::balancer_v2::swap::fixed_point::Bfp;
     use shared::{
         addr,
-        sources::balancer_v2::pool_fetching::{TokenState, WeightedTokenState},
+        sources::{
+            balancer_v2::pool_fetching::{TokenState, WeightedTokenState},
+            curve::pool_fetching::Pool as CurvePool,
+            uniswap_v3::{
+                graph_api::Token,
+                pool_fetching::{PoolInfo, PoolState, PoolStats},
+            },
+        },
     };
 
     #[test]
@@ -378,7 +602,7 @@ mod tests {
         let liquidity = amms.into_iter().map(Liquidity::ConstantProduct).collect();
 
         let base_tokens = Arc::new(BaseTokens::new(native_token, &[]));
-        let solver = BaselineSolver::new(account(), base_tokens);
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
         let result = solver.must_solve(orders, liquidity);
         assert_eq!(
             result.clearing_prices(),
@@ -410,6 +634,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn splits_order_across_paths() {
+        let sell_token = H160::from_low_u64_be(1);
+        let buy_token = H160::from_low_u64_be(0);
+        let native_token = H160::from_low_u64_be(3);
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            let order_handler = CapturingSettlementHandler::arc();
+            let orders = vec![LimitOrder {
+                sell_amount: 60_000.into(),
+                buy_amount: 40_000.into(),
+                sell_token,
+                buy_token,
+                kind,
+                settlement_handling: order_handler.clone(),
+                id: "0".into(),
+                ..Default::default()
+            }];
+
+            let amm_handler = vec![
+                CapturingSettlementHandler::arc(),
+                CapturingSettlementHandler::arc(),
+                CapturingSettlementHandler::arc(),
+            ];
+            let amms = vec![
+                ConstantProductOrder {
+                    tokens: TokenPair::new(sell_token, buy_token).unwrap(),
+                    reserves: (100_000, 100_000),
+                    fee: Ratio::new(3, 1000),
+                    settlement_handling: amm_handler[0].clone(),
+                },
+                ConstantProductOrder {
+                    tokens: TokenPair::new(sell_token, native_token).unwrap(),
+                    reserves: (1_000_000, 1_000_000),
+                    fee: Ratio::new(3, 1000),
+                    settlement_handling: amm_handler[1].clone(),
+                },
+                ConstantProductOrder {
+                    tokens: TokenPair::new(native_token, buy_token).unwrap(),
+                    reserves: (1_000_000, 1_000_000),
+                    fee: Ratio::new(3, 1000),
+                    settlement_handling: amm_handler[2].clone(),
+                },
+            ];
+            let liquidity: Vec<_> = amms.into_iter().map(Liquidity::ConstantProduct).collect();
+
+            let base_tokens = Arc::new(BaseTokens::new(native_token, &[]));
+            let unsplit = BaselineSolver::new(account(), base_tokens.clone(), 1)
+                .must_solve(orders.clone(), liquidity.clone());
+            let split =
+                BaselineSolver::new(account(), base_tokens, 3).must_solve(orders, liquidity);
+
+            // Both paths are used and the order gets a better price than on the best single path.
+            let price = |settlement: &Settlement| {
+                let prices = settlement.clearing_prices();
+                (prices[&sell_token], prices[&buy_token])
+            };
+            let (unsplit_sell_price, unsplit_buy_price) = price(&unsplit);
+            let (split_sell_price, split_buy_price) = price(&split);
+            assert!(split_sell_price * unsplit_buy_price > unsplit_sell_price * split_buy_price);
+
+            let direct = amm_handler[0].clone().calls();
+            let indirect_first = amm_handler[1].clone().calls();
+            let indirect_second = amm_handler[2].clone().calls();
+            // The unsplit settlement only trades on the path via the native token.
+            assert_eq!(direct.len(), 1);
+            assert_eq!(indirect_first.len(), 2);
+            assert_eq!(indirect_second.len(), 2);
+            assert_eq!(indirect_first[1].output.1, indirect_second[1].input.1);
+            assert_eq!(
+                direct[0].input.1 + indirect_first[1].input.1,
+                split_buy_price
+            );
+            assert!(direct[0].output.1 + indirect_second[1].output.1 >= split_sell_price);
+        }
+    }
+
     #[test]
     fn finds_best_route_buy_order() {
         let sell_token = H160::from_low_u64_be(1);
@@ -480,7 +781,7 @@ mod tests {
         let liquidity = amms.into_iter().map(Liquidity::ConstantProduct).collect();
 
         let base_tokens = Arc::new(BaseTokens::new(native_token, &[]));
-        let solver = BaselineSolver::new(account(), base_tokens);
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
         let result = solver.must_solve(orders, liquidity);
         assert_eq!(
             result.clearing_prices(),
@@ -546,10 +847,59 @@ mod tests {
         let liquidity = amms.into_iter().map(Liquidity::ConstantProduct).collect();
 
         let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
-        let solver = BaselineSolver::new(account(), base_tokens);
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
         assert_eq!(solver.solve_(orders, liquidity).len(), 1);
     }
 
+    #[test]
+    fn partially_fills_order_exceeding_amm_liquidity() {
+        let sell_token = H160::from_low_u64_be(1);
+        let buy_token = H160::from_low_u64_be(0);
+
+        let order_handler = CapturingSettlementHandler::arc();
+        let order = LimitOrder {
+            sell_amount: 1_000_000.into(),
+            buy_amount: 900_000.into(),
+            sell_token,
+            buy_token,
+            kind: OrderKind::Sell,
+            partially_fillable: true,
+            settlement_handling: order_handler.clone(),
+            id: "0".into(),
+            ..Default::default()
+        };
+
+        let amm_handler = CapturingSettlementHandler::arc();
+        let liquidity = vec![Liquidity::ConstantProduct(ConstantProductOrder {
+            tokens: TokenPair::new(buy_token, sell_token).unwrap(),
+            reserves: (1_000_000, 1_000_000),
+            fee: Ratio::new(3, 1000),
+            settlement_handling: amm_handler.clone(),
+        })];
+
+        let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
+        let solver = BaselineSolver::new(account(), base_tokens.clone(), 1);
+        solver.must_solve(vec![order.clone()], liquidity.clone());
+
+        // The pool can only provide the order's limit price for selling up to
+        // ~108_102 tokens.
+        let executed_amount = order_handler.calls()[0];
+        assert!(executed_amount <= 108_102.into());
+        assert!(executed_amount >= (108_102 - 100).into());
+
+        let amm_execution = amm_handler.calls()[0].clone();
+        assert_eq!(amm_execution.input, (sell_token, executed_amount));
+        assert!(amm_execution.output.1 * 10 >= executed_amount * 9);
+
+        // Fill-or-kill orders don't get settled at all.
+        let fill_or_kill = LimitOrder {
+            partially_fillable: false,
+            ..order
+        };
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
+        assert!(solver.solve_(vec![fill_or_kill], liquidity).is_empty());
+    }
+
     #[test]
     fn does_not_panic_when_building_solution() {
         // Regression test for https://github.com/gnosis/gp-v2-services/issues/838
@@ -599,7 +949,7 @@ mod tests {
             addr!("c778417e063141139fce010982780140aa0cd5ab"),
             &[],
         ));
-        let solver = BaselineSolver::new(account(), base_tokens);
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
         assert_eq!(solver.solve_(vec![order], liquidity).len(), 0);
     }
 
@@ -675,8 +1025,130 @@ mod tests {
             Liquidity::BalancerWeighted(pool_1),
         ];
         let base_tokens = Arc::new(BaseTokens::new(tokens[0], &tokens));
-        let solver = BaselineSolver::new(account(), base_tokens);
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
         let settlements = solver.solve_(vec![order], liquidity);
         assert!(settlements.is_empty());
     }
+
+    #[test]
+    fn settles_through_concentrated_liquidity() {
+        let sell_token = H160::from_low_u64_be(2);
+        let buy_token = H160::from_low_u64_be(3);
+
+        let order_handler = CapturingSettlementHandler::arc();
+        let order = LimitOrder {
+            sell_amount: 1_000_000_000_000_000u128.into(),
+            buy_amount: 990_000_000_000_000u128.into(),
+            sell_token,
+            buy_token,
+            kind: OrderKind::Sell,
+            settlement_handling: order_handler.clone(),
+            id: "0".into(),
+            ..Default::default()
+        };
+
+        let token = |id| Token {
+            id,
+            symbol: Default::default(),
+            decimals: 18,
+        };
+        let amm_handler = CapturingSettlementHandler::arc();
+        let liquidity = 10i128.pow(18);
+        let liquidity = vec![Liquidity::Concentrated(ConcentratedLiquidity {
+            tokens: TokenPair::new(sell_token, buy_token).unwrap(),
+            pool: PoolInfo {
+                address: H160::from_low_u64_be(1),
+                tokens: vec![token(sell_token), token(buy_token)],
+                state: PoolState {
+                    // A price of 1 with liquidity in the tick range [-600, 600].
+                    sqrt_price: U256::one() << 96,
+                    liquidity: U256::from(liquidity as u128),
+                    tick: 0.into(),
+                    liquidity_net: vec![
+                        ((-600).into(), liquidity.into()),
+                        (600.into(), (-liquidity).into()),
+                    ],
+                    fee: Ratio::new(3, 1000),
+                },
+                gas_stats: PoolStats {
+                    mean_gas: 300_000.into(),
+                },
+            },
+            settlement_handling: amm_handler.clone(),
+        })];
+
+        let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
+        let result = solver.must_solve(vec![order], liquidity);
+        assert_eq!(
+            result.clearing_prices(),
+            &hashmap! {
+                sell_token => 996_006_981_039_903u128.into(),
+                buy_token => 1_000_000_000_000_000u128.into(),
+            }
+        );
+        assert_eq!(
+            amm_handler.calls(),
+            vec![AmmOrderExecution {
+                input: (sell_token, 1_000_000_000_000_000u128.into()),
+                output: (buy_token, 996_006_981_039_903u128.into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn settles_through_curve_pools() {
+        let tokens = [
+            H160::from_low_u64_be(1),
+            H160::from_low_u64_be(2),
+            H160::from_low_u64_be(3),
+        ];
+
+        let order_handler = CapturingSettlementHandler::arc();
+        let order = LimitOrder {
+            sell_amount: 1_000_000_000_000_000_000_000u128.into(),
+            buy_amount: 990_000_000u128.into(),
+            sell_token: tokens[0],
+            buy_token: tokens[1],
+            kind: OrderKind::Sell,
+            settlement_handling: order_handler.clone(),
+            id: "0".into(),
+            ..Default::default()
+        };
+
+        let amm_handler = CapturingSettlementHandler::arc();
+        let liquidity = vec![Liquidity::Curve(CurvePoolOrder {
+            pool: CurvePool {
+                address: H160::from_low_u64_be(4),
+                tokens: tokens.to_vec(),
+                balances: vec![
+                    U256::exp10(24),
+                    U256::from(1_100_000) * U256::exp10(6),
+                    U256::from(900_000) * U256::exp10(6),
+                ],
+                rates: vec![U256::exp10(18), U256::exp10(30), U256::exp10(30)],
+                amplification_parameter: 2000.into(),
+                fee: 4_000_000.into(),
+            },
+            settlement_handling: amm_handler.clone(),
+        })];
+
+        let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
+        let solver = BaselineSolver::new(account(), base_tokens, 1);
+        let result = solver.must_solve(vec![order], liquidity);
+        assert_eq!(
+            result.clearing_prices(),
+            &hashmap! {
+                tokens[0] => 999_645_411u128.into(),
+                tokens[1] => 1_000_000_000_000_000_000_000u128.into(),
+            }
+        );
+        assert_eq!(
+            amm_handler.calls(),
+            vec![AmmOrderExecution {
+                input: (tokens[0], 1_000_000_000_000_000_000_000u128.into()),
+                output: (tokens[1], 999_645_411u128.into()),
+            }]
+        );
+    }
 }
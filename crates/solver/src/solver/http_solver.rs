@@ -18,8 +18,9 @@ use num::{BigInt, BigRational};
 use primitive_types::H160;
 use shared::http_solver::{DefaultHttpSolverApi, HttpSolverApi};
 use shared::{
+    conversions::U256Ext as _,
     http_solver::{gas_model::GasModel, model::*},
-    sources::balancer_v2::pools::common::compute_scaling_rate,
+    sources::{balancer_v2::pools::common::compute_scaling_rate, curve},
 };
 use shared::{
     measure_time,
@@ -44,7 +45,7 @@ pub fn is_transaction_failure(error: &ExecutionError) -> bool {
 /// Data shared between multiple instances of the http solver for the same driver run.
 pub struct InstanceData {
     run_id: u64,
-    model: BatchAuctionModel,
+    model: BatchAuctionModelV2,
     context: SettlementContext,
 }
 
@@ -92,7 +93,13 @@ impl HttpSolver {
         liquidity: Vec<Liquidity>,
         gas_price: f64,
         external_prices: ExternalPrices,
-    ) -> Result<(BatchAuctionModel, SettlementContext)> {
+    ) -> Result<(BatchAuctionModelV2, SettlementContext)> {
+        // There is no HTTP solver model for linear pools. Remove them here so
+        // that the AMM indices of the model and the settlement context match.
+        let liquidity = liquidity
+            .into_iter()
+            .filter(|liquidity| !matches!(liquidity, Liquidity::BalancerLinear(_)))
+            .collect::<Vec<_>>();
         let tokens = map_tokens_for_solver(&orders, &liquidity);
         let (token_infos, buffers_result) = join!(
             measure_time(
@@ -132,6 +139,7 @@ impl HttpSolver {
         // objective value by the driver. It is possible that we have AMM pools that contain tokens
         // that are not any order's tokens. We used to fetch these extra prices but it would often
         // slow down the solver and the solver can estimate them on its own.
+        let native_prices = external_prices.to_native_prices();
         let price_estimates = external_prices.into_http_solver_prices();
 
         // For the solver to run correctly we need to be sure that there are no
@@ -145,10 +153,12 @@ impl HttpSolver {
         let token_models = token_models(&token_infos, &price_estimates, &buffers, &gas_model);
         let order_models = order_models(&orders, &fee_connected_tokens, &gas_model);
         let amm_models = amm_models(&liquidity, &gas_model);
-        let model = BatchAuctionModel {
+        let model = BatchAuctionModelV2 {
+            auction_id: Some(auction_id),
             tokens: token_models,
             orders: order_models,
             amms: amm_models,
+            native_prices,
             metadata: Some(MetadataModel {
                 environment: Some(self.solver.network_name.clone()),
                 auction_id: Some(auction_id),
@@ -173,8 +183,11 @@ fn map_tokens_for_solver(orders: &[LimitOrder], liquidity: &[Liquidity]) -> Vec<
             Liquidity::ConstantProduct(amm) => token_set.extend(amm.tokens),
             Liquidity::BalancerWeighted(amm) => token_set.extend(amm.reserves.keys()),
             Liquidity::BalancerStable(amm) => token_set.extend(amm.reserves.keys()),
+            Liquidity::BalancerComposableStable(amm) => token_set.extend(amm.pool.reserves.keys()),
+            Liquidity::BalancerLinear(amm) => token_set.extend(amm.pool.reserves.keys()),
             Liquidity::LimitOrder(order) => token_set.extend([order.sell_token, order.buy_token]),
             Liquidity::Concentrated(amm) => token_set.extend(amm.tokens),
+            Liquidity::Curve(amm) => token_set.extend(amm.pool.tokens.iter()),
         }
     }
 
@@ -227,7 +240,7 @@ fn order_models(
     orders: &[LimitOrder],
     fee_connected_tokens: &HashSet<H160>,
     gas_model: &GasModel,
-) -> BTreeMap<usize, OrderModel> {
+) -> BTreeMap<usize, OrderModelV2> {
     orders
         .iter()
         .enumerate()
@@ -246,18 +259,26 @@ fn order_models(
 
             Some((
                 index,
-                OrderModel {
-                    sell_token: order.sell_token,
-                    buy_token: order.buy_token,
-                    sell_amount: order.sell_amount,
-                    buy_amount: order.buy_amount,
-                    allow_partial_fill: order.partially_fillable,
-                    is_sell_order: matches!(order.kind, OrderKind::Sell),
-                    fee: order_fee(order),
-                    cost,
-                    is_liquidity_order: order.is_liquidity_order,
-                    mandatory: false,
-                    has_atomic_execution: !matches!(order.exchange, Exchange::GnosisProtocol),
+                OrderModelV2 {
+                    order: OrderModel {
+                        sell_token: order.sell_token,
+                        buy_token: order.buy_token,
+                        sell_amount: order.sell_amount,
+                        buy_amount: order.buy_amount,
+                        allow_partial_fill: order.partially_fillable,
+                        is_sell_order: matches!(order.kind, OrderKind::Sell),
+                        fee: order_fee(order),
+                        cost,
+                        is_liquidity_order: order.is_liquidity_order,
+                        mandatory: false,
+                        has_atomic_execution: !matches!(order.exchange, Exchange::GnosisProtocol),
+                    },
+                    identity: order.gpv2_order.as_ref().map(|order| OrderIdentityModel {
+                        uid: order.metadata.uid,
+                        owner: order.metadata.owner,
+                        app_data: order.data.app_data,
+                        creation_date: order.metadata.creation_date,
+                    }),
                 },
             ))
         })
@@ -327,6 +348,35 @@ fn amm_models(liquidity: &[Liquidity], gas_model: &GasModel) -> BTreeMap<usize,
                     cost: gas_model.balancer_cost(),
                     mandatory: false,
                 },
+                Liquidity::BalancerComposableStable(amm) => AmmModel {
+                    parameters: AmmParameters::Stable(StablePoolParameters {
+                        reserves: amm
+                            .pool
+                            .reserves
+                            .iter()
+                            .map(|(token, state)| (*token, state.common.balance))
+                            .collect(),
+                        scaling_rates: amm
+                            .pool
+                            .reserves
+                            .iter()
+                            .map(|(token, state)| Ok((*token, state.scaling_rate()?)))
+                            .collect::<Result<_>>()
+                            .with_context(|| {
+                                format!(
+                                    "error converting composable stable pool to solver model: {:?}",
+                                    amm
+                                )
+                            })?,
+                        amplification_parameter: amm.pool.amplification_parameter.as_big_rational(),
+                    }),
+                    fee: amm.pool.common.swap_fee.into(),
+                    cost: gas_model.balancer_cost(),
+                    mandatory: false,
+                },
+                Liquidity::BalancerLinear(_) => {
+                    unreachable!("filtered out when preparing the model")
+                }
                 Liquidity::LimitOrder(_) => unreachable!("filtered out before"),
                 Liquidity::Concentrated(amm) => AmmModel {
                     parameters: AmmParameters::Concentrated(ConcentratedPoolParameters {
@@ -339,6 +389,43 @@ fn amm_models(liquidity: &[Liquidity], gas_model: &GasModel) -> BTreeMap<usize,
                     cost: gas_model.cost_for_gas(amm.pool.gas_stats.mean_gas),
                     mandatory: false,
                 },
+                Liquidity::Curve(amm) => AmmModel {
+                    parameters: AmmParameters::Stable(StablePoolParameters {
+                        reserves: amm
+                            .pool
+                            .tokens
+                            .iter()
+                            .copied()
+                            .zip(amm.pool.balances.iter().copied())
+                            .collect(),
+                        // Curve rates are `10 ** (36 - decimals)`, we want the
+                        // rate which is `10 ** decimals`.
+                        scaling_rates: amm
+                            .pool
+                            .tokens
+                            .iter()
+                            .zip(&amm.pool.rates)
+                            .map(|(token, rate)| {
+                                Ok((
+                                    *token,
+                                    U256::exp10(36).checked_div(*rate).with_context(|| {
+                                        format!(
+                                            "error converting Curve pool to solver model: {:?}",
+                                            amm
+                                        )
+                                    })?,
+                                ))
+                            })
+                            .collect::<Result<_>>()?,
+                        amplification_parameter: amm.pool.amplification_parameter.to_big_rational(),
+                    }),
+                    fee: BigRational::new(
+                        amm.pool.fee.to_big_int(),
+                        curve::swap::FEE_DENOMINATOR.into(),
+                    ),
+                    cost: gas_model.curve_cost(),
+                    mandatory: false,
+                },
             })
         })
         .enumerate()
@@ -431,15 +518,14 @@ impl Solver for HttpSolver {
         let timeout = deadline
             .checked_duration_since(Instant::now())
             .ok_or_else(|| anyhow!("no time left to send request"))?;
-        let settled = self.solver.solve(&model, timeout).await?;
-
-        if !settled.has_execution_plan() {
-            tracing::debug!(
-                name = %self.name(), ?settled,
-                "ignoring settlement without execution plan",
-            );
-            return Ok(Vec::new());
-        }
+        let settled = match self.solver.config.instance_version {
+            InstanceVersion::V1 => {
+                self.solver
+                    .solve(&BatchAuctionModel::from(model), timeout)
+                    .await?
+            }
+            InstanceVersion::V2 => self.solver.solve_v2(&model, timeout).await?,
+        };
 
         tracing::debug!(
             "Solution received from http solver {} (json):\n{:}",
@@ -447,21 +533,42 @@ impl Solver for HttpSolver {
             serde_json::to_string_pretty(&settled).unwrap()
         );
 
-        match settlement::convert_settlement(
-            settled.clone(),
-            context,
-            self.allowance_manager.clone(),
-        )
-        .await
-        {
-            Ok(settlement) => Ok(vec![settlement]),
-            Err(err) => {
+        // Solvers may propose multiple ranked solutions. All of them get
+        // converted so that they can compete in the settlement rating, and the
+        // driver bounds how many of them are considered.
+        let mut settlements = Vec::new();
+        let mut first_error = None;
+        for (rank, settled) in settled.into_ranked_solutions().into_iter().enumerate() {
+            if !settled.has_execution_plan() {
                 tracing::debug!(
-                    name = %self.name(), ?settled,
-                    "failed to process HTTP solver result",
+                    name = %self.name(), %rank, ?settled,
+                    "ignoring settlement without execution plan",
                 );
-                Err(err)
+                continue;
             }
+
+            match settlement::convert_settlement(
+                settled.clone(),
+                context.clone(),
+                self.allowance_manager.clone(),
+            )
+            .await
+            {
+                Ok(settlement) => settlements.push(settlement),
+                Err(err) => {
+                    tracing::debug!(
+                        name = %self.name(), %rank, ?settled, ?err,
+                        "failed to process HTTP solver result",
+                    );
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        // Only report an error if none of the proposed solutions were usable.
+        match first_error {
+            Some(err) if settlements.is_empty() => Err(err),
+            _ => Ok(settlements),
         }
     }
 
@@ -565,7 +672,7 @@ mod tests {
             .unwrap();
         let settled = solver
             .solver
-            .solve(&model, Duration::from_secs(1000))
+            .solve(&model.into(), Duration::from_secs(1000))
             .await
             .unwrap();
         dbg!(&settled);
@@ -650,6 +757,44 @@ mod tests {
         assert_eq!(order_models.len(), 6);
     }
 
+    #[test]
+    fn order_models_include_order_identity() {
+        let native_token = H160::from_low_u64_be(0);
+        let token = H160::from_low_u64_be(1);
+        let gas_model = GasModel {
+            gas_price: 1e9,
+            native_token,
+        };
+        let order = ::model::order::Order {
+            metadata: ::model::order::OrderMetadata {
+                uid: ::model::order::OrderUid([1; 56]),
+                owner: H160([2; 20]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let orders = [
+            LimitOrder {
+                sell_token: native_token,
+                buy_token: token,
+                gpv2_order: Some(order),
+                ..Default::default()
+            },
+            LimitOrder {
+                sell_token: native_token,
+                buy_token: token,
+                exchange: Exchange::ZeroEx,
+                ..Default::default()
+            },
+        ];
+
+        let order_models = order_models(&orders, &hashset![native_token], &gas_model);
+        let identity = order_models[&0].identity.as_ref().unwrap();
+        assert_eq!(identity.uid, ::model::order::OrderUid([1; 56]));
+        assert_eq!(identity.owner, H160([2; 20]));
+        assert!(order_models[&1].identity.is_none());
+    }
+
     #[test]
     fn decode_response() {
         let example_response = r#"
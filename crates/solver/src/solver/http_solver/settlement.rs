@@ -77,11 +77,18 @@ impl Execution {
                     Liquidity::BalancerStable(liquidity) => {
                         settlement.with_liquidity(liquidity, execution)
                     }
+                    Liquidity::BalancerComposableStable(liquidity) => {
+                        settlement.with_liquidity(liquidity, execution)
+                    }
+                    Liquidity::BalancerLinear(liquidity) => {
+                        settlement.with_liquidity(liquidity, execution)
+                    }
                     // This sort of liquidity gets used elsewhere
                     Liquidity::LimitOrder(_) => Ok(()),
                     Liquidity::Concentrated(liquidity) => {
                         settlement.with_liquidity(liquidity, execution)
                     }
+                    Liquidity::Curve(liquidity) => settlement.with_liquidity(liquidity, execution),
                 }
             }
             CustomInteraction(interaction_data) => {
@@ -133,6 +140,16 @@ impl Interaction for InteractionData {
     fn encode(&self) -> Vec<EncodedInteraction> {
         vec![(self.target, self.value, Bytes(self.call_data.clone()))]
     }
+
+    fn swap_execution(&self) -> Option<AmmOrderExecution> {
+        match (self.inputs.as_slice(), self.outputs.as_slice()) {
+            ([input], [output]) if self.value.is_zero() => Some(AmmOrderExecution {
+                input: (input.token, input.amount),
+                output: (output.token, output.amount),
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl IntermediateSettlement {
@@ -475,6 +492,7 @@ mod tests {
             approvals: Vec::new(),
             interaction_data: Vec::new(),
             metadata: None,
+            alternatives: Vec::new(),
         };
 
         let prepared = SettlementContext { orders, liquidity };
@@ -45,9 +45,7 @@ pub fn solve(
     let mut orders: Vec<LimitOrder> = orders.into_iter().collect();
     while !orders.is_empty() {
         let (context_a, context_b) = split_into_contexts(&orders, pool);
-        if let Some(valid_solution) = solve_orders(&orders, pool, &context_a, &context_b)
-            .filter(|settlement| is_valid_solution(settlement, pool.tokens))
-        {
+        if let Some(valid_solution) = solve_valid_orders(&orders, pool, &context_a, &context_b) {
             return Some(valid_solution);
         } else {
             // remove order with worst limit price that is selling excess token (to make it less excessive) and try again
@@ -65,7 +63,14 @@ pub fn solve(
                         .cmp(&(lhs.1.sell_amount * rhs.1.buy_amount))
                 });
             match order_to_remove {
-                Some((index, _)) => orders.swap_remove(index),
+                Some((index, _)) => {
+                    // Partially fillable orders only get removed if no part of them can be
+                    // settled.
+                    if let Some(valid_solution) = solve_with_partial_fill(&orders, index, pool) {
+                        return Some(valid_solution);
+                    }
+                    orders.swap_remove(index)
+                }
                 None => break,
             };
         }
@@ -74,6 +79,36 @@ pub fn solve(
     None
 }
 
+fn solve_valid_orders(
+    orders: &[LimitOrder],
+    pool: &ConstantProductOrder,
+    context_a: &TokenContext,
+    context_b: &TokenContext,
+) -> Option<Settlement> {
+    solve_orders(orders, pool, context_a, context_b)
+        .filter(|settlement| is_valid_solution(settlement, pool.tokens))
+}
+
+///
+/// Computes a valid settlement where the partially fillable order at `index` is only partially
+/// executed. The largest execution amount for which all clearing prices are valid is used.
+///
+fn solve_with_partial_fill(
+    orders: &[LimitOrder],
+    index: usize,
+    pool: &ConstantProductOrder,
+) -> Option<Settlement> {
+    let order = &orders[index];
+    let mut partial_orders = orders.to_vec();
+    let mut solve_partial_fill = |amount| {
+        partial_orders[index] = order.partial_fill(amount)?;
+        let (context_a, context_b) = split_into_contexts(&partial_orders, pool);
+        solve_valid_orders(&partial_orders, pool, &context_a, &context_b)
+    };
+    let amount = order.max_partial_execution(|amount| solve_partial_fill(amount).is_some())?;
+    solve_partial_fill(amount)
+}
+
 ///
 /// Computes a settlement using orders of a single pair and the direct AMM between those tokens.get(.
 /// Panics if orders are not already filtered for a specific token pair, or the reserve information
@@ -677,6 +712,43 @@ mod tests {
         assert!(is_valid_solution(&result, pool.tokens));
     }
 
+    #[test]
+    fn partially_fills_order_whose_limit_price_is_not_satisfiable() {
+        let token_a = Address::from_low_u64_be(0);
+        let token_b = Address::from_low_u64_be(1);
+        let order = |partially_fillable| -> LimitOrder {
+            Order {
+                data: OrderData {
+                    sell_token: token_a,
+                    buy_token: token_b,
+                    sell_amount: to_wei(1000),
+                    buy_amount: to_wei(995),
+                    kind: OrderKind::Sell,
+                    partially_fillable,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .into()
+        };
+
+        let pool = ConstantProductOrder {
+            tokens: TokenPair::new(token_a, token_b).unwrap(),
+            reserves: (to_wei(100_000).as_u128(), to_wei(100_000).as_u128()),
+            fee: Ratio::new(3, 1000),
+            settlement_handling: CapturingSettlementHandler::arc(),
+        };
+        assert!(solve(vec![order(false)], &pool).is_none());
+
+        let result = solve(vec![order(true)], &pool).unwrap();
+        assert!(is_valid_solution(&result, pool.tokens));
+
+        // The pool only offers the limit price for selling up to ~201.1 tokens.
+        let (_, execution) = result.executed_trades().next().unwrap();
+        assert!(execution.sell_amount > to_wei(200));
+        assert!(execution.sell_amount < to_wei(202));
+    }
+
     #[test]
     fn returns_empty_solution_if_orders_have_no_overlap() {
         let token_a = Address::from_low_u64_be(0);
@@ -0,0 +1,757 @@
+//! A solver that matches orders trading in cycles (for example A→B, B→C and C→A) directly against
+//! each other.
+//!
+//! Such rings are coincidences of wants that can be settled purely from the traders' funds, but
+//! they are not found by the naive solver since it only matches orders on a single token pair.
+//! Rings whose amounts do not line up exactly can optionally have their residuals closed through
+//! the same AMM paths the baseline solver uses.
+
+use super::baseline_solver::{self, traverse_path_forward, Amm};
+use crate::{
+    liquidity::{LimitOrder, Liquidity},
+    settlement::{external_prices::ExternalPrices, Settlement},
+    solver::{Auction, Solver},
+};
+use anyhow::Result;
+use ethcontract::{Account, H160, U256};
+use model::{order::OrderKind, TokenPair};
+use num::{BigInt, BigRational, Integer as _, One, ToPrimitive, Zero as _};
+use number_conversions::big_int_to_u256;
+use shared::{
+    baseline_solver::{estimate_buy_amount, estimate_sell_amount, BaseTokens},
+    conversions::U256Ext,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    iter,
+    sync::Arc,
+};
+
+/// The maximum number of orders in a ring.
+const MAX_RING_LENGTH: usize = 4;
+
+/// How many times clearing prices get adjusted when trying to close the residuals of a ring
+/// through AMMs.
+const MAX_RESIDUAL_ITERATIONS: usize = 5;
+
+/// Additional price adjustment applied on top of an AMM shortfall to account for price impact
+/// and rounding.
+const SHORTFALL_MARGIN: f64 = 1.001;
+
+/// The value every order of a ring sells at the clearing prices if the exact value would overflow.
+/// It is much larger than the square of realistic amounts so that rounding errors stay below one
+/// atom, while leaving room for scaling the prices when merging settlements.
+const APPROXIMATE_RING_VALUE: U256 = U256([0, 0, 0, 1]);
+
+pub struct RingTradeSolver {
+    account: Account,
+    base_tokens: Arc<BaseTokens>,
+    close_residuals: bool,
+}
+
+#[async_trait::async_trait]
+impl Solver for RingTradeSolver {
+    async fn solve(
+        &self,
+        Auction {
+            orders,
+            liquidity,
+            external_prices,
+            ..
+        }: Auction,
+    ) -> Result<Vec<Settlement>> {
+        Ok(self.solve_(orders, liquidity, &external_prices))
+    }
+
+    fn account(&self) -> &Account {
+        &self.account
+    }
+
+    fn name(&self) -> &'static str {
+        "RingTradeSolver"
+    }
+}
+
+/// A token graph edge from the sell to the buy token of an order.
+type Graph = HashMap<H160, Vec<(H160, usize)>>;
+
+impl RingTradeSolver {
+    pub fn new(account: Account, base_tokens: Arc<BaseTokens>, close_residuals: bool) -> Self {
+        Self {
+            account,
+            base_tokens,
+            close_residuals,
+        }
+    }
+
+    fn solve_(
+        &self,
+        orders: Vec<LimitOrder>,
+        liquidity: Vec<Liquidity>,
+        external_prices: &ExternalPrices,
+    ) -> Vec<Settlement> {
+        let amms = if self.close_residuals {
+            baseline_solver::amm_map(liquidity)
+        } else {
+            HashMap::new()
+        };
+        let mut orders = orders.into_iter().filter(usable_order).collect::<Vec<_>>();
+
+        // Settlements have to be independent, so every order is used in at most one ring. Once
+        // the best orders on some token pairs have been used up, orders with worse limit prices on
+        // the same pairs might form new rings, so we keep searching until no ring can be settled.
+        let mut settlements = Vec::new();
+        loop {
+            let mut used = HashSet::new();
+            for ring in find_rings(&orders) {
+                if ring.iter().any(|index| used.contains(index)) {
+                    continue;
+                }
+                let ring_orders = ring.iter().map(|index| &orders[*index]).collect::<Vec<_>>();
+                if let Some(settlement) = self.settle_ring(&ring_orders, &amms, external_prices) {
+                    settlements.push(settlement);
+                    used.extend(ring);
+                }
+            }
+            if used.is_empty() {
+                break;
+            }
+            orders = orders
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| !used.contains(index))
+                .map(|(_, order)| order)
+                .collect();
+        }
+
+        settlements
+    }
+
+    fn settle_ring(
+        &self,
+        ring: &[&LimitOrder],
+        amms: &HashMap<TokenPair, Vec<Amm>>,
+        external_prices: &ExternalPrices,
+    ) -> Option<Settlement> {
+        let solution = match balanced_amounts(ring)
+            .and_then(|amounts| balanced_prices(ring, &amounts))
+            .filter(|prices| rounding_covered_by_fees(ring, prices))
+        {
+            Some(prices) => RingSolution {
+                prices,
+                swaps: Vec::new(),
+            },
+            None if self.close_residuals => {
+                self.solve_with_residuals(ring, amms, external_prices)?
+            }
+            None => {
+                tracing::debug!(?ring, "ring cannot be settled without AMMs");
+                return None;
+            }
+        };
+
+        match solution.into_settlement(ring) {
+            Ok(settlement) => Some(settlement),
+            Err(err) => {
+                tracing::error!(?err, "ring_trade_solver failed to create settlement");
+                None
+            }
+        }
+    }
+
+    /// Settles a ring at clearing prices close to the external prices, trading the tokens that
+    /// are left over in the settlement contract for the ones that are missing through AMMs.
+    ///
+    /// Since AMMs trade at worse rates than the external prices, the clearing prices of missing
+    /// tokens are raised until the AMMs can cover them.
+    fn solve_with_residuals<'a>(
+        &self,
+        ring: &[&LimitOrder],
+        amms: &'a HashMap<TokenPair, Vec<Amm>>,
+        external_prices: &ExternalPrices,
+    ) -> Option<RingSolution<'a>> {
+        let mut prices = ring
+            .iter()
+            .map(|order| {
+                let price = external_prices.price(&order.sell_token)?.to_f64()?;
+                Some((order.sell_token, price))
+            })
+            .collect::<Option<HashMap<_, _>>>()?;
+
+        for _ in 0..MAX_RESIDUAL_ITERATIONS {
+            let clearing_prices = to_clearing_prices(&prices)?;
+            let residuals = match residuals(ring, &clearing_prices) {
+                Some(residuals) => residuals,
+                None => {
+                    tracing::debug!(?ring, "ring limit prices not satisfied");
+                    return None;
+                }
+            };
+            match self.close_residuals(residuals, amms) {
+                Closing::Closed(swaps) => {
+                    return Some(RingSolution {
+                        prices: clearing_prices,
+                        swaps,
+                    })
+                }
+                Closing::Shortfalls(shortfalls) => {
+                    for (token, factor) in shortfalls {
+                        *prices.get_mut(&token)? *= factor * SHORTFALL_MARGIN;
+                    }
+                }
+                Closing::Impossible => break,
+            }
+        }
+
+        tracing::debug!(?ring, "ring residuals cannot be closed through AMMs");
+        None
+    }
+
+    /// Finds AMM swaps that trade excess tokens for each of the missing tokens.
+    fn close_residuals<'a>(
+        &self,
+        Residuals {
+            mut excess,
+            deficits,
+        }: Residuals,
+        amms: &'a HashMap<TokenPair, Vec<Amm>>,
+    ) -> Closing<'a> {
+        let mut swaps = Vec::new();
+        let mut shortfalls = Vec::new();
+        for (buy_token, buy_amount) in deficits {
+            let swap = excess
+                .iter()
+                .enumerate()
+                .find_map(|(index, (sell_token, available))| {
+                    let estimate = self
+                        .base_tokens
+                        .path_candidates(*sell_token, buy_token)
+                        .iter()
+                        .filter_map(|path| estimate_sell_amount(buy_amount, path, amms))
+                        .filter(|estimate| estimate.value <= *available)
+                        // As in the baseline solver, make sure that trading forward along the path
+                        // yields enough tokens since AMM amounts aren't always symmetrical.
+                        .filter(|estimate| {
+                            matches!(
+                                traverse_path_forward(*sell_token, estimate.value, &estimate.path),
+                                Some(amount) if amount >= buy_amount
+                            )
+                        })
+                        .min_by_key(|estimate| estimate.value)?;
+                    Some((index, estimate))
+                });
+
+            match swap {
+                Some((index, estimate)) => {
+                    let (sell_token, available) = &mut excess[index];
+                    *available -= estimate.value;
+                    swaps.push(Swap {
+                        input: (*sell_token, estimate.value),
+                        path: estimate.path,
+                    });
+                }
+                None => {
+                    // Find out how much of the missing token the AMMs could provide at most so
+                    // that we know by how much to raise its clearing price.
+                    let max_buy_amount = excess
+                        .iter()
+                        .flat_map(|(sell_token, available)| {
+                            self.base_tokens
+                                .path_candidates(*sell_token, buy_token)
+                                .into_iter()
+                                .filter_map(move |path| {
+                                    estimate_buy_amount(*available, &path, amms)
+                                })
+                                .map(|estimate| estimate.value)
+                        })
+                        .max()
+                        .filter(|amount| !amount.is_zero());
+                    match max_buy_amount {
+                        Some(amount) => shortfalls
+                            .push((buy_token, buy_amount.to_f64_lossy() / amount.to_f64_lossy())),
+                        None => return Closing::Impossible,
+                    }
+                }
+            }
+        }
+
+        if shortfalls.is_empty() {
+            Closing::Closed(swaps)
+        } else {
+            Closing::Shortfalls(shortfalls)
+        }
+    }
+}
+
+/// Finds all profitable rings in the specified orders, best rings first.
+///
+/// For every pair of tokens only the order with the best limit price is considered. Rings are
+/// returned as indices into `orders` where each order buys the token the next one sells.
+fn find_rings(orders: &[LimitOrder]) -> Vec<Vec<usize>> {
+    let mut best_orders = HashMap::<(H160, H160), usize>::new();
+    for (index, order) in orders.iter().enumerate() {
+        best_orders
+            .entry((order.sell_token, order.buy_token))
+            .and_modify(|best| {
+                if has_better_limit_price(order, &orders[*best]) {
+                    *best = index;
+                }
+            })
+            .or_insert(index);
+    }
+    let mut graph = Graph::new();
+    for ((sell_token, buy_token), index) in best_orders {
+        graph
+            .entry(sell_token)
+            .or_default()
+            .push((buy_token, index));
+    }
+
+    let mut rings = Vec::new();
+    for start in graph.keys() {
+        collect_rings(&graph, *start, *start, &mut Vec::new(), &mut rings);
+    }
+
+    let mut rings = rings
+        .into_iter()
+        .map(|ring| (surplus_ratio(orders, &ring), ring))
+        .filter(|(ratio, _)| *ratio >= BigRational::one())
+        .collect::<Vec<_>>();
+    rings.sort_by(|(a, _), (b, _)| b.cmp(a));
+    rings.into_iter().map(|(_, ring)| ring).collect()
+}
+
+/// Depth first search for rings starting and ending at `start`.
+///
+/// Only rings whose tokens are all greater than `start` are collected so that every ring is found
+/// exactly once instead of once per rotation.
+fn collect_rings(
+    graph: &Graph,
+    start: H160,
+    token: H160,
+    path: &mut Vec<(H160, usize)>,
+    rings: &mut Vec<Vec<usize>>,
+) {
+    for (next, order) in graph.get(&token).into_iter().flatten() {
+        if *next == start {
+            rings.push(
+                path.iter()
+                    .map(|(_, order)| *order)
+                    .chain(iter::once(*order))
+                    .collect(),
+            );
+        } else if *next > start
+            && path.len() + 1 < MAX_RING_LENGTH
+            && !path.iter().any(|(visited, _)| visited == next)
+        {
+            path.push((*next, *order));
+            collect_rings(graph, start, *next, path, rings);
+            path.pop();
+        }
+    }
+}
+
+/// The product of the limit exchange rates of all orders in a ring. Rings are profitable, i.e.
+/// all limit prices can be satisfied at once, if this is at least 1.
+fn surplus_ratio(orders: &[LimitOrder], ring: &[usize]) -> BigRational {
+    ring.iter()
+        .map(|index| {
+            let order = &orders[*index];
+            order.sell_amount.to_big_rational() / order.buy_amount.to_big_rational()
+        })
+        .product()
+}
+
+fn has_better_limit_price(a: &LimitOrder, b: &LimitOrder) -> bool {
+    a.buy_amount.full_mul(b.sell_amount) < b.buy_amount.full_mul(a.sell_amount)
+}
+
+/// Liquidity orders are not supposed to receive surplus, so only user orders are matched in rings.
+fn usable_order(order: &LimitOrder) -> bool {
+    !order.is_liquidity_order
+        && !order.sell_amount.is_zero()
+        && !order.buy_amount.is_zero()
+        && order.sell_token != order.buy_token
+}
+
+/// Computes how much of each token gets traded when the ring is settled purely from the traders'
+/// funds with all orders fully executed.
+///
+/// `amounts[i]` is the amount of the sell token of `ring[i]`, which is sold by `ring[i]` and bought
+/// by the previous order in the ring. Returns `None` if no such amounts satisfy all limit prices.
+fn balanced_amounts(ring: &[&LimitOrder]) -> Option<Vec<U256>> {
+    let len = ring.len();
+    let amounts = (0..len)
+        .map(|i| {
+            let seller = ring[i];
+            let buyer = ring[(i + len - 1) % len];
+            match (seller.kind, buyer.kind) {
+                (OrderKind::Sell, OrderKind::Sell) => Some(seller.sell_amount),
+                (OrderKind::Buy, OrderKind::Buy) => Some(buyer.buy_amount),
+                (OrderKind::Sell, OrderKind::Buy) if seller.sell_amount == buyer.buy_amount => {
+                    Some(seller.sell_amount)
+                }
+                (OrderKind::Sell, OrderKind::Buy) => None,
+                // Neither order fixes the amount, so we pick the one in the middle of the range
+                // allowed by the limit prices of both orders.
+                (OrderKind::Buy, OrderKind::Sell) if buyer.buy_amount <= seller.sell_amount => {
+                    Some(buyer.buy_amount + (seller.sell_amount - buyer.buy_amount) / 2)
+                }
+                (OrderKind::Buy, OrderKind::Sell) => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let satisfies_limit_prices = ring.iter().enumerate().all(|(i, order)| match order.kind {
+        OrderKind::Sell => amounts[(i + 1) % len] >= order.buy_amount,
+        OrderKind::Buy => amounts[i] <= order.sell_amount,
+    });
+    if satisfies_limit_prices {
+        Some(amounts)
+    } else {
+        None
+    }
+}
+
+/// Computes uniform clearing prices for which every order trades the balanced amounts.
+///
+/// Every order of the ring sells the same value, so the price of each token is that value divided
+/// by the token's amount. Using the least common multiple of all amounts as the value makes all
+/// executed amounts exact, so no rounding errors can unbalance the ring. If the settlement contract
+/// can't multiply the amounts with such prices without overflowing, the value is approximated
+/// instead, which leaves rounding errors of a few atoms at most.
+fn balanced_prices(ring: &[&LimitOrder], amounts: &[U256]) -> Option<HashMap<H160, U256>> {
+    let lcm = amounts.iter().try_fold(BigInt::one(), |lcm, amount| {
+        let amount = amount.to_big_int();
+        (!amount.is_zero()).then(|| lcm.lcm(&amount))
+    })?;
+    let value = big_int_to_u256(&lcm).unwrap_or(APPROXIMATE_RING_VALUE);
+    ring.iter()
+        .zip(amounts)
+        .map(|(order, amount)| {
+            let price = value / *amount;
+            (!price.is_zero()).then(|| (order.sell_token, price))
+        })
+        .collect()
+}
+
+/// Whether the fees the settlement contract receives cover the deficits that rounding leaves when
+/// settling a ring at the specified prices.
+fn rounding_covered_by_fees(ring: &[&LimitOrder], prices: &HashMap<H160, U256>) -> bool {
+    let residuals = match residuals(ring, prices) {
+        Some(residuals) => residuals,
+        None => return false,
+    };
+    residuals.deficits.iter().all(|(token, deficit)| {
+        ring.iter()
+            .any(|order| order.sell_token == *token && order.unscaled_subsidized_fee >= *deficit)
+    })
+}
+
+/// Converts floating point prices into clearing prices where the cheapest token has a price of
+/// 1e18 so that precision is retained for all tokens.
+fn to_clearing_prices(prices: &HashMap<H160, f64>) -> Option<HashMap<H160, U256>> {
+    let min_price = prices.values().copied().fold(f64::INFINITY, f64::min);
+    prices
+        .iter()
+        .map(|(token, price)| {
+            let price = price / min_price * 1e18;
+            if !price.is_normal() {
+                return None;
+            }
+            Some((*token, U256::from_f64_lossy(price)))
+        })
+        .collect()
+}
+
+/// Computes the executed sell and buy amounts of a fully executed order the same way the
+/// settlement encoder does.
+fn executed_amounts(order: &LimitOrder, prices: &HashMap<H160, U256>) -> Option<(U256, U256)> {
+    let sell_price = prices.get(&order.sell_token)?;
+    let buy_price = prices.get(&order.buy_token)?;
+    match order.kind {
+        OrderKind::Sell => {
+            let buy_amount = order
+                .sell_amount
+                .checked_mul(*sell_price)?
+                .checked_ceil_div(buy_price)?;
+            Some((order.sell_amount, buy_amount))
+        }
+        OrderKind::Buy => {
+            let sell_amount = order
+                .buy_amount
+                .checked_mul(*buy_price)?
+                .checked_div(*sell_price)?;
+            Some((sell_amount, order.buy_amount))
+        }
+    }
+}
+
+/// Token amounts the settlement contract is left with or is missing after executing a ring.
+#[derive(Debug, Default)]
+struct Residuals {
+    excess: Vec<(H160, U256)>,
+    deficits: Vec<(H160, U256)>,
+}
+
+/// Computes the residuals of executing all orders of a ring at the specified clearing prices.
+///
+/// Returns `None` if the prices don't satisfy the limit price of some order.
+fn residuals(ring: &[&LimitOrder], prices: &HashMap<H160, U256>) -> Option<Residuals> {
+    let mut sold = HashMap::new();
+    let mut bought = HashMap::new();
+    for order in ring {
+        let (sell_amount, buy_amount) = executed_amounts(order, prices)?;
+        if sell_amount > order.sell_amount || buy_amount < order.buy_amount {
+            return None;
+        }
+        sold.insert(order.sell_token, sell_amount);
+        bought.insert(order.buy_token, buy_amount);
+    }
+
+    // Every token of a ring is sold by exactly one order and bought by exactly one other order.
+    let mut residuals = Residuals::default();
+    for (token, sold) in sold {
+        let bought = bought.get(&token)?;
+        if sold > *bought {
+            residuals.excess.push((token, sold - bought));
+        } else if sold < *bought {
+            residuals.deficits.push((token, bought - sold));
+        }
+    }
+    Some(residuals)
+}
+
+enum Closing<'a> {
+    /// All deficits can be covered by swapping excess tokens through AMMs.
+    Closed(Vec<Swap<'a>>),
+    /// Deficit tokens together with the factor by which the AMMs fall short of covering them.
+    Shortfalls(Vec<(H160, f64)>),
+    /// Some deficit cannot be covered through AMMs at all.
+    Impossible,
+}
+
+struct Swap<'a> {
+    input: (H160, U256),
+    path: Vec<&'a Amm>,
+}
+
+struct RingSolution<'a> {
+    prices: HashMap<H160, U256>,
+    swaps: Vec<Swap<'a>>,
+}
+
+impl RingSolution<'_> {
+    fn into_settlement(self, ring: &[&LimitOrder]) -> Result<Settlement> {
+        let mut settlement = Settlement::new(self.prices);
+        for order in ring {
+            settlement.with_liquidity(*order, order.full_execution_amount())?;
+        }
+        for swap in self.swaps {
+            baseline_solver::settle_path(&mut settlement, swap.input, swap.path)?;
+        }
+        Ok(settlement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        liquidity::{tests::CapturingSettlementHandler, AmmOrderExecution, ConstantProductOrder},
+        test::account,
+    };
+    use maplit::hashmap;
+    use num::rational::Ratio;
+
+    fn order(
+        (sell_token, sell_amount): (H160, u128),
+        (buy_token, buy_amount): (H160, u128),
+        kind: OrderKind,
+    ) -> (LimitOrder, Arc<CapturingSettlementHandler<LimitOrder>>) {
+        let handler = CapturingSettlementHandler::arc();
+        let order = LimitOrder {
+            sell_token,
+            buy_token,
+            sell_amount: sell_amount.into(),
+            buy_amount: buy_amount.into(),
+            kind,
+            settlement_handling: handler.clone(),
+            ..Default::default()
+        };
+        (order, handler)
+    }
+
+    fn solver(close_residuals: bool) -> RingTradeSolver {
+        let native_token = H160([0xee; 20]);
+        RingTradeSolver::new(
+            account(),
+            Arc::new(BaseTokens::new(native_token, &[])),
+            close_residuals,
+        )
+    }
+
+    #[test]
+    fn settles_three_token_ring_without_amms() {
+        let tokens = [H160([1; 20]), H160([2; 20]), H160([3; 20])];
+        let (a, a_handler) = order((tokens[0], 100), (tokens[1], 90), OrderKind::Sell);
+        let (b, b_handler) = order((tokens[1], 100), (tokens[2], 90), OrderKind::Sell);
+        let (c, c_handler) = order((tokens[2], 100), (tokens[0], 90), OrderKind::Sell);
+        // Doesn't close a ring.
+        let (d, d_handler) = order((tokens[0], 100), (tokens[2], 90), OrderKind::Sell);
+
+        let settlements = solver(false).solve_(vec![a, b, c, d], Vec::new(), &Default::default());
+        assert_eq!(settlements.len(), 1);
+        assert_eq!(
+            settlements[0].clearing_prices(),
+            &hashmap! {
+                tokens[0] => 1.into(),
+                tokens[1] => 1.into(),
+                tokens[2] => 1.into(),
+            }
+        );
+        for handler in [a_handler, b_handler, c_handler] {
+            assert_eq!(handler.calls(), vec![U256::from(100)]);
+        }
+        assert!(d_handler.calls().is_empty());
+    }
+
+    #[test]
+    fn balances_buy_and_sell_orders() {
+        let tokens = [H160([1; 20]), H160([2; 20])];
+        let (a, a_handler) = order((tokens[0], 100), (tokens[1], 90), OrderKind::Sell);
+        let (b, b_handler) = order((tokens[1], 120), (tokens[0], 100), OrderKind::Buy);
+
+        let settlements = solver(false).solve_(vec![a, b], Vec::new(), &Default::default());
+        assert_eq!(settlements.len(), 1);
+        // The amount of the second token is not fixed by either order, so the surplus is split.
+        assert_eq!(
+            settlements[0].clearing_prices(),
+            &hashmap! {
+                tokens[0] => 21.into(),
+                tokens[1] => 20.into(),
+            }
+        );
+        assert_eq!(a_handler.calls(), vec![U256::from(100)]);
+        assert_eq!(b_handler.calls(), vec![U256::from(100)]);
+    }
+
+    #[test]
+    fn settles_four_token_ring_of_realistic_amounts() {
+        let tokens = [H160([1; 20]), H160([2; 20]), H160([3; 20]), H160([4; 20])];
+        // Amounts with 18 decimals whose least common multiple overflows.
+        let amounts = [
+            1_234_567_891_234_567_891_237_u128,
+            2_345_678_912_345_678_912_343,
+            3_456_789_123_456_789_123_451,
+            987_654_321_987_654_321_991,
+        ];
+        let (orders, handlers): (Vec<_>, Vec<_>) = (0..4)
+            .map(|i| {
+                let (order, handler) = order(
+                    (tokens[i], amounts[i]),
+                    (tokens[(i + 1) % 4], amounts[(i + 1) % 4] / 10 * 9),
+                    OrderKind::Sell,
+                );
+                let order = LimitOrder {
+                    unscaled_subsidized_fee: U256::exp10(15),
+                    ..order
+                };
+                (order, handler)
+            })
+            .unzip();
+
+        let settlements = solver(false).solve_(orders.clone(), Vec::new(), &Default::default());
+        assert_eq!(settlements.len(), 1);
+        for (handler, amount) in handlers.iter().zip(amounts) {
+            assert_eq!(handler.calls(), vec![U256::from(amount)]);
+        }
+
+        // Rounding leaves at most one atom of any token uncovered.
+        let ring = orders.iter().collect::<Vec<_>>();
+        let residuals = residuals(&ring, settlements[0].clearing_prices()).unwrap();
+        assert!(residuals
+            .deficits
+            .iter()
+            .all(|(_, deficit)| *deficit <= U256::one()));
+    }
+
+    #[test]
+    fn ignores_unprofitable_rings_and_liquidity_orders() {
+        let tokens = [H160([1; 20]), H160([2; 20]), H160([3; 20])];
+        let (a, _) = order((tokens[0], 100), (tokens[1], 100), OrderKind::Sell);
+        let (b, _) = order((tokens[1], 100), (tokens[2], 100), OrderKind::Sell);
+        let (c, _) = order((tokens[2], 100), (tokens[0], 101), OrderKind::Sell);
+        let (d, _) = order((tokens[2], 100), (tokens[0], 90), OrderKind::Sell);
+        let d = LimitOrder {
+            is_liquidity_order: true,
+            ..d
+        };
+
+        let settlements = solver(true).solve_(vec![a, b, c, d], Vec::new(), &Default::default());
+        assert!(settlements.is_empty());
+    }
+
+    #[test]
+    fn uses_every_order_at_most_once() {
+        let tokens = [H160([1; 20]), H160([2; 20])];
+        let (a, a_handler) = order((tokens[0], 100), (tokens[1], 90), OrderKind::Sell);
+        let (b, b_handler) = order((tokens[1], 100), (tokens[0], 80), OrderKind::Sell);
+        let (c, c_handler) = order((tokens[1], 100), (tokens[0], 90), OrderKind::Sell);
+
+        let settlements = solver(false).solve_(vec![a, b, c], Vec::new(), &Default::default());
+        assert_eq!(settlements.len(), 1);
+        // The order with the better limit price gets matched.
+        assert_eq!(a_handler.calls(), vec![U256::from(100)]);
+        assert_eq!(b_handler.calls(), vec![U256::from(100)]);
+        assert!(c_handler.calls().is_empty());
+    }
+
+    #[test]
+    fn closes_residuals_through_amms() {
+        let tokens = [H160([1; 20]), H160([2; 20])];
+        let (a, a_handler) = order((tokens[0], 100_000), (tokens[1], 90_000), OrderKind::Sell);
+        let (b, b_handler) = order((tokens[1], 80_000), (tokens[0], 70_000), OrderKind::Sell);
+        let amm_handler = CapturingSettlementHandler::arc();
+        let liquidity = vec![Liquidity::ConstantProduct(ConstantProductOrder {
+            tokens: TokenPair::new(tokens[0], tokens[1]).unwrap(),
+            reserves: (10_000_000, 10_000_000),
+            fee: Ratio::new(3, 1000),
+            settlement_handling: amm_handler.clone(),
+        })];
+        let external_prices = ExternalPrices::new(
+            H160([0xee; 20]),
+            hashmap! {
+                tokens[0] => BigRational::one(),
+                tokens[1] => BigRational::one(),
+            },
+        )
+        .unwrap();
+
+        // The orders don't line up exactly, so they can't be settled without AMMs.
+        assert!(solver(false)
+            .solve_(
+                vec![a.clone(), b.clone()],
+                liquidity.clone(),
+                &external_prices
+            )
+            .is_empty());
+
+        let settlements =
+            solver(true).solve_(vec![a.clone(), b.clone()], liquidity, &external_prices);
+        assert_eq!(settlements.len(), 1);
+        assert_eq!(a_handler.calls(), vec![U256::from(100_000)]);
+        assert_eq!(b_handler.calls(), vec![U256::from(80_000)]);
+
+        // The excess of the first token is swapped for the missing amount of the second token.
+        let prices = settlements[0].clearing_prices();
+        let (_, a_buy_amount) = executed_amounts(&a, prices).unwrap();
+        let (_, b_buy_amount) = executed_amounts(&b, prices).unwrap();
+        let calls = amm_handler.calls();
+        let AmmOrderExecution { input, output } = &calls[0];
+        assert_eq!(input.0, tokens[0]);
+        assert!(input.1 <= a.sell_amount - b_buy_amount);
+        assert_eq!(output.0, tokens[1]);
+        assert!(b.sell_amount + output.1 >= a_buy_amount);
+    }
+}
@@ -0,0 +1,7 @@
+-- Stores the full app data documents that order app data hashes commit to. Orders only contain
+-- the hash, so several orders can share the same document.
+
+CREATE TABLE app_data (
+    contract_app_data bytea PRIMARY KEY,
+    full_app_data bytea NOT NULL
+);
@@ -0,0 +1,8 @@
+-- Links orders that were replaced with an `OrderReplacement` to their replacement. Both orders are
+-- part of the order history, the old one is cancelled at the time of the replacement.
+
+CREATE TABLE order_replacements (
+    old_order_uid bytea PRIMARY KEY,
+    new_order_uid bytea UNIQUE NOT NULL,
+    timestamp timestamptz NOT NULL
+);